@@ -0,0 +1,231 @@
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+// #[sql(crate = path)] on the struct names the sql crate when it is not `::sql`, for example
+// when it is renamed in Cargo.toml or re-exported by another crate
+#[proc_macro_derive(FromRow, attributes(sql))]
+pub fn derive_from_row(input: TokenStream) -> TokenStream {
+    let Struct {
+        name,
+        krate,
+        fields,
+    } = match named_struct(input) {
+        Ok(parsed) => parsed,
+        Err(message) => return compile_error(&message),
+    };
+    let values: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let parse = if field.optional {
+                "parse_optional_field"
+            } else {
+                "parse_field"
+            };
+            format!(
+                "{}: {}::{}(row, {:?})?,",
+                field.name,
+                krate,
+                parse,
+                column(&field.name)
+            )
+        })
+        .collect();
+    format!(
+        "impl {krate}::FromRow for {} {{
+            fn from_row(row: &{krate}::Row) -> ::std::result::Result<Self, ::std::string::String> {{
+                ::std::result::Result::Ok({} {{ {} }})
+            }}
+        }}",
+        name,
+        name,
+        values.join(" "),
+        krate = krate
+    )
+    .parse()
+    .unwrap()
+}
+
+#[proc_macro_derive(ToRow, attributes(sql))]
+pub fn derive_to_row(input: TokenStream) -> TokenStream {
+    let Struct {
+        name,
+        krate,
+        fields,
+    } = match named_struct(input) {
+        Ok(parsed) => parsed,
+        Err(message) => return compile_error(&message),
+    };
+    let columns: Vec<String> = fields
+        .iter()
+        .map(|field| format!("{:?},", column(&field.name)))
+        .collect();
+    // None is written as an empty cell, the way nulls are stored
+    let values: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.optional {
+                format!(
+                    "match &self.{} {{
+                        ::std::option::Option::Some(value) => ::std::string::ToString::to_string(value),
+                        ::std::option::Option::None => ::std::string::String::new(),
+                    }},",
+                    field.name
+                )
+            } else {
+                format!("::std::string::ToString::to_string(&self.{}),", field.name)
+            }
+        })
+        .collect();
+    format!(
+        "impl {}::ToRow for {} {{
+            fn column_names() -> ::std::vec::Vec<&'static str> {{
+                ::std::vec![{}]
+            }}
+
+            fn to_row(&self) -> ::std::vec::Vec<::std::string::String> {{
+                ::std::vec![{}]
+            }}
+        }}",
+        krate,
+        name,
+        columns.join(" "),
+        values.join(" ")
+    )
+    .parse()
+    .unwrap()
+}
+
+struct Struct {
+    name: String,
+    // path of the sql crate
+    krate: String,
+    fields: Vec<Field>,
+}
+
+struct Field {
+    name: String,
+    // the type is Option<..>, a null cell is None
+    optional: bool,
+}
+
+// the column of a field is its name, without the raw identifier prefix
+fn column(field: &str) -> &str {
+    field.trim_start_matches("r#")
+}
+
+fn compile_error(message: &str) -> TokenStream {
+    format!("compile_error!({:?});", message).parse().unwrap()
+}
+
+// name and fields of `struct Name { field: Type, .. }`, without generics
+fn named_struct(input: TokenStream) -> Result<Struct, String> {
+    let mut tokens = input.into_iter().peekable();
+    let mut name = None;
+    let mut krate = None;
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Bracket => {
+                if let Some(path) = crate_path(group.stream())? {
+                    krate = Some(path);
+                }
+            }
+            TokenTree::Ident(ident) if ident.to_string() == "struct" => {
+                name = tokens.next().map(|t| t.to_string());
+                break;
+            }
+            TokenTree::Ident(ident) if ident.to_string() == "enum" => {
+                return Err("FromRow and ToRow can only be derived for structs".to_string());
+            }
+            _ => {}
+        }
+    }
+    let name = name.ok_or_else(|| "expected a struct".to_string())?;
+    let krate = krate.unwrap_or_else(|| "::sql".to_string());
+    match tokens.next() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => Ok(Struct {
+            name,
+            krate,
+            fields: fields(group.stream()),
+        }),
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {
+            Err("FromRow and ToRow do not support generic structs".to_string())
+        }
+        _ => Err("FromRow and ToRow need a struct with named fields".to_string()),
+    }
+}
+
+// the path of `sql(crate = path)` or `sql(crate = "path")`, None for the other attributes
+fn crate_path(attribute: TokenStream) -> Result<Option<String>, String> {
+    let mut tokens = attribute.into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Ident(ident)), Some(TokenTree::Group(group)))
+            if ident.to_string() == "sql" && group.delimiter() == Delimiter::Parenthesis =>
+        {
+            let mut tokens = group.stream().into_iter();
+            match (tokens.next(), tokens.next()) {
+                (Some(TokenTree::Ident(key)), Some(TokenTree::Punct(eq)))
+                    if key.to_string() == "crate" && eq.as_char() == '=' =>
+                {
+                    let path: String = tokens.map(|token| token.to_string()).collect();
+                    let path = path.trim_matches('"').to_string();
+                    if path.is_empty() {
+                        Err("expected #[sql(crate = path)]".to_string())
+                    } else {
+                        Ok(Some(path))
+                    }
+                }
+                _ => Err("expected #[sql(crate = path)]".to_string()),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+fn fields(tokens: TokenStream) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let mut name = None;
+    // tokens of the type, once the ':' after the name is found
+    let mut ty: Option<Vec<String>> = None;
+    // commas inside the angle brackets of a type do not end the field
+    let mut depth = 0;
+    // the '>' of `->`, as in `Box<dyn Fn() -> u8>`, does not close a bracket
+    let mut after_minus = false;
+    for token in tokens {
+        match &token {
+            TokenTree::Punct(punct) if ty.is_none() && punct.as_char() == ':' => {
+                ty = Some(Vec::new());
+                continue;
+            }
+            TokenTree::Punct(punct) => match punct.as_char() {
+                '<' => depth += 1,
+                '>' if depth > 0 && !after_minus => depth -= 1,
+                ',' if depth == 0 => {
+                    fields.extend(field(name.take(), ty.take()));
+                    after_minus = false;
+                    continue;
+                }
+                _ => {}
+            },
+            TokenTree::Ident(ident) if ty.is_none() => name = Some(ident.to_string()),
+            _ => {}
+        }
+        after_minus = matches!(&token, TokenTree::Punct(punct)
+            if punct.as_char() == '-' && punct.spacing() == Spacing::Joint);
+        if let Some(ty) = &mut ty {
+            ty.push(token.to_string());
+        }
+    }
+    fields.extend(field(name, ty));
+    fields
+}
+
+fn field(name: Option<String>, ty: Option<Vec<String>>) -> Option<Field> {
+    let (name, ty) = (name?, ty?);
+    // Option<T>, std::option::Option<T> and the like: the last segment before the '<'
+    let optional = match ty.iter().position(|token| token == "<") {
+        Some(bracket) => bracket > 0 && ty[bracket - 1] == "Option",
+        None => false,
+    };
+    Some(Field { name, optional })
+}
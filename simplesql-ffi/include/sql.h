@@ -0,0 +1,35 @@
+#ifndef SQL_H
+#define SQL_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct SqlTable SqlTable;
+
+/* message of the last failure on this thread, owned by the library */
+const char *sql_last_error(void);
+void sql_string_free(char *text);
+void sql_table_free(SqlTable *table);
+
+SqlTable *sql_table_load_tsv(const char *path, size_t skip_lines);
+SqlTable *sql_table_parse_tsv(const char *input, size_t skip_lines);
+
+size_t sql_table_rows_count(const SqlTable *table);
+size_t sql_table_columns_count(const SqlTable *table);
+
+SqlTable *sql_table_select_columns(const SqlTable *table, const char *const *col_names, size_t count);
+SqlTable *sql_table_filter_expr(const SqlTable *table, const char *expr);
+SqlTable *sql_table_join(const SqlTable *table, const char *col_name, const SqlTable *other, const char *other_col_name);
+
+/* free the result with sql_string_free */
+char *sql_table_to_tsv(const SqlTable *table, const char *const *header, size_t count);
+int sql_table_write_tsv(const SqlTable *table, const char *path, const char *const *header, size_t count);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,282 @@
+// Every pointer taken here must be null or come from this API, and every
+// string must be NUL-terminated UTF-8. Functions report failure with a
+// null pointer or -1; sql_last_error then describes it. A panic inside the
+// library is caught at the boundary and reported the same way.
+#![allow(clippy::missing_safety_doc)]
+
+use sql::messages::{self, Message};
+use sql::{JoinCollision, Table};
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+type Res<T> = Result<T, String>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(err: String) {
+    let message = CString::new(err.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+// a panic must not unwind into C, it becomes the last error and the failure value
+fn guard<T>(on_panic: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        set_error(messages::text(
+            Message::Panicked,
+            &[&panic_message(&*payload)],
+        ));
+        on_panic
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload.downcast_ref::<String>().map_or("", String::as_str),
+    }
+}
+
+unsafe fn to_str<'a>(text: *const c_char) -> Res<&'a str> {
+    if text.is_null() {
+        return Err(messages::text(Message::NullStringPointer, &[]));
+    }
+    CStr::from_ptr(text)
+        .to_str()
+        .map_err(|_| messages::text(Message::NotUtf8, &[]))
+}
+
+unsafe fn to_strs<'a>(texts: *const *const c_char, count: usize) -> Res<Vec<&'a str>> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    if texts.is_null() {
+        return Err(messages::text(Message::NullStringListPointer, &[]));
+    }
+    std::slice::from_raw_parts(texts, count)
+        .iter()
+        .map(|&text| to_str(text))
+        .collect()
+}
+
+unsafe fn to_table<'a>(table: *const Table) -> Res<&'a Table> {
+    table
+        .as_ref()
+        .ok_or_else(|| messages::text(Message::NullTablePointer, &[]))
+}
+
+fn table_or_null(result: Res<Table>) -> *mut Table {
+    match result {
+        Ok(table) => Box::into_raw(Box::new(table)),
+        Err(err) => {
+            set_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+fn string_or_null(result: Res<String>) -> *mut c_char {
+    match result.and_then(|text| {
+        CString::new(text).map_err(|_| messages::text(Message::NulByteInResult, &[]))
+    }) {
+        Ok(text) => text.into_raw(),
+        Err(err) => {
+            set_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+fn status(result: Res<()>) -> c_int {
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            set_error(err);
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn sql_last_error() -> *const c_char {
+    guard(ptr::null(), || {
+        LAST_ERROR.with(|last| {
+            last.borrow()
+                .as_ref()
+                .map_or(ptr::null(), |message| message.as_ptr())
+        })
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_string_free(text: *mut c_char) {
+    guard((), || {
+        if !text.is_null() {
+            drop(CString::from_raw(text));
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_table_free(table: *mut Table) {
+    guard((), || {
+        if !table.is_null() {
+            drop(Box::from_raw(table));
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_table_load_tsv(path: *const c_char, skip_lines: usize) -> *mut Table {
+    guard(ptr::null_mut(), || {
+        table_or_null(to_str(path).and_then(|path| Table::load_tsv(path, skip_lines)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_table_parse_tsv(
+    input: *const c_char,
+    skip_lines: usize,
+) -> *mut Table {
+    guard(ptr::null_mut(), || {
+        table_or_null(to_str(input).and_then(|input| Table::parse_tsv(input, skip_lines)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_table_rows_count(table: *const Table) -> usize {
+    guard(0, || to_table(table).map_or(0, Table::rows_count))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_table_columns_count(table: *const Table) -> usize {
+    guard(0, || to_table(table).map_or(0, Table::columns_count))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_table_select_columns(
+    table: *const Table,
+    col_names: *const *const c_char,
+    count: usize,
+) -> *mut Table {
+    guard(ptr::null_mut(), || {
+        table_or_null(
+            to_table(table).and_then(|table| table.select_columns(&to_strs(col_names, count)?)),
+        )
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_table_filter_expr(
+    table: *const Table,
+    expr: *const c_char,
+) -> *mut Table {
+    guard(ptr::null_mut(), || {
+        table_or_null(to_table(table).and_then(|table| table.filter_expr(to_str(expr)?)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_table_join(
+    table: *const Table,
+    col_name: *const c_char,
+    other: *const Table,
+    other_col_name: *const c_char,
+) -> *mut Table {
+    guard(ptr::null_mut(), || {
+        table_or_null(to_table(table).and_then(|table| {
+            table.join_on_columns_with(
+                to_str(col_name)?,
+                to_table(other)?,
+                to_str(other_col_name)?,
+                JoinCollision::Error,
+            )
+        }))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_table_to_tsv(
+    table: *const Table,
+    header: *const *const c_char,
+    count: usize,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        string_or_null(to_table(table).and_then(|table| {
+            let header = to_strs(header, count)?;
+            table.to_tsv(header.into_iter().map(String::from).collect())
+        }))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sql_table_write_tsv(
+    table: *const Table,
+    path: *const c_char,
+    header: *const *const c_char,
+    count: usize,
+) -> c_int {
+    guard(-1, || {
+        status(to_table(table).and_then(|table| {
+            let header = to_strs(header, count)?;
+            table.write_tsv_file(
+                to_str(path)?,
+                header.into_iter().map(String::from).collect(),
+            )
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(sql_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn tables_cross_the_boundary() {
+        let input = CString::new("a\tb\n1\tx\n2\ty\n").unwrap();
+        let header = [CString::new("b").unwrap()];
+        let header: Vec<*const c_char> = header.iter().map(|c| c.as_ptr()).collect();
+        unsafe {
+            let table = sql_table_parse_tsv(input.as_ptr(), 0);
+            assert!(!table.is_null());
+            assert_eq!(sql_table_rows_count(table), 2);
+            assert_eq!(sql_table_columns_count(table), 2);
+            let tsv = sql_table_to_tsv(table, header.as_ptr(), header.len());
+            assert_eq!(CStr::from_ptr(tsv).to_str().unwrap(), "b\nx\ny");
+            sql_string_free(tsv);
+            sql_table_free(table);
+        }
+    }
+
+    #[test]
+    fn failures_set_the_last_error() {
+        unsafe {
+            assert!(sql_table_parse_tsv(ptr::null(), 0).is_null());
+            assert!(!last_error().is_empty());
+            let expr = CString::new("a >").unwrap();
+            let input = CString::new("a\n1\n").unwrap();
+            let table = sql_table_parse_tsv(input.as_ptr(), 0);
+            assert!(sql_table_filter_expr(table, expr.as_ptr()).is_null());
+            sql_table_free(table);
+        }
+    }
+
+    #[test]
+    fn panics_become_errors() {
+        let result = guard(-1, || -> c_int { panic!("boom") });
+        assert_eq!(result, -1);
+        assert!(last_error().contains("boom"));
+        assert_eq!(guard(-1, || 0), 0);
+    }
+}
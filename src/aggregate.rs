@@ -0,0 +1,248 @@
+use super::messages::{self, Message};
+use super::table::Res;
+use std::ops::Add;
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Mean,
+    Min,
+    Max,
+    First,
+    Last,
+    ConcatWith(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum NullPolicy {
+    #[default]
+    Skip,
+    Propagate,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CumOp {
+    Sum,
+    Count,
+    Min,
+    Max,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RollingStart {
+    Partial,
+    Empty,
+}
+
+impl Aggregate {
+    pub fn apply(&self, items: &[&str]) -> Res<String> {
+        match self {
+            Aggregate::Count => Ok(items.len().to_string()),
+            Aggregate::First => Ok(items.first().copied().unwrap_or("").to_string()),
+            Aggregate::Last => Ok(items.last().copied().unwrap_or("").to_string()),
+            Aggregate::ConcatWith(separator) => Ok(items.join(separator)),
+            Aggregate::Mean => {
+                let items = non_null(items);
+                if items.is_empty() {
+                    return Ok(String::new());
+                }
+                let numbers = parse_floats(&items)?;
+                Ok((numbers.iter().sum::<f64>() / numbers.len() as f64).to_string())
+            }
+            Aggregate::Sum | Aggregate::Min | Aggregate::Max => {
+                let items = non_null(items);
+                if items.is_empty() {
+                    return Ok(String::new());
+                }
+                if let Some(integers) = parse_integers(&items) {
+                    let result = match self {
+                        Aggregate::Sum => Some(checked_sum(integers.iter().copied())?),
+                        Aggregate::Min => integers.iter().copied().min(),
+                        _ => integers.iter().copied().max(),
+                    };
+                    return Ok(result.map_or(String::new(), |n: i64| n.to_string()));
+                }
+                let numbers = parse_floats(&items)?;
+                let result = match self {
+                    Aggregate::Sum => numbers.iter().sum(),
+                    Aggregate::Min => numbers.iter().copied().fold(f64::INFINITY, f64::min),
+                    _ => numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                };
+                Ok(result.to_string())
+            }
+        }
+    }
+}
+
+impl NullPolicy {
+    // Skip drops nulls before the operation, Propagate makes any null the result
+    pub(crate) fn apply<T>(
+        self,
+        items: &[&str],
+        counts_rows: bool,
+        operation: impl FnOnce(&[&str]) -> T,
+        null: impl FnOnce() -> T,
+    ) -> T {
+        match self {
+            NullPolicy::Skip => operation(&non_null(items)),
+            NullPolicy::Propagate if !counts_rows && items.iter().any(|item| item.is_empty()) => {
+                null()
+            }
+            NullPolicy::Propagate => operation(items),
+        }
+    }
+}
+
+impl CumOp {
+    pub fn running(self, items: &[&str]) -> Res<Vec<String>> {
+        if self == CumOp::Count {
+            return Ok((1..=items.len()).map(|n| n.to_string()).collect());
+        }
+        // null rows repeat the running value reached so far
+        let values = non_null(items);
+        let running: Vec<String> = if let Some(integers) = parse_integers(&values) {
+            self.scan(integers).map(|n| n.to_string()).collect()
+        } else {
+            let numbers = parse_floats(&values)?;
+            self.scan(numbers).map(|n| n.to_string()).collect()
+        };
+        let mut running = running.into_iter();
+        let mut current = String::new();
+        Ok(items
+            .iter()
+            .map(|item| {
+                if !item.is_empty() {
+                    current = running.next().unwrap_or_default();
+                }
+                current.clone()
+            })
+            .collect())
+    }
+
+    fn scan<T: Copy + PartialOrd + Add<Output = T>>(
+        self,
+        numbers: Vec<T>,
+    ) -> impl Iterator<Item = T> {
+        let mut accumulator: Option<T> = None;
+        numbers.into_iter().map(move |n| {
+            let next = match accumulator {
+                None => n,
+                Some(acc) => match self {
+                    CumOp::Min if n < acc => n,
+                    CumOp::Max if n > acc => n,
+                    CumOp::Min | CumOp::Max => acc,
+                    _ => acc + n,
+                },
+            };
+            accumulator = Some(next);
+            next
+        })
+    }
+}
+
+fn non_null<'a>(items: &[&'a str]) -> Vec<&'a str> {
+    items
+        .iter()
+        .copied()
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+// an integer sum that does not fit is an error, rather than a wrapped or panicking result
+pub(crate) fn checked_sum(mut numbers: impl Iterator<Item = i64>) -> Res<i64> {
+    numbers
+        .try_fold(0i64, |sum, n| sum.checked_add(n))
+        .ok_or_else(|| messages::text(Message::SumOverflow, &[]))
+}
+
+fn parse_integers(items: &[&str]) -> Option<Vec<i64>> {
+    items.iter().map(|item| item.parse::<i64>().ok()).collect()
+}
+
+fn parse_floats(items: &[&str]) -> Res<Vec<f64>> {
+    items
+        .iter()
+        .map(|item| {
+            item.parse::<f64>()
+                .map_err(|_| messages::text(Message::NotNumeric, &[&item]))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColumnType, LoadOptions, Op, Schema, Table};
+
+    #[test]
+    fn integer_sum_overflow_is_an_error() {
+        let max = i64::MAX.to_string();
+        assert_eq!(
+            Aggregate::Sum.apply(&[&max, "-1", ""]).unwrap(),
+            (i64::MAX - 1).to_string()
+        );
+        let err = Aggregate::Sum.apply(&[&max, "1"]).err().unwrap();
+        assert!(err.starts_with("E114: "), "{}", err);
+    }
+
+    #[test]
+    fn typed_sum_overflow_is_an_error() {
+        let text = format!("k\tn\na\t{}\na\t1\nb\t2\n", i64::MAX);
+        let options = LoadOptions::new().schema(Schema::new().column("n", ColumnType::Int64));
+        let table = Table::parse_tsv_with(&text, &options).unwrap();
+        let ops = [Op::aggregate("n", Aggregate::Sum)];
+        let err = table.group_by_column("k", &ops).err().unwrap();
+        assert!(
+            err.starts_with("E034: ") && err.contains("E114: "),
+            "{}",
+            err
+        );
+        let table = Table::parse_tsv_with("k\tn\na\t\nb\t2\n", &options).unwrap();
+        let sums = table
+            .group_by_column("k", &ops)
+            .unwrap()
+            .column("n")
+            .unwrap();
+        assert_eq!(
+            sums.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            vec!["", "2"]
+        );
+    }
+
+    #[test]
+    fn builtin_aggregates_skip_nulls_and_keep_integers_exact() {
+        let items = ["3", "", "10", "-2"];
+        let apply = |aggregate: Aggregate| aggregate.apply(&items).unwrap();
+        assert_eq!(apply(Aggregate::Count), "4");
+        assert_eq!(apply(Aggregate::Sum), "11");
+        assert_eq!(apply(Aggregate::Mean), (11.0f64 / 3.0).to_string());
+        assert_eq!(apply(Aggregate::Min), "-2");
+        assert_eq!(apply(Aggregate::Max), "10");
+        assert_eq!(apply(Aggregate::First), "3");
+        assert_eq!(apply(Aggregate::Last), "-2");
+        assert_eq!(apply(Aggregate::ConcatWith("|".into())), "3||10|-2");
+        assert_eq!(Aggregate::Sum.apply(&["1.5", "2"]).unwrap(), "3.5");
+        assert_eq!(Aggregate::Max.apply(&["", ""]).unwrap(), "");
+        let err = Aggregate::Mean.apply(&["1", "x"]).err().unwrap();
+        assert_eq!(err, "E019: value 'x' is not numeric");
+    }
+
+    #[test]
+    fn group_by_mixes_builtin_and_custom_operations() {
+        let text = "k\tamount\tnote\na\t2\tx\nb\t5\ty\na\t4\tz\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        let ops = [
+            Op::aggregate("amount", Aggregate::Sum),
+            Op::new("note", Box::new(|notes| notes.concat())),
+        ];
+        let grouped = table.group_by_column("k", &ops).unwrap();
+        let cells = |col_name: &str| -> Vec<String> {
+            let column = grouped.column(col_name).unwrap();
+            column.iter().map(|c| c.to_string()).collect()
+        };
+        assert_eq!(cells("k"), ["a", "b"]);
+        assert_eq!(cells("amount"), ["6", "5"]);
+        assert_eq!(cells("note"), ["xz", "y"]);
+    }
+}
@@ -0,0 +1,170 @@
+use super::column::Column;
+use super::messages::{self, Message};
+use super::table::{Res, Table};
+use super::types::ColumnType;
+use arrow_array::{
+    ArrayRef, BooleanArray, Date32Array, Float64Array, Int64Array, RecordBatch, StringArray,
+};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use std::io::Write;
+use std::ops::Range;
+use std::sync::Arc;
+
+// typed columns keep their type, every other column is utf8; empty cells are arrow nulls
+pub(crate) fn schema(table: &Table) -> Schema {
+    let fields: Vec<Field> = table
+        .column_names()
+        .into_iter()
+        .map(|col_name| {
+            let data_type = match table.column_ref(col_name).unwrap().column_type() {
+                ColumnType::Int64 => DataType::Int64,
+                ColumnType::Float64 => DataType::Float64,
+                ColumnType::Bool => DataType::Boolean,
+                ColumnType::Date => DataType::Date32,
+                ColumnType::Str => DataType::Utf8,
+            };
+            Field::new(col_name, data_type, true)
+        })
+        .collect();
+    Schema::new(fields)
+}
+
+pub(crate) fn batch(table: &Table, schema: &Arc<Schema>, rows: Range<usize>) -> Res<RecordBatch> {
+    let mut arrays = Vec::with_capacity(schema.fields().len());
+    for col_name in table.column_names() {
+        arrays.push(array(table.column_ref(col_name)?, rows.clone()));
+    }
+    RecordBatch::try_new(schema.clone(), arrays).map_err(arrow_error)
+}
+
+// one IPC message per batch of rows, flushed so a reader on the other end can start on it
+pub(crate) fn write_stream(table: &Table, out: impl Write, rows_per_batch: usize) -> Res<usize> {
+    let schema = Arc::new(schema(table));
+    let mut writer = StreamWriter::try_new(out, &schema).map_err(arrow_error)?;
+    let rows_per_batch = rows_per_batch.max(1);
+    let mut batches = 0;
+    for start in (0..table.rows_count()).step_by(rows_per_batch) {
+        let end = (start + rows_per_batch).min(table.rows_count());
+        writer
+            .write(&batch(table, &schema, start..end)?)
+            .and_then(|_| writer.flush())
+            .map_err(arrow_error)?;
+        batches += 1;
+    }
+    writer.finish().map_err(arrow_error)?;
+    Ok(batches)
+}
+
+fn array(column: &Column, rows: Range<usize>) -> ArrayRef {
+    match column.column_type() {
+        ColumnType::Int64 => Arc::new(Int64Array::from(column.as_i64().unwrap()[rows].to_vec())),
+        ColumnType::Float64 => {
+            Arc::new(Float64Array::from(column.as_f64().unwrap()[rows].to_vec()))
+        }
+        ColumnType::Bool => Arc::new(BooleanArray::from(column.as_bool().unwrap()[rows].to_vec())),
+        // iso dates have four digit years, so the days always fit in an i32
+        ColumnType::Date => Arc::new(
+            column.as_date_days().unwrap()[rows]
+                .iter()
+                .map(|days| days.map(|days| days as i32))
+                .collect::<Date32Array>(),
+        ),
+        ColumnType::Str => Arc::new(
+            column
+                .iter()
+                .skip(rows.start)
+                .take(rows.len())
+                .map(|cell| Some(cell.as_str()).filter(|cell| !cell.is_empty()))
+                .collect::<StringArray>(),
+        ),
+    }
+}
+
+fn arrow_error(err: ArrowError) -> String {
+    messages::text(Message::Arrow, &[&err])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Date32Type, Float64Type, Int64Type};
+    use arrow_array::Array;
+    use arrow_ipc::reader::StreamReader;
+
+    #[test]
+    fn streamed_batches_read_back_with_types_and_nulls() {
+        let table = Table::parse_tsv(
+            "id\tprice\tpaid\tday\tnote\n1\t2.5\ttrue\t2024-03-01\ta\n2\t\tfalse\t1969-12-31\t\n3\t4\t\t\tc\n",
+            0,
+        )
+        .unwrap()
+        .cast_column("id", ColumnType::Int64)
+        .unwrap()
+        .cast_column("price", ColumnType::Float64)
+        .unwrap()
+        .cast_column("paid", ColumnType::Bool)
+        .unwrap()
+        .cast_column("day", ColumnType::Date)
+        .unwrap();
+        let mut out = Vec::new();
+        assert_eq!(table.write_arrow_stream(&mut out, 2).unwrap(), 2);
+
+        let reader = StreamReader::try_new(out.as_slice(), None).unwrap();
+        let schema = reader.schema();
+        let types: Vec<&DataType> = schema.fields().iter().map(|f| f.data_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                &DataType::Int64,
+                &DataType::Float64,
+                &DataType::Boolean,
+                &DataType::Date32,
+                &DataType::Utf8
+            ]
+        );
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        let first = &batches[0];
+        assert_eq!(
+            first.column(0).as_primitive::<Int64Type>().values(),
+            &[1, 2]
+        );
+        let price = first.column(1).as_primitive::<Float64Type>();
+        assert_eq!((price.value(0), price.is_null(1)), (2.5, true));
+        assert!(!first.column(2).as_boolean().value(1));
+        assert_eq!(
+            first.column(3).as_primitive::<Date32Type>().values(),
+            &[19783, -1]
+        );
+        let note = first.column(4).as_string::<i32>();
+        assert_eq!((note.value(0), note.is_null(1)), ("a", true));
+        let last = &batches[1];
+        assert!(last.column(2).is_null(0) && last.column(3).is_null(0));
+        assert_eq!(last.column(4).as_string::<i32>().value(0), "c");
+    }
+
+    #[test]
+    fn whole_tables_convert_to_one_record_batch() {
+        let table = Table::parse_tsv("k\tv\na\t1\nb\t2\n", 0).unwrap();
+        let batch = table.to_arrow().unwrap();
+        assert_eq!((batch.num_rows(), batch.num_columns()), (2, 2));
+        assert_eq!(batch.schema().field(1).name(), "v");
+        let mut out = Vec::new();
+        assert_eq!(
+            table
+                .filter_rows(&["k"], |_| false)
+                .unwrap()
+                .write_arrow_stream(&mut out, 10)
+                .unwrap(),
+            0
+        );
+        let reader = StreamReader::try_new(out.as_slice(), None).unwrap();
+        assert_eq!(reader.schema().fields().len(), 2);
+        assert_eq!(reader.count(), 0);
+    }
+}
@@ -0,0 +1,433 @@
+use super::value::Value;
+use std::collections::HashMap;
+
+// columns at least this long with this share of nulls are stored sparse
+const SPARSE_MIN_LEN: usize = 64;
+const SPARSE_NULL_SHARE: f64 = 0.9;
+// columns at least this long with few distinct values are dictionary encoded
+const DICTIONARY_MIN_LEN: usize = 64;
+const DICTIONARY_ROWS_PER_VALUE: usize = 8;
+const DICTIONARY_MAX_VALUES: usize = 4096;
+
+#[derive(Clone)]
+pub(crate) enum Cells {
+    Dense(Vec<Value>),
+    Sparse(Sparse),
+    Dictionary(Dictionary),
+}
+
+// the cells that are not null, the others share a single null value
+#[derive(Clone)]
+pub(crate) struct Sparse {
+    len: usize,
+    null: Value,
+    // sorted by position
+    exceptions: Vec<(usize, Value)>,
+}
+
+// every distinct value once, and for each row the position of its value
+#[derive(Clone)]
+pub(crate) struct Dictionary {
+    codes: Vec<u32>,
+    values: Vec<Value>,
+    code_of: HashMap<Value, u32>,
+}
+
+impl Cells {
+    pub(crate) fn new(cells: Vec<Value>) -> Cells {
+        let nulls = cells.iter().filter(|cell| cell.is_null()).count();
+        if !sparse_enough(cells.len(), cells.len() - nulls) {
+            return Cells::Dense(cells);
+        }
+        let len = cells.len();
+        let exceptions = cells
+            .into_iter()
+            .enumerate()
+            .filter(|(_, cell)| !cell.is_null())
+            .collect();
+        Cells::sparse(len, exceptions)
+    }
+
+    // like new, with repetitive columns dictionary encoded
+    pub(crate) fn encoded(cells: Vec<Value>) -> Cells {
+        let cells = match Cells::new(cells) {
+            Cells::Dense(cells) => cells,
+            cells => return cells,
+        };
+        match dictionary(&cells) {
+            Some((codes, values)) => Cells::Dictionary(Dictionary::new(codes, values)),
+            None => Cells::Dense(cells),
+        }
+    }
+
+    fn sparse(len: usize, exceptions: Vec<(usize, Value)>) -> Cells {
+        Cells::Sparse(Sparse {
+            len,
+            null: Value::null(),
+            exceptions,
+        })
+    }
+
+    pub(crate) fn is_sparse(&self) -> bool {
+        matches!(self, Cells::Sparse(_))
+    }
+
+    pub(crate) fn dictionary(&self) -> Option<(&[u32], &[Value])> {
+        match self {
+            Cells::Dictionary(dictionary) => Some((&dictionary.codes, &dictionary.values)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn dictionary_code(&self, value: &str) -> Option<u32> {
+        match self {
+            Cells::Dictionary(dictionary) => dictionary.code_of.get(value).copied(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Cells::Dense(cells) => cells.len(),
+            Cells::Sparse(sparse) => sparse.len,
+            Cells::Dictionary(dictionary) => dictionary.codes.len(),
+        }
+    }
+
+    pub(crate) fn dense(&self) -> Option<&[Value]> {
+        match self {
+            Cells::Dense(cells) => Some(cells),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn iter(&self) -> Iter<'_> {
+        match self {
+            Cells::Dense(cells) => Iter::Dense(cells.iter()),
+            Cells::Sparse(sparse) => Iter::Sparse {
+                sparse,
+                position: 0,
+                exception: 0,
+            },
+            Cells::Dictionary(dictionary) => Iter::Dictionary {
+                codes: dictionary.codes.iter(),
+                values: &dictionary.values,
+            },
+        }
+    }
+
+    // without building the dense cells
+    pub(crate) fn get(&self, position: usize) -> &Value {
+        match self {
+            Cells::Dense(cells) => &cells[position],
+            Cells::Sparse(sparse) => {
+                assert!(position < sparse.len);
+                match sparse
+                    .exceptions
+                    .binary_search_by_key(&position, |(p, _)| *p)
+                {
+                    Ok(i) => &sparse.exceptions[i].1,
+                    Err(_) => &sparse.null,
+                }
+            }
+            Cells::Dictionary(dictionary) => {
+                &dictionary.values[dictionary.codes[position] as usize]
+            }
+        }
+    }
+
+    pub(crate) fn remap(&self, indices: &[usize]) -> Cells {
+        match self {
+            Cells::Dense(cells) => {
+                Cells::Dense(indices.iter().map(|&i| cells[i].clone()).collect())
+            }
+            Cells::Sparse(_) => {
+                let exceptions: Vec<(usize, Value)> = indices
+                    .iter()
+                    .enumerate()
+                    .map(|(position, &i)| (position, self.get(i)))
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(position, value)| (position, value.clone()))
+                    .collect();
+                if sparse_enough(indices.len(), exceptions.len()) {
+                    Cells::sparse(indices.len(), exceptions)
+                } else {
+                    Cells::Dense(indices.iter().map(|&i| self.get(i).clone()).collect())
+                }
+            }
+            // values no row uses anymore stay in the dictionary
+            Cells::Dictionary(dictionary) => Cells::Dictionary(Dictionary {
+                codes: indices.iter().map(|&i| dictionary.codes[i]).collect(),
+                values: dictionary.values.clone(),
+                code_of: dictionary.code_of.clone(),
+            }),
+        }
+    }
+
+    pub(crate) fn retain(&mut self, keep: &[bool]) {
+        match self {
+            Cells::Dense(cells) => {
+                let mut keep = keep.iter();
+                cells.retain(|_| *keep.next().unwrap());
+            }
+            Cells::Sparse(sparse) => {
+                // new position of each kept row
+                let mut kept = 0;
+                let positions: Vec<usize> = keep
+                    .iter()
+                    .map(|&k| {
+                        let position = kept;
+                        kept += k as usize;
+                        position
+                    })
+                    .collect();
+                let exceptions: Vec<(usize, Value)> = std::mem::take(&mut sparse.exceptions)
+                    .into_iter()
+                    .filter(|(position, _)| keep[*position])
+                    .map(|(position, value)| (positions[position], value))
+                    .collect();
+                *self = if sparse_enough(kept, exceptions.len()) {
+                    Cells::sparse(kept, exceptions)
+                } else {
+                    Cells::Dense(Cells::sparse(kept, exceptions).iter().cloned().collect())
+                };
+            }
+            Cells::Dictionary(dictionary) => {
+                let mut keep = keep.iter();
+                dictionary.codes.retain(|_| *keep.next().unwrap());
+            }
+        }
+    }
+
+    pub(crate) fn set(&mut self, position: usize, value: Value) {
+        match self {
+            Cells::Dense(cells) => cells[position] = value,
+            Cells::Sparse(sparse) => {
+                assert!(position < sparse.len);
+                let found = sparse
+                    .exceptions
+                    .binary_search_by_key(&position, |(p, _)| *p);
+                match (found, value.is_null()) {
+                    (Ok(i), true) => {
+                        sparse.exceptions.remove(i);
+                    }
+                    (Ok(i), false) => sparse.exceptions[i].1 = value,
+                    (Err(i), false) => sparse.exceptions.insert(i, (position, value)),
+                    (Err(_), true) => {}
+                }
+            }
+            Cells::Dictionary(dictionary) => {
+                dictionary.codes[position] = dictionary.code(value);
+                self.densify_if_large();
+            }
+        }
+    }
+
+    pub(crate) fn push(&mut self, value: Value) {
+        match self {
+            Cells::Dense(cells) => cells.push(value),
+            Cells::Sparse(sparse) => {
+                if !value.is_null() {
+                    sparse.exceptions.push((sparse.len, value));
+                }
+                sparse.len += 1;
+            }
+            Cells::Dictionary(dictionary) => {
+                let code = dictionary.code(value);
+                dictionary.codes.push(code);
+                self.densify_if_large();
+            }
+        }
+    }
+
+    // a dictionary that grew by writes past its limit is no longer worth searching
+    fn densify_if_large(&mut self) {
+        if let Cells::Dictionary(dictionary) = self {
+            if dictionary.values.len() > DICTIONARY_MAX_VALUES {
+                *self = Cells::Dense(self.iter().cloned().collect());
+            }
+        }
+    }
+}
+
+impl Dictionary {
+    fn new(codes: Vec<u32>, values: Vec<Value>) -> Dictionary {
+        let code_of = values
+            .iter()
+            .enumerate()
+            .map(|(code, value)| (value.clone(), code as u32))
+            .collect();
+        Dictionary {
+            codes,
+            values,
+            code_of,
+        }
+    }
+
+    // a new value is added at the end
+    fn code(&mut self, value: Value) -> u32 {
+        if let Some(&code) = self.code_of.get(&value) {
+            return code;
+        }
+        let code = self.values.len() as u32;
+        self.values.push(value.clone());
+        self.code_of.insert(value, code);
+        code
+    }
+}
+
+// the cells in row order, whatever their representation
+pub(crate) enum Iter<'a> {
+    Dense(std::slice::Iter<'a, Value>),
+    Sparse {
+        sparse: &'a Sparse,
+        position: usize,
+        // the first exception at or after position
+        exception: usize,
+    },
+    Dictionary {
+        codes: std::slice::Iter<'a, u32>,
+        values: &'a [Value],
+    },
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Value;
+
+    fn next(&mut self) -> Option<&'a Value> {
+        match self {
+            Iter::Dense(cells) => cells.next(),
+            Iter::Sparse {
+                sparse,
+                position,
+                exception,
+            } => {
+                if *position == sparse.len {
+                    return None;
+                }
+                let value = match sparse.exceptions.get(*exception) {
+                    Some((at, value)) if at == position => {
+                        *exception += 1;
+                        value
+                    }
+                    _ => &sparse.null,
+                };
+                *position += 1;
+                Some(value)
+            }
+            Iter::Dictionary { codes, values } => codes.next().map(|&code| &values[code as usize]),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = match self {
+            Iter::Dense(cells) => cells.len(),
+            Iter::Sparse {
+                sparse, position, ..
+            } => sparse.len - position,
+            Iter::Dictionary { codes, .. } => codes.len(),
+        };
+        (left, Some(left))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+fn dictionary(cells: &[Value]) -> Option<(Vec<u32>, Vec<Value>)> {
+    if cells.len() < DICTIONARY_MIN_LEN {
+        return None;
+    }
+    let max_values = DICTIONARY_MAX_VALUES.min(cells.len() / DICTIONARY_ROWS_PER_VALUE);
+    let mut codes_of: HashMap<&str, u32> = HashMap::new();
+    let mut values = Vec::new();
+    let mut codes = Vec::with_capacity(cells.len());
+    for cell in cells {
+        let code = match codes_of.get(cell.as_str()) {
+            Some(&code) => code,
+            None => {
+                if values.len() == max_values {
+                    return None;
+                }
+                values.push(cell.clone());
+                codes_of.insert(cell.as_str(), (values.len() - 1) as u32);
+                (values.len() - 1) as u32
+            }
+        };
+        codes.push(code);
+    }
+    Some((codes, values))
+}
+
+fn sparse_enough(len: usize, not_null: usize) -> bool {
+    len >= SPARSE_MIN_LEN && (len - not_null) as f64 >= len as f64 * SPARSE_NULL_SHARE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sparse_cells() -> Vec<Value> {
+        (0..200)
+            .map(|i| match i % 25 {
+                0 => Value::from(i.to_string().as_str()),
+                _ => Value::null(),
+            })
+            .collect()
+    }
+
+    fn repetitive_cells() -> Vec<Value> {
+        (0..200)
+            .map(|i| Value::from(["a", "b", "c"][i % 3]))
+            .collect()
+    }
+
+    #[test]
+    fn iter_and_get_agree_with_the_plain_cells() {
+        for plain in [sparse_cells(), repetitive_cells()] {
+            let cells = Cells::encoded(plain.clone());
+            assert!(!matches!(cells, Cells::Dense(_)));
+            assert_eq!(cells.iter().len(), plain.len());
+            assert!(cells.iter().eq(plain.iter()));
+            assert!((0..plain.len()).all(|i| *cells.get(i) == plain[i]));
+        }
+    }
+
+    #[test]
+    fn remap_and_retain_keep_the_representation_consistent() {
+        for plain in [sparse_cells(), repetitive_cells()] {
+            let cells = Cells::encoded(plain.clone());
+            let indices: Vec<usize> = (0..plain.len()).rev().step_by(2).collect();
+            let remapped = cells.remap(&indices);
+            assert!(remapped.iter().eq(indices.iter().map(|&i| &plain[i])));
+            let keep: Vec<bool> = (0..plain.len()).map(|i| i % 3 != 1).collect();
+            let mut retained = cells.clone();
+            retained.retain(&keep);
+            let expected = plain.iter().zip(&keep).filter(|(_, k)| **k).map(|(v, _)| v);
+            assert!(retained.iter().eq(expected));
+        }
+    }
+
+    #[test]
+    fn dictionary_writes_reuse_codes() {
+        let mut cells = Cells::encoded(repetitive_cells());
+        cells.push(Value::from("b"));
+        cells.push(Value::from("d"));
+        cells.set(0, Value::from("d"));
+        let (codes, values) = cells.dictionary().unwrap();
+        assert_eq!(values.len(), 4);
+        assert_eq!(codes[0], codes[201]);
+        assert_eq!(codes[200], codes[1]);
+        assert_eq!(cells.get(201).as_str(), "d");
+    }
+
+    #[test]
+    fn a_dictionary_grown_past_its_limit_becomes_dense() {
+        let mut cells = Cells::encoded(repetitive_cells());
+        for i in 0..DICTIONARY_MAX_VALUES {
+            cells.push(Value::from(i.to_string().as_str()));
+        }
+        assert!(matches!(cells, Cells::Dense(_)));
+        assert_eq!(cells.get(0).as_str(), "a");
+        assert_eq!(cells.len(), 200 + DICTIONARY_MAX_VALUES);
+    }
+}
@@ -1,68 +1,720 @@
-use std::cell::{Ref, RefCell};
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::rc::*;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, OnceLock};
 
+use super::cells::Cells;
+use super::messages::{self, Message};
+use super::table::Res;
+use super::types::{ColumnType, TypedCells};
 use super::value::*;
 
 pub type ColumnIndex = HashMap<Value, Vec<usize>>;
 
+// cells shown by Debug
+const DEBUG_CELLS: usize = 10;
+// a remap keeping fewer rows than this share of the column leaves the index to be rebuilt
+// from the kept rows, translating it would cost a pass over every old position
+const REMAP_INDEX_MIN_SHARE: f64 = 0.25;
+
+// cells and typed values are shared on their own, so dropping an index or order of a shared
+// column copies neither
 struct ColumnData {
-    cells: Vec<Value>,
-    maybe_index: RefCell<Option<ColumnIndex>>,
+    cells: Arc<Cells>,
+    // the plain cells of a sparse or dictionary column, built by the first call to cells()
+    maybe_dense: OnceLock<Vec<Value>>,
+    maybe_index: OnceLock<ColumnIndex>,
+    maybe_order: OnceLock<SortOrder>,
+    typed: Option<Arc<TypedCells>>,
+}
+
+// rows in the order sort_column would put them
+enum SortOrder {
+    Ascending,
+    Positions(Vec<usize>),
+}
+
+impl SortOrder {
+    fn position(&self, rank: usize) -> usize {
+        match self {
+            SortOrder::Ascending => rank,
+            SortOrder::Positions(positions) => positions[rank],
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Column {
-    data: Rc<ColumnData>,
+    data: Arc<ColumnData>,
 }
 
 impl Column {
     pub fn new(cells: Vec<Value>) -> Column {
+        Column::with_typed(cells, None)
+    }
+
+    fn with_typed(cells: Vec<Value>, typed: Option<TypedCells>) -> Column {
         Column {
-            data: Rc::new(ColumnData {
-                cells,
-                maybe_index: RefCell::new(None),
+            data: Arc::new(ColumnData {
+                cells: Arc::new(Cells::new(cells)),
+                maybe_dense: OnceLock::new(),
+                maybe_index: OnceLock::new(),
+                maybe_order: OnceLock::new(),
+                typed: typed.map(Arc::new),
             }),
         }
     }
 
+    pub(crate) fn bools(cells: Vec<Value>, values: Vec<Option<bool>>) -> Column {
+        Column::with_typed(cells, Some(TypedCells::Bool(values)))
+    }
+
+    pub fn cast(&self, column_type: ColumnType) -> Res<Column> {
+        let (cells, typed) = TypedCells::cast(self.iter(), column_type)?;
+        Ok(Column::with_typed(cells, typed))
+    }
+
+    pub(crate) fn typed(&self) -> Option<&TypedCells> {
+        self.data.typed.as_deref()
+    }
+
+    pub fn column_type(&self) -> ColumnType {
+        self.typed()
+            .map_or(ColumnType::Str, TypedCells::column_type)
+    }
+
+    pub fn as_i64(&self) -> Option<&[Option<i64>]> {
+        match self.typed() {
+            Some(TypedCells::Int64(values)) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<&[Option<f64>]> {
+        match self.typed() {
+            Some(TypedCells::Float64(values)) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<&[Option<bool>]> {
+        match self.typed() {
+            Some(TypedCells::Bool(values)) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_date_days(&self) -> Option<&[Option<i64>]> {
+        match self.typed() {
+            Some(TypedCells::Date(values)) => Some(values),
+            _ => None,
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.data.cells.len()
     }
 
-    pub fn remap(&self, indices: &[usize]) -> Column {
-        let cells = &self.data.cells;
-        Column::new(indices.iter().map(|&i| cells[i].clone()).collect())
-    }
-
-    pub fn get_index(&self) -> Ref<ColumnIndex> {
-        {
-            let mut maybe_index = self.data.maybe_index.borrow_mut();
-            if maybe_index.is_none() {
-                let mut index: HashMap<Value, Vec<_>> =
-                    HashMap::with_capacity(self.data.cells.len());
-                for (i, cell) in self.data.cells.iter().enumerate() {
-                    if let Some(indices) = index.get_mut(cell) {
-                        indices.push(i);
-                    } else {
-                        let mut indices_list = Vec::with_capacity(1);
-                        indices_list.push(i);
-                        index.insert(cell.clone(), indices_list);
-                    }
+    pub fn is_empty(&self) -> bool {
+        self.data.cells.len() == 0
+    }
+
+    // mostly null columns are stored sparse, cells() works the same for both
+    pub fn is_sparse(&self) -> bool {
+        self.data.cells.is_sparse()
+    }
+
+    // repetitive columns keep each distinct value once, cells() works the same
+    pub fn is_dictionary(&self) -> bool {
+        self.data.cells.dictionary().is_some()
+    }
+
+    pub(crate) fn encoded(cells: Vec<Value>) -> Column {
+        Column {
+            data: Arc::new(ColumnData {
+                cells: Arc::new(Cells::encoded(cells)),
+                maybe_dense: OnceLock::new(),
+                maybe_index: OnceLock::new(),
+                maybe_order: OnceLock::new(),
+                typed: None,
+            }),
+        }
+    }
+
+    // typed values read from the cells, which keep their text: "007" stays "007" as an int64
+    pub(crate) fn parsed(cells: Vec<Value>, column_type: ColumnType, encoded: bool) -> Column {
+        let typed = TypedCells::parse(&cells, column_type);
+        Column {
+            data: Arc::new(ColumnData {
+                cells: Arc::new(if encoded {
+                    Cells::encoded(cells)
+                } else {
+                    Cells::new(cells)
+                }),
+                maybe_dense: OnceLock::new(),
+                maybe_index: OnceLock::new(),
+                maybe_order: OnceLock::new(),
+                typed: typed.map(Arc::new),
+            }),
+        }
+    }
+
+    // dictionary encoded if it repeats few values, typed values are dropped
+    pub fn dictionary_encoded(&self) -> Column {
+        Column::encoded(self.iter().cloned().collect())
+    }
+
+    pub(crate) fn dictionary(&self) -> Option<(&[u32], &[Value])> {
+        self.data.cells.dictionary()
+    }
+
+    pub(crate) fn dictionary_code(&self, value: &str) -> Option<u32> {
+        self.data.cells.dictionary_code(value)
+    }
+
+    pub fn concat(&self, other: &Column) -> Column {
+        let mut cells = Vec::with_capacity(self.len() + other.len());
+        cells.extend(self.iter().cloned());
+        cells.extend(other.iter().cloned());
+        let typed = match (self.typed(), other.typed()) {
+            (Some(a), Some(b)) => a.concat(b),
+            _ => None,
+        };
+        Column::with_typed(cells, typed)
+    }
+
+    pub fn push(&mut self, value: Value) {
+        let data = self.data_mut();
+        // keep an already built index in sync instead of dropping it
+        let position = data.cells.len();
+        data.maybe_dense = OnceLock::new();
+        if let Some(index) = data.maybe_index.get_mut() {
+            index.entry(value.clone()).or_default().push(position);
+        }
+        data.maybe_order = OnceLock::new();
+        if let Some(typed) = &mut data.typed {
+            if !Arc::make_mut(typed).push(&value) {
+                data.typed = None;
+            }
+        }
+        Arc::make_mut(&mut data.cells).push(value);
+    }
+
+    pub(crate) fn set(&mut self, position: usize, value: Value) {
+        let data = self.data_mut();
+        data.maybe_dense = OnceLock::new();
+        data.maybe_index = OnceLock::new();
+        data.maybe_order = OnceLock::new();
+        if let Some(typed) = &mut data.typed {
+            if !Arc::make_mut(typed).set(position, &value) {
+                data.typed = None;
+            }
+        }
+        Arc::make_mut(&mut data.cells).set(position, value);
+    }
+
+    // keep[i] tells whether row i stays
+    pub(crate) fn retain(&mut self, keep: &[bool]) {
+        let data = self.data_mut();
+        data.maybe_dense = OnceLock::new();
+        data.maybe_index = OnceLock::new();
+        // a sorted column stays sorted when rows are dropped
+        if !matches!(data.maybe_order.get(), Some(SortOrder::Ascending)) {
+            data.maybe_order = OnceLock::new();
+        }
+        Arc::make_mut(&mut data.cells).retain(keep);
+        if let Some(typed) = &mut data.typed {
+            Arc::make_mut(typed).retain(keep);
+        }
+    }
+
+    fn data_mut(&mut self) -> &mut ColumnData {
+        if Arc::get_mut(&mut self.data).is_none() {
+            // shared with other tables, the cells are copied by the first write to them
+            self.data = Arc::new(ColumnData {
+                cells: self.data.cells.clone(),
+                maybe_dense: OnceLock::new(),
+                maybe_index: OnceLock::new(),
+                maybe_order: OnceLock::new(),
+                typed: self.data.typed.clone(),
+            });
+        }
+        Arc::get_mut(&mut self.data).unwrap()
+    }
+
+    pub fn sum_f64(&self) -> Res<f64> {
+        Ok(self.numbers()?.iter().sum())
+    }
+
+    pub fn mean_f64(&self) -> Res<f64> {
+        let numbers = self.numbers()?;
+        if numbers.is_empty() {
+            return Err(messages::text(Message::NoNumericValues, &[]));
+        }
+        Ok(numbers.iter().sum::<f64>() / numbers.len() as f64)
+    }
+
+    pub fn min_f64(&self) -> Res<f64> {
+        self.numbers()?
+            .into_iter()
+            .reduce(f64::min)
+            .ok_or_else(|| messages::text(Message::NoNumericValues, &[]))
+    }
+
+    pub fn max_f64(&self) -> Res<f64> {
+        self.numbers()?
+            .into_iter()
+            .reduce(f64::max)
+            .ok_or_else(|| messages::text(Message::NoNumericValues, &[]))
+    }
+
+    // non null values as numbers, typed columns skip parsing
+    fn numbers(&self) -> Res<Vec<f64>> {
+        Ok(self.numbers_by_row()?.into_iter().flatten().collect())
+    }
+
+    pub(crate) fn numbers_by_row(&self) -> Res<Vec<Option<f64>>> {
+        if let Some(values) = self.as_f64() {
+            return Ok(values.to_vec());
+        }
+        if let Some(values) = self.as_i64() {
+            return Ok(values.iter().map(|n| n.map(|n| n as f64)).collect());
+        }
+        self.iter()
+            .enumerate()
+            .map(|(row, cell)| {
+                if cell.is_null() {
+                    return Ok(None);
                 }
-                index.shrink_to_fit();
-                *maybe_index = Some(index);
+                cell.parse::<f64>().map(Some).map_err(|_| {
+                    messages::text(
+                        Message::AtRow,
+                        &[
+                            &row,
+                            &messages::text(Message::NotNumeric, &[&cell.as_str()]),
+                        ],
+                    )
+                })
+            })
+            .collect()
+    }
+
+    pub fn remap(&self, indices: &[usize]) -> Column {
+        // the same rows in the same order share the cells instead of copying them
+        if indices.len() == self.len() && indices.iter().enumerate().all(|(i, &j)| i == j) {
+            return self.clone();
+        }
+        // an index already built is carried over instead of being rebuilt on the next lookup
+        let maybe_index = OnceLock::new();
+        if let Some(index) = self.data.maybe_index.get() {
+            if worth_remapping_index(self.len(), indices.len()) {
+                let _ = maybe_index.set(remap_index(index, self.len(), indices));
             }
         }
-        Ref::map(self.data.maybe_index.borrow(), |opt_some_index| {
-            opt_some_index.as_ref().unwrap()
+        let maybe_order = OnceLock::new();
+        // the scan of the indices is only worth it when there is an order to keep
+        if self.is_sorted() && indices.windows(2).all(|pair| pair[0] < pair[1]) {
+            let _ = maybe_order.set(SortOrder::Ascending);
+        }
+        Column {
+            data: Arc::new(ColumnData {
+                cells: Arc::new(self.data.cells.remap(indices)),
+                maybe_dense: OnceLock::new(),
+                maybe_index,
+                maybe_order,
+                typed: self
+                    .data
+                    .typed
+                    .as_ref()
+                    .map(|typed| Arc::new(typed.remap(indices))),
+            }),
+        }
+    }
+
+    pub fn get_index(&self) -> &ColumnIndex {
+        // built once, threads racing on the first call wait for the winner
+        self.data.maybe_index.get_or_init(|| {
+            if let Some((codes, values)) = self.dictionary() {
+                // one hash per distinct value instead of one per row
+                let mut positions: Vec<Vec<usize>> = vec![Vec::new(); values.len()];
+                for (i, &code) in codes.iter().enumerate() {
+                    positions[code as usize].push(i);
+                }
+                return values
+                    .iter()
+                    .cloned()
+                    .zip(positions)
+                    .filter(|(_, positions)| !positions.is_empty())
+                    .collect();
+            }
+            let cells = &self.data.cells;
+            let mut index: HashMap<Value, Vec<_>> = HashMap::with_capacity(cells.len());
+            for (i, cell) in cells.iter().enumerate() {
+                if let Some(indices) = index.get_mut(cell) {
+                    indices.push(i);
+                } else {
+                    index.insert(cell.clone(), vec![i]);
+                }
+            }
+            index.shrink_to_fit();
+            index
         })
     }
+
+    pub fn distinct_count(&self) -> usize {
+        self.get_index().len()
+    }
+
+    pub(crate) fn same_data(&self, other: &Column) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
+
+    pub fn same_cells(&self, other: &Column) -> bool {
+        Arc::ptr_eq(&self.data, &other.data) || self.iter().eq(other.iter())
+    }
+
     pub fn has_index(&self) -> bool {
-        self.data.maybe_index.borrow().is_some()
+        self.data.maybe_index.get().is_some()
+    }
+
+    // the order of the rows sorted by this column, built once
+    fn sort_order(&self) -> &SortOrder {
+        self.data.maybe_order.get_or_init(|| {
+            let mut positions: Vec<usize> = (0..self.len()).collect();
+            positions.sort_by(|&a, &b| self.compare(a, b));
+            if positions.iter().enumerate().all(|(rank, &p)| rank == p) {
+                SortOrder::Ascending
+            } else {
+                SortOrder::Positions(positions)
+            }
+        })
+    }
+
+    // same order as Table::sort_column
+    fn compare(&self, a: usize, b: usize) -> Ordering {
+        match self.typed() {
+            Some(typed) => typed.compare(a, b),
+            None => self.data.cells.get(a).cmp(self.data.cells.get(b)),
+        }
+    }
+
+    pub(crate) fn create_sorted_index(&self) {
+        self.sort_order();
+    }
+
+    // known to be in sort_column order, without looking at the cells
+    pub fn is_sorted(&self) -> bool {
+        matches!(self.data.maybe_order.get(), Some(SortOrder::Ascending))
     }
 
+    pub fn has_sorted_index(&self) -> bool {
+        self.data.maybe_order.get().is_some()
+    }
+
+    pub(crate) fn mark_sorted(&self) {
+        let _ = self.data.maybe_order.set(SortOrder::Ascending);
+    }
+
+    pub(crate) fn sorted_positions(&self) -> Option<Vec<usize>> {
+        match self.data.maybe_order.get()? {
+            SortOrder::Ascending => Some((0..self.len()).collect()),
+            SortOrder::Positions(positions) => Some(positions.clone()),
+        }
+    }
+
+    // rows with low <= value <= high in sort order, found by binary search on the sorted index
+    pub(crate) fn range(&self, low: &str, high: &str) -> Res<Vec<usize>> {
+        let bounds = match self.typed() {
+            Some(typed) => {
+                let bounds = [Value::from(low), Value::from(high)];
+                let (_, typed_bounds) = TypedCells::cast(bounds.iter(), typed.column_type())?;
+                typed_bounds
+            }
+            None => None,
+        };
+        // bounds are compared like the cells: on text columns "9" comes after "10"
+        let reversed = match &bounds {
+            Some(bounds) => bounds.compare(0, 1) == Ordering::Greater,
+            None => low > high,
+        };
+        if reversed {
+            return Err(messages::text(Message::RangeReversed, &[&low, &high]));
+        }
+        let compare_bound = |position: usize, bound: usize| match (self.typed(), &bounds) {
+            (Some(typed), Some(bounds)) => typed.compare_with(position, bounds, bound),
+            _ => {
+                let bound = if bound == 0 { low } else { high };
+                self.data.cells.get(position).as_str().cmp(bound)
+            }
+        };
+        let order = self.sort_order();
+        let ranks = 0..self.len();
+        let start = partition_point(ranks.clone(), |rank| {
+            compare_bound(order.position(rank), 0) == Ordering::Less
+        });
+        let end = partition_point(ranks, |rank| {
+            compare_bound(order.position(rank), 1) != Ordering::Greater
+        });
+        Ok((start..end.max(start))
+            .map(|rank| order.position(rank))
+            .collect())
+    }
+
+    pub(crate) fn drop_index(&mut self) {
+        match Arc::get_mut(&mut self.data) {
+            Some(data) => {
+                data.maybe_index.take();
+                if let Some(SortOrder::Positions(_)) = data.maybe_order.get() {
+                    data.maybe_order.take();
+                }
+            }
+            // data_mut shares the cells without the index
+            None => {
+                self.data_mut();
+            }
+        }
+    }
+
+    // sparse and dictionary columns are expanded by the first call and keep the expansion,
+    // iter and get read any column without it
     pub fn cells(&self) -> &[Value] {
-        self.data.cells.as_ref()
+        match self.data.cells.dense() {
+            Some(cells) => cells,
+            None => self
+                .data
+                .maybe_dense
+                .get_or_init(|| self.data.cells.iter().cloned().collect()),
+        }
+    }
+
+    pub(crate) fn cells_range(&self, rows: std::ops::Range<usize>) -> Cow<'_, [Value]> {
+        match self.data.cells.dense() {
+            Some(cells) => Cow::Borrowed(&cells[rows]),
+            None => Cow::Owned(rows.map(|row| self.get(row).clone()).collect()),
+        }
+    }
+
+    pub fn get(&self, position: usize) -> &Value {
+        self.data.cells.get(position)
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &Value> + '_ {
+        self.data.cells.iter()
+    }
+}
+
+// first rank in the range for which before is false, before must be true then false
+fn partition_point(ranks: std::ops::Range<usize>, before: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (ranks.start, ranks.end);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if before(middle) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+pub(crate) fn worth_remapping_index(len: usize, kept: usize) -> bool {
+    kept as f64 >= len as f64 * REMAP_INDEX_MIN_SHARE
+}
+
+// the index of the remapped column from the old one, no cell is hashed again
+pub(crate) fn remap_index<K: Clone + Eq + Hash>(
+    index: &HashMap<K, Vec<usize>>,
+    len: usize,
+    indices: &[usize],
+) -> HashMap<K, Vec<usize>> {
+    let mut key_of: Vec<u32> = vec![0; len];
+    let mut keys: Vec<&K> = Vec::with_capacity(index.len());
+    for (value, positions) in index.iter() {
+        for &position in positions {
+            key_of[position] = keys.len() as u32;
+        }
+        keys.push(value);
+    }
+    let mut positions: Vec<Vec<usize>> = vec![Vec::new(); keys.len()];
+    for (new_position, &old_position) in indices.iter().enumerate() {
+        positions[key_of[old_position] as usize].push(new_position);
+    }
+    keys.into_iter()
+        .cloned()
+        .zip(positions)
+        .filter(|(_, positions)| !positions.is_empty())
+        .collect()
+}
+
+impl fmt::Debug for Column {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cells: Vec<&Value> = self.iter().take(DEBUG_CELLS).collect();
+        f.debug_struct("Column")
+            .field("type", &self.column_type())
+            .field("len", &self.len())
+            .field("cells", &cells)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(len: usize) -> Column {
+        Column::new(
+            (0..len)
+                .map(|i| Value::from((i % 10).to_string().as_str()))
+                .collect(),
+        )
+    }
+
+    fn fresh_index(column: &Column) -> ColumnIndex {
+        Column::new(column.iter().cloned().collect())
+            .get_index()
+            .clone()
+    }
+
+    #[test]
+    fn remap_translates_the_index_when_most_rows_stay() {
+        let column = column(100);
+        column.get_index();
+        let indices: Vec<usize> = (0..100).rev().filter(|i| i % 3 != 0).collect();
+        let remapped = column.remap(&indices);
+        assert!(remapped.has_index());
+        assert_eq!(*remapped.get_index(), fresh_index(&remapped));
+    }
+
+    #[test]
+    fn remap_leaves_the_index_unbuilt_when_few_rows_stay() {
+        let column = column(100);
+        column.get_index();
+        let remapped = column.remap(&[7, 3, 17]);
+        assert!(!remapped.has_index());
+        assert_eq!(remapped.get_index()[&Value::from("7")], [0, 2]);
+    }
+
+    #[test]
+    fn dropping_the_index_of_a_shared_column_keeps_sharing_the_cells() {
+        let column = column(100);
+        column.get_index();
+        let mut copy = column.clone();
+        copy.drop_index();
+        assert!(!copy.has_index());
+        assert!(column.has_index());
+        assert!(Arc::ptr_eq(&column.data.cells, &copy.data.cells));
+        copy.push(Value::from("x"));
+        assert_eq!(column.len(), 100);
+        assert_eq!(copy.len(), 101);
+    }
+
+    #[test]
+    fn concat_keeps_the_type_both_columns_share() {
+        let ints = column(3).cast(ColumnType::Int64).unwrap();
+        let joined = ints.concat(&ints);
+        assert_eq!(joined.len(), 6);
+        assert_eq!(joined.column_type(), ColumnType::Int64);
+        assert_eq!(joined.get(4).as_str(), "1");
+        assert_eq!(ints.concat(&column(2)).column_type(), ColumnType::Str);
+    }
+
+    #[test]
+    fn push_copies_shared_cells_and_keeps_an_owned_index_in_sync() {
+        let column = column(12).cast(ColumnType::Int64).unwrap();
+        column.get_index();
+        let mut grown = column.clone();
+        grown.push(Value::from("3"));
+        assert_eq!(column.len(), 12);
+        assert!(column.has_index());
+        assert_eq!(grown.column_type(), ColumnType::Int64);
+
+        grown.get_index();
+        grown.push(Value::from("3"));
+        assert!(grown.has_index());
+        assert_eq!(*grown.get_index(), fresh_index(&grown));
+        grown.push(Value::from("x"));
+        assert_eq!(*grown.get_index(), fresh_index(&grown));
+        assert_eq!(grown.column_type(), ColumnType::Str);
+    }
+
+    fn text_column(cells: &[&str]) -> Column {
+        Column::new(cells.iter().map(|&cell| Value::from(cell)).collect())
+    }
+
+    #[test]
+    fn numeric_helpers_skip_nulls_and_name_the_bad_row() {
+        let column = text_column(&["1.5", "", "-2", "4"]);
+        assert_eq!(column.sum_f64().unwrap(), 3.5);
+        assert_eq!(column.mean_f64().unwrap(), 3.5 / 3.0);
+        assert_eq!(column.min_f64().unwrap(), -2.0);
+        assert_eq!(column.max_f64().unwrap(), 4.0);
+        let ints = text_column(&["3", "", "5"])
+            .cast(ColumnType::Int64)
+            .unwrap();
+        assert_eq!(ints.mean_f64().unwrap(), 4.0);
+
+        let err = text_column(&["1", "x"]).sum_f64().err().unwrap();
+        assert_eq!(err, "E005: row 1: E019: value 'x' is not numeric");
+        let empty = text_column(&["", ""]);
+        assert_eq!(empty.sum_f64().unwrap(), 0.0);
+        assert_eq!(
+            empty.mean_f64().err().unwrap(),
+            "E027: no numeric values in the column"
+        );
+        assert!(empty.max_f64().is_err());
+    }
+
+    #[test]
+    fn debug_shows_the_type_and_the_first_cells() {
+        assert_eq!(format!("{:?}", Value::from("a\"b")), "\"a\\\"b\"");
+        let debug = format!("{:?}", text_column(&["1", "2"]));
+        assert_eq!(debug, "Column { type: Str, len: 2, cells: [\"1\", \"2\"] }");
+        let long = format!("{:?}", column(100));
+        assert!(
+            long.starts_with("Column { type: Str, len: 100, cells: [\"0\""),
+            "{}",
+            long
+        );
+        assert!(long.matches(", ").count() < 100, "{}", long);
+    }
+
+    #[test]
+    fn identity_remaps_share_the_cells_and_the_index() {
+        let column = column(20);
+        column.get_index();
+        let same = column.remap(&(0..20).collect::<Vec<_>>());
+        assert!(Arc::ptr_eq(&same.data, &column.data));
+        assert!(same.has_index());
+        let reversed = column.remap(&(0..20).rev().collect::<Vec<_>>());
+        assert!(!Arc::ptr_eq(&reversed.data, &column.data));
+        assert_eq!(reversed.get(0), column.get(19));
+        let prefix = column.remap(&(0..10).collect::<Vec<_>>());
+        assert!(!Arc::ptr_eq(&prefix.data, &column.data));
+        assert_eq!(prefix.len(), 10);
+    }
+
+    #[test]
+    fn sparse_cells_are_expanded_once_and_only_when_asked() {
+        let plain: Vec<Value> = (0..100)
+            .map(|i| match i {
+                7 => Value::from("x"),
+                _ => Value::null(),
+            })
+            .collect();
+        let mut sparse = Column::new(plain.clone());
+        assert!(sparse.is_sparse());
+        assert!(sparse.iter().eq(plain.iter()));
+        assert_eq!(sparse.get(7).as_str(), "x");
+        assert_eq!(sparse.cast(ColumnType::Str).unwrap().len(), 100);
+        assert!(sparse.data.maybe_dense.get().is_none());
+        let first = sparse.cells().as_ptr();
+        assert_eq!(sparse.cells(), &plain[..]);
+        assert_eq!(sparse.cells().as_ptr(), first);
+        // a write drops the expansion, the next call sees the new cell
+        sparse.push(Value::from("y"));
+        assert!(sparse.data.maybe_dense.get().is_none());
+        assert_eq!(sparse.cells()[100].as_str(), "y");
     }
 }
@@ -0,0 +1,141 @@
+use super::column::Column;
+use super::value::Value;
+use std::collections::HashMap;
+
+// columns in insertion order, with a name index for lookups
+#[derive(Clone, Default)]
+pub(crate) struct ColumnMap {
+    entries: Vec<(Value, Column)>,
+    positions: HashMap<Value, usize>,
+}
+
+impl ColumnMap {
+    pub(crate) fn with_capacity(capacity: usize) -> ColumnMap {
+        ColumnMap {
+            entries: Vec::with_capacity(capacity),
+            positions: HashMap::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn get(&self, col_name: &str) -> Option<&Column> {
+        self.positions
+            .get(col_name)
+            .map(|&position| &self.entries[position].1)
+    }
+
+    pub(crate) fn get_mut(&mut self, col_name: &str) -> Option<&mut Column> {
+        let position = *self.positions.get(col_name)?;
+        Some(&mut self.entries[position].1)
+    }
+
+    pub(crate) fn position(&self, col_name: &str) -> Option<usize> {
+        self.positions.get(col_name).copied()
+    }
+
+    pub(crate) fn contains_key(&self, col_name: &str) -> bool {
+        self.positions.contains_key(col_name)
+    }
+
+    // a column that already exists is replaced in place, a new one goes last
+    pub(crate) fn insert(&mut self, col_name: Value, column: Column) -> Option<Column> {
+        match self.positions.get(&col_name) {
+            Some(&position) => Some(std::mem::replace(&mut self.entries[position].1, column)),
+            None => {
+                self.positions.insert(col_name.clone(), self.entries.len());
+                self.entries.push((col_name, column));
+                None
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, col_name: &str) -> Option<Column> {
+        let position = self.positions.remove(col_name)?;
+        let (_, column) = self.entries.remove(position);
+        for (name, _) in self.entries[position..].iter() {
+            *self.positions.get_mut(name).unwrap() -= 1;
+        }
+        Some(column)
+    }
+
+    // keeps the position of the column
+    pub(crate) fn rename(&mut self, col_name: &str, new_col_name: Value) {
+        if let Some(position) = self.positions.remove(col_name) {
+            self.positions.insert(new_col_name.clone(), position);
+            self.entries[position].0 = new_col_name;
+        }
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(col_name, _)| col_name)
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Column> {
+        self.entries.iter().map(|(_, column)| column)
+    }
+
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut Column> {
+        self.entries.iter_mut().map(|(_, column)| column)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&Value, &Column)> {
+        self.entries
+            .iter()
+            .map(|(col_name, column)| (col_name, column))
+    }
+}
+
+impl IntoIterator for ColumnMap {
+    type Item = (Value, Column);
+    type IntoIter = std::vec::IntoIter<(Value, Column)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl std::ops::Index<&str> for ColumnMap {
+    type Output = Column;
+
+    fn index(&self, col_name: &str) -> &Column {
+        &self.entries[self.positions[col_name]].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(map: &ColumnMap) -> Vec<&str> {
+        map.keys().map(Value::as_str).collect()
+    }
+
+    fn column(cell: &str) -> Column {
+        Column::new(vec![Value::from(cell)])
+    }
+
+    #[test]
+    fn columns_keep_their_insertion_order() {
+        let mut map = ColumnMap::default();
+        for name in ["z", "a", "m", "b"] {
+            map.insert(Value::from(name), column(name));
+        }
+        assert!(map.insert(Value::from("a"), column("new")).is_some());
+        assert_eq!(names(&map), vec!["z", "a", "m", "b"]);
+        assert_eq!(map["a"].get(0).as_str(), "new");
+
+        assert_eq!(map.remove("a").unwrap().get(0).as_str(), "new");
+        assert_eq!(names(&map), vec!["z", "m", "b"]);
+        assert_eq!(map.position("b"), Some(2));
+        assert_eq!(map["b"].get(0).as_str(), "b");
+
+        map.rename("z", Value::from("y"));
+        assert_eq!(names(&map), vec!["y", "m", "b"]);
+        assert!(map.get("z").is_none());
+        assert_eq!(map.get("y").unwrap().get(0).as_str(), "z");
+        assert!(map.remove("z").is_none());
+    }
+}
@@ -0,0 +1,74 @@
+use super::column::{remap_index, worth_remapping_index, Column};
+use super::column_map::ColumnMap;
+use super::value::Value;
+use std::collections::HashMap;
+
+// an index over several columns, it holds their data so a changed column is noticed
+pub(crate) struct CompositeIndex {
+    columns: Vec<(Value, Column)>,
+    index: HashMap<Vec<Value>, Vec<usize>>,
+}
+
+impl CompositeIndex {
+    pub(crate) fn build(columns: Vec<(Value, Column)>) -> CompositeIndex {
+        let rows = columns.first().map_or(0, |(_, column)| column.len());
+        let mut index: HashMap<Vec<Value>, Vec<usize>> = HashMap::new();
+        for position in 0..rows {
+            index
+                .entry(key(&columns, position))
+                .or_default()
+                .push(position);
+        }
+        CompositeIndex { columns, index }
+    }
+
+    pub(crate) fn is_on(&self, col_names: &[&str]) -> bool {
+        self.columns.len() == col_names.len()
+            && self
+                .columns
+                .iter()
+                .zip(col_names)
+                .all(|((name, _), col_name)| name.as_str() == *col_name)
+    }
+
+    // false once any of the columns was replaced or written to
+    pub(crate) fn is_valid(&self, columns: &ColumnMap) -> bool {
+        self.columns.iter().all(|(col_name, column)| {
+            columns
+                .get(col_name)
+                .is_some_and(|current| current.same_data(column))
+        })
+    }
+
+    pub(crate) fn get(&self, key: &[Value]) -> Option<&Vec<usize>> {
+        self.index.get(key)
+    }
+
+    pub(crate) fn groups(&self) -> impl Iterator<Item = &[usize]> {
+        self.index.values().map(Vec::as_slice)
+    }
+
+    // the index of the remapped columns, translated without hashing the keys again, none when
+    // so few rows are kept that building it again on them is cheaper
+    pub(crate) fn remap(&self, columns: &ColumnMap, positions: &[usize]) -> Option<CompositeIndex> {
+        let rows = self.columns.first().map_or(0, |(_, column)| column.len());
+        if !worth_remapping_index(rows, positions.len()) {
+            return None;
+        }
+        Some(CompositeIndex {
+            columns: self
+                .columns
+                .iter()
+                .map(|(col_name, _)| (col_name.clone(), columns[col_name.as_str()].clone()))
+                .collect(),
+            index: remap_index(&self.index, rows, positions),
+        })
+    }
+}
+
+pub(crate) fn key(columns: &[(Value, Column)], position: usize) -> Vec<Value> {
+    columns
+        .iter()
+        .map(|(_, column)| column.get(position).clone())
+        .collect()
+}
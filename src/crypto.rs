@@ -0,0 +1,109 @@
+use super::messages::{self, Message};
+use super::table::Res;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes128Gcm, Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+const NONCE_LEN: usize = 12;
+
+pub enum Cipher {
+    Aes128(Box<Aes128Gcm>),
+    Aes256(Box<Aes256Gcm>),
+}
+
+impl Cipher {
+    pub fn new(key: &[u8]) -> Res<Cipher> {
+        match key.len() {
+            16 => Ok(Cipher::Aes128(Box::new(
+                Aes128Gcm::new_from_slice(key).unwrap(),
+            ))),
+            32 => Ok(Cipher::Aes256(Box::new(
+                Aes256Gcm::new_from_slice(key).unwrap(),
+            ))),
+            len => Err(messages::text(Message::InvalidKeyLength, &[&len])),
+        }
+    }
+
+    // base64 of a random nonce followed by the ciphertext and its tag
+    pub fn encrypt(&self, cell: &str) -> String {
+        let nonce = Aes128Gcm::generate_nonce(&mut OsRng);
+        // AES-GCM only refuses plaintexts longer than 64 GiB
+        let sealed = match self {
+            Cipher::Aes128(cipher) => cipher.encrypt(&nonce, cell.as_bytes()),
+            Cipher::Aes256(cipher) => cipher.encrypt(&nonce, cell.as_bytes()),
+        }
+        .expect("cell too long to encrypt");
+        let mut bytes = Vec::with_capacity(NONCE_LEN + sealed.len());
+        bytes.extend_from_slice(&nonce);
+        bytes.extend(sealed);
+        STANDARD.encode(bytes)
+    }
+
+    pub fn decrypt(&self, cell: &str) -> Res<String> {
+        let invalid = || messages::text(Message::InvalidCiphertext, &[]);
+        let bytes = STANDARD
+            .decode(cell)
+            .map_err(|_| messages::text(Message::InvalidBase64, &[&cell]))?;
+        if bytes.len() < NONCE_LEN {
+            return Err(invalid());
+        }
+        let (nonce, sealed) = bytes.split_at(NONCE_LEN);
+        let nonce = Nonce::from_slice(nonce);
+        let plain = match self {
+            Cipher::Aes128(cipher) => cipher.decrypt(nonce, sealed),
+            Cipher::Aes256(cipher) => cipher.decrypt(nonce, sealed),
+        }
+        .map_err(|_| invalid())?;
+        String::from_utf8(plain).map_err(|_| invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_answer_from_the_gcm_specification() {
+        // test case 2 of the GCM specification: zero key, zero nonce, 16 zero bytes
+        let mut sealed = vec![0u8; NONCE_LEN];
+        sealed.extend_from_slice(&[
+            0x03, 0x88, 0xda, 0xce, 0x60, 0xb6, 0xa3, 0x92, 0xf3, 0x28, 0xc2, 0xb9, 0x71, 0xb2,
+            0xfe, 0x78, 0xab, 0x6e, 0x47, 0xd4, 0x2c, 0xec, 0x13, 0xbd, 0xf5, 0x3a, 0x67, 0xb2,
+            0x12, 0x57, 0xbd, 0xdf,
+        ]);
+        let cipher = Cipher::new(&[0; 16]).unwrap();
+        assert_eq!(
+            cipher.decrypt(&STANDARD.encode(&sealed)).unwrap(),
+            "\0".repeat(16)
+        );
+        sealed[NONCE_LEN] ^= 1;
+        let err = cipher.decrypt(&STANDARD.encode(&sealed)).err().unwrap();
+        assert_eq!(err, "E060: invalid encrypted data or wrong key");
+    }
+
+    #[test]
+    fn cells_round_trip_with_fresh_nonces() {
+        let cipher = Cipher::new(&[7; 32]).unwrap();
+        let first = cipher.encrypt("secret");
+        let second = cipher.encrypt("secret");
+        assert_ne!(first, second);
+        assert_eq!(cipher.decrypt(&first).unwrap(), "secret");
+        assert_eq!(cipher.decrypt(&second).unwrap(), "secret");
+        let other = Cipher::new(&[8; 32]).unwrap();
+        assert!(other.decrypt(&first).unwrap_err().starts_with("E060: "));
+        assert_eq!(
+            cipher.decrypt("AAAA").unwrap_err(),
+            "E060: invalid encrypted data or wrong key"
+        );
+        assert_eq!(
+            cipher.decrypt("not base64!").unwrap_err(),
+            "E061: invalid base64 'not base64!'"
+        );
+        let err = Cipher::new(&[0; 24]).err().unwrap();
+        assert_eq!(
+            err,
+            "E059: invalid key of 24 bytes, 16 or 32 bytes are needed"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use super::format::TsvEscaping;
+use super::messages::{self, Message};
+use super::table::{Res, Table};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Default)]
+pub struct Database {
+    tables: Vec<(String, Table, Vec<String>)>,
+}
+
+impl Database {
+    pub fn new() -> Database {
+        Database::default()
+    }
+
+    pub fn register(&mut self, name: &str, table: Table, header: Vec<String>) -> Res<()> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(messages::text(Message::InvalidTableName, &[&name]));
+        }
+        let table = table.with_name(name);
+        match self.tables.iter_mut().find(|(n, _, _)| n == name) {
+            Some(entry) => *entry = (name.to_string(), table, header),
+            None => self.tables.push((name.to_string(), table, header)),
+        }
+        Ok(())
+    }
+
+    pub fn table(&self, name: &str) -> Res<&Table> {
+        self.tables
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .map(|(_, table, _)| table)
+            .ok_or_else(|| messages::text(Message::TableNotRegistered, &[&name]))
+    }
+
+    pub fn write_all(&self, dir: &str) -> Res<()> {
+        self.write_all_with(dir, TsvEscaping::default())
+    }
+
+    pub fn write_all_with(&self, dir: &str, escaping: TsvEscaping) -> Res<()> {
+        let target = Path::new(dir);
+        let staging = sibling(target, "tmp");
+        let write_error = |err: std::io::Error| messages::text(Message::WriteTables, &[&dir, &err]);
+        if staging.exists() {
+            fs::remove_dir_all(&staging).map_err(write_error)?;
+        }
+        fs::create_dir_all(&staging).map_err(write_error)?;
+        for (name, table, header) in self.tables.iter() {
+            let header: Vec<&str> = header.iter().map(String::as_str).collect();
+            let tsv = table.to_tsv_with(&header, escaping)?;
+            let mut file =
+                File::create(staging.join(format!("{}.tsv", name))).map_err(write_error)?;
+            file.write_all(tsv.as_bytes()).map_err(write_error)?;
+            file.sync_all().map_err(write_error)?;
+        }
+        sync_dir(&staging).map_err(write_error)?;
+        // a directory cannot replace another in one rename, so the old set is moved aside
+        // first. Between the two renames the target is missing and a reader finds no tables,
+        // but never a mix of old and new files. A failed second rename puts the old set back.
+        if target.exists() {
+            let previous = sibling(target, "old");
+            if previous.exists() {
+                fs::remove_dir_all(&previous).map_err(write_error)?;
+            }
+            fs::rename(target, &previous).map_err(write_error)?;
+            if let Err(err) = fs::rename(&staging, target) {
+                let _ = fs::rename(&previous, target);
+                return Err(write_error(err));
+            }
+            fs::remove_dir_all(&previous).map_err(write_error)?;
+        } else {
+            fs::rename(&staging, target).map_err(write_error)?;
+        }
+        // the renames are only durable once the parent directory is on disk
+        match target.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => sync_dir(parent),
+            _ => sync_dir(Path::new(".")),
+        }
+        .map_err(write_error)
+    }
+}
+
+// directories cannot be opened for syncing on every platform
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+fn sibling(dir: &Path, suffix: &str) -> PathBuf {
+    let mut name = dir.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}-{}", suffix, std::process::id()));
+    dir.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sql-database-{}-{}", std::process::id(), name))
+    }
+
+    fn database(cell: &str) -> Database {
+        let mut table = Table::parse_tsv("id\tname\n1\tone\n", 0).unwrap();
+        table.set_cell("name", 0, cell).unwrap();
+        let mut database = Database::new();
+        database
+            .register("t", table, vec!["id".to_string(), "name".to_string()])
+            .unwrap();
+        database
+    }
+
+    #[test]
+    fn write_all_replaces_the_previous_set() {
+        let dir = temp_dir("replace");
+        let dir_name = dir.to_str().unwrap();
+        database("first").write_all(dir_name).unwrap();
+        fs::write(dir.join("stale.tsv"), "x").unwrap();
+        database("second").write_all(dir_name).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("t.tsv")).unwrap(),
+            "id\tname\n1\tsecond"
+        );
+        assert!(!dir.join("stale.tsv").exists());
+        assert!(!sibling(&dir, "old").exists());
+        assert!(!sibling(&dir, "tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_all_with_escapes_cells_that_break_rows() {
+        let dir = temp_dir("escape");
+        let dir_name = dir.to_str().unwrap();
+        assert!(database("a\tb").write_all(dir_name).is_err());
+        assert!(!dir.exists());
+        database("a\tb")
+            .write_all_with(dir_name, TsvEscaping::Backslash)
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("t.tsv")).unwrap(),
+            "id\tname\n1\ta\\tb"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        let _ = fs::remove_dir_all(sibling(&dir, "tmp"));
+    }
+}
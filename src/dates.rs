@@ -0,0 +1,413 @@
+use std::cmp::Ordering;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        _ if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        _ => 28,
+    }
+}
+
+pub fn parse_iso_date(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+pub fn format_iso_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// format uses %Y %y %m %d %b %B and %%, any other character must match itself
+pub fn parse_date(date: &str, format: &str) -> Option<i64> {
+    let mut rest = date;
+    let mut year = None;
+    let mut month = None;
+    let mut day = None;
+    let mut spec = format.chars();
+    while let Some(c) = spec.next() {
+        if c != '%' {
+            rest = rest.strip_prefix(c)?;
+            continue;
+        }
+        match spec.next()? {
+            'Y' => year = Some(take_number(&mut rest, 4)?),
+            'y' => {
+                let short = take_number(&mut rest, 2)?;
+                year = Some(if short < 69 {
+                    2000 + short
+                } else {
+                    1900 + short
+                });
+            }
+            'm' => month = Some(take_number(&mut rest, 2)? as u32),
+            'd' => day = Some(take_number(&mut rest, 2)? as u32),
+            'b' | 'B' => {
+                // full name first, then the three letter abbreviation
+                let (index, len) = MONTHS.iter().enumerate().find_map(|(index, name)| {
+                    [name.len(), 3]
+                        .iter()
+                        .copied()
+                        .find(|&len| {
+                            rest.get(..len)
+                                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&name[..len]))
+                        })
+                        .map(|len| (index, len))
+                })?;
+                rest = &rest[len..];
+                month = Some(index as u32 + 1);
+            }
+            '%' => rest = rest.strip_prefix('%')?,
+            _ => return None,
+        }
+    }
+    let (year, month, day) = (year?, month?, day?);
+    if !rest.is_empty() || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month)
+    {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+pub fn format_date(days: i64, format: &str) -> String {
+    let (year, month, day) = civil_from_days(days);
+    let mut result = String::with_capacity(format.len() + 8);
+    let mut spec = format.chars();
+    while let Some(c) = spec.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match spec.next() {
+            Some('Y') => result += &format!("{:04}", year),
+            Some('y') => result += &format!("{:02}", year.rem_euclid(100)),
+            Some('m') => result += &format!("{:02}", month),
+            Some('d') => result += &format!("{:02}", day),
+            Some('b') => result += &MONTHS[month as usize - 1][..3],
+            Some('B') => result += MONTHS[month as usize - 1],
+            Some('%') => result.push('%'),
+            Some(other) => {
+                result.push('%');
+                result.push(other);
+            }
+            None => result.push('%'),
+        }
+    }
+    result
+}
+
+// dates that do not parse sort after all the others
+pub fn order_by_format(format: &str) -> impl Fn(&str, &str) -> Ordering + '_ {
+    move |a, b| match (parse_date(a, format), parse_date(b, format)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+fn take_number(rest: &mut &str, max_digits: usize) -> Option<i64> {
+    let digits = rest
+        .bytes()
+        .take(max_digits)
+        .take_while(u8::is_ascii_digit)
+        .count();
+    if digits == 0 {
+        return None;
+    }
+    let number = rest[..digits].parse().ok()?;
+    *rest = &rest[digits..];
+    Some(number)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DateTime {
+    pub utc_seconds: i64,
+    pub nanos: u32,
+    pub offset_seconds: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl DateTime {
+    // YYYY-MM-DD[T ]HH:MM[:SS[.fraction]] followed by Z, +HH:MM, +HHMM or nothing for UTC
+    pub fn parse(text: &str) -> Option<DateTime> {
+        let (date, rest) = (text.get(..10)?, text.get(10..)?);
+        let days = parse_iso_date(date)?;
+        let rest = rest.strip_prefix(['T', 't', ' '])?;
+        let mut rest = rest;
+        let hour = take_fixed(&mut rest, 2)?;
+        rest = rest.strip_prefix(':')?;
+        let minute = take_fixed(&mut rest, 2)?;
+        let mut second = 0;
+        let mut nanos = 0;
+        if let Some(after) = rest.strip_prefix(':') {
+            rest = after;
+            second = take_fixed(&mut rest, 2)?;
+            if let Some(after) = rest.strip_prefix(['.', ',']) {
+                let digits = after.bytes().take_while(u8::is_ascii_digit).count();
+                if digits == 0 || digits > 9 {
+                    return None;
+                }
+                nanos = after[..digits].parse::<u32>().ok()? * 10u32.pow(9 - digits as u32);
+                rest = &after[digits..];
+            }
+        }
+        if hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+        let offset_seconds = if rest.is_empty() {
+            0
+        } else {
+            parse_offset(rest)?
+        };
+        let local_seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+        Some(DateTime {
+            utc_seconds: local_seconds - offset_seconds as i64,
+            nanos,
+            offset_seconds,
+        })
+    }
+
+    pub fn with_offset(self, offset_seconds: i32) -> DateTime {
+        DateTime {
+            offset_seconds,
+            ..self
+        }
+    }
+
+    // truncates in local time, so a day starts at local midnight
+    pub fn truncate(self, unit: TimeUnit) -> DateTime {
+        let step = match unit {
+            TimeUnit::Minute => 60,
+            TimeUnit::Hour => 3_600,
+            TimeUnit::Day => 86_400,
+        };
+        let local_seconds = self.utc_seconds + self.offset_seconds as i64;
+        DateTime {
+            utc_seconds: local_seconds
+                - local_seconds.rem_euclid(step)
+                - self.offset_seconds as i64,
+            nanos: 0,
+            offset_seconds: self.offset_seconds,
+        }
+    }
+
+    pub fn format(&self) -> String {
+        let local_seconds = self.utc_seconds + self.offset_seconds as i64;
+        let days = local_seconds.div_euclid(86_400);
+        let seconds_of_day = local_seconds.rem_euclid(86_400);
+        let mut result = format!(
+            "{}T{:02}:{:02}:{:02}",
+            format_iso_date(days),
+            seconds_of_day / 3_600,
+            seconds_of_day % 3_600 / 60,
+            seconds_of_day % 60
+        );
+        if self.nanos > 0 {
+            let fraction = format!("{:09}", self.nanos);
+            result.push('.');
+            result += fraction.trim_end_matches('0');
+        }
+        result += &format_offset(self.offset_seconds);
+        result
+    }
+}
+
+// Z, UTC, +HH:MM, +HHMM or +HH
+pub fn parse_offset(offset: &str) -> Option<i32> {
+    if offset == "Z" || offset == "z" || offset == "UTC" {
+        return Some(0);
+    }
+    let sign = match offset.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let mut rest = &offset[1..];
+    let hours = take_fixed(&mut rest, 2)?;
+    let rest = rest.strip_prefix(':').unwrap_or(rest);
+    let minutes = if rest.is_empty() {
+        0
+    } else {
+        let mut rest = rest;
+        let minutes = take_fixed(&mut rest, 2)?;
+        if !rest.is_empty() {
+            return None;
+        }
+        minutes
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3_600 + minutes * 60) as i32)
+}
+
+fn format_offset(offset_seconds: i32) -> String {
+    if offset_seconds == 0 {
+        return String::from("Z");
+    }
+    let sign = if offset_seconds < 0 { '-' } else { '+' };
+    let minutes = offset_seconds.abs() / 60;
+    format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+fn take_fixed(rest: &mut &str, digits: usize) -> Option<i64> {
+    let text = rest.get(..digits)?;
+    if !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    *rest = &rest[digits..];
+    text.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iso_dates_round_trip_through_day_numbers() {
+        assert_eq!(parse_iso_date("1970-01-01"), Some(0));
+        assert_eq!(parse_iso_date("2000-03-01"), Some(11_017));
+        for days in [-700_000, -1, 0, 59, 60, 11_016, 19_782, 2_932_896] {
+            assert_eq!(parse_iso_date(&format_iso_date(days)), Some(days));
+        }
+        assert_eq!(parse_iso_date("2024-02-29"), Some(19_782));
+        assert_eq!(parse_iso_date("2023-02-29"), None);
+        assert_eq!(parse_iso_date("1900-02-29"), None);
+        assert_eq!(parse_iso_date("2024-13-01"), None);
+    }
+
+    #[test]
+    fn formats_parse_and_print_the_same_dates() {
+        let day = parse_iso_date("2023-02-01").unwrap();
+        assert_eq!(parse_date("01/02/2023", "%d/%m/%Y"), Some(day));
+        assert_eq!(parse_date("1/2/23", "%d/%m/%y"), Some(day));
+        assert_eq!(parse_date("1 Feb 2023", "%d %b %Y"), Some(day));
+        assert_eq!(parse_date("1 february 2023", "%d %B %Y"), Some(day));
+        assert_eq!(
+            parse_date("1/2/69", "%d/%m/%y"),
+            parse_iso_date("1969-02-01")
+        );
+        assert_eq!(parse_date("31/02/2023", "%d/%m/%Y"), None);
+        assert_eq!(parse_date("01/02/2023 x", "%d/%m/%Y"), None);
+        assert_eq!(format_date(day, "%d/%m/%y"), "01/02/23");
+        assert_eq!(
+            format_date(day, "%B %d, %Y (100%%)"),
+            "February 01, 2023 (100%)"
+        );
+    }
+
+    #[test]
+    fn format_order_sorts_unparsed_dates_last() {
+        let mut dates = vec!["soon", "02/01/2023", "31/12/2022", "01/02/2023"];
+        let order = order_by_format("%d/%m/%Y");
+        dates.sort_by(|a, b| order(a, b));
+        assert_eq!(
+            dates,
+            vec!["31/12/2022", "02/01/2023", "01/02/2023", "soon"]
+        );
+    }
+
+    #[test]
+    fn datetimes_keep_their_offset_and_compare_in_utc() {
+        let local = DateTime::parse("2024-03-10 23:30:05.250+02:00").unwrap();
+        let utc = DateTime::parse("2024-03-10T21:30:05.25Z").unwrap();
+        assert_eq!(local.utc_seconds, utc.utc_seconds);
+        assert_eq!(local.nanos, 250_000_000);
+        assert_eq!(local.format(), "2024-03-10T23:30:05.25+02:00");
+        assert_eq!(
+            utc.with_offset(-5 * 3_600).format(),
+            "2024-03-10T16:30:05.25-05:00"
+        );
+        assert_eq!(
+            DateTime::parse("2024-03-10T09:15").unwrap().format(),
+            "2024-03-10T09:15:00Z"
+        );
+        assert_eq!(DateTime::parse("2024-03-10T24:00"), None);
+        assert_eq!(DateTime::parse("2024-03-10T09:15+2"), None);
+    }
+
+    #[test]
+    fn truncation_happens_in_local_time() {
+        let local = DateTime::parse("2024-03-11T01:45:30+02:00").unwrap();
+        assert_eq!(
+            local.truncate(TimeUnit::Day).format(),
+            "2024-03-11T00:00:00+02:00"
+        );
+        assert_eq!(
+            local.truncate(TimeUnit::Hour).format(),
+            "2024-03-11T01:00:00+02:00"
+        );
+        let utc = local.with_offset(0);
+        assert_eq!(utc.truncate(TimeUnit::Day).format(), "2024-03-10T00:00:00Z");
+        let before_epoch = DateTime::parse("1969-12-31T23:59:59Z").unwrap();
+        assert_eq!(
+            before_epoch.truncate(TimeUnit::Minute).format(),
+            "1969-12-31T23:59:00Z"
+        );
+    }
+
+    #[test]
+    fn offsets_accept_the_usual_spellings() {
+        assert_eq!(parse_offset("Z"), Some(0));
+        assert_eq!(parse_offset("UTC"), Some(0));
+        assert_eq!(parse_offset("+05:30"), Some(19_800));
+        assert_eq!(parse_offset("-0800"), Some(-28_800));
+        assert_eq!(parse_offset("+01"), Some(3_600));
+        assert_eq!(parse_offset("+24:00"), None);
+        assert_eq!(parse_offset("0100"), None);
+    }
+}
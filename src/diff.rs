@@ -0,0 +1,157 @@
+use super::messages::{self, Message};
+use super::table::{row_key, Res, Table};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+//   - column discount
+//   + column currency
+//   - id=3 | name=bolt | price=0.10
+//   ~ id=4 | price: 2.50 -> 2.75
+//   + id=9 | name=nut | price=0.05
+// rows of old are matched to rows of new by the key columns
+pub(crate) fn render(old: &Table, new: &Table, key_cols: &[&str]) -> Res<String> {
+    let keys_old = index(old, key_cols)?;
+    let keys_new = index(new, key_cols)?;
+    let names_old = old.column_names();
+    let names_new = new.column_names();
+    let mut output = String::new();
+    for col_name in names_old.iter().filter(|c| !names_new.contains(c)) {
+        let _ = writeln!(output, "- column {}", col_name);
+    }
+    for col_name in names_new.iter().filter(|c| !names_old.contains(c)) {
+        let _ = writeln!(output, "+ column {}", col_name);
+    }
+    let shared: Vec<&str> = names_old
+        .iter()
+        .copied()
+        .filter(|c| names_new.contains(c) && !key_cols.contains(c))
+        .collect();
+    let describe = |key: &[&str]| -> String {
+        key_cols
+            .iter()
+            .zip(key)
+            .map(|(col_name, value)| format!("{}={}", col_name, value))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    };
+    let whole_row = |table: &Table, names: &[&str], key: &[&str], position: usize| {
+        let mut line = describe(key);
+        for col_name in names.iter().filter(|c| !key_cols.contains(c)) {
+            let _ = write!(line, " | {}={}", col_name, cell(table, col_name, position));
+        }
+        line
+    };
+    for (key, &position_old) in keys_old.iter() {
+        match keys_new.get(key) {
+            None => {
+                let line = whole_row(old, &names_old, key, position_old);
+                let _ = writeln!(output, "- {}", line);
+            }
+            Some(&position_new) => {
+                let mut line = describe(key);
+                let mut changed = false;
+                for &col_name in shared.iter() {
+                    let before = cell(old, col_name, position_old);
+                    let after = cell(new, col_name, position_new);
+                    if before != after {
+                        changed = true;
+                        let _ = write!(line, " | {}: {} -> {}", col_name, before, after);
+                    }
+                }
+                if changed {
+                    let _ = writeln!(output, "~ {}", line);
+                }
+            }
+        }
+    }
+    for (key, &position_new) in keys_new.iter() {
+        if !keys_old.contains_key(key) {
+            let line = whole_row(new, &names_new, key, position_new);
+            let _ = writeln!(output, "+ {}", line);
+        }
+    }
+    Ok(output)
+}
+
+fn cell<'t>(table: &'t Table, col_name: &str, position: usize) -> &'t str {
+    table.cell(position, col_name).unwrap_or_default()
+}
+
+// key of every row with its position, in row order
+fn index<'t>(table: &'t Table, key_cols: &[&str]) -> Res<KeyIndex<'t>> {
+    let mut columns = Vec::with_capacity(key_cols.len());
+    for &col_name in key_cols {
+        columns.push(table.column_ref(col_name)?);
+    }
+    let mut index = KeyIndex::default();
+    for position in 0..table.rows_count() {
+        let key = row_key(&columns, position);
+        if let Some(&first) = index.positions.get(&key) {
+            return Err(messages::text(
+                Message::DiffKeyDuplicate,
+                &[&key.join(", "), &first, &position],
+            ));
+        }
+        index.positions.insert(key.clone(), position);
+        index.keys.push(key);
+    }
+    Ok(index)
+}
+
+#[derive(Default)]
+struct KeyIndex<'t> {
+    keys: Vec<Vec<&'t str>>,
+    positions: HashMap<Vec<&'t str>, usize>,
+}
+
+impl<'t> KeyIndex<'t> {
+    fn get(&self, key: &[&'t str]) -> Option<&usize> {
+        self.positions.get(key)
+    }
+
+    fn contains_key(&self, key: &[&'t str]) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&[&'t str], &usize)> {
+        self.keys
+            .iter()
+            .map(move |key| (key.as_slice(), &self.positions[key]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_lists_columns_then_removed_changed_and_added_rows() {
+        let old = Table::parse_tsv(
+            "id\tname\tprice\tdiscount\n3\tbolt\t0.10\t0\n4\tscrew\t2.50\t0\n5\twasher\t1\t0\n",
+            0,
+        )
+        .unwrap();
+        let new = Table::parse_tsv(
+            "id\tname\tprice\tcurrency\n9\tnut\t0.05\teur\n5\twasher\t1\teur\n4\tscrew\t2.75\teur\n",
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            render(&old, &new, &["id"]).unwrap(),
+            "- column discount\n\
+             + column currency\n\
+             - id=3 | name=bolt | price=0.10 | discount=0\n\
+             ~ id=4 | price: 2.50 -> 2.75\n\
+             + id=9 | name=nut | price=0.05 | currency=eur\n"
+        );
+        assert_eq!(render(&old, &old, &["id"]).unwrap(), "");
+    }
+
+    #[test]
+    fn diff_keys_must_be_unique() {
+        let table = Table::parse_tsv("k\tv\na\t1\nb\t2\na\t3\n", 0).unwrap();
+        let err = render(&table, &table, &["k"]).err().unwrap();
+        assert_eq!(err, "E103: duplicate key a at rows 0 and 2");
+        assert!(render(&table, &table, &["k", "v"]).is_ok());
+    }
+}
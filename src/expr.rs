@@ -0,0 +1,313 @@
+use super::messages::{self, Message};
+use super::table::Res;
+use std::cmp::Ordering;
+
+// nesting of parentheses and NOT, the parser and the evaluation recurse once per level
+const MAX_DEPTH: usize = 200;
+
+// a chain of AND or OR is one node, so a long chain does not nest
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, Comparison, Operand),
+}
+
+pub enum Operand {
+    Column(String, usize),
+    Literal(String),
+}
+
+#[derive(Clone, Copy)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> Res<Expr> {
+        let mut parser = Parser {
+            chars: input.chars().collect(),
+            position: 0,
+            depth: 0,
+        };
+        let expr = parser.or()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(parser.error(&messages::text(Message::UnexpectedAfterExpression, &[])));
+        }
+        Ok(expr)
+    }
+
+    // gives every column operand the slot its cells are passed in by `eval`
+    pub fn bind(&mut self, slot_of: &mut impl FnMut(&str) -> Res<usize>) -> Res<()> {
+        match self {
+            Expr::And(exprs) | Expr::Or(exprs) => {
+                exprs.iter_mut().try_for_each(|expr| expr.bind(slot_of))
+            }
+            Expr::Not(a) => a.bind(slot_of),
+            Expr::Compare(left, _, right) => {
+                for operand in [left, right] {
+                    if let Operand::Column(col_name, slot) = operand {
+                        *slot = slot_of(col_name)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn eval(&self, cells: &[&str]) -> bool {
+        match self {
+            Expr::And(exprs) => exprs.iter().all(|expr| expr.eval(cells)),
+            Expr::Or(exprs) => exprs.iter().any(|expr| expr.eval(cells)),
+            Expr::Not(a) => !a.eval(cells),
+            Expr::Compare(left, comparison, right) => {
+                let ordering = compare(left.value(cells), right.value(cells));
+                match comparison {
+                    Comparison::Eq => ordering == Ordering::Equal,
+                    Comparison::Ne => ordering != Ordering::Equal,
+                    Comparison::Lt => ordering == Ordering::Less,
+                    Comparison::Le => ordering != Ordering::Greater,
+                    Comparison::Gt => ordering == Ordering::Greater,
+                    Comparison::Ge => ordering != Ordering::Less,
+                }
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn value<'a>(&'a self, cells: &[&'a str]) -> &'a str {
+        match self {
+            Operand::Column(_, slot) => cells[*slot],
+            Operand::Literal(literal) => literal,
+        }
+    }
+}
+
+fn compare(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        messages::text(Message::InvalidExpression, &[&self.position, &message])
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn keyword(&mut self, word: &str) -> bool {
+        self.skip_whitespace();
+        let end = self.position + word.len();
+        if end > self.chars.len() {
+            return false;
+        }
+        let matches = self.chars[self.position..end]
+            .iter()
+            .zip(word.chars())
+            .all(|(a, b)| a.eq_ignore_ascii_case(&b));
+        let boundary = !self.chars.get(end).is_some_and(|c| is_identifier_char(*c));
+        if matches && boundary {
+            self.position = end;
+        }
+        matches && boundary
+    }
+
+    fn or(&mut self) -> Res<Expr> {
+        let mut exprs = vec![self.and()?];
+        while self.keyword("OR") {
+            exprs.push(self.and()?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.pop().unwrap(),
+            _ => Expr::Or(exprs),
+        })
+    }
+
+    fn and(&mut self) -> Res<Expr> {
+        let mut exprs = vec![self.not()?];
+        while self.keyword("AND") {
+            exprs.push(self.not()?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.pop().unwrap(),
+            _ => Expr::And(exprs),
+        })
+    }
+
+    fn nested(&mut self, parse: impl FnOnce(&mut Parser) -> Res<Expr>) -> Res<Expr> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error(&messages::text(Message::NestingTooDeep, &[&MAX_DEPTH])));
+        }
+        let expr = parse(self)?;
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Res<Expr> {
+        if self.keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.nested(Parser::not)?)));
+        }
+        self.skip_whitespace();
+        if self.peek() == Some('(') {
+            self.position += 1;
+            let expr = self.nested(Parser::or)?;
+            self.skip_whitespace();
+            if self.peek() != Some(')') {
+                return Err(self.error(&messages::text(Message::Expected, &[&')'])));
+            }
+            self.position += 1;
+            return Ok(expr);
+        }
+        let left = self.operand()?;
+        let comparison = self.comparison()?;
+        let right = self.operand()?;
+        Ok(Expr::Compare(left, comparison, right))
+    }
+
+    fn comparison(&mut self) -> Res<Comparison> {
+        self.skip_whitespace();
+        let rest: String = self.chars[self.position..].iter().take(2).collect();
+        let (comparison, len) = if rest.starts_with("==") {
+            (Comparison::Eq, 2)
+        } else if rest.starts_with("!=") || rest.starts_with("<>") {
+            (Comparison::Ne, 2)
+        } else if rest.starts_with("<=") {
+            (Comparison::Le, 2)
+        } else if rest.starts_with(">=") {
+            (Comparison::Ge, 2)
+        } else if rest.starts_with('=') {
+            (Comparison::Eq, 1)
+        } else if rest.starts_with('<') {
+            (Comparison::Lt, 1)
+        } else if rest.starts_with('>') {
+            (Comparison::Gt, 1)
+        } else {
+            return Err(self.error(&messages::text(Message::ExpectedComparison, &[])));
+        };
+        self.position += len;
+        Ok(comparison)
+    }
+
+    fn operand(&mut self) -> Res<Operand> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(quote @ '\'') | Some(quote @ '"') => Ok(Operand::Literal(self.quoted(quote)?)),
+            Some('`') => Ok(Operand::Column(self.quoted('`')?, 0)),
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let start = self.position;
+                self.position += 1;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '.')
+                {
+                    self.position += 1;
+                }
+                Ok(Operand::Literal(
+                    self.chars[start..self.position].iter().collect(),
+                ))
+            }
+            Some(c) if is_identifier_char(c) => {
+                let start = self.position;
+                while self.peek().is_some_and(is_identifier_char) {
+                    self.position += 1;
+                }
+                Ok(Operand::Column(
+                    self.chars[start..self.position].iter().collect(),
+                    0,
+                ))
+            }
+            _ => Err(self.error(&messages::text(Message::ExpectedOperand, &[]))),
+        }
+    }
+
+    fn quoted(&mut self, quote: char) -> Res<String> {
+        self.position += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error(&messages::text(Message::MissingClosing, &[&quote]))),
+                Some(c) if c == quote => {
+                    self.position += 1;
+                    // a doubled quote stands for the quote itself
+                    if self.peek() != Some(quote) {
+                        return Ok(text);
+                    }
+                    text.push(quote);
+                    self.position += 1;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // columns a, b, c.. are passed in that order
+    fn matches(expr: &str, cells: &[&str]) -> bool {
+        let mut expr = Expr::parse(expr).unwrap();
+        let mut slot_of = |col_name: &str| Ok((col_name.as_bytes()[0] - b'a') as usize);
+        expr.bind(&mut slot_of).unwrap();
+        expr.eval(cells)
+    }
+
+    #[test]
+    fn chains_and_nesting() {
+        let expr = "NOT (a = 1 OR b = 2) AND c > 3 AND c < 9";
+        assert!(matches(expr, &["0", "0", "5"]));
+        assert!(!matches(expr, &["1", "0", "5"]));
+        assert!(!matches(expr, &["0", "0", "9"]));
+    }
+
+    #[test]
+    fn long_chains_parse() {
+        let chain = vec!["a = 1"; 100_000].join(" OR ");
+        let mut expr = Expr::parse(&chain).unwrap();
+        expr.bind(&mut |_| Ok(0)).unwrap();
+        assert!(expr.eval(&["1"]));
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let deep = format!("{}a = 1{}", "(".repeat(100_000), ")".repeat(100_000));
+        let err = Expr::parse(&deep).err().unwrap();
+        assert!(err.contains("E111: "), "{}", err);
+        let nots = format!("{}a = 1", "NOT ".repeat(100_000));
+        assert!(Expr::parse(&nots).is_err());
+        let nested = format!("{}a = 1{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        assert!(Expr::parse(&nested).is_ok());
+    }
+}
@@ -0,0 +1,292 @@
+use super::column::Column;
+use super::messages::{self, Message};
+use super::table::{Res, Table};
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Tsv,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum TsvEscaping {
+    #[default]
+    Reject,
+    Backslash,
+    Raw,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PartitionLayout {
+    Flat,
+    Hive,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Tsv => "tsv",
+        }
+    }
+
+    pub(crate) fn parse(&self, input: &str) -> Res<Table> {
+        match self {
+            Format::Tsv => Table::parse_tsv(input, 0),
+        }
+    }
+
+    pub(crate) fn breaks_row(&self, cell: &str) -> bool {
+        match self {
+            Format::Tsv => cell.contains(['\t', '\n', '\r']),
+        }
+    }
+
+    // same bytes as Table::to_tsv_with, written row by row
+    pub(crate) fn write_rows(
+        &self,
+        out: &mut impl Write,
+        header: &[String],
+        columns: &[Column],
+        positions: &[usize],
+        escaping: TsvEscaping,
+        write_error: impl Fn(io::Error) -> String,
+    ) -> Res<()> {
+        match self {
+            Format::Tsv => {
+                for (i, col_name) in header.iter().enumerate() {
+                    let separator: &[u8] = if i == 0 { b"" } else { b"\t" };
+                    out.write_all(separator).map_err(&write_error)?;
+                    let name = escaping.escape(col_name, col_name)?;
+                    out.write_all(name.as_bytes()).map_err(&write_error)?;
+                }
+                for &position in positions {
+                    for (i, (col, col_name)) in columns.iter().zip(header).enumerate() {
+                        out.write_all(if i == 0 { b"\n" } else { b"\t" })
+                            .map_err(&write_error)?;
+                        let cell = escaping.escape(col.get(position), col_name)?;
+                        out.write_all(cell.as_bytes()).map_err(&write_error)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl TsvEscaping {
+    // col_name only names the column in the error
+    pub(crate) fn escape<'a>(self, cell: &'a str, col_name: &str) -> Res<Cow<'a, str>> {
+        let breaks_row = Format::Tsv.breaks_row(cell);
+        match self {
+            TsvEscaping::Reject if breaks_row => {
+                Err(messages::text(Message::CellBreaksTsv, &[&col_name]))
+            }
+            TsvEscaping::Backslash if breaks_row || cell.contains('\\') => {
+                let mut escaped = String::with_capacity(cell.len() + 2);
+                for c in cell.chars() {
+                    match c {
+                        '\\' => escaped.push_str("\\\\"),
+                        '\t' => escaped.push_str("\\t"),
+                        '\n' => escaped.push_str("\\n"),
+                        '\r' => escaped.push_str("\\r"),
+                        c => escaped.push(c),
+                    }
+                }
+                Ok(Cow::Owned(escaped))
+            }
+            _ => Ok(Cow::Borrowed(cell)),
+        }
+    }
+}
+
+// reverses TsvEscaping::Backslash, unknown escapes are kept as they are
+pub(crate) fn unescape_tsv(field: &str) -> Cow<'_, str> {
+    if !field.contains('\\') {
+        return Cow::Borrowed(field);
+    }
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+// keeps any cell value usable as a single path component
+pub(crate) fn path_component(value: &str) -> String {
+    if value.is_empty() {
+        return String::from("_");
+    }
+    let mut component = String::with_capacity(value.len());
+    for (i, byte) in value.bytes().enumerate() {
+        let plain = byte.is_ascii_alphanumeric() || byte == b'-' || (byte == b'.' && i > 0);
+        if plain {
+            component.push(byte as char);
+        } else {
+            component += &format!("%{:02X}", byte);
+        }
+    }
+    component
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::LoadOptions;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("sql-format-{}-{}", std::process::id(), name))
+    }
+
+    fn table_with_tab() -> Table {
+        let mut table = Table::parse_tsv("part\tnote\na\tplain\nb\tx\n", 0).unwrap();
+        table.set_cell("note", 1, "tab\there").unwrap();
+        table
+    }
+
+    #[test]
+    fn escape_and_unescape_round_trip() {
+        for cell in [
+            "plain",
+            "a\tb",
+            "line\nbreak\r",
+            "back\\slash",
+            "\\t literal",
+        ] {
+            let escaped = TsvEscaping::Backslash.escape(cell, "c").unwrap();
+            assert!(!Format::Tsv.breaks_row(&escaped));
+            assert_eq!(unescape_tsv(&escaped), cell);
+        }
+        assert!(TsvEscaping::Reject.escape("a\tb", "c").is_err());
+        assert_eq!(TsvEscaping::Raw.escape("a\tb", "c").unwrap(), "a\tb");
+    }
+
+    #[test]
+    fn write_partitioned_rejects_or_escapes_breaking_cells() {
+        let table = table_with_tab();
+        let header = vec!["part".to_string(), "note".to_string()];
+        let dir = temp_dir("partitions");
+        let dir_name = dir.to_str().unwrap();
+        let written = table.write_partitioned(
+            dir_name,
+            "part",
+            header.clone(),
+            Format::Tsv,
+            PartitionLayout::Flat,
+        );
+        assert!(written.is_err());
+        table
+            .write_partitioned_with(
+                dir_name,
+                "part",
+                header,
+                Format::Tsv,
+                PartitionLayout::Flat,
+                TsvEscaping::Backslash,
+            )
+            .unwrap();
+        let path = dir.join("b.tsv");
+        let read =
+            Table::load_tsv_with(path.to_str().unwrap(), &LoadOptions::new().unescape()).unwrap();
+        assert_eq!(read.column("note").unwrap().get(0).as_str(), "tab\there");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verify_roundtrip_still_reports_breaking_cells() {
+        let report = table_with_tab().verify_roundtrip(Format::Tsv).unwrap();
+        assert_eq!(report.rows_count(), 1);
+        assert_eq!(report.column("column").unwrap().get(0).as_str(), "note");
+    }
+
+    #[test]
+    fn partitions_go_to_one_file_per_value_flat_or_hive() {
+        let table = Table::parse_tsv("region\tn\nEU\t1\nUS/W\t2\nEU\t3\n\t4\n", 0).unwrap();
+        let header = vec!["n".to_string(), "region".to_string()];
+        let read = |path: std::path::PathBuf| std::fs::read_to_string(path).unwrap();
+
+        let dir = temp_dir("flat");
+        let dir_name = dir.to_str().unwrap();
+        table
+            .write_partitioned(
+                dir_name,
+                "region",
+                header.clone(),
+                Format::Tsv,
+                PartitionLayout::Flat,
+            )
+            .unwrap();
+        assert_eq!(read(dir.join("EU.tsv")), "n\tregion\n1\tEU\n3\tEU");
+        assert_eq!(read(dir.join("US%2FW.tsv")), "n\tregion\n2\tUS/W");
+        assert_eq!(read(dir.join("_.tsv")), "n\tregion\n4\t");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let dir = temp_dir("hive");
+        let dir_name = dir.to_str().unwrap();
+        table
+            .write_partitioned(
+                dir_name,
+                "region",
+                header,
+                Format::Tsv,
+                PartitionLayout::Hive,
+            )
+            .unwrap();
+        assert_eq!(
+            read(dir.join("region=EU").join("part.tsv")),
+            "n\tregion\n1\tEU\n3\tEU"
+        );
+        assert!(dir.join("region=US%2FW").join("part.tsv").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let missing = vec!["missing".to_string()];
+        let err = table.write_partitioned(
+            "unused",
+            "region",
+            missing,
+            Format::Tsv,
+            PartitionLayout::Flat,
+        );
+        assert_eq!(err.err().unwrap(), "E001: column 'missing' does not exist");
+    }
+
+    #[test]
+    fn verify_roundtrip_reports_only_cells_that_change() {
+        let clean = Table::parse_tsv("a\tb\n1\tx\n2\ty\n", 0).unwrap();
+        assert_eq!(clean.verify_roundtrip(Format::Tsv).unwrap().rows_count(), 0);
+
+        let mut table = Table::parse_tsv("a\tb\n1\tx\n2\ty\n3\tz\n", 0).unwrap();
+        table.set_cell("b", 0, "two\nlines").unwrap();
+        // a lone carriage return is read back as the end of a line
+        table.set_cell("a", 2, "\r").unwrap();
+        let report = table.verify_roundtrip(Format::Tsv).unwrap();
+        let cells = |col_name: &str| -> Vec<String> {
+            let column = report.column(col_name).unwrap();
+            column.iter().map(|c| c.to_string()).collect()
+        };
+        assert_eq!(
+            report.column_names(),
+            ["row", "column", "written", "read", "issue"]
+        );
+        assert_eq!(cells("row"), ["0", "2"]);
+        assert_eq!(cells("column"), ["b", "a"]);
+        assert_eq!(cells("issue"), ["row lost", "changed"]);
+        assert_eq!(cells("written")[1], "\r");
+        assert_eq!(cells("read")[1], "");
+    }
+}
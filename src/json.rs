@@ -0,0 +1,103 @@
+use super::messages::{self, Message};
+use super::table::Res;
+use serde_json::Value as Json;
+
+pub enum PathStep {
+    Key(String),
+    Index(usize),
+}
+
+// serde_json refuses documents nested deeper than 128 levels, so this never overflows the stack
+pub fn parse(input: &str) -> Res<Json> {
+    serde_json::from_str(input)
+        .map_err(|err| messages::text(Message::InvalidJson, &[&err.line(), &err.column()]))
+}
+
+pub fn get<'a>(json: &'a Json, path: &[PathStep]) -> Option<&'a Json> {
+    path.iter().try_fold(json, |json, step| match step {
+        PathStep::Key(key) => json.as_object()?.get(key),
+        PathStep::Index(index) => json.as_array()?.get(*index),
+    })
+}
+
+// strings lose their quotes and null is an empty cell, anything else stays JSON
+pub fn to_cell(json: &Json) -> String {
+    match json {
+        Json::Null => String::new(),
+        Json::String(string) => string.clone(),
+        _ => json.to_string(),
+    }
+}
+
+pub fn parse_path(path: &str) -> Res<Vec<PathStep>> {
+    let invalid = || messages::text(Message::InvalidJsonPath, &[&path]);
+    let chars: Vec<char> = path.chars().collect();
+    let mut position = if chars.first() == Some(&'$') { 1 } else { 0 };
+    let mut steps = Vec::new();
+    while position < chars.len() {
+        match chars[position] {
+            '.' => {
+                let end = chars[position + 1..]
+                    .iter()
+                    .position(|&c| c == '.' || c == '[')
+                    .map_or(chars.len(), |p| position + 1 + p);
+                let key: String = chars[position + 1..end].iter().collect();
+                if key.is_empty() {
+                    return Err(invalid());
+                }
+                steps.push(PathStep::Key(key));
+                position = end;
+            }
+            '[' => {
+                let end = chars[position..]
+                    .iter()
+                    .position(|&c| c == ']')
+                    .map(|p| position + p)
+                    .ok_or_else(invalid)?;
+                let inner: String = chars[position + 1..end].iter().collect();
+                let quoted = inner.len() >= 2
+                    && ((inner.starts_with('"') && inner.ends_with('"'))
+                        || (inner.starts_with('\'') && inner.ends_with('\'')));
+                if quoted {
+                    steps.push(PathStep::Key(inner[1..inner.len() - 1].to_string()));
+                } else {
+                    steps.push(PathStep::Index(inner.parse().map_err(|_| invalid())?));
+                }
+                position = end + 1;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_walk_keys_and_indexes() {
+        let json = parse(r#"{"a":[1,{"b":null,"c":"x\"y"}],"d":{"z":1.50,"y":true}}"#).unwrap();
+        let cell = |path: &str| get(&json, &parse_path(path).unwrap()).map(to_cell);
+        assert_eq!(cell("$.a[1].b").unwrap(), "");
+        assert_eq!(cell("$.a[1]['c']").unwrap(), "x\"y");
+        assert_eq!(cell(".d").unwrap(), r#"{"z":1.50,"y":true}"#);
+        assert_eq!(cell("$.a[0]").unwrap(), "1");
+        assert!(cell("$.a[2]").is_none());
+        assert!(cell("$.a.b").is_none());
+        for path in &["$.", "$[x]", "$[0", "a"] {
+            let err = parse_path(path).err().unwrap();
+            assert_eq!(err, format!("E069: invalid JSON path '{}'", path));
+        }
+    }
+
+    #[test]
+    fn malformed_and_deep_documents_are_errors() {
+        let err = parse("{\"a\":\n 1,}").err().unwrap();
+        assert_eq!(err, "E067: invalid JSON at line 2, column 4");
+        let deep = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(parse(&deep).is_err());
+        let nested = format!("{}{}", "[".repeat(100), "]".repeat(100));
+        assert!(parse(&nested).is_ok());
+    }
+}
@@ -0,0 +1,269 @@
+pub fn strip_accents(string: &str) -> String {
+    let mut result = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => result.push('a'),
+            'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => result.push('A'),
+            'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => result.push('c'),
+            'Ç' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' => result.push('C'),
+            'ď' | 'đ' => result.push('d'),
+            'Ď' | 'Đ' => result.push('D'),
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => result.push('e'),
+            'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ĕ' | 'Ė' | 'Ę' | 'Ě' => result.push('E'),
+            'ĝ' | 'ğ' | 'ġ' | 'ģ' => result.push('g'),
+            'Ĝ' | 'Ğ' | 'Ġ' | 'Ģ' => result.push('G'),
+            'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => result.push('i'),
+            'Ì' | 'Í' | 'Î' | 'Ï' | 'Ĩ' | 'Ī' | 'Ĭ' | 'Į' | 'İ' => result.push('I'),
+            'ł' | 'ĺ' | 'ļ' | 'ľ' => result.push('l'),
+            'Ł' | 'Ĺ' | 'Ļ' | 'Ľ' => result.push('L'),
+            'ñ' | 'ń' | 'ņ' | 'ň' => result.push('n'),
+            'Ñ' | 'Ń' | 'Ņ' | 'Ň' => result.push('N'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => result.push('o'),
+            'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ŏ' | 'Ő' => result.push('O'),
+            'ŕ' | 'ŗ' | 'ř' => result.push('r'),
+            'Ŕ' | 'Ŗ' | 'Ř' => result.push('R'),
+            'ś' | 'ŝ' | 'ş' | 'š' => result.push('s'),
+            'Ś' | 'Ŝ' | 'Ş' | 'Š' => result.push('S'),
+            'ţ' | 'ť' => result.push('t'),
+            'Ţ' | 'Ť' => result.push('T'),
+            'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => result.push('u'),
+            'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ũ' | 'Ū' | 'Ŭ' | 'Ů' | 'Ű' | 'Ų' => result.push('U'),
+            'ý' | 'ÿ' => result.push('y'),
+            'Ý' | 'Ÿ' => result.push('Y'),
+            'ź' | 'ż' | 'ž' => result.push('z'),
+            'Ź' | 'Ż' | 'Ž' => result.push('Z'),
+            'ß' => result.push_str("ss"),
+            'æ' => result.push_str("ae"),
+            'Æ' => result.push_str("AE"),
+            'œ' => result.push_str("oe"),
+            'Œ' => result.push_str("OE"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+pub fn collapse_whitespace(string: &str) -> String {
+    string.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+pub fn remove_punctuation(string: &str) -> String {
+    string
+        .chars()
+        .filter(|c| !c.is_ascii_punctuation())
+        .collect()
+}
+
+pub fn normalize(string: &str) -> String {
+    collapse_whitespace(&remove_punctuation(&strip_accents(string)).to_lowercase())
+}
+
+pub fn soundex(string: &str) -> String {
+    let code = |c: char| match c {
+        'B' | 'F' | 'P' | 'V' => '1',
+        'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => '2',
+        'D' | 'T' => '3',
+        'L' => '4',
+        'M' | 'N' => '5',
+        'R' => '6',
+        _ => '0',
+    };
+    let letters: Vec<char> = ascii_letters(string);
+    let first = match letters.first() {
+        Some(&first) => first,
+        None => return String::new(),
+    };
+    let mut result = String::with_capacity(4);
+    result.push(first);
+    let mut previous = code(first);
+    for &c in letters[1..].iter() {
+        let digit = code(c);
+        if digit != '0' && digit != previous {
+            result.push(digit);
+            if result.len() == 4 {
+                break;
+            }
+        }
+        // H and W do not separate letters with the same code
+        if c != 'H' && c != 'W' {
+            previous = digit;
+        }
+    }
+    while result.len() < 4 {
+        result.push('0');
+    }
+    result
+}
+
+pub fn metaphone(string: &str) -> String {
+    let word = ascii_letters(string);
+    let is_vowel =
+        |c: Option<&char>| matches!(c, Some('A') | Some('E') | Some('I') | Some('O') | Some('U'));
+    let at = |i: usize| word.get(i).copied();
+
+    let mut result = String::new();
+    let start = match (at(0), at(1)) {
+        (Some('X'), _) => {
+            result.push('S');
+            1
+        }
+        (Some('W'), Some('H')) => {
+            result.push('W');
+            2
+        }
+        (Some('A'), Some('E'))
+        | (Some('G'), Some('N'))
+        | (Some('K'), Some('N'))
+        | (Some('P'), Some('N'))
+        | (Some('W'), Some('R')) => 1,
+        _ => 0,
+    };
+
+    for i in start..word.len() {
+        let c = word[i];
+        let previous = if i > 0 { at(i - 1) } else { None };
+        let next = at(i + 1);
+        if previous == Some(c) && c != 'C' {
+            continue;
+        }
+        match c {
+            'A' | 'E' | 'I' | 'O' | 'U' => {
+                if i == start && result.is_empty() {
+                    result.push(c);
+                }
+            }
+            'B' => {
+                if !(previous == Some('M') && i + 1 == word.len()) {
+                    result.push('B');
+                }
+            }
+            'C' => {
+                if next == Some('I') && at(i + 2) == Some('A') {
+                    result.push('X');
+                } else if next == Some('H') {
+                    result.push(if previous == Some('S') { 'K' } else { 'X' });
+                } else if matches!(next, Some('I') | Some('E') | Some('Y')) {
+                    if previous != Some('S') {
+                        result.push('S');
+                    }
+                } else {
+                    result.push('K');
+                }
+            }
+            'D' => {
+                if next == Some('G') && matches!(at(i + 2), Some('E') | Some('Y') | Some('I')) {
+                    result.push('J');
+                } else {
+                    result.push('T');
+                }
+            }
+            'G' => {
+                if next == Some('H') && !(i + 2 == word.len() || is_vowel(word.get(i + 2))) {
+                    continue;
+                }
+                if next == Some('N')
+                    && (i + 2 == word.len()
+                        || (at(i + 2) == Some('E')
+                            && at(i + 3) == Some('D')
+                            && i + 4 == word.len()))
+                {
+                    continue;
+                }
+                if previous == Some('D') && matches!(next, Some('E') | Some('Y') | Some('I')) {
+                    continue;
+                }
+                if matches!(next, Some('I') | Some('E') | Some('Y')) && previous != Some('G') {
+                    result.push('J');
+                } else {
+                    result.push('K');
+                }
+            }
+            'H' => {
+                let after_vowel_only = is_vowel(previous.as_ref()) && !is_vowel(next.as_ref());
+                let after_modifier = matches!(
+                    previous,
+                    Some('C') | Some('S') | Some('P') | Some('T') | Some('G')
+                );
+                if !after_vowel_only && !after_modifier {
+                    result.push('H');
+                }
+            }
+            'K' => {
+                if previous != Some('C') {
+                    result.push('K');
+                }
+            }
+            'P' => result.push(if next == Some('H') { 'F' } else { 'P' }),
+            'Q' => result.push('K'),
+            'S' => {
+                if next == Some('H')
+                    || (next == Some('I') && matches!(at(i + 2), Some('O') | Some('A')))
+                {
+                    result.push('X');
+                } else {
+                    result.push('S');
+                }
+            }
+            'T' => {
+                if next == Some('I') && matches!(at(i + 2), Some('O') | Some('A')) {
+                    result.push('X');
+                } else if next == Some('H') {
+                    result.push('0');
+                } else if !(next == Some('C') && at(i + 2) == Some('H')) {
+                    result.push('T');
+                }
+            }
+            'V' => result.push('F'),
+            'W' | 'Y' => {
+                if is_vowel(next.as_ref()) {
+                    result.push(c);
+                }
+            }
+            'X' => result.push_str("KS"),
+            'Z' => result.push('S'),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+fn ascii_letters(string: &str) -> Vec<char> {
+    strip_accents(string)
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_folds_case_accents_and_punctuation() {
+        assert_eq!(strip_accents("Ærøskøbing straße"), "AEroskobing strasse");
+        assert_eq!(normalize("  José,  O'Neil!  "), "jose oneil");
+        assert_eq!(collapse_whitespace(" a \t b\n"), "a b");
+    }
+
+    #[test]
+    fn soundex_codes() {
+        assert_eq!(soundex("Robert"), "R163");
+        assert_eq!(soundex("Rupert"), "R163");
+        assert_eq!(soundex("Ashcraft"), "A261");
+        assert_eq!(soundex("Tymczak"), "T522");
+        assert_eq!(soundex("Pfister"), "P236");
+        assert_eq!(soundex("Lee"), "L000");
+        assert_eq!(soundex("123"), "");
+    }
+
+    #[test]
+    fn metaphone_groups_names_that_sound_alike() {
+        assert_eq!(metaphone("Smith"), "SM0");
+        assert_eq!(metaphone("Smyth"), "SM0");
+        assert_eq!(metaphone("Knight"), metaphone("Night"));
+        assert_eq!(metaphone("Philip"), metaphone("Filip"));
+        assert_ne!(metaphone("Smith"), metaphone("Jones"));
+        assert_eq!(metaphone(""), "");
+    }
+}
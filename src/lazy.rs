@@ -0,0 +1,254 @@
+use super::load::{LoadOptions, RowFilter};
+use super::table::{Op, Res, Table};
+use std::sync::Arc;
+
+enum Step {
+    Select(Vec<String>),
+    Filter(String, RowFilter),
+    Join(String, Table, String),
+    Group(Vec<String>, Vec<Op>),
+}
+
+enum Source {
+    Table(Table),
+    Tsv(String, LoadOptions),
+}
+
+// records the steps, nothing runs until collect
+pub struct LazyTable {
+    source: Source,
+    steps: Vec<Step>,
+}
+
+impl LazyTable {
+    pub(crate) fn new(source: Table) -> LazyTable {
+        LazyTable {
+            source: Source::Table(source),
+            steps: Vec::new(),
+        }
+    }
+
+    pub(crate) fn scan_tsv(path: &str, options: &LoadOptions) -> LazyTable {
+        LazyTable {
+            source: Source::Tsv(path.to_string(), options.clone()),
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn select(mut self, col_names: &[&str]) -> LazyTable {
+        let col_names = col_names.iter().map(|c| c.to_string()).collect();
+        self.steps.push(Step::Select(col_names));
+        self
+    }
+
+    pub fn filter(mut self, col_name: &str, filter: impl Fn(&str) -> bool + 'static) -> LazyTable {
+        self.steps
+            .push(Step::Filter(col_name.to_string(), Arc::new(filter)));
+        self
+    }
+
+    pub fn join(mut self, col_name_self: &str, other: &Table, col_name_other: &str) -> LazyTable {
+        self.steps.push(Step::Join(
+            col_name_self.to_string(),
+            other.clone(),
+            col_name_other.to_string(),
+        ));
+        self
+    }
+
+    pub fn group_by(mut self, col_names: &[&str], column_operations: Vec<Op>) -> LazyTable {
+        let col_names = col_names.iter().map(|c| c.to_string()).collect();
+        self.steps.push(Step::Group(col_names, column_operations));
+        self
+    }
+
+    pub fn collect(mut self) -> Res<Table> {
+        let mut table = match self.source {
+            Source::Table(table) => table,
+            Source::Tsv(path, mut options) => {
+                self.steps = push_down(self.steps, &mut options);
+                Table::load_tsv_with(&path, &options)?
+            }
+        };
+        // rows kept by the filters seen so far, the table is only remapped when a step needs it
+        let mut positions: Option<Vec<usize>> = None;
+        let mut steps = self.steps.iter().peekable();
+        while let Some(step) = steps.next() {
+            match step {
+                Step::Select(col_names) => {
+                    let col_names: Vec<&str> = col_names.iter().map(String::as_str).collect();
+                    table = table.select_columns(&col_names)?;
+                }
+                Step::Filter(col_name, filter) => {
+                    // consecutive filters are fused into a single pass over the rows
+                    let mut filters = vec![(table.column(col_name)?, filter)];
+                    while let Some(Step::Filter(col_name, filter)) = steps.peek() {
+                        filters.push((table.column(col_name)?, filter));
+                        steps.next();
+                    }
+                    let keep = |position: &usize| {
+                        filters
+                            .iter()
+                            .all(|(column, filter)| filter(column.get(*position)))
+                    };
+                    positions = Some(match positions {
+                        Some(positions) => positions.into_iter().filter(keep).collect(),
+                        None => (0..table.rows_count()).filter(keep).collect(),
+                    });
+                }
+                Step::Join(col_name_self, other, col_name_other) => {
+                    table = materialize(table, positions.take());
+                    table = table.join_on_columns(col_name_self, other, col_name_other)?;
+                }
+                Step::Group(col_names, column_operations) => {
+                    table = materialize(table, positions.take());
+                    let col_names: Vec<&str> = col_names.iter().map(String::as_str).collect();
+                    table = table.group_by_columns(&col_names, column_operations)?;
+                }
+            }
+        }
+        Ok(materialize(table, positions))
+    }
+}
+
+// moves the filters and the first selection ahead of any join or group into the scan,
+// so dropped rows and columns are never built
+fn push_down(steps: Vec<Step>, options: &mut LoadOptions) -> Vec<Step> {
+    // the scan filters the text of the file, a schema or inference may change it afterwards
+    let raw_cells = options.schema.is_none() && !options.infer_types;
+    let mut visible: Option<Vec<String>> = None;
+    let mut kept = Vec::with_capacity(steps.len());
+    let mut steps = steps.into_iter();
+    for step in steps.by_ref() {
+        match step {
+            Step::Filter(col_name, filter)
+                if raw_cells
+                    && visible
+                        .as_ref()
+                        .is_none_or(|visible| visible.contains(&col_name)) =>
+            {
+                options.filters.push((col_name, filter));
+            }
+            Step::Select(col_names) => {
+                // a selection already given in the options wins, the step still checks the names
+                if visible.is_none() && options.columns.is_none() {
+                    options.columns = Some(col_names.clone());
+                }
+                visible = Some(col_names.clone());
+                kept.push(Step::Select(col_names));
+            }
+            // a filter on a column selected away stays in memory, where it fails, and so does
+            // every step after one that is not pushed
+            step => {
+                kept.push(step);
+                break;
+            }
+        }
+    }
+    kept.extend(steps);
+    kept
+}
+
+fn materialize(table: Table, positions: Option<Vec<usize>>) -> Table {
+    match positions {
+        Some(positions) if positions.len() < table.rows_count() => table.remap(&positions),
+        _ => table,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregate::Aggregate;
+    use crate::schema::Schema;
+    use crate::types::ColumnType;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn write_temp(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("sql-lazy-{}-{}", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn cells(table: &Table, col_name: &str) -> Vec<String> {
+        let column = table.column(col_name).unwrap();
+        column.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn in_memory_plans_fuse_filters_and_group_what_they_keep() {
+        let table = Table::parse_tsv(
+            "id\tcountry\tamount\n1\tit\t10\n2\tfr\t5\n3\tit\t7\n4\tit\t1\n5\tfr\t2\n",
+            0,
+        )
+        .unwrap();
+        let calls = Rc::new(Cell::new(0));
+        let counted = calls.clone();
+        let plan = table
+            .lazy()
+            .filter("amount", move |amount| {
+                counted.set(counted.get() + 1);
+                amount != "1"
+            })
+            .filter("id", |id| id != "5")
+            .group_by(&["country"], vec![Op::aggregate("amount", Aggregate::Sum)]);
+        assert_eq!(calls.get(), 0);
+        let grouped = plan.collect().unwrap();
+        assert_eq!(calls.get(), 5);
+        assert_eq!(cells(&grouped, "country"), ["it", "fr"]);
+        assert_eq!(cells(&grouped, "amount"), ["17", "5"]);
+        assert_eq!(cells(&table, "id").len(), 5);
+        let selected = table
+            .lazy()
+            .select(&["id"])
+            .filter("id", |id| id > "3")
+            .collect()
+            .unwrap();
+        assert_eq!(cells(&selected, "id"), ["4", "5"]);
+        assert!(table
+            .lazy()
+            .select(&["id"])
+            .filter("country", |_| true)
+            .collect()
+            .is_err());
+    }
+
+    #[test]
+    fn filters_and_selection_are_pushed_into_plain_scans() {
+        let path = write_temp("plain", "id\tname\tx\n1\ta\t-\n2\tb\t-\n3\tc\t-\n");
+        let table = Table::scan_tsv(&path)
+            .filter("id", |id| id != "2")
+            .select(&["id", "name"])
+            .collect()
+            .unwrap();
+        assert_eq!(cells(&table, "name"), ["a", "c"]);
+        assert_eq!(table.columns_count(), 2);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn filters_see_coerced_cells_when_a_schema_is_given() {
+        let path = write_temp("schema", "id\tname\n05\ta\n+7\tb\n");
+        let options = LoadOptions::new().schema(Schema::new().column("id", ColumnType::Int64));
+        let table = Table::scan_tsv_with(&path, &options)
+            .filter("id", |id| id == "5" || id == "7")
+            .collect()
+            .unwrap();
+        assert_eq!(cells(&table, "name"), ["a", "b"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn steps_after_a_join_run_in_memory() {
+        let path = write_temp("join", "id\tname\n1\ta\n2\tb\n");
+        let other = Table::parse_tsv("key\tscore\n1\t10\n2\t20\n", 0).unwrap();
+        let table = Table::scan_tsv(&path)
+            .join("id", &other, "key")
+            .filter("score", |score| score == "20")
+            .collect()
+            .unwrap();
+        assert_eq!(cells(&table, "name"), ["b"]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
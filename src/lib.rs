@@ -1,4 +1,72 @@
+mod aggregate;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod cells;
+mod checksum;
 mod column;
+mod column_map;
+mod composite;
+#[cfg(feature = "encryption")]
+mod crypto;
+mod database;
+pub mod dates;
+mod diff;
+mod expr;
+mod format;
+#[cfg(feature = "json")]
+mod json;
+pub mod keys;
+mod lazy;
+mod lineage;
+mod load;
+mod mapped;
+mod mapping;
+pub mod messages;
+pub mod order;
+mod outlier;
+pub mod parallel;
+mod parts;
+mod profile;
+#[cfg(feature = "python")]
+mod python;
+mod random;
+mod regex;
+mod repr;
+mod row;
+mod rules;
+mod schema;
+mod service;
+mod similarity;
+mod stream;
 mod table;
+mod template;
+pub mod tuner;
+mod types;
 mod value;
-pub use table::Table;
+mod view;
+pub use aggregate::{Aggregate, CumOp, NullPolicy, RollingStart};
+pub use checksum::Checksum;
+pub use column::Column;
+pub use database::Database;
+pub use format::{Format, PartitionLayout, TsvEscaping};
+pub use lazy::LazyTable;
+pub use lineage::Source;
+pub use load::{EncodingPolicy, LoadOptions, LoadReport, LoadState};
+pub use mapped::{MappedTable, MappedTsv};
+pub use mapping::{parse_field, parse_optional_field, FromRow, ToRow};
+pub use messages::{set_locale, Locale, Message};
+pub use outlier::Method;
+pub use parts::Part;
+pub use repr::{Align, Border, ReprOptions};
+pub use row::Row;
+pub use rules::{Rule, Rules};
+pub use schema::{Schema, ValidationReport, Violation};
+pub use service::{ReadStats, TableService};
+pub use similarity::{FuzzyMatch, Metric};
+#[cfg(feature = "derive")]
+pub use simplesql_derive::{FromRow, ToRow};
+pub use stream::{TableStream, TsvChunks};
+pub use table::{Derivations, JoinCollision, MaskOp, MiOp, Op, Rank, Sniff, Table};
+pub use types::ColumnType;
+pub use value::Value;
+pub use view::TableView;
@@ -0,0 +1,33 @@
+use super::value::Value;
+use std::collections::HashMap;
+
+// columns without an entry come from the table holding them
+pub(crate) type Lineage = HashMap<Value, Vec<Source>>;
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Source {
+    table: Option<Value>,
+    column: Value,
+}
+
+impl Source {
+    pub(crate) fn new(table: Option<Value>, column: Value) -> Source {
+        Source { table, column }
+    }
+
+    // None for tables that were never named
+    pub fn table(&self) -> Option<&str> {
+        self.table.as_deref()
+    }
+
+    pub fn column(&self) -> &str {
+        self.column.as_str()
+    }
+}
+
+pub(crate) fn union(sources: impl IntoIterator<Item = Source>) -> Vec<Source> {
+    let mut sources: Vec<Source> = sources.into_iter().collect();
+    sources.sort();
+    sources.dedup();
+    sources
+}
@@ -0,0 +1,477 @@
+use super::column::Column;
+use super::format;
+use super::messages::{self, Message};
+use super::schema::{self, Schema};
+use super::table::Res;
+use super::types::ColumnType;
+use super::value::Value;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+// filters run on the calling thread, so they may capture anything
+pub(crate) type RowFilter = Arc<dyn Fn(&str) -> bool>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncodingPolicy {
+    #[default]
+    Error,
+    Replace,
+    SkipRow,
+}
+
+// a row the parse could not load, validated loads put it in their quarantine table
+pub(crate) struct RejectedRow {
+    // counts the rows of the file that were not filtered out, loaded or not
+    pub(crate) row: usize,
+    // by loaded column, empty where the row has no field for it
+    pub(crate) cells: Vec<String>,
+    pub(crate) reason: String,
+}
+
+#[derive(Clone, Default)]
+pub struct LoadReport {
+    pub skipped_lines: Vec<usize>,
+}
+
+#[derive(Clone, Default)]
+pub struct LoadOptions {
+    pub(crate) skip_lines: usize,
+    pub(crate) columns: Option<Vec<String>>,
+    pub(crate) filters: Vec<(String, RowFilter)>,
+    pub(crate) schema: Option<Schema>,
+    pub(crate) encoding: EncodingPolicy,
+    pub(crate) infer_types: bool,
+    pub(crate) plain_cells: bool,
+    pub(crate) unescape: bool,
+    pub(crate) null_values: Vec<String>,
+    // types inferred from the first chunk of a stream, later chunks are checked against them
+    pub(crate) pinned_types: Option<Schema>,
+    // lines of the file before the text being parsed, so errors name lines of the file
+    pub(crate) line_offset: usize,
+}
+
+// the options that turn raw text into cells, parsing threads share it without the filters
+#[derive(Clone, Copy)]
+pub(crate) struct FieldReader<'a> {
+    null_values: &'a [String],
+    unescape: bool,
+}
+
+impl FieldReader<'_> {
+    pub(crate) fn read_header<'a>(&self, raw: &'a str) -> Cow<'a, str> {
+        let name = raw.trim();
+        if self.unescape {
+            format::unescape_tsv(name)
+        } else {
+            Cow::Borrowed(name)
+        }
+    }
+
+    // null markers are stored as empty cells
+    pub(crate) fn read_field<'a>(&self, raw: &'a str) -> Cow<'a, str> {
+        let field = raw.trim();
+        if self.null_values.iter().any(|null| null == field) {
+            Cow::Borrowed("")
+        } else {
+            self.read_header(field)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LoadState {
+    pub(crate) options: LoadOptions,
+    pub(crate) header: String,
+    pub(crate) offset: u64,
+    pub(crate) tail: Vec<u8>,
+}
+
+impl LoadState {
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl LoadOptions {
+    pub fn new() -> LoadOptions {
+        LoadOptions::default()
+    }
+
+    pub fn skip_lines(mut self, skip_lines: usize) -> LoadOptions {
+        self.skip_lines = skip_lines;
+        self
+    }
+
+    pub fn columns(mut self, col_names: &[&str]) -> LoadOptions {
+        self.columns = Some(col_names.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    pub fn filter(
+        mut self,
+        col_name: &str,
+        filter: impl Fn(&str) -> bool + 'static,
+    ) -> LoadOptions {
+        self.filters.push((col_name.to_string(), Arc::new(filter)));
+        self
+    }
+
+    pub fn schema(mut self, schema: Schema) -> LoadOptions {
+        self.schema = Some(schema);
+        self
+    }
+
+    pub fn null_values(mut self, values: &[&str]) -> LoadOptions {
+        self.null_values = values.iter().map(|v| v.to_string()).collect();
+        self
+    }
+
+    pub fn infer_types(mut self) -> LoadOptions {
+        self.infer_types = true;
+        self
+    }
+
+    // keeps every cell on its own instead of dictionary encoding repetitive columns
+    pub fn plain_cells(mut self) -> LoadOptions {
+        self.plain_cells = true;
+        self
+    }
+
+    // reads back files written with TsvEscaping::Backslash
+    pub fn unescape(mut self) -> LoadOptions {
+        self.unescape = true;
+        self
+    }
+
+    pub fn on_invalid_utf8(mut self, policy: EncodingPolicy) -> LoadOptions {
+        self.encoding = policy;
+        self
+    }
+
+    pub(crate) fn reader(&self) -> FieldReader<'_> {
+        FieldReader {
+            null_values: &self.null_values,
+            unescape: self.unescape,
+        }
+    }
+
+    pub(crate) fn read_header<'a>(&self, raw: &'a str) -> Cow<'a, str> {
+        self.reader().read_header(raw)
+    }
+
+    pub(crate) fn read_field<'a>(&self, raw: &'a str) -> Cow<'a, str> {
+        self.reader().read_field(raw)
+    }
+
+    pub(crate) fn pinned_type(&self, col_name: &str) -> Option<ColumnType> {
+        self.pinned_types
+            .as_ref()
+            .and_then(|schema| schema.column_type(col_name))
+    }
+
+    // the cells of a loaded column, typed as declared or inferred. A declared type has already
+    // coerced the cells, an inferred one never rewrites them.
+    pub(crate) fn column(&self, col_name: &str, cells: Vec<Value>) -> Res<Column> {
+        let declared = self
+            .schema
+            .as_ref()
+            .and_then(|schema| schema.column_type(col_name));
+        let column_type = match declared {
+            Some(column_type) => column_type,
+            None if self.infer_types => match self.pinned_type(col_name) {
+                Some(column_type) => column_type,
+                None => ColumnType::infer(cells.iter().map(Value::as_str)),
+            },
+            None => ColumnType::Str,
+        };
+        Ok(Column::parsed(cells, column_type, !self.plain_cells))
+    }
+}
+
+// line is 1 based
+// an inferred type is checked without rewriting the cell
+pub(crate) fn check_cell(
+    cell: &str,
+    column_type: ColumnType,
+    line: usize,
+    col_name: &str,
+) -> Res<()> {
+    if cell.is_empty() || column_type.accepts(cell) {
+        Ok(())
+    } else {
+        coerce_cell(cell, column_type, line, col_name).map(|_| ())
+    }
+}
+
+pub(crate) fn coerce_cell(
+    cell: &str,
+    column_type: ColumnType,
+    line: usize,
+    col_name: &str,
+) -> Res<Value> {
+    schema::coerce(cell, column_type)
+        .map(Value::new)
+        .map_err(|err| messages::text(Message::CellAt, &[&line, &col_name, &err]))
+}
+
+// how many bytes before the checkpoint are compared to tell appends from rewrites
+pub(crate) const TAIL_LEN: usize = 64;
+
+pub(crate) fn complete_lines_len(bytes: &[u8]) -> usize {
+    bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1)
+}
+
+// 1 based line of a byte position
+pub(crate) fn line_of(bytes: &[u8], position: usize) -> usize {
+    bytes[..position].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+// a single line for readers that go line by line, line is 1 based
+pub(crate) fn decode_line<'a>(
+    bytes: &'a [u8],
+    policy: EncodingPolicy,
+    line: usize,
+    report: &mut LoadReport,
+) -> Res<Cow<'a, str>> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(Cow::Borrowed(text)),
+        Err(_) => match policy {
+            EncodingPolicy::Error => Err(messages::text(Message::InvalidUtf8AtLine, &[&line])),
+            EncodingPolicy::Replace => Ok(String::from_utf8_lossy(bytes)),
+            EncodingPolicy::SkipRow => {
+                report.skipped_lines.push(line);
+                Ok(Cow::Borrowed(""))
+            }
+        },
+    }
+}
+
+// a whole file, changed in place so that a few bad bytes do not cost a second copy of the file.
+// header_after is the number of lines before the header when the bytes start with one:
+// whatever the policy, a header that is not text cannot name the columns
+pub(crate) fn decode(
+    bytes: Vec<u8>,
+    policy: EncodingPolicy,
+    header_after: Option<usize>,
+    report: &mut LoadReport,
+) -> Res<String> {
+    let error = match String::from_utf8(bytes) {
+        Ok(text) => return Ok(text),
+        Err(error) => error,
+    };
+    let first_invalid = error.utf8_error().valid_up_to();
+    let mut bytes = error.into_bytes();
+    if let Some(skip_lines) = header_after {
+        check_header(&bytes, skip_lines)?;
+    }
+    match policy {
+        EncodingPolicy::Error => {
+            let line = line_of(&bytes, first_invalid);
+            return Err(messages::text(Message::InvalidUtf8AtLine, &[&line]));
+        }
+        EncodingPolicy::Replace => replace_invalid(&mut bytes, first_invalid),
+        EncodingPolicy::SkipRow => skip_invalid_lines(&mut bytes, report),
+    }
+    String::from_utf8(bytes).map_err(|error| {
+        let line = line_of(error.as_bytes(), error.utf8_error().valid_up_to());
+        messages::text(Message::InvalidUtf8AtLine, &[&line])
+    })
+}
+
+fn check_header(bytes: &[u8], skip_lines: usize) -> Res<()> {
+    let header = bytes
+        .split(|&b| b == b'\n')
+        .enumerate()
+        .skip(skip_lines)
+        .find(|(_, line)| line.iter().any(|&b| b != b'\r'));
+    match header {
+        Some((i, line)) if std::str::from_utf8(line).is_err() => {
+            Err(messages::text(Message::InvalidUtf8AtLine, &[&(i + 1)]))
+        }
+        _ => Ok(()),
+    }
+}
+
+// every invalid sequence becomes U+FFFD like String::from_utf8_lossy does. The replacement can
+// be longer than the sequence, so the bytes are first moved right by the total growth and then
+// written back from the start, the write position never passing the read position
+fn replace_invalid(bytes: &mut Vec<u8>, first_invalid: usize) {
+    const REPLACEMENT: &[u8] = "\u{FFFD}".as_bytes();
+    let growth: usize = bytes[first_invalid..]
+        .utf8_chunks()
+        .filter(|chunk| !chunk.invalid().is_empty())
+        .map(|chunk| REPLACEMENT.len() - chunk.invalid().len())
+        .sum();
+    let len = bytes.len();
+    bytes.resize(len + growth, 0);
+    bytes.copy_within(first_invalid..len, first_invalid + growth);
+    let mut read = first_invalid + growth;
+    let mut write = first_invalid;
+    while read < bytes.len() {
+        let (valid, invalid) = match std::str::from_utf8(&bytes[read..]) {
+            Ok(text) => (text.len(), 0),
+            Err(error) => {
+                let rest = bytes.len() - read - error.valid_up_to();
+                (error.valid_up_to(), error.error_len().unwrap_or(rest))
+            }
+        };
+        bytes.copy_within(read..read + valid, write);
+        read += valid + invalid;
+        write += valid;
+        if invalid > 0 {
+            bytes[write..write + REPLACEMENT.len()].copy_from_slice(REPLACEMENT);
+            write += REPLACEMENT.len();
+        }
+    }
+    bytes.truncate(write);
+}
+
+// invalid lines become empty so line numbers stay the same
+fn skip_invalid_lines(bytes: &mut Vec<u8>, report: &mut LoadReport) {
+    let mut read = 0;
+    let mut write = 0;
+    let mut line = 1;
+    while read < bytes.len() {
+        let end = bytes[read..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(bytes.len(), |position| read + position);
+        if std::str::from_utf8(&bytes[read..end]).is_ok() {
+            bytes.copy_within(read..end, write);
+            write += end - read;
+        } else {
+            report.skipped_lines.push(line);
+        }
+        if end < bytes.len() {
+            bytes[write] = b'\n';
+            write += 1;
+        }
+        read = end + 1;
+        line += 1;
+    }
+    bytes.truncate(write);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Table;
+
+    fn cells(table: &Table, col_name: &str) -> Vec<String> {
+        let column = table.column(col_name).unwrap();
+        column.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn inferred_types_keep_the_text() {
+        let text = "zip\tprice\tbig\n02134\t0.10\t1e3\n00501\t12.5\t2\n";
+        let table = Table::parse_tsv_with(text, &LoadOptions::new().infer_types()).unwrap();
+        assert_eq!(cells(&table, "zip"), vec!["02134", "00501"]);
+        assert_eq!(cells(&table, "price"), vec!["0.10", "12.5"]);
+        assert_eq!(cells(&table, "big"), vec!["1e3", "2"]);
+        let schema = table.schema();
+        assert_eq!(schema.column_type("zip"), Some(ColumnType::Int64));
+        assert_eq!(schema.column_type("big"), Some(ColumnType::Float64));
+        // typed order is numeric: 501 before 2134
+        let sorted = table.sort_column("zip").unwrap();
+        assert_eq!(cells(&sorted, "zip"), vec!["00501", "02134"]);
+    }
+
+    #[test]
+    fn declared_types_coerce() {
+        let schema = Schema::new().column("n", ColumnType::Int64);
+        let options = LoadOptions::new().schema(schema);
+        let table = Table::parse_tsv_with("n\n007\n", &options).unwrap();
+        assert_eq!(cells(&table, "n"), vec!["7"]);
+        assert!(Table::parse_tsv_with("n\nx\n", &options).is_err());
+    }
+
+    #[test]
+    fn fields_are_trimmed_nulled_and_unescaped() {
+        let options = LoadOptions::new().null_values(&["NA"]).unescape();
+        assert_eq!(options.read_field(" NA "), "");
+        assert_eq!(options.read_field(" a\\tb "), "a\tb");
+        assert_eq!(options.read_header(" NA "), "NA");
+        assert_eq!(LoadOptions::new().read_field("a\\tb"), "a\\tb");
+    }
+
+    #[test]
+    fn replace_matches_lossy_decoding() {
+        let inputs: [&[u8]; 4] = [
+            b"a\tb\n\xff\xfe\tc\n",
+            b"h\n\xe2\x82x\xf0\x9f\x98\n\xe2",
+            b"h\n\xc3\xa9\x80\x80\x80\n",
+            b"h\n\xe2\x82\xac\xff",
+        ];
+        for input in inputs {
+            let text = decode(
+                input.to_vec(),
+                EncodingPolicy::Replace,
+                None,
+                &mut LoadReport::default(),
+            );
+            assert_eq!(text.unwrap(), String::from_utf8_lossy(input));
+        }
+    }
+
+    #[test]
+    fn skip_row_keeps_line_numbers() {
+        let mut report = LoadReport::default();
+        let input = b"h\na\n\xffb\nc\n".to_vec();
+        let text = decode(input, EncodingPolicy::SkipRow, Some(0), &mut report).unwrap();
+        assert_eq!(text, "h\na\n\nc\n");
+        assert_eq!(report.skipped_lines, vec![3]);
+    }
+
+    #[test]
+    fn invalid_header_fails_whatever_the_policy() {
+        for policy in [
+            EncodingPolicy::Error,
+            EncodingPolicy::Replace,
+            EncodingPolicy::SkipRow,
+        ] {
+            let input = b"skipped\n\r\nh\xff\na\n".to_vec();
+            let err = decode(input, policy, Some(1), &mut LoadReport::default()).unwrap_err();
+            assert_eq!(err, "E017: non UTF-8 byte at line 3");
+        }
+        // the skipped lines before the header may be anything
+        let input = b"\xff\nh\na\n".to_vec();
+        let text = decode(
+            input,
+            EncodingPolicy::SkipRow,
+            Some(1),
+            &mut LoadReport::default(),
+        );
+        assert_eq!(text.unwrap(), "\nh\na\n");
+    }
+
+    #[test]
+    fn inference_picks_the_narrowest_type_that_fits_every_cell() {
+        let text =
+            "n\tx\tok\tday\tmixed\tempty\n1\t1\ttrue\t2024-01-31\t1\t\n\t2.5\tFALSE\t\tyes\t\n";
+        let table = Table::parse_tsv_with(text, &LoadOptions::new().infer_types()).unwrap();
+        let schema = table.schema();
+        let types: Vec<(&str, ColumnType)> = schema
+            .columns()
+            .iter()
+            .map(|(col_name, column_type)| (col_name.as_str(), *column_type))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("n", ColumnType::Int64),
+                ("x", ColumnType::Float64),
+                ("ok", ColumnType::Bool),
+                ("day", ColumnType::Date),
+                ("mixed", ColumnType::Str),
+                ("empty", ColumnType::Str),
+            ]
+        );
+        let plain = Table::parse_tsv(text, 0).unwrap();
+        assert!(plain
+            .schema()
+            .columns()
+            .iter()
+            .all(|(_, column_type)| *column_type == ColumnType::Str));
+    }
+}
@@ -0,0 +1,364 @@
+use super::column_map::ColumnMap;
+use super::load::{self, LoadOptions};
+use super::messages::{self, Message};
+use super::table::{Res, Table};
+use super::value::Value;
+use std::borrow::Cow;
+use std::fs::File;
+
+// the file, mapped or read, the text is checked to be UTF-8 once when opened
+pub struct MappedTsv {
+    bytes: Bytes,
+}
+
+// cells are slices of the file unless reading changed them, a Table is only built for the
+// columns asked for
+pub struct MappedTable<'a> {
+    col_names: Vec<Cow<'a, str>>,
+    columns: Vec<Vec<Cow<'a, str>>>,
+    // 1 based line of every row
+    lines: Vec<usize>,
+    options: LoadOptions,
+}
+
+impl MappedTsv {
+    // Safety: the file must not be truncated or written to while the MappedTsv is alive.
+    // A shorter file makes reads fault, and changed bytes can break the UTF-8 checked here.
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn open(path: &str) -> Res<MappedTsv> {
+        MappedTsv::with_bytes(path, Bytes::map)
+    }
+
+    // reads the whole file into memory, for files that can change while they are scanned
+    pub fn read(path: &str) -> Res<MappedTsv> {
+        MappedTsv::with_bytes(path, Bytes::read)
+    }
+
+    fn with_bytes(
+        path: &str,
+        load: impl FnOnce(&File) -> std::io::Result<Bytes>,
+    ) -> Res<MappedTsv> {
+        let read_error = |err: String| messages::text(Message::ReadTsv, &[&path, &err]);
+        let file = File::open(path).map_err(|err| read_error(err.to_string()))?;
+        let bytes = load(&file).map_err(|err| read_error(err.to_string()))?;
+        if let Err(error) = std::str::from_utf8(bytes.as_slice()) {
+            let line = load::line_of(bytes.as_slice(), error.valid_up_to());
+            return Err(read_error(messages::text(
+                Message::InvalidUtf8AtLine,
+                &[&line],
+            )));
+        }
+        Ok(MappedTsv { bytes })
+    }
+
+    pub fn text(&self) -> &str {
+        // checked when opened
+        std::str::from_utf8(self.bytes.as_slice()).unwrap()
+    }
+
+    pub fn scan(&self, skip_lines: usize) -> Res<MappedTable<'_>> {
+        self.scan_with(&LoadOptions::new().skip_lines(skip_lines))
+    }
+
+    // fields are read as parse_tsv_with reads them, schema and inference apply to the tables
+    // built from the scan
+    pub fn scan_with(&self, options: &LoadOptions) -> Res<MappedTable<'_>> {
+        let mut lines = self
+            .text()
+            .lines()
+            .enumerate()
+            .skip(options.skip_lines)
+            .skip_while(|(_, l)| l.is_empty());
+        let header: Vec<Cow<str>> = match lines.next() {
+            Some((_, header)) => header.split('\t').map(|h| options.read_header(h)).collect(),
+            None => return Err(messages::text(Message::HeaderMissing, &[])),
+        };
+        let field_of = |col_name: &str| {
+            header
+                .iter()
+                .position(|h| h == col_name)
+                .ok_or_else(|| messages::text(Message::ColumnMissing, &[&col_name]))
+        };
+        let fields_loaded: Vec<usize> = match &options.columns {
+            Some(wanted) => wanted
+                .iter()
+                .map(|col_name| field_of(col_name))
+                .collect::<Res<_>>()?,
+            None => (0..header.len()).collect(),
+        };
+        let mut filters = Vec::with_capacity(options.filters.len());
+        for (col_name, filter) in options.filters.iter() {
+            filters.push((field_of(col_name)?, filter));
+        }
+        if let Some(schema) = &options.schema {
+            for (col_name, _) in schema.columns.iter() {
+                field_of(col_name)?;
+            }
+        }
+
+        let mut columns: Vec<Vec<Cow<str>>> = vec![Vec::new(); fields_loaded.len()];
+        let mut row_lines = Vec::new();
+        let mut fields: Vec<Cow<str>> = Vec::with_capacity(header.len());
+        for (line_number, line) in lines.filter(|(_, line)| !line.is_empty()) {
+            fields.clear();
+            fields.extend(line.split('\t').map(|field| options.read_field(field)));
+            if fields.len() != header.len() {
+                return Err(messages::text(
+                    Message::RowLength,
+                    &[&fields.len(), &header.len()],
+                ));
+            }
+            if !filters
+                .iter()
+                .all(|(field, filter)| filter(&fields[*field]))
+            {
+                continue;
+            }
+            for (cells, &field) in columns.iter_mut().zip(&fields_loaded) {
+                cells.push(fields[field].clone());
+            }
+            row_lines.push(line_number + 1);
+        }
+        let col_names = fields_loaded
+            .iter()
+            .map(|&field| header[field].clone())
+            .collect();
+        Ok(MappedTable {
+            col_names,
+            columns,
+            lines: row_lines,
+            options: options.clone(),
+        })
+    }
+}
+
+impl<'a> MappedTable<'a> {
+    pub fn rows_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn columns_count(&self) -> usize {
+        self.col_names.len()
+    }
+
+    pub fn column_names(&self) -> Vec<&str> {
+        self.col_names.iter().map(|name| name.as_ref()).collect()
+    }
+
+    pub fn cells(&self, col_name: &str) -> Res<&[Cow<'a, str>]> {
+        self.col_names
+            .iter()
+            .position(|name| name == col_name)
+            .map(|field| self.columns[field].as_slice())
+            .ok_or_else(|| messages::text(Message::ColumnMissing, &[&col_name]))
+    }
+
+    pub fn cell(&self, row: usize, col_name: &str) -> Res<&str> {
+        self.cells(col_name)?
+            .get(row)
+            .map(|cell| cell.as_ref())
+            .ok_or_else(|| messages::text(Message::RowMissingInColumn, &[&row, &col_name]))
+    }
+
+    pub fn to_table(&self) -> Res<Table> {
+        self.select_to_table(&self.column_names())
+    }
+
+    pub fn select_to_table(&self, col_names: &[&str]) -> Res<Table> {
+        let mut columns = ColumnMap::with_capacity(col_names.len());
+        for &col_name in col_names {
+            let cells = self.cells(col_name)?;
+            let declared = self
+                .options
+                .schema
+                .as_ref()
+                .and_then(|schema| schema.column_type(col_name));
+            let values = match declared {
+                Some(column_type) => cells
+                    .iter()
+                    .zip(&self.lines)
+                    .map(|(cell, &line)| load::coerce_cell(cell, column_type, line, col_name))
+                    .collect::<Res<Vec<Value>>>()?,
+                None => cells
+                    .iter()
+                    .map(|cell| Value::from(cell.as_ref()))
+                    .collect(),
+            };
+            columns.insert(
+                Value::from(col_name),
+                self.options.column(col_name, values)?,
+            );
+        }
+        Ok(Table::from_columns(columns))
+    }
+}
+
+enum Bytes {
+    #[cfg(unix)]
+    Mapped(Mapping),
+    Owned(Vec<u8>),
+}
+
+impl Bytes {
+    #[cfg(unix)]
+    fn map(file: &File) -> std::io::Result<Bytes> {
+        Mapping::new(file).map(Bytes::Mapped)
+    }
+
+    // without mmap the file is read whole, cells still borrow from it
+    #[cfg(not(unix))]
+    fn map(file: &File) -> std::io::Result<Bytes> {
+        Bytes::read(file)
+    }
+
+    fn read(mut file: &File) -> std::io::Result<Bytes> {
+        use std::io::Read;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        Ok(Bytes::Owned(bytes))
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            #[cfg(unix)]
+            Bytes::Mapped(mapping) => mapping.as_slice(),
+            Bytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+#[cfg(unix)]
+struct Mapping {
+    pointer: *mut std::os::raw::c_void,
+    len: usize,
+}
+
+// the mapping is private and read only, nothing writes through the pointer
+#[cfg(unix)]
+unsafe impl Send for Mapping {}
+#[cfg(unix)]
+unsafe impl Sync for Mapping {}
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::{c_int, c_long, c_void};
+
+    pub const PROT_READ: c_int = 1;
+    pub const MAP_PRIVATE: c_int = 2;
+
+    extern "C" {
+        pub fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: c_long,
+        ) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+}
+
+#[cfg(unix)]
+impl Mapping {
+    fn new(file: &File) -> std::io::Result<Mapping> {
+        use std::os::unix::io::AsRawFd;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            // an empty mapping is rejected by mmap
+            return Ok(Mapping {
+                pointer: std::ptr::null_mut(),
+                len,
+            });
+        }
+        // MappedTsv::open leaves it to the caller that the file keeps its length and bytes
+        let pointer = unsafe {
+            sys::mmap(
+                std::ptr::null_mut(),
+                len,
+                sys::PROT_READ,
+                sys::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if pointer as isize == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Mapping { pointer, len })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.pointer as *const u8, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                sys::munmap(self.pointer, self.len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TsvEscaping;
+
+    fn write_temp(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("sql-mapped-{}-{}", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    const TEXT: &str = "id\tnote\n1\ta\\tb\n2\tNA\n3\tplain\n";
+
+    #[test]
+    fn scan_reads_fields_like_parse_tsv() {
+        let path = write_temp("fields.tsv", TEXT);
+        let options = LoadOptions::new().null_values(&["NA"]).unescape();
+        let file = MappedTsv::read(&path).unwrap();
+        let scanned = file.scan_with(&options).unwrap();
+        assert_eq!(scanned.cell(0, "note").unwrap(), "a\tb");
+        assert_eq!(scanned.cell(1, "note").unwrap(), "");
+        let parsed = Table::parse_tsv_with(TEXT, &options).unwrap();
+        assert_eq!(
+            scanned
+                .to_table()
+                .unwrap()
+                .to_tsv_with(&["id", "note"], TsvEscaping::Backslash),
+            parsed.to_tsv_with(&["id", "note"], TsvEscaping::Backslash)
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn scan_applies_filters_and_projection() {
+        let path = write_temp("filters.tsv", TEXT);
+        let options = LoadOptions::new()
+            .columns(&["note"])
+            .filter("id", |id| id != "2");
+        let file = unsafe { MappedTsv::open(&path).unwrap() };
+        let scanned = file.scan_with(&options).unwrap();
+        assert_eq!(scanned.column_names(), vec!["note"]);
+        assert_eq!(scanned.rows_count(), 2);
+        assert_eq!(scanned.cell(1, "note").unwrap(), "plain");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn scan_checks_row_length_and_utf8() {
+        let path = write_temp("short.tsv", "a\tb\n1\n");
+        assert!(MappedTsv::read(&path).unwrap().scan(0).is_err());
+        std::fs::write(&path, b"a\n\xff\n").unwrap();
+        assert!(MappedTsv::read(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
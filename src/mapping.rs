@@ -0,0 +1,150 @@
+use super::messages::{self, Message};
+use super::row::Row;
+use super::table::Res;
+use std::str::FromStr;
+
+// implemented by #[derive(FromRow)] with the "derive" feature
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Res<Self>;
+}
+
+// implemented by #[derive(ToRow)] with the "derive" feature
+pub trait ToRow {
+    fn column_names() -> Vec<&'static str>;
+    fn to_row(&self) -> Vec<String>;
+}
+
+pub fn parse_field<T: FromStr>(row: &Row, col_name: &str) -> Res<T> {
+    let value = row
+        .get(col_name)
+        .ok_or_else(|| messages::text(Message::ColumnMissing, &[&col_name]))?;
+    value.parse().map_err(|_| {
+        messages::text(
+            Message::FieldNotParsable,
+            &[
+                &value,
+                &col_name,
+                &row.position(),
+                &std::any::type_name::<T>(),
+            ],
+        )
+    })
+}
+
+// for Option fields: a null cell, stored empty, is None
+pub fn parse_optional_field<T: FromStr>(row: &Row, col_name: &str) -> Res<Option<T>> {
+    match row.get(col_name) {
+        Some("") => Ok(None),
+        _ => parse_field(row, col_name).map(Some),
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use crate::{FromRow, Table, ToRow};
+    use std::fmt;
+    use std::marker::PhantomData;
+    use std::str::FromStr;
+
+    // a field type whose generics hold a `->` and a path
+    #[derive(Debug, PartialEq)]
+    struct Tagged<A, B>(String, PhantomData<(A, B)>);
+
+    impl<A, B> FromStr for Tagged<A, B> {
+        type Err = ();
+
+        fn from_str(text: &str) -> Result<Self, ()> {
+            Ok(Tagged(text.to_string(), PhantomData))
+        }
+    }
+
+    impl<A, B> fmt::Display for Tagged<A, B> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    #[derive(FromRow, ToRow, Debug, PartialEq)]
+    #[sql(crate = crate)]
+    struct Item {
+        id: u32,
+        pub name: Option<String>,
+        score: std::option::Option<f64>,
+        r#type: String,
+        tag: Tagged<fn() -> u8, std::string::String>,
+    }
+
+    #[test]
+    fn fields_map_to_columns() {
+        assert_eq!(
+            Item::column_names(),
+            vec!["id", "name", "score", "type", "tag"]
+        );
+    }
+
+    #[test]
+    fn null_cells_are_none() {
+        let text = "id\tname\tscore\ttype\ttag\n1\tpen\t\tx\ta\n2\t\t0.5\ty\tb\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        let items: Vec<Item> = table.rows_as().unwrap();
+        assert_eq!(items[0].name.as_deref(), Some("pen"));
+        assert_eq!(items[0].score, None);
+        assert_eq!(items[1].name, None);
+        assert_eq!(items[1].score, Some(0.5));
+        assert_eq!(items[1].tag.0, "b");
+        assert_eq!(items[1].to_row(), vec!["2", "", "0.5", "y", "b"]);
+        let table = Table::from_structs(&items).unwrap();
+        assert_eq!(table.rows_as::<Item>().unwrap(), items);
+    }
+
+    #[test]
+    fn bad_cells_still_fail() {
+        let text = "id\tname\tscore\ttype\ttag\n1\t\tnope\tx\ta\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        assert!(table.rows_as::<Item>().is_err());
+    }
+
+    #[derive(FromRow, ToRow, Debug, PartialEq)]
+    #[sql(crate = crate)]
+    struct Invoice {
+        number: String,
+        amount: i64,
+        paid: bool,
+    }
+
+    #[test]
+    fn structs_round_trip_through_tables() {
+        let invoices = vec![
+            Invoice {
+                number: "A1".to_string(),
+                amount: 120,
+                paid: true,
+            },
+            Invoice {
+                number: "A2".to_string(),
+                amount: -5,
+                paid: false,
+            },
+        ];
+        let table = Table::from_structs(&invoices).unwrap();
+        assert_eq!(
+            table.to_tsv_all().unwrap(),
+            "number\tamount\tpaid\nA1\t120\ttrue\nA2\t-5\tfalse"
+        );
+        assert_eq!(table.rows_as::<Invoice>().unwrap(), invoices);
+        // extra columns are ignored, missing or unparsable ones are errors
+        let text = "paid\tnote\tamount\tnumber\nfalse\tx\t7\tB1\n";
+        let items: Vec<Invoice> = Table::parse_tsv(text, 0).unwrap().rows_as().unwrap();
+        assert_eq!(items[0].amount, 7);
+        let missing = Table::parse_tsv("number\tamount\nA1\t1\n", 0).unwrap();
+        assert_eq!(
+            missing.rows_as::<Invoice>().err().unwrap(),
+            "E001: column 'paid' does not exist"
+        );
+        let bad = Table::parse_tsv("number\tamount\tpaid\nA1\tten\ttrue\n", 0).unwrap();
+        assert_eq!(
+            bad.rows_as::<Invoice>().err().unwrap(),
+            "E097: value 'ten' in column 'amount' at row 0 is not a valid i64"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Locale {
+    #[default]
+    English,
+    Italian,
+}
+
+static LOCALE: AtomicU8 = AtomicU8::new(0);
+
+// process wide, errors built after the call use the new language
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::Italian,
+        _ => Locale::English,
+    }
+}
+
+macro_rules! catalog {
+    ($($message:ident $code:literal $english:literal $italian:literal,)*) => {
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        pub enum Message {
+            $($message,)*
+        }
+
+        impl Message {
+            pub const ALL: &'static [Message] = &[$(Message::$message,)*];
+
+            pub fn code(self) -> &'static str {
+                match self {
+                    $(Message::$message => $code,)*
+                }
+            }
+
+            // every {} is replaced by the next argument
+            pub fn template(self, locale: Locale) -> &'static str {
+                match (self, locale) {
+                    $(
+                        (Message::$message, Locale::English) => $english,
+                        (Message::$message, Locale::Italian) => $italian,
+                    )*
+                }
+            }
+        }
+    };
+}
+
+catalog! {
+    ColumnMissing "E001" "column '{}' does not exist" "colonna '{}' non esiste",
+    ColumnExists "E002" "column '{}' already exists" "colonna '{}' gia' esistente",
+    InColumn "E003" "column '{}': {}" "colonna '{}': {}",
+    AtRowOfColumn "E004" "row {} of column '{}': {}" "riga {} della colonna '{}': {}",
+    AtRow "E005" "row {}: {}" "riga {}: {}",
+    RowMissingInColumn "E006" "row {} does not exist in column '{}'" "riga {} non esiste nella colonna '{}'",
+    RowMissingInView "E007" "row {} does not exist in the view" "riga {} non esiste nella vista",
+    RowLength "E008" "row of length {} given but it should be {}" "fornita riga di lunghezza {} ma dovrebbe essere {}",
+    HeaderMissing "E009" "missing column names" "mancano i nomi di colonna",
+    HeaderMissingInFile "E010" "missing column names in file {}" "mancano i nomi di colonna nel file {}",
+    ReadFile "E011" "error reading file {}: {}" "Errore leggendo file {}: {}",
+    ReadTsv "E012" "error reading tsv file {}: {}" "Errore leggendo tsv file {}: {}",
+    ParseTsv "E013" "error creating the table from tsv file {}: {}" "Errore creando la table dal tsv file {}: {}",
+    WriteTsv "E014" "cannot write the table to tsv file '{}': {}" "Impossibile scrivere su file tsv '{}' la table: {}",
+    WritePartition "E015" "cannot write the partition to file '{}': {}" "Impossibile scrivere su file '{}' la partizione: {}",
+    WriteTables "E016" "cannot write the tables to directory '{}': {}" "Impossibile scrivere le table nella cartella '{}': {}",
+    InvalidUtf8AtLine "E017" "non UTF-8 byte at line {}" "byte non UTF-8 alla riga {}",
+    NotUtf8 "E018" "text is not UTF-8" "testo non UTF-8",
+    NotNumeric "E019" "value '{}' is not numeric" "valore '{}' non numerico",
+    NotNumericAt "E020" "value '{}' at row {} of column '{}' is not numeric" "valore '{}' alla riga {} della colonna '{}' non numerico",
+    NotIntegerAt "E021" "value '{}' at row {} of column '{}' is not an integer" "valore '{}' alla riga {} della colonna '{}' non e' un intero",
+    NotBooleanAt "E022" "value '{}' at row {} of column '{}' is not boolean" "valore '{}' alla riga {} della colonna '{}' non booleano",
+    NotDateAt "E023" "value '{}' at row {} of column '{}' is not a date" "valore '{}' alla riga {} della colonna '{}' non e' una data",
+    NotDateInFormatAt "E024" "value '{}' at row {} of column '{}' is not a date in format '{}'" "valore '{}' alla riga {} della colonna '{}' non e' una data nel formato '{}'",
+    NotDateTimeAt "E025" "value '{}' at row {} of column '{}' is not a date and time" "valore '{}' alla riga {} della colonna '{}' non e' una data e ora",
+    NotOfType "E026" "value '{}' is not of type {}" "valore '{}' non e' di tipo {}",
+    NoNumericValues "E027" "no numeric values in the column" "nessun valore numerico nella colonna",
+    InvalidIsoDate "E028" "invalid date '{}', expected format YYYY-MM-DD" "data '{}' non valida, formato atteso AAAA-MM-GG",
+    DateStepZero "E029" "the step of the date range must be positive" "il passo dell'intervallo di date deve essere positivo",
+    InvalidTimezone "E030" "invalid time zone '{}'" "fuso orario '{}' non valido",
+    FractionOutOfRange "E031" "fraction {} is not between 0 and 1" "frazione {} non compresa tra 0 e 1",
+    ThresholdOutOfRange "E032" "threshold {} is not between 0 and 1" "soglia {} non compresa tra 0 e 1",
+    EmptyWindow "E033" "the window must contain at least one row" "la finestra deve contenere almeno una riga",
+    AggregationOnColumn "E034" "aggregation on column '{}': {}" "aggregazione sulla colonna '{}': {}",
+    NoSharedColumns "E035" "the two tables have no columns in common" "le due table non hanno colonne in comune",
+    ColumnCountMismatch "E036" "comparing {} columns with {} columns" "confronto tra {} colonne e {} colonne",
+    ConcatColumnMissing "E037" "the second table in the concatenation has no column '{}'" "la seconda table in concatenazione non ha la colonna '{}'",
+    ColumnNotSorted "E038" "column '{}' is not sorted" "colonna '{}' non e' ordinata",
+    ColumnInBothTables "E039" "column '{}' is in both tables of the join" "colonna '{}' presente in entrambe le table del join",
+    ColumnExistsInJoin "E040" "column '{}' already exists in the join" "colonna '{}' gia' esistente nel join",
+    PivotValueIsIndex "E041" "value '{}' of column '{}' is the same as the index column" "il valore '{}' della colonna '{}' coincide con la colonna indice",
+    RegexGroupsMismatch "E042" "the regular expression has {} groups but {} columns were given" "l'espressione regolare ha {} gruppi ma sono state date {} colonne",
+    NoPrimaryKey "E043" "the table has no primary key" "la table non ha una chiave primaria",
+    PrimaryKeyEmpty "E044" "the primary key needs at least one column" "la chiave primaria deve avere almeno una colonna",
+    PrimaryKeyNull "E045" "null value at row {} of column '{}' in the primary key" "valore nullo alla riga {} della colonna '{}' nella chiave primaria",
+    PrimaryKeyDuplicate "E046" "duplicate primary key at rows {} and {}" "chiave primaria duplicata alle righe {} e {}",
+    ColumnInPrimaryKey "E047" "column '{}' is part of the primary key" "colonna '{}' fa parte della chiave primaria",
+    DuplicateAtRow "E048" "duplicate value at row {}" "valore duplicato alla riga {}",
+    DuplicateAtRows "E049" "duplicate value at {} other rows: {}" "valore duplicato in altre {} righe: {}",
+    EmptyValue "E050" "empty value" "valore vuoto",
+    MissingColumn "E051" "missing column" "colonna mancante",
+    NullNotAllowed "E052" "null value not allowed" "valore nullo non ammesso",
+    ValueNotDateInFormat "E053" "value is not a date in format '{}'" "valore non e' una data nel formato '{}'",
+    ValueNotOfType "E054" "value is not of type {}" "valore non e' di tipo {}",
+    ValueNotMatching "E055" "value does not match pattern '{}'" "valore non corrisponde al pattern '{}'",
+    ColumnPattern "E056" "pattern of column '{}': {}" "pattern della colonna '{}': {}",
+    InvalidTableName "E057" "invalid table name '{}'" "nome di table '{}' non valido",
+    TableNotRegistered "E058" "table '{}' is not registered" "table '{}' non registrata",
+    InvalidKeyLength "E059" "invalid key of {} bytes, 16 or 32 bytes are needed" "chiave di {} byte non valida, servono 16 o 32 byte",
+    InvalidCiphertext "E060" "invalid encrypted data or wrong key" "dato cifrato non valido o chiave errata",
+    InvalidBase64 "E061" "invalid base64 '{}'" "base64 '{}' non valido",
+    NullStringPointer "E062" "null string pointer" "puntatore a stringa nullo",
+    NullStringListPointer "E063" "null string list pointer" "puntatore a lista di stringhe nullo",
+    NullTablePointer "E064" "null table pointer" "puntatore a table nullo",
+    NulByteInResult "E065" "the result contains a nul byte" "il risultato contiene un byte nullo",
+    InvalidExpression "E066" "invalid expression at position {}: {}" "espressione non valida alla posizione {}: {}",
+    InvalidJson "E067" "invalid JSON at line {}, column {}" "JSON non valido alla riga {}, colonna {}",
+    InvalidRegex "E068" "invalid regular expression at position {}: {}" "espressione regolare non valida alla posizione {}: {}",
+    InvalidJsonPath "E069" "invalid JSON path '{}'" "percorso JSON '{}' non valido",
+    Expected "E070" "expected '{}'" "atteso '{}'",
+    // E071, E074 and E077 to E082 were used by the JSON reader replaced by serde_json
+    ExpectedComparison "E072" "expected a comparison operator" "atteso un operatore di confronto",
+    ExpectedOperand "E073" "expected a column or a value" "atteso una colonna o un valore",
+    MissingClosing "E075" "missing closing {}" "manca la chiusura {}",
+    UnexpectedAfterExpression "E076" "unexpected content after the expression" "contenuto inatteso dopo l'espressione",
+    UnmatchedParenthesis "E083" "')' without a matching '('" "')' senza '(' corrispondente",
+    NothingToRepeat "E084" "quantifier with nothing to repeat" "quantificatore senza niente da ripetere",
+    InvalidRepetition "E085" "invalid repetition range" "intervallo di ripetizione non valido",
+    TrailingBackslash "E086" "'\\' at the end of the pattern" "'\\' alla fine del pattern",
+    InvalidCharRange "E087" "invalid character range" "intervallo di caratteri non valido",
+    AtLine "E088" "line {}: {}" "riga {}: {}",
+    RuleTooShort "E089" "a rule needs an id, a kind, a column and its arguments" "una regola richiede id, tipo, colonna e argomenti",
+    RuleArguments "E090" "wrong number of arguments for a '{}' rule" "numero di argomenti sbagliato per una regola '{}'",
+    UnknownRule "E091" "unknown rule kind '{}'" "tipo di regola '{}' sconosciuto",
+    ValueNotInSet "E092" "value is not one of the allowed values" "valore non tra quelli ammessi",
+    ValueNotBetween "E093" "value is not between {} and {}" "valore non compreso tra {} e {}",
+    ValueNotNumeric "E094" "value is not numeric" "valore non numerico",
+    ValueNotSum "E095" "value is not {}, which is {}" "valore diverso da {}, che vale {}",
+    RowMissing "E096" "row {} does not exist in the table" "riga {} non esiste nella tabella",
+    FieldNotParsable "E097" "value '{}' in column '{}' at row {} is not a valid {}" "valore '{}' nella colonna '{}' alla riga {} non e' un {} valido",
+    UnclosedTag "E098" "tag opened at offset {} is not closed" "tag aperto alla posizione {} non chiuso",
+    UnknownSection "E099" "unknown section '{}' at offset {}, only 'rows' is supported" "sezione '{}' sconosciuta alla posizione {}, e' supportata solo 'rows'",
+    UnopenedSection "E100" "section closed at offset {} was never opened" "sezione chiusa alla posizione {} mai aperta",
+    UnclosedSection "E101" "rows section is not closed" "sezione rows non chiusa",
+    CellOutsideRows "E102" "column '{}' can only be used inside a rows section" "la colonna '{}' puo' essere usata solo dentro una sezione rows",
+    DiffKeyDuplicate "E103" "duplicate key {} at rows {} and {}" "chiave {} duplicata alle righe {} e {}",
+    RowsShown "E104" "{} of {} rows" "{} di {} righe",
+    RowsCount "E105" "{} rows" "{} righe",
+    ColumnLength "E106" "column of length {} given but the table has {} rows" "fornita colonna di lunghezza {} ma la tabella ha {} righe",
+    NotMergeable "E107" "the operation on column '{}' cannot be merged across chunks" "l'operazione sulla colonna '{}' non si puo' combinare tra blocchi",
+    KeyColumnsCount "E108" "{} key columns given for the first table but {} for the second" "{} colonne chiave date per la prima tabella ma {} per la seconda",
+    CellBreaksTsv "E109" "a cell of column '{}' contains a tab or a line break, write it with TsvEscaping::Backslash" "una cella della colonna '{}' contiene una tabulazione o un a capo, scriverla con TsvEscaping::Backslash",
+    PatternTooLarge "E110" "the pattern is too large once its repetitions are expanded" "il pattern e' troppo grande una volta espanse le ripetizioni",
+    NestingTooDeep "E111" "nested more than {} levels deep" "annidato oltre {} livelli",
+    Panicked "E112" "internal error: {}" "errore interno: {}",
+    CellAt "E113" "line {}, column '{}': {}" "riga {}, colonna '{}': {}",
+    SumOverflow "E114" "the sum does not fit in a 64 bit integer" "la somma non sta in un intero a 64 bit",
+    RangeReversed "E115" "the low bound '{}' comes after the high bound '{}' in the order of the column" "il limite inferiore '{}' viene dopo il limite superiore '{}' nell'ordine della colonna",
+    Arrow "E116" "error writing the arrow stream: {}" "errore nella scrittura dello stream arrow: {}",
+    UnknownAggregate "E117" "unknown aggregate '{}'" "aggregazione sconosciuta '{}'",
+}
+
+// starts with the code of the message, "E008: ...", so logs can be searched by code whatever
+// the locale
+pub fn text(message: Message, args: &[&dyn Display]) -> String {
+    let mut result = format!("{}: ", message.code());
+    fill(&mut result, message, args);
+    result
+}
+
+// without the code, for text shown as part of the output rather than as an error
+pub fn plain(message: Message, args: &[&dyn Display]) -> String {
+    let mut result = String::new();
+    fill(&mut result, message, args);
+    result
+}
+
+fn fill(result: &mut String, message: Message, args: &[&dyn Display]) {
+    let mut args = args.iter();
+    let mut parts = message.template(locale()).split("{}");
+    *result += parts.next().unwrap_or_default();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            let _ = write!(result, "{}", arg);
+        }
+        *result += part;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ColumnType, LoadOptions, Schema, Table};
+    use std::collections::HashSet;
+
+    #[test]
+    fn text_starts_with_the_code() {
+        let text = text(Message::RowsCount, &[&3]);
+        assert!(text.starts_with("E105: "));
+        assert_eq!(plain(Message::RowsCount, &[&3]), "3 rows");
+    }
+
+    #[test]
+    fn codes_are_unique_and_translated() {
+        let mut codes = HashSet::new();
+        for &message in Message::ALL {
+            assert!(codes.insert(message.code()), "{}", message.code());
+            let english = message.template(Locale::English).matches("{}").count();
+            let italian = message.template(Locale::Italian).matches("{}").count();
+            assert_eq!(english, italian, "{}", message.code());
+        }
+    }
+
+    #[test]
+    fn bad_cell_reports_line_and_column() {
+        let options = LoadOptions::new().schema(Schema::new().column("n", ColumnType::Int64));
+        let err = Table::parse_tsv_with("n\n1\nx\n", &options).err().unwrap();
+        assert!(err.starts_with("E113: line 3, column 'n': "), "{}", err);
+    }
+}
@@ -0,0 +1,73 @@
+use std::cmp::Ordering;
+
+pub fn numeric(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+pub fn natural(a: &str, b: &str) -> Ordering {
+    let mut rest_a = a;
+    let mut rest_b = b;
+    loop {
+        let (chunk_a, tail_a) = next_chunk(rest_a);
+        let (chunk_b, tail_b) = next_chunk(rest_b);
+        if chunk_a.is_empty() || chunk_b.is_empty() {
+            return chunk_a.len().cmp(&chunk_b.len()).then_with(|| a.cmp(b));
+        }
+        let both_digits = is_digit_chunk(chunk_a) && is_digit_chunk(chunk_b);
+        let ordering = if both_digits {
+            let trimmed_a = chunk_a.trim_start_matches('0');
+            let trimmed_b = chunk_b.trim_start_matches('0');
+            trimmed_a
+                .len()
+                .cmp(&trimmed_b.len())
+                .then_with(|| trimmed_a.cmp(trimmed_b))
+        } else {
+            chunk_a.cmp(chunk_b)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        rest_a = tail_a;
+        rest_b = tail_b;
+    }
+}
+
+fn is_digit_chunk(chunk: &str) -> bool {
+    chunk.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn next_chunk(string: &str) -> (&str, &str) {
+    let digits = is_digit_chunk(string);
+    let end = string
+        .find(|c: char| c.is_ascii_digit() != digits)
+        .unwrap_or(string.len());
+    string.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_compares_digit_runs_as_numbers() {
+        let mut names = vec!["file10", "file2", "file02b", "file", "File1", "file2a"];
+        names.sort_by(|a, b| natural(a, b));
+        assert_eq!(
+            names,
+            vec!["File1", "file", "file2", "file2a", "file02b", "file10"]
+        );
+        assert_eq!(natural("a007", "a7"), Ordering::Less);
+    }
+
+    #[test]
+    fn numeric_puts_numbers_before_text() {
+        let mut values = vec!["10", "b", "9", "-1.5", "a"];
+        values.sort_by(|a, b| numeric(a, b));
+        assert_eq!(values, vec!["-1.5", "9", "10", "a", "b"]);
+    }
+}
@@ -0,0 +1,79 @@
+#[derive(Clone, Copy, PartialEq)]
+pub enum Method {
+    IqrFactor(f64),
+    ZScore(f64),
+}
+
+impl Method {
+    // one flag per value, nulls are never outliers
+    pub(crate) fn flag(self, values: &[Option<f64>]) -> Vec<bool> {
+        let mut present: Vec<f64> = values.iter().flatten().copied().collect();
+        if present.is_empty() {
+            return vec![false; values.len()];
+        }
+        let (low, high) = match self {
+            Method::IqrFactor(factor) => {
+                present.sort_unstable_by(|a, b| a.total_cmp(b));
+                let q1 = quantile(&present, 0.25);
+                let q3 = quantile(&present, 0.75);
+                let range = q3 - q1;
+                (q1 - factor * range, q3 + factor * range)
+            }
+            Method::ZScore(threshold) => {
+                let n = present.len() as f64;
+                let mean = present.iter().sum::<f64>() / n;
+                let variance = present.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+                let deviation = threshold * variance.sqrt();
+                (mean - deviation, mean + deviation)
+            }
+        };
+        values
+            .iter()
+            .map(|value| value.is_some_and(|x| x < low || x > high))
+            .collect()
+    }
+}
+
+// linear interpolation between the closest ranks of sorted values
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    sorted[below] + (sorted[above] - sorted[below]) * (position - below as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(numbers: &[f64]) -> Vec<Option<f64>> {
+        numbers.iter().map(|&n| Some(n)).collect()
+    }
+
+    #[test]
+    fn quantiles_interpolate_between_ranks() {
+        let sorted = [1.0, 2.0, 4.0, 8.0];
+        assert_eq!(quantile(&sorted, 0.0), 1.0);
+        assert_eq!(quantile(&sorted, 0.5), 3.0);
+        assert_eq!(quantile(&sorted, 0.75), 5.0);
+        assert_eq!(quantile(&[7.0], 0.25), 7.0);
+    }
+
+    #[test]
+    fn both_methods_flag_the_far_value_and_never_a_null() {
+        let mut numbers = values(&[3.0, 1.0, 100.0, 2.0, 4.0]);
+        numbers.push(None);
+        let flags = vec![false, false, true, false, false, false];
+        assert_eq!(Method::IqrFactor(1.5).flag(&numbers), flags);
+        assert_eq!(Method::ZScore(1.5).flag(&numbers), flags);
+        assert_eq!(Method::ZScore(2.0).flag(&numbers), vec![false; 6]);
+        assert_eq!(
+            Method::IqrFactor(1.5).flag(&[None, None]),
+            vec![false, false]
+        );
+        assert_eq!(
+            Method::ZScore(0.0).flag(&values(&[5.0, 5.0])),
+            vec![false, false]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::ops::Range;
+#[cfg(feature = "parallel")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// smaller inputs are not worth spawning a thread for
+#[cfg(feature = "parallel")]
+const MIN_CHUNK_ROWS: usize = 16_384;
+
+#[cfg(feature = "parallel")]
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+// 0 uses one thread per core
+#[cfg(feature = "parallel")]
+pub fn set_threads(threads: usize) {
+    THREADS.store(threads, Ordering::Relaxed);
+}
+
+#[cfg(feature = "parallel")]
+pub fn threads() -> usize {
+    match THREADS.load(Ordering::Relaxed) {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        threads => threads,
+    }
+}
+
+// runs work over consecutive row ranges, the results come back in row order
+#[cfg(feature = "parallel")]
+pub(crate) fn chunks<R: Send>(
+    rows: usize,
+    work: impl Fn(Range<usize>) -> R + Send + Sync,
+) -> Vec<R> {
+    let threads = threads().min(rows / MIN_CHUNK_ROWS).max(1);
+    if threads == 1 {
+        return vec![work(0..rows)];
+    }
+    let chunk_rows = rows.div_ceil(threads);
+    let work = &work;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..rows)
+            .step_by(chunk_rows)
+            .map(|start| scope.spawn(move || work(start..rows.min(start + chunk_rows))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn chunks<R>(rows: usize, work: impl Fn(Range<usize>) -> R) -> Vec<R> {
+    vec![work(0..rows)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::LoadOptions;
+    use crate::table::{MiOp, Table};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn big_tsv(rows: usize) -> String {
+        let mut text = String::from("id\tname\n");
+        for row in 0..rows {
+            text.push_str(&format!("{}\tname {}\n", row, row % 7));
+            if row % 1000 == 0 {
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    fn same_table(a: &Table, b: &Table) -> bool {
+        a.column_names() == b.column_names()
+            && a.column_names().iter().all(|col_name| {
+                let column = a.column(col_name).unwrap();
+                column.same_cells(&b.column(col_name).unwrap())
+            })
+    }
+
+    #[test]
+    fn chunks_come_back_in_row_order() {
+        let ranges = chunks(100_000, |rows| rows);
+        let mut expected = 0;
+        for range in ranges {
+            assert_eq!(range.start, expected);
+            expected = range.end;
+        }
+        assert_eq!(expected, 100_000);
+    }
+
+    #[test]
+    fn parsing_and_filtering_keep_row_order() {
+        let table = Table::parse_tsv(&big_tsv(50_000), 0).unwrap();
+        assert_eq!(table.rows_count(), 50_000);
+        let ids = table.column("id").unwrap();
+        assert!(ids
+            .iter()
+            .enumerate()
+            .all(|(row, id)| id.to_string() == row.to_string()));
+        let filtered = table
+            .filter_column_par("name", |name| name == "name 3")
+            .unwrap();
+        assert_eq!(
+            filtered.rows_count(),
+            (0..50_000).filter(|row| row % 7 == 3).count()
+        );
+        assert_eq!(filtered.column("id").unwrap().get(1).to_string(), "10");
+        let options = LoadOptions::new().filter("name", |name| name == "name 3");
+        let loaded = Table::parse_tsv_with(&big_tsv(50_000), &options).unwrap();
+        assert!(same_table(&loaded, &filtered));
+    }
+
+    #[test]
+    fn parallel_variants_match_the_sequential_ones() {
+        let table = Table::parse_tsv(&big_tsv(40_000), 0).unwrap();
+        let shout = |name: &str| name.to_uppercase();
+        assert!(same_table(
+            &table.map_column_par("name", shout).unwrap(),
+            &table.map_column("name", shout).unwrap()
+        ));
+        let joined = |args: &[&str]| args.join("-");
+        let parallel = table
+            .create_column(MiOp::new_par(&["id", "name"], "key", Box::new(joined)))
+            .unwrap();
+        let sequential = table
+            .create_column(MiOp::new(&["id", "name"], "key", Box::new(joined)))
+            .unwrap();
+        assert!(same_table(&parallel, &sequential));
+        assert_eq!(parallel.column("key").unwrap().get(8).as_str(), "8-name 1");
+    }
+
+    // only the _par variants ask for Send + Sync, the feature does not change that
+    #[test]
+    fn closures_capturing_rc_and_refcell_are_accepted() {
+        let table = Table::parse_tsv("id\tname\n1\tone\n2\ttwo\n3\tone\n", 0).unwrap();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let wanted: Rc<str> = Rc::from("one");
+        let filtered = table
+            .filter_column("name", |name| {
+                seen.borrow_mut().push(name.to_string());
+                name == &*wanted
+            })
+            .unwrap();
+        assert_eq!(filtered.rows_count(), 2);
+        assert!(!seen.borrow().is_empty());
+        let suffix = Rc::new("!".to_string());
+        let mapped = table
+            .map_column("name", |name| format!("{}{}", name, suffix))
+            .unwrap();
+        assert_eq!(mapped.column("name").unwrap().get(1).as_str(), "two!");
+        let created = table
+            .create_column(MiOp::new(
+                &["name"],
+                "loud",
+                Box::new(move |args: &[&str]| format!("{}{}", args[0], suffix)),
+            ))
+            .unwrap();
+        assert_eq!(created.column("loud").unwrap().get(0).as_str(), "one!");
+        let options = LoadOptions::new().filter("name", move |name| name == &*wanted);
+        let loaded = Table::parse_tsv_with("id\tname\n1\tone\n2\ttwo\n", &options).unwrap();
+        assert_eq!(loaded.rows_count(), 1);
+        let count = Rc::new(RefCell::new(0));
+        let lazy = table
+            .lazy()
+            .filter("id", move |_| {
+                *count.borrow_mut() += 1;
+                true
+            })
+            .collect()
+            .unwrap();
+        assert_eq!(lazy.rows_count(), 3);
+    }
+}
@@ -0,0 +1,121 @@
+use std::net::Ipv4Addr;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    EmailUser,
+    EmailDomain,
+    UrlScheme,
+    UrlHost,
+    UrlPort,
+    UrlPath,
+    UrlQuery,
+    Ipv4Subnet24,
+}
+
+impl Part {
+    pub fn extract(self, value: &str) -> String {
+        let value = value.trim();
+        match self {
+            Part::EmailUser => split_email(value).map_or("", |(user, _)| user).to_string(),
+            Part::EmailDomain => {
+                split_email(value).map_or(String::new(), |(_, domain)| domain.to_lowercase())
+            }
+            Part::UrlScheme => {
+                split_url(value).map_or(String::new(), |url| url.scheme.to_lowercase())
+            }
+            Part::UrlHost => split_url(value).map_or(String::new(), |url| url.host.to_lowercase()),
+            Part::UrlPort => split_url(value).map_or("", |url| url.port).to_string(),
+            Part::UrlPath => split_url(value).map_or("", |url| url.path).to_string(),
+            Part::UrlQuery => split_url(value).map_or("", |url| url.query).to_string(),
+            Part::Ipv4Subnet24 => match value.parse::<Ipv4Addr>() {
+                Ok(ip) => {
+                    let [a, b, c, _] = ip.octets();
+                    format!("{}.{}.{}.0/24", a, b, c)
+                }
+                Err(_) => String::new(),
+            },
+        }
+    }
+}
+
+fn split_email(value: &str) -> Option<(&str, &str)> {
+    let at = value.rfind('@')?;
+    let (user, domain) = (&value[..at], &value[at + 1..]);
+    let invalid = |c: char| c.is_whitespace() || c == '/' || c == ':';
+    if user.is_empty() || domain.is_empty() || value.contains(invalid) {
+        None
+    } else {
+        Some((user, domain))
+    }
+}
+
+struct Url<'a> {
+    scheme: &'a str,
+    host: &'a str,
+    port: &'a str,
+    path: &'a str,
+    query: &'a str,
+}
+
+fn split_url(value: &str) -> Option<Url<'_>> {
+    let scheme_end = value.find("://")?;
+    let scheme = &value[..scheme_end];
+    let rest = &value[scheme_end + 3..];
+    let rest = rest.split('#').next().unwrap_or("");
+    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let authority = &rest[..authority_end];
+    let rest = &rest[authority_end..];
+    let (path, query) = match rest.find('?') {
+        Some(q) => (&rest[..q], &rest[q + 1..]),
+        None => (rest, ""),
+    };
+    let host_port = authority.rsplit('@').next().unwrap_or("");
+    let (host, port) = match host_port.rfind(':') {
+        Some(colon) if !host_port.ends_with(']') => (&host_port[..colon], &host_port[colon + 1..]),
+        _ => (host_port, ""),
+    };
+    if scheme.is_empty() || host.is_empty() {
+        return None;
+    }
+    Some(Url {
+        scheme,
+        host,
+        port,
+        path,
+        query,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn email_parts() {
+        assert_eq!(Part::EmailUser.extract(" ann.lee@Example.COM "), "ann.lee");
+        assert_eq!(
+            Part::EmailDomain.extract("ann.lee@Example.COM"),
+            "example.com"
+        );
+        assert_eq!(Part::EmailDomain.extract("not an email"), "");
+        assert_eq!(Part::EmailUser.extract("@example.com"), "");
+    }
+
+    #[test]
+    fn url_parts() {
+        let url = "HTTPS://user@Example.com:8080/a/b?x=1&y=2#top";
+        assert_eq!(Part::UrlScheme.extract(url), "https");
+        assert_eq!(Part::UrlHost.extract(url), "example.com");
+        assert_eq!(Part::UrlPort.extract(url), "8080");
+        assert_eq!(Part::UrlPath.extract(url), "/a/b");
+        assert_eq!(Part::UrlQuery.extract(url), "x=1&y=2");
+        assert_eq!(Part::UrlHost.extract("http://[::1]/"), "[::1]");
+        assert_eq!(Part::UrlPath.extract("example.com/a"), "");
+    }
+
+    #[test]
+    fn ipv4_subnets() {
+        assert_eq!(Part::Ipv4Subnet24.extract("192.168.7.42"), "192.168.7.0/24");
+        assert_eq!(Part::Ipv4Subnet24.extract("300.1.1.1"), "");
+    }
+}
@@ -0,0 +1,48 @@
+pub(crate) const CHAR_CLASSES: [&str; 6] = ["upper", "lower", "digit", "space", "punct", "other"];
+
+// how many of the most frequent shapes a profile lists
+pub(crate) const TOP_SHAPES: usize = 10;
+
+pub(crate) fn char_class(c: char) -> usize {
+    if c.is_uppercase() {
+        0
+    } else if c.is_lowercase() {
+        1
+    } else if c.is_ascii_digit() {
+        2
+    } else if c.is_whitespace() {
+        3
+    } else if c.is_ascii_punctuation() {
+        4
+    } else {
+        5
+    }
+}
+
+// letters become A or a and digits 9, anything else stays, so "AB-1234" is "AA-9999"
+pub(crate) fn shape(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match char_class(c) {
+            0 => 'A',
+            1 => 'a',
+            2 => '9',
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_keep_everything_but_letters_and_digits() {
+        assert_eq!(shape("AB-1234"), "AA-9999");
+        assert_eq!(shape("Zoë 7/b"), "Aaa 9/a");
+        assert_eq!(shape("٣"), "٣");
+        assert_eq!(CHAR_CLASSES[char_class('_')], "punct");
+        assert_eq!(CHAR_CLASSES[char_class('€')], "other");
+        assert_eq!(CHAR_CLASSES[char_class('\t')], "space");
+    }
+}
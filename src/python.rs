@@ -0,0 +1,274 @@
+// The sql Python module: tables and databases backed by this crate, so a notebook runs the
+// same joins and groupings as the Rust pipeline. Build it with maturin and the python
+// feature, adding pyo3/extension-module for a wheel. Errors are raised as ValueError with
+// the library message.
+// The pyo3 macros convert PyErr into itself, which clippy reports on every method.
+#![allow(clippy::useless_conversion)]
+
+use super::aggregate::Aggregate;
+use super::database::Database;
+use super::messages::{self, Message};
+use super::table::{Op, Res, Table};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+fn raise<T>(result: Res<T>) -> PyResult<T> {
+    result.map_err(PyValueError::new_err)
+}
+
+fn aggregate(name: &str) -> Res<Aggregate> {
+    match name {
+        "count" => Ok(Aggregate::Count),
+        "sum" => Ok(Aggregate::Sum),
+        "mean" => Ok(Aggregate::Mean),
+        "min" => Ok(Aggregate::Min),
+        "max" => Ok(Aggregate::Max),
+        "first" => Ok(Aggregate::First),
+        "last" => Ok(Aggregate::Last),
+        _ => Err(messages::text(Message::UnknownAggregate, &[&name])),
+    }
+}
+
+#[pyclass(name = "Table", module = "sql", frozen)]
+#[derive(Clone)]
+pub struct PyTable {
+    table: Table,
+}
+
+impl From<Table> for PyTable {
+    fn from(table: Table) -> PyTable {
+        PyTable { table }
+    }
+}
+
+#[pymethods]
+impl PyTable {
+    #[staticmethod]
+    #[pyo3(signature = (path, skip_lines = 0))]
+    fn load_tsv(path: &str, skip_lines: usize) -> PyResult<PyTable> {
+        raise(Table::load_tsv(path, skip_lines)).map(PyTable::from)
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (text, skip_lines = 0))]
+    fn parse_tsv(text: &str, skip_lines: usize) -> PyResult<PyTable> {
+        raise(Table::parse_tsv(text, skip_lines)).map(PyTable::from)
+    }
+
+    fn __len__(&self) -> usize {
+        self.table.rows_count()
+    }
+
+    fn __repr__(&self) -> String {
+        self.table.to_repr()
+    }
+
+    fn column_names(&self) -> Vec<String> {
+        self.table
+            .column_names()
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    fn column(&self, col_name: &str) -> PyResult<Vec<String>> {
+        let column = raise(self.table.column(col_name))?;
+        Ok(column.iter().map(|cell| cell.to_string()).collect())
+    }
+
+    fn cell(&self, row: usize, col_name: &str) -> PyResult<String> {
+        raise(self.table.cell(row, col_name)).map(String::from)
+    }
+
+    fn select_columns(&self, col_names: Vec<String>) -> PyResult<PyTable> {
+        let col_names: Vec<&str> = col_names.iter().map(String::as_str).collect();
+        raise(self.table.select_columns(&col_names)).map(PyTable::from)
+    }
+
+    fn filter_expr(&self, expr: &str) -> PyResult<PyTable> {
+        raise(self.table.filter_expr(expr)).map(PyTable::from)
+    }
+
+    #[pyo3(signature = (col_name, descending = false))]
+    fn sort_column(&self, col_name: &str, descending: bool) -> PyResult<PyTable> {
+        let sorted = if descending {
+            self.table.sort_column_desc(col_name)
+        } else {
+            self.table.sort_column(col_name)
+        };
+        raise(sorted).map(PyTable::from)
+    }
+
+    fn join_on_columns(
+        &self,
+        col_name: &str,
+        other: &PyTable,
+        other_col_name: &str,
+    ) -> PyResult<PyTable> {
+        raise(
+            self.table
+                .join_on_columns(col_name, &other.table, other_col_name),
+        )
+        .map(PyTable::from)
+    }
+
+    // operations are (column, aggregate) pairs, the aggregate being one of count, sum, mean,
+    // min, max, first or last
+    fn group_by_columns(
+        &self,
+        col_names: Vec<String>,
+        operations: Vec<(String, String)>,
+    ) -> PyResult<PyTable> {
+        let col_names: Vec<&str> = col_names.iter().map(String::as_str).collect();
+        let operations = raise(
+            operations
+                .iter()
+                .map(|(col_name, name)| Ok(Op::aggregate(col_name, aggregate(name)?)))
+                .collect::<Res<Vec<Op>>>(),
+        )?;
+        raise(self.table.group_by_columns(&col_names, &operations)).map(PyTable::from)
+    }
+
+    // every column in table order unless columns are given
+    #[pyo3(signature = (columns = None))]
+    fn to_tsv(&self, columns: Option<Vec<String>>) -> PyResult<String> {
+        raise(match columns {
+            Some(columns) => self.table.to_tsv(columns),
+            None => self.table.to_tsv_all(),
+        })
+    }
+
+    #[pyo3(signature = (path, columns = None))]
+    fn write_tsv(&self, path: &str, columns: Option<Vec<String>>) -> PyResult<()> {
+        raise(match columns {
+            Some(columns) => self.table.write_tsv_file(path, columns),
+            None => self.table.write_tsv_file_all(path),
+        })
+    }
+}
+
+#[pyclass(name = "Database", module = "sql")]
+#[derive(Default)]
+pub struct PyDatabase {
+    database: Database,
+}
+
+#[pymethods]
+impl PyDatabase {
+    #[new]
+    fn new() -> PyDatabase {
+        PyDatabase::default()
+    }
+
+    #[pyo3(signature = (name, table, columns = None))]
+    fn register(
+        &mut self,
+        name: &str,
+        table: &PyTable,
+        columns: Option<Vec<String>>,
+    ) -> PyResult<()> {
+        let header = columns.unwrap_or_else(|| {
+            table
+                .table
+                .column_names()
+                .into_iter()
+                .map(String::from)
+                .collect()
+        });
+        raise(self.database.register(name, table.table.clone(), header))
+    }
+
+    fn table(&self, name: &str) -> PyResult<PyTable> {
+        raise(self.database.table(name)).map(|table| PyTable::from(table.clone()))
+    }
+
+    fn write_all(&self, dir: &str) -> PyResult<()> {
+        raise(self.database.write_all(dir))
+    }
+}
+
+#[pymodule]
+pub fn sql(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyTable>()?;
+    module.add_class::<PyDatabase>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    fn run(code: &str) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new_bound(py, "sql").unwrap();
+            sql(&module).unwrap();
+            let globals = PyDict::new_bound(py);
+            globals.set_item("sql", module).unwrap();
+            if let Err(err) = py.run_bound(code, Some(&globals), None) {
+                panic!("{}", err);
+            }
+        });
+    }
+
+    #[test]
+    fn python_joins_and_groups_match_the_rust_results() {
+        let orders = "id\tcustomer\tamount\n1\ta\t10\n2\tb\t5\n3\ta\t7\n";
+        let customers = "customer\tcity\na\tRome\nb\tMilan\n";
+        let table = Table::parse_tsv(orders, 0)
+            .unwrap()
+            .join_on_columns(
+                "customer",
+                &Table::parse_tsv(customers, 0).unwrap(),
+                "customer",
+            )
+            .unwrap()
+            .group_by_columns(&["city"], &[Op::aggregate("amount", Aggregate::Sum)])
+            .unwrap()
+            .sort_column("city")
+            .unwrap();
+        let expected = table.to_tsv_columns(&["city", "amount"]).unwrap();
+        run(&format!(
+            r#"
+orders = sql.Table.parse_tsv({:?})
+customers = sql.Table.parse_tsv({:?})
+grouped = (
+    orders.join_on_columns("customer", customers, "customer")
+    .group_by_columns(["city"], [("amount", "sum")])
+    .sort_column("city")
+)
+assert grouped.to_tsv(["city", "amount"]) == {:?}, grouped.to_tsv()
+assert len(orders) == 3 and orders.column_names() == ["id", "customer", "amount"], orders.column_names()
+assert orders.filter_expr("amount > 6").column("id") == ["1", "3"], repr(orders.filter_expr("amount > 6"))
+assert orders.sort_column("customer", descending=True).cell(0, "id") == "2"
+"#,
+            orders, customers, expected
+        ));
+    }
+
+    #[test]
+    fn python_errors_carry_the_library_message() {
+        run(r#"
+table = sql.Table.parse_tsv("k\tv\na\t1\n")
+try:
+    table.column("missing")
+    raise AssertionError("no error")
+except ValueError as err:
+    assert str(err) == "E001: column 'missing' does not exist", str(err)
+try:
+    table.group_by_columns(["k"], [("v", "median")])
+    raise AssertionError("no error")
+except ValueError as err:
+    assert str(err).startswith("E117: "), str(err)
+db = sql.Database()
+db.register("t", table)
+assert db.table("t").column("v") == ["1"]
+try:
+    db.table("other")
+    raise AssertionError("no error")
+except ValueError as err:
+    assert str(err).startswith("E058: "), str(err)
+"#);
+    }
+}
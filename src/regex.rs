@@ -0,0 +1,585 @@
+use super::messages::{self, Message};
+use super::table::Res;
+
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<(char, char)>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat(Box<Node>, usize, Option<usize>, bool),
+}
+
+type Captures = Vec<Option<(usize, usize)>>;
+
+// nesting of groups, the parser and the compiler recurse once per level
+const MAX_DEPTH: usize = 200;
+// counted repetitions are unrolled, this bounds the program they make
+const MAX_PROGRAM: usize = 100_000;
+
+pub struct Regex {
+    program: Vec<Inst>,
+    groups: usize,
+}
+
+// a Pike VM program: every thread runs in lock step over the text, so matching is linear in
+// the text times the program and never recurses
+enum Inst {
+    Char(char),
+    Any,
+    Class(Vec<(char, char)>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Save(usize),
+    // both branches, the first one preferred
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Res<Regex> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            position: 0,
+            groups: 0,
+            depth: 0,
+        };
+        let root = parser.alternation()?;
+        if parser.position < parser.chars.len() {
+            return Err(parser.error(&messages::text(Message::UnmatchedParenthesis, &[])));
+        }
+        let mut program = Vec::new();
+        compile(&root, &mut program)
+            .map_err(|err| messages::text(Message::InvalidRegex, &[&0, &err]))?;
+        program.push(Inst::Match);
+        Ok(Regex {
+            program,
+            groups: parser.groups,
+        })
+    }
+
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    pub fn captures<'t>(&self, text: &'t str) -> Option<Vec<Option<&'t str>>> {
+        let offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let captures = self.find(text)?;
+        Some(
+            captures
+                .into_iter()
+                .map(|span| span.map(|(start, end)| &text[offsets[start]..offsets[end]]))
+                .collect(),
+        )
+    }
+
+    // leftmost match, group 0 is the whole match. Threads are kept in priority order, so the
+    // match found is the one a backtracking matcher would find first.
+    fn find(&self, text: &str) -> Option<Captures> {
+        let chars: Vec<char> = text.chars().collect();
+        let slots = 2 * (self.groups + 1);
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut matched: Option<Vec<Option<usize>>> = None;
+        for pos in 0..=chars.len() {
+            if matched.is_none() {
+                // a match starting here ranks below every match that started earlier
+                let mut slots = vec![None; slots];
+                slots[0] = Some(pos);
+                self.add_thread(&mut current, 0, pos, &chars, slots);
+            }
+            if current.list.is_empty() && matched.is_some() {
+                break;
+            }
+            for (pc, slots) in current.list.drain(..) {
+                let consumes = match &self.program[pc] {
+                    Inst::Char(c) => chars.get(pos) == Some(c),
+                    Inst::Any => chars.get(pos).is_some_and(|&c| c != '\n'),
+                    Inst::Class(ranges, negated) => chars.get(pos).is_some_and(|&c| {
+                        ranges.iter().any(|&(low, high)| low <= c && c <= high) != *negated
+                    }),
+                    Inst::Match => {
+                        let mut slots = slots;
+                        slots[1] = Some(pos);
+                        matched = Some(slots);
+                        // threads after this one have a lower priority
+                        break;
+                    }
+                    _ => false,
+                };
+                if consumes {
+                    self.add_thread(&mut next, pc + 1, pos + 1, &chars, slots);
+                }
+            }
+            current.clear();
+            std::mem::swap(&mut current, &mut next);
+        }
+        matched.map(|slots| slots.chunks(2).map(|pair| pair[0].zip(pair[1])).collect())
+    }
+
+    // follows jumps, splits, saves and assertions up to the instructions that consume a char,
+    // with a stack instead of recursion
+    fn add_thread(
+        &self,
+        threads: &mut Threads,
+        pc: usize,
+        pos: usize,
+        chars: &[char],
+        slots: Vec<Option<usize>>,
+    ) {
+        let mut stack = vec![(pc, slots)];
+        while let Some((pc, mut slots)) = stack.pop() {
+            if threads.seen[pc] == threads.generation {
+                continue;
+            }
+            threads.seen[pc] = threads.generation;
+            match &self.program[pc] {
+                Inst::Jump(target) => stack.push((*target, slots)),
+                Inst::Split(first, second) => {
+                    stack.push((*second, slots.clone()));
+                    stack.push((*first, slots));
+                }
+                Inst::Save(slot) => {
+                    slots[*slot] = Some(pos);
+                    stack.push((pc + 1, slots));
+                }
+                Inst::Start => {
+                    if pos == 0 {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::End => {
+                    if pos == chars.len() {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::WordBoundary(expected) => {
+                    let before = pos > 0 && is_word(chars[pos - 1]);
+                    let after = chars.get(pos).is_some_and(|&c| is_word(c));
+                    if (before != after) == *expected {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                _ => threads.list.push((pc, slots)),
+            }
+        }
+    }
+}
+
+struct Threads {
+    list: Vec<(usize, Vec<Option<usize>>)>,
+    // instructions already added at the current position
+    seen: Vec<usize>,
+    generation: usize,
+}
+
+impl Threads {
+    fn new(len: usize) -> Threads {
+        Threads {
+            list: Vec::new(),
+            seen: vec![0; len],
+            generation: 1,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.generation += 1;
+    }
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) -> Result<(), String> {
+    if program.len() > MAX_PROGRAM {
+        return Err(messages::text(Message::PatternTooLarge, &[]));
+    }
+    match node {
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(ranges, negated) => program.push(Inst::Class(ranges.clone(), *negated)),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::WordBoundary(expected) => program.push(Inst::WordBoundary(*expected)),
+        Node::Group(inner, None) => compile(inner, program)?,
+        Node::Group(inner, Some(group)) => {
+            program.push(Inst::Save(2 * group));
+            compile(inner, program)?;
+            program.push(Inst::Save(2 * group + 1));
+        }
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, program)?;
+            }
+        }
+        Node::Alternation(branches) => {
+            // every branch but the last starts with a split to the next one
+            let mut jumps = Vec::with_capacity(branches.len());
+            for (i, branch) in branches.iter().enumerate() {
+                if i + 1 == branches.len() {
+                    compile(branch, program)?;
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(branch, program)?;
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                let next = program.len();
+                program[split] = Inst::Split(split + 1, next);
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat(inner, min, max, greedy) => {
+            for _ in 0..*min {
+                compile(inner, program)?;
+            }
+            let split = |program: &mut Vec<Inst>, at: usize, body: usize, exit: usize| {
+                program[at] = if *greedy {
+                    Inst::Split(body, exit)
+                } else {
+                    Inst::Split(exit, body)
+                };
+            };
+            match max {
+                None => {
+                    let start = program.len();
+                    program.push(Inst::Match);
+                    compile(inner, program)?;
+                    program.push(Inst::Jump(start));
+                    let exit = program.len();
+                    split(program, start, start + 1, exit);
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Match);
+                        compile(inner, program)?;
+                        if program.len() > MAX_PROGRAM {
+                            return Err(messages::text(Message::PatternTooLarge, &[]));
+                        }
+                    }
+                    let exit = program.len();
+                    for at in splits {
+                        split(program, at, at + 1, exit);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    groups: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        messages::text(Message::InvalidRegex, &[&self.position, &message])
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn alternation(&mut self) -> Res<Node> {
+        let mut branches = vec![self.concat()?];
+        while self.eat('|') {
+            branches.push(self.concat()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Node::Alternation(branches)
+        })
+    }
+
+    fn concat(&mut self) -> Res<Node> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn quantified(&mut self, atom: Node) -> Res<Node> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match self.bounds() {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+        // past the quantifier, or the closing brace of its bounds
+        self.position += 1;
+        if let Node::Start | Node::End | Node::WordBoundary(_) = atom {
+            return Err(self.error(&messages::text(Message::NothingToRepeat, &[])));
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error(&messages::text(Message::InvalidRepetition, &[])));
+        }
+        let greedy = !self.eat('?');
+        Ok(Node::Repeat(Box::new(atom), min, max, greedy))
+    }
+
+    // `{n}`, `{n,}` or `{n,m}`; anything else is taken literally
+    fn bounds(&mut self) -> Option<(usize, Option<usize>)> {
+        let close = self.chars[self.position..].iter().position(|&c| c == '}')?;
+        let inside: String = self.chars[self.position + 1..self.position + close]
+            .iter()
+            .collect();
+        let bounds = match inside.split_once(',') {
+            None => {
+                let n = inside.parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        self.position += close;
+        Some(bounds)
+    }
+
+    fn atom(&mut self) -> Res<Node> {
+        let c = self.peek().unwrap();
+        self.position += 1;
+        match c {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '(' => {
+                let group = if self.chars[self.position..].starts_with(&['?', ':']) {
+                    self.position += 2;
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                self.depth += 1;
+                if self.depth > MAX_DEPTH {
+                    return Err(self.error(&messages::text(Message::NestingTooDeep, &[&MAX_DEPTH])));
+                }
+                let inner = self.alternation()?;
+                self.depth -= 1;
+                if !self.eat(')') {
+                    return Err(self.error(&messages::text(Message::Expected, &[&')'])));
+                }
+                Ok(Node::Group(Box::new(inner), group))
+            }
+            '[' => self.class(),
+            '\\' => self.escape(),
+            '*' | '+' | '?' => Err(self.error(&messages::text(Message::NothingToRepeat, &[]))),
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn escape(&mut self) -> Res<Node> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error(&messages::text(Message::TrailingBackslash, &[])))?;
+        self.position += 1;
+        Ok(match c {
+            'b' => Node::WordBoundary(true),
+            'B' => Node::WordBoundary(false),
+            _ => match shorthand(c) {
+                Some((ranges, negated)) => Node::Class(ranges, negated),
+                None => Node::Char(escaped_char(c)),
+            },
+        })
+    }
+
+    fn class(&mut self) -> Res<Node> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self
+                .peek()
+                .ok_or_else(|| self.error(&messages::text(Message::MissingClosing, &[&"']'"])))?;
+            self.position += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let low = if c == '\\' {
+                let e = self
+                    .peek()
+                    .ok_or_else(|| self.error(&messages::text(Message::TrailingBackslash, &[])))?;
+                self.position += 1;
+                if let Some((shorthand_ranges, false)) = shorthand(e) {
+                    ranges.extend(shorthand_ranges);
+                    continue;
+                }
+                escaped_char(e)
+            } else {
+                c
+            };
+            let is_range = self.peek() == Some('-')
+                && self.chars.get(self.position + 1).is_some_and(|&c| c != ']');
+            if is_range {
+                self.position += 1;
+                let mut high = self.peek().unwrap();
+                self.position += 1;
+                if high == '\\' {
+                    high = escaped_char(self.peek().ok_or_else(|| {
+                        self.error(&messages::text(Message::TrailingBackslash, &[]))
+                    })?);
+                    self.position += 1;
+                }
+                if high < low {
+                    return Err(self.error(&messages::text(Message::InvalidCharRange, &[])));
+                }
+                ranges.push((low, high));
+            } else {
+                ranges.push((low, low));
+            }
+        }
+        Ok(Node::Class(ranges, negated))
+    }
+}
+
+fn shorthand(c: char) -> Option<(Vec<(char, char)>, bool)> {
+    let digits = vec![('0', '9')];
+    let word = vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+    let space = vec![(' ', ' '), ('\t', '\r')];
+    Some(match c {
+        'd' => (digits, false),
+        'D' => (digits, true),
+        'w' => (word, false),
+        'W' => (word, true),
+        's' => (space, false),
+        'S' => (space, true),
+        _ => return None,
+    })
+}
+
+fn escaped_char(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups(pattern: &str, text: &str) -> Option<Vec<Option<String>>> {
+        let regex = Regex::new(pattern).unwrap();
+        regex
+            .captures(text)
+            .map(|groups| groups.into_iter().map(|g| g.map(str::to_string)).collect())
+    }
+
+    fn whole(pattern: &str, text: &str) -> Option<String> {
+        groups(pattern, text).and_then(|groups| groups[0].clone())
+    }
+
+    #[test]
+    fn finds_the_leftmost_match() {
+        assert_eq!(whole("b+", "aabbbcbb"), Some("bbb".to_string()));
+        assert_eq!(whole("x", "abc"), None);
+        assert_eq!(whole("", "abc"), Some(String::new()));
+    }
+
+    #[test]
+    fn greedy_and_lazy_repetitions() {
+        assert_eq!(whole("<.+>", "<a><b>"), Some("<a><b>".to_string()));
+        assert_eq!(whole("<.+?>", "<a><b>"), Some("<a>".to_string()));
+        assert_eq!(whole("a{2,3}", "aaaa"), Some("aaa".to_string()));
+        assert_eq!(whole("a{2,3}?", "aaaa"), Some("aa".to_string()));
+        assert_eq!(whole("a{2}", "a"), None);
+    }
+
+    #[test]
+    fn alternation_prefers_the_first_branch() {
+        assert_eq!(whole("ab|abc", "abc"), Some("ab".to_string()));
+        assert_eq!(whole("^(?:ab|abc)$", "abc"), Some("abc".to_string()));
+    }
+
+    #[test]
+    fn captures_groups() {
+        let found = groups(r"(\d+)-(\d+)?-(x)?", "on 12-34- day").unwrap();
+        assert_eq!(found[1].as_deref(), Some("12"));
+        assert_eq!(found[2].as_deref(), Some("34"));
+        assert_eq!(found[3], None);
+        let found = groups("(a|b)*", "abab").unwrap();
+        assert_eq!(found[1].as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn anchors_classes_and_boundaries() {
+        assert!(Regex::new("^abc$").unwrap().is_match("abc"));
+        assert!(!Regex::new("^abc$").unwrap().is_match("abcd"));
+        assert_eq!(whole(r"\bcat\b", "concat cat"), Some("cat".to_string()));
+        assert_eq!(whole("[^a-c]+", "abcdefa"), Some("def".to_string()));
+        assert_eq!(whole(r"\w+", "  héllo"), Some("h".to_string()));
+        assert_eq!(whole("é.", "caffé!"), Some("é!".to_string()));
+    }
+
+    #[test]
+    fn nested_repetitions_do_not_blow_up() {
+        let text = format!("{}!", "a".repeat(5000));
+        assert!(!Regex::new("^(a+)+b$").unwrap().is_match(&text));
+        assert!(!Regex::new("^(a|a)*b$").unwrap().is_match(&text));
+        assert!(Regex::new("^(a*)*!$").unwrap().is_match(&text));
+    }
+
+    #[test]
+    fn long_texts_do_not_overflow_the_stack() {
+        let text = "ab".repeat(200_000);
+        assert!(Regex::new("^(?:ab)*$").unwrap().is_match(&text));
+        assert!(Regex::new("^.*?b$").unwrap().is_match(&text));
+    }
+
+    #[test]
+    fn rejects_deep_nesting_and_huge_programs() {
+        let deep = format!("{}a{}", "(".repeat(1000), ")".repeat(1000));
+        assert!(Regex::new(&deep).is_err());
+        assert!(Regex::new("(?:a{1000}){1000}").is_err());
+        assert!(Regex::new("(a").is_err());
+        assert!(Regex::new("*a").is_err());
+    }
+}
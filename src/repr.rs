@@ -0,0 +1,383 @@
+use super::column::Column;
+use super::messages::{self, Message};
+use std::borrow::Cow;
+use std::ops::Range;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Right,
+    // right for numeric columns, left for the others
+    Auto,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Border {
+    #[default]
+    Ascii,
+    Unicode,
+}
+
+#[derive(Clone, Default)]
+pub struct ReprOptions {
+    pub(crate) max_rows: Option<usize>,
+    pub(crate) max_col_width: Option<usize>,
+    pub(crate) row_count: bool,
+    pub(crate) align: Align,
+    pub(crate) column_aligns: Vec<(String, Align)>,
+    pub(crate) border: Border,
+    pub(crate) hide_frame: bool,
+}
+
+impl ReprOptions {
+    pub fn new() -> ReprOptions {
+        ReprOptions::default()
+    }
+
+    // the first and last rows are shown, with a row of '…' between them
+    pub fn max_rows(mut self, max_rows: usize) -> ReprOptions {
+        self.max_rows = Some(max_rows);
+        self
+    }
+
+    // longer cells are cut and end with '…'
+    pub fn max_col_width(mut self, max_col_width: usize) -> ReprOptions {
+        self.max_col_width = Some(max_col_width.max(1));
+        self
+    }
+
+    pub fn row_count(mut self) -> ReprOptions {
+        self.row_count = true;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> ReprOptions {
+        self.align = align;
+        self
+    }
+
+    pub fn align_column(mut self, col_name: &str, align: Align) -> ReprOptions {
+        self.column_aligns.push((col_name.to_string(), align));
+        self
+    }
+
+    pub fn border(mut self, border: Border) -> ReprOptions {
+        self.border = border;
+        self
+    }
+
+    // only the lines between columns and below the header are drawn
+    pub fn hide_frame(mut self) -> ReprOptions {
+        self.hide_frame = true;
+        self
+    }
+
+    fn align_of(&self, col_name: &str) -> Align {
+        self.column_aligns
+            .iter()
+            .rev()
+            .find(|(name, _)| name == col_name)
+            .map_or(self.align, |(_, align)| *align)
+    }
+
+    fn clip<'v>(&self, value: &'v str) -> Cow<'v, str> {
+        match self.max_col_width {
+            Some(max) if value.chars().count() > max => {
+                let mut clipped: String = value.chars().take(max - 1).collect();
+                clipped.push('…');
+                Cow::Owned(clipped)
+            }
+            _ => Cow::Borrowed(value),
+        }
+    }
+}
+
+struct ReprColumn<'c> {
+    name: Cow<'c, str>,
+    width: usize,
+    cells: Vec<Cow<'c, str>>,
+    align: Align,
+}
+
+// only the rows shown are read from the columns, every column covers the same range of rows
+pub(crate) fn repr_columns(
+    columns: Vec<(&str, &Column)>,
+    rows: Range<usize>,
+    options: &ReprOptions,
+) -> String {
+    let first_row = rows.start;
+    let total_rows = rows.len();
+    let shown = shown_rows(total_rows, options.max_rows);
+    // lines of the table, with the '…' row when rows are left out
+    let rows = shown.len();
+    let data_rows = shown.iter().flatten().count();
+    let columns: Vec<ReprColumn> = columns
+        .into_iter()
+        .map(|(col_name, column)| {
+            let cells: Vec<Cow<str>> = shown
+                .iter()
+                .map(|row| match row {
+                    Some(row) => options.clip(column.get(first_row + row)),
+                    None => Cow::Borrowed("…"),
+                })
+                .collect();
+            let align = match options.align_of(col_name) {
+                Align::Auto if is_numeric(&cells) => Align::Right,
+                Align::Auto => Align::Left,
+                align => align,
+            };
+            let name = options.clip(col_name);
+            let width = cells
+                .iter()
+                .map(|cell| cell.chars().count())
+                .chain(std::iter::once(name.chars().count()))
+                .max()
+                .unwrap_or(0);
+            ReprColumn {
+                name,
+                width,
+                cells,
+                align,
+            }
+        })
+        .collect();
+    let width: usize = columns.iter().map(|c| c.width + 3).sum::<usize>() + 1;
+    let mut result = String::with_capacity((rows + 4) * (width + 2));
+    if options.border == Border::Ascii && !options.hide_frame {
+        ascii_frame(&mut result, &columns, rows, width);
+    } else {
+        lines(&mut result, &columns, rows, options);
+    }
+    if options.row_count {
+        result += &if data_rows < total_rows {
+            messages::plain(Message::RowsShown, &[&data_rows, &total_rows])
+        } else {
+            messages::plain(Message::RowsCount, &[&total_rows])
+        };
+        result.push('\n');
+    }
+    result
+}
+
+//   ___________
+//   | a  | b  |
+//   |----+----|
+//   | 1  | x  |
+//   |_________|
+fn ascii_frame(result: &mut String, columns: &[ReprColumn], rows: usize, width: usize) {
+    result.extend(std::iter::repeat_n('_', width));
+    result.push('\n');
+    result.push_str("| ");
+    for column in columns {
+        pad(result, &column.name, column.width, Align::Left);
+        result.push_str(" | ");
+    }
+    result.push_str("\n|");
+    let segments: Vec<String> = columns.iter().map(|c| "-".repeat(c.width + 2)).collect();
+    result.push_str(&segments.join("+"));
+    result.push('|');
+    for row in 0..rows {
+        result.push_str("\n| ");
+        for column in columns {
+            pad(result, &column.cells[row], column.width, column.align);
+            result.push_str(" | ");
+        }
+    }
+    result.push_str("\n|");
+    result.extend(std::iter::repeat_n('_', width.saturating_sub(2)));
+    result.push_str("|\n");
+}
+
+//   ┌────┬────┐      a  │ b
+//   │ a  │ b  │     ────┼───
+//   ├────┼────┤      1  │ x
+//   │ 1  │ x  │
+//   └────┴────┘
+fn lines(result: &mut String, columns: &[ReprColumn], rows: usize, options: &ReprOptions) {
+    let (horizontal, vertical, cross) = match options.border {
+        Border::Ascii => ('-', '|', '+'),
+        Border::Unicode => ('─', '│', '┼'),
+    };
+    let framed = !options.hide_frame;
+    let rule = |result: &mut String, left: char, middle: char, right: char| {
+        let segments: Vec<String> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let mut width = column.width + 2;
+                // without the frame the outer columns have no padding outside
+                if !framed {
+                    width -= (i == 0) as usize + (i == columns.len() - 1) as usize;
+                }
+                horizontal.to_string().repeat(width)
+            })
+            .collect();
+        if framed {
+            result.push(left);
+        }
+        result.push_str(&segments.join(&middle.to_string()));
+        if framed {
+            result.push(right);
+        }
+        result.push('\n');
+    };
+    let line = |result: &mut String, cells: &mut dyn Iterator<Item = (&str, usize, Align)>| {
+        let mut text = String::new();
+        if framed {
+            text.push(vertical);
+            text.push(' ');
+        }
+        for (i, (cell, width, align)) in cells.enumerate() {
+            if i > 0 {
+                text.push(' ');
+                text.push(vertical);
+                text.push(' ');
+            }
+            pad(&mut text, cell, width, align);
+        }
+        if framed {
+            text.push(' ');
+            text.push(vertical);
+        }
+        result.push_str(text.trim_end());
+        result.push('\n');
+    };
+    if framed {
+        rule(result, '┌', '┬', '┐');
+    }
+    line(
+        result,
+        &mut columns
+            .iter()
+            .map(|c| (c.name.as_ref(), c.width, Align::Left)),
+    );
+    rule(result, '├', cross, '┤');
+    for row in 0..rows {
+        line(
+            result,
+            &mut columns
+                .iter()
+                .map(|c| (c.cells[row].as_ref(), c.width, c.align)),
+        );
+    }
+    if framed {
+        rule(result, '└', '┴', '┘');
+    }
+}
+
+fn pad(result: &mut String, value: &str, width: usize, align: Align) {
+    let padding = width.saturating_sub(value.chars().count());
+    if align == Align::Right {
+        result.extend(std::iter::repeat_n(' ', padding));
+        result.push_str(value);
+    } else {
+        result.push_str(value);
+        result.extend(std::iter::repeat_n(' ', padding));
+    }
+}
+
+fn is_numeric(cells: &[Cow<str>]) -> bool {
+    let mut values = cells
+        .iter()
+        .filter(|cell| !cell.is_empty() && cell.as_ref() != "…")
+        .peekable();
+    values.peek().is_some() && values.all(|cell| cell.parse::<f64>().is_ok())
+}
+
+// None stands for the hidden rows
+fn shown_rows(total_rows: usize, max_rows: Option<usize>) -> Vec<Option<usize>> {
+    match max_rows {
+        Some(max) if max < total_rows => {
+            let head = max.div_ceil(2);
+            let tail = max / 2;
+            (0..head)
+                .map(Some)
+                .chain(std::iter::once(None))
+                .chain((total_rows - tail..total_rows).map(Some))
+                .collect()
+        }
+        _ => (0..total_rows).map(Some).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Table;
+
+    fn footer(max_rows: usize) -> String {
+        let table = Table::parse_tsv("n\n1\n2\n3\n4\n", 0).unwrap();
+        let options = ReprOptions::new().max_rows(max_rows).row_count();
+        let repr = table.to_repr_with(&options);
+        repr.lines().last().unwrap().to_string()
+    }
+
+    #[test]
+    fn footer_counts_the_rows_shown() {
+        assert_eq!(footer(3), "3 of 4 rows");
+        assert_eq!(footer(2), "2 of 4 rows");
+        assert_eq!(footer(0), "0 of 4 rows");
+        assert_eq!(footer(4), "4 rows");
+    }
+
+    #[test]
+    fn display_is_the_repr_and_precision_limits_the_rows() {
+        let table = Table::parse_tsv("n\tname\n1\ta long name\n2\tb\n3\tc\n", 0).unwrap();
+        assert_eq!(format!("{}", table), table.to_repr());
+        let limited = format!("{:.2}", table);
+        assert_eq!(
+            limited,
+            table.to_repr_with(&ReprOptions::new().max_rows(2).row_count())
+        );
+        assert!(limited.contains("| … | …           | \n"), "{}", limited);
+        assert!(limited.ends_with("|\n2 of 3 rows\n"), "{}", limited);
+        let clipped = table.to_repr_with(&ReprOptions::new().max_col_width(4));
+        assert!(clipped.contains("| 1 | a l… | \n"), "{}", clipped);
+    }
+
+    #[test]
+    fn numbers_align_right_and_borders_can_change() {
+        let table = Table::parse_tsv("n\tname\n1\tab\n10\tc\n", 0).unwrap();
+        let unicode = ReprOptions::new()
+            .align(Align::Auto)
+            .border(Border::Unicode);
+        assert_eq!(
+            table.to_repr_with(&unicode),
+            "┌────┬──────┐\n\
+             │ n  │ name │\n\
+             ├────┼──────┤\n\
+             │  1 │ ab   │\n\
+             │ 10 │ c    │\n\
+             └────┴──────┘\n"
+        );
+        let bare = ReprOptions::new()
+            .hide_frame()
+            .align_column("name", Align::Right);
+        assert_eq!(
+            table.to_repr_with(&bare),
+            "n  | name\n---+-----\n1  |   ab\n10 |    c\n"
+        );
+    }
+
+    #[test]
+    fn truncated_reprs_show_head_and_tail_with_clipped_cells() {
+        let text: String = (1..=6)
+            .map(|i| format!("{}\tname number {}\n", i, i))
+            .collect();
+        let table = Table::parse_tsv(&format!("id\tname\n{}", text), 0).unwrap();
+        assert_eq!(
+            table.to_repr_truncated(3, 6),
+            "_______________\n\
+             | id | name   | \n\
+             |----+--------|\n\
+             | 1  | name … | \n\
+             | 2  | name … | \n\
+             | …  | …      | \n\
+             | 6  | name … | \n\
+             |_____________|\n"
+        );
+        // a table within the limits is shown whole
+        assert_eq!(table.to_repr_truncated(6, 20), table.to_repr());
+    }
+}
@@ -0,0 +1,51 @@
+use super::table::Table;
+
+// borrows the table, one row at a time
+#[derive(Clone, Copy)]
+pub struct Row<'a> {
+    table: &'a Table,
+    position: usize,
+}
+
+impl<'a> Row<'a> {
+    pub(crate) fn new(table: &'a Table, position: usize) -> Row<'a> {
+        Row { table, position }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn get(&self, col_name: &str) -> Option<&'a str> {
+        let column = self.table.column_ref(col_name).ok()?;
+        (self.position < column.len()).then(|| column.get(self.position).as_str())
+    }
+
+    // cells in column order
+    pub fn values(&self) -> Vec<&'a str> {
+        self.table
+            .column_names()
+            .into_iter()
+            .filter_map(|col_name| self.get(col_name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_walk_the_table_in_order() {
+        let table = Table::parse_tsv("b\ta\n1\tx\n2\ty\n", 0).unwrap();
+        let rows: Vec<(usize, Vec<&str>)> = table
+            .rows()
+            .map(|row| (row.position(), row.values()))
+            .collect();
+        assert_eq!(rows, vec![(0, vec!["1", "x"]), (1, vec!["2", "y"])]);
+        let last = table.rows().last().unwrap();
+        assert_eq!(last.get("a"), Some("y"));
+        assert_eq!(last.get("missing"), None);
+        assert_eq!(Table::parse_tsv("a\n", 0).unwrap().rows().count(), 0);
+    }
+}
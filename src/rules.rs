@@ -0,0 +1,293 @@
+use super::messages::{self, Message};
+use super::regex::Regex;
+use super::table::{Res, Table, TableBuilder};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Rule {
+    Matches(String, String),
+    InSet(String, Vec<String>),
+    Between(String, f64, f64),
+    SumEquals(Vec<String>, String),
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Rules {
+    rules: Vec<(String, Rule)>,
+}
+
+pub(crate) struct RuleViolation<'r> {
+    pub(crate) rule: &'r str,
+    pub(crate) row: usize,
+    pub(crate) column: &'r str,
+    pub(crate) value: String,
+    pub(crate) message: String,
+}
+
+impl Rules {
+    pub fn new() -> Rules {
+        Rules::default()
+    }
+
+    pub fn rule(mut self, id: &str, rule: Rule) -> Rules {
+        self.rules.push((id.to_string(), rule));
+        self
+    }
+
+    pub fn matches(self, id: &str, col_name: &str, pattern: &str) -> Rules {
+        self.rule(id, Rule::Matches(col_name.to_string(), pattern.to_string()))
+    }
+
+    pub fn in_set(self, id: &str, col_name: &str, values: &[&str]) -> Rules {
+        let values = values.iter().map(|v| v.to_string()).collect();
+        self.rule(id, Rule::InSet(col_name.to_string(), values))
+    }
+
+    pub fn between(self, id: &str, col_name: &str, low: f64, high: f64) -> Rules {
+        self.rule(id, Rule::Between(col_name.to_string(), low, high))
+    }
+
+    pub fn sum_equals(self, id: &str, col_names: &[&str], target_col_name: &str) -> Rules {
+        let col_names = col_names.iter().map(|c| c.to_string()).collect();
+        self.rule(id, Rule::SumEquals(col_names, target_col_name.to_string()))
+    }
+
+    // one rule per line: id, kind, column and arguments separated by tabs
+    //   code_format  matches  code    [A-Z]{2}-\d{4}
+    //   status_ok    in       status  open  closed
+    //   amount_ok    between  amount  0     1000
+    //   total_ok     sum      total   net   tax
+    // empty lines and lines starting with # are skipped
+    pub fn parse(text: &str) -> Res<Rules> {
+        let mut rules = Rules::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let at_line = |err: String| messages::text(Message::AtLine, &[&(i + 1), &err]);
+            let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+            if fields.len() < 4 {
+                return Err(at_line(messages::text(Message::RuleTooShort, &[])));
+            }
+            let (id, kind, col_name, args) = (fields[0], fields[1], fields[2], &fields[3..]);
+            let number = |arg: &str| {
+                arg.parse::<f64>()
+                    .map_err(|_| at_line(messages::text(Message::NotNumeric, &[&arg])))
+            };
+            let rule = match (kind, args) {
+                ("matches", [pattern]) => Rule::Matches(col_name.to_string(), pattern.to_string()),
+                ("in", values) => Rule::InSet(
+                    col_name.to_string(),
+                    values.iter().map(|v| v.to_string()).collect(),
+                ),
+                ("between", [low, high]) => {
+                    Rule::Between(col_name.to_string(), number(low)?, number(high)?)
+                }
+                ("sum", operands) => Rule::SumEquals(
+                    operands.iter().map(|c| c.to_string()).collect(),
+                    col_name.to_string(),
+                ),
+                ("matches", _) | ("between", _) => {
+                    return Err(at_line(messages::text(Message::RuleArguments, &[&kind])))
+                }
+                _ => return Err(at_line(messages::text(Message::UnknownRule, &[&kind]))),
+            };
+            rules = rules.rule(id, rule);
+        }
+        Ok(rules)
+    }
+
+    pub fn load(path: &str) -> Res<Rules> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| messages::text(Message::ReadFile, &[&path, &err]))?;
+        Rules::parse(&text)
+    }
+
+    // a row per violation, null cells are never checked
+    pub fn evaluate(&self, table: &Table) -> Res<Table> {
+        let mut builder = TableBuilder::new(vec![
+            String::from("rule"),
+            String::from("row"),
+            String::from("column"),
+            String::from("value"),
+            String::from("message"),
+        ]);
+        for violation in self.violations(table)? {
+            builder = builder.add_row(vec![
+                violation.rule.to_string(),
+                violation.row.to_string(),
+                violation.column.to_string(),
+                violation.value,
+                violation.message,
+            ])?;
+        }
+        Ok(builder.build())
+    }
+
+    // in rule order, then by row
+    pub(crate) fn violations(&self, table: &Table) -> Res<Vec<RuleViolation<'_>>> {
+        let mut all = Vec::new();
+        for (id, rule) in self.rules.iter() {
+            let mut violations: Vec<(usize, &str, String, String)> = Vec::new();
+            match rule {
+                Rule::Matches(col_name, pattern) => {
+                    let column = table.column(col_name)?;
+                    Regex::new(pattern).map_err(|err| {
+                        messages::text(Message::ColumnPattern, &[&col_name, &err])
+                    })?;
+                    let regex = Regex::new(&format!("^(?:{})$", pattern))?;
+                    for (row, value) in column.iter().enumerate() {
+                        if !value.is_null() && !regex.is_match(value) {
+                            let message = messages::text(Message::ValueNotMatching, &[&pattern]);
+                            violations.push((row, col_name, value.to_string(), message));
+                        }
+                    }
+                }
+                Rule::InSet(col_name, values) => {
+                    let column = table.column(col_name)?;
+                    for (row, value) in column.iter().enumerate() {
+                        if !value.is_null() && !values.iter().any(|v| v == value.as_str()) {
+                            let message = messages::text(Message::ValueNotInSet, &[]);
+                            violations.push((row, col_name, value.to_string(), message));
+                        }
+                    }
+                }
+                Rule::Between(col_name, low, high) => {
+                    let column = table.column(col_name)?;
+                    for (row, value) in column.iter().enumerate() {
+                        if value.is_null() {
+                            continue;
+                        }
+                        let message = match value.parse::<f64>() {
+                            Ok(n) if *low <= n && n <= *high => continue,
+                            Ok(_) => messages::text(Message::ValueNotBetween, &[low, high]),
+                            Err(_) => messages::text(Message::ValueNotNumeric, &[]),
+                        };
+                        violations.push((row, col_name, value.to_string(), message));
+                    }
+                }
+                Rule::SumEquals(col_names, target_col_name) => {
+                    let target = table.column(target_col_name)?;
+                    let mut operands = Vec::with_capacity(col_names.len());
+                    for col_name in col_names {
+                        operands.push(table.column(col_name)?);
+                    }
+                    for (row, value) in target.iter().enumerate() {
+                        let cells: Vec<&str> =
+                            operands.iter().map(|col| col.get(row).as_str()).collect();
+                        if value.is_null() || cells.iter().any(|cell| cell.is_empty()) {
+                            continue;
+                        }
+                        let numbers: Option<Vec<f64>> =
+                            cells.iter().map(|cell| cell.parse().ok()).collect();
+                        let message = match (value.parse::<f64>(), numbers) {
+                            (Ok(expected), Some(numbers)) => {
+                                let sum: f64 = numbers.iter().sum();
+                                // tolerance for the rounding of decimal sums
+                                if (sum - expected).abs() <= 1e-9 * expected.abs().max(1.0) {
+                                    continue;
+                                }
+                                messages::text(
+                                    Message::ValueNotSum,
+                                    &[&col_names.join(" + "), &sum],
+                                )
+                            }
+                            _ => messages::text(Message::ValueNotNumeric, &[]),
+                        };
+                        violations.push((row, target_col_name, value.to_string(), message));
+                    }
+                }
+            }
+            all.extend(
+                violations
+                    .into_iter()
+                    .map(|(row, column, value, message)| RuleViolation {
+                        rule: id,
+                        row,
+                        column,
+                        value,
+                        message,
+                    }),
+            );
+        }
+        Ok(all)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_one_rule_per_line() {
+        let text = "# checks\ncode_format\tmatches\tcode\t[A-Z]+-[0-9]+\r\n\n\
+                    status_ok\tin\tstatus\topen\tclosed\n\
+                    amount_ok\tbetween\tamount\t0\t1000\n\
+                    total_ok\tsum\ttotal\tnet\ttax\n";
+        let rules = Rules::parse(text).unwrap();
+        let expected = Rules::new()
+            .matches("code_format", "code", "[A-Z]+-[0-9]+")
+            .in_set("status_ok", "status", &["open", "closed"])
+            .between("amount_ok", "amount", 0.0, 1000.0)
+            .sum_equals("total_ok", &["net", "tax"], "total");
+        assert_eq!(rules.rules, expected.rules);
+
+        let err = |text| Rules::parse(text).err().unwrap();
+        assert_eq!(
+            err("a\tin\tb"),
+            "E088: line 1: E089: a rule needs an id, a kind, a column and its arguments"
+        );
+        assert_eq!(
+            err("\na\tbetween\tb\t1"),
+            "E088: line 2: E090: wrong number of arguments for a 'between' rule"
+        );
+        assert_eq!(
+            err("a\tlike\tb\tc"),
+            "E088: line 1: E091: unknown rule kind 'like'"
+        );
+        assert!(Rules::parse("a\tbetween\tb\tlow\t2").is_err());
+    }
+
+    #[test]
+    fn violations_come_in_rule_order_then_by_row() {
+        let table = Table::parse_tsv(
+            "code\tstatus\tamount\tnet\ttax\ttotal\n\
+             AB-1\topen\t10\t0.1\t0.2\t0.3\n\
+             ab-2\tlost\t-5\t1\t1\t3\n\
+             \t\tmany\t1\t\t9\n",
+            0,
+        )
+        .unwrap();
+        let rules = Rules::new()
+            .between("amount_ok", "amount", 0.0, 1000.0)
+            .matches("code_format", "code", "[A-Z]+-[0-9]+")
+            .in_set("status_ok", "status", &["open", "closed"])
+            .sum_equals("total_ok", &["net", "tax"], "total");
+        let violations = rules.violations(&table).unwrap();
+        let found: Vec<(&str, usize, &str, &str)> = violations
+            .iter()
+            .map(|v| (v.rule, v.row, v.column, v.value.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("amount_ok", 1, "amount", "-5"),
+                ("amount_ok", 2, "amount", "many"),
+                ("code_format", 1, "code", "ab-2"),
+                ("status_ok", 1, "status", "lost"),
+                ("total_ok", 1, "total", "3"),
+            ]
+        );
+        assert_eq!(
+            violations[0].message,
+            "E093: value is not between 0 and 1000"
+        );
+        assert_eq!(
+            violations[4].message,
+            "E095: value is not net + tax, which is 2"
+        );
+        assert_eq!(rules.evaluate(&table).unwrap().rows_count(), 5);
+        let missing = Rules::new().in_set("x", "missing", &["a"]);
+        assert!(missing.evaluate(&table).is_err());
+    }
+}
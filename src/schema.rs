@@ -0,0 +1,291 @@
+use super::dates;
+use super::messages::{self, Message};
+use super::regex::Regex;
+use super::table::{Res, Table};
+use super::types::{self, ColumnType};
+
+#[derive(Clone, Default)]
+pub struct Schema {
+    pub(crate) columns: Vec<(String, ColumnType)>,
+    not_null: Vec<String>,
+    patterns: Vec<(String, String)>,
+    date_formats: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub row: Option<usize>,
+    pub column: String,
+    pub value: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    pub violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationReport> {
+        if self.is_valid() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+pub(crate) fn missing_column(col_name: &str) -> ValidationReport {
+    ValidationReport {
+        violations: vec![Violation {
+            row: None,
+            column: col_name.to_string(),
+            value: String::new(),
+            message: messages::text(Message::MissingColumn, &[]),
+        }],
+    }
+}
+
+impl Schema {
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    pub fn column(mut self, col_name: &str, column_type: ColumnType) -> Schema {
+        self.columns.push((col_name.to_string(), column_type));
+        self
+    }
+
+    pub fn not_null(mut self, col_name: &str) -> Schema {
+        self.not_null.push(col_name.to_string());
+        self
+    }
+
+    pub fn pattern(mut self, col_name: &str, pattern: &str) -> Schema {
+        self.patterns
+            .push((col_name.to_string(), pattern.to_string()));
+        self
+    }
+
+    pub fn date_format(mut self, col_name: &str, format: &str) -> Schema {
+        self.date_formats
+            .push((col_name.to_string(), format.to_string()));
+        self
+    }
+
+    pub fn validate(&self, table: &Table) -> Res<ValidationReport> {
+        let mut patterns = Vec::with_capacity(self.patterns.len());
+        for (col_name, pattern) in self.patterns.iter() {
+            let column_error = |err| messages::text(Message::ColumnPattern, &[&col_name, &err]);
+            Regex::new(pattern).map_err(column_error)?;
+            // the whole cell has to match
+            let regex = Regex::new(&format!("^(?:{})$", pattern)).map_err(column_error)?;
+            patterns.push((col_name.as_str(), pattern.as_str(), regex));
+        }
+        let mut report = ValidationReport::default();
+        // every column named anywhere in the schema, in declaration order
+        let mut col_names: Vec<&str> = self.columns.iter().map(|(c, _)| c.as_str()).collect();
+        for col_name in self
+            .not_null
+            .iter()
+            .map(String::as_str)
+            .chain(patterns.iter().map(|(c, _, _)| *c))
+            .chain(self.date_formats.iter().map(|(c, _)| c.as_str()))
+        {
+            if !col_names.contains(&col_name) {
+                col_names.push(col_name);
+            }
+        }
+        for col_name in col_names {
+            let column = match table.column(col_name) {
+                Ok(column) => column,
+                Err(_) => {
+                    report
+                        .violations
+                        .extend(missing_column(col_name).violations);
+                    continue;
+                }
+            };
+            let not_null = self.not_null.iter().any(|c| c == col_name);
+            let column_type = self.column_type(col_name);
+            let date_format = self
+                .date_formats
+                .iter()
+                .find(|(c, _)| c == col_name)
+                .map(|(_, f)| f.as_str());
+            let col_patterns: Vec<&(&str, &str, Regex)> =
+                patterns.iter().filter(|(c, _, _)| *c == col_name).collect();
+            for (row, value) in column.iter().enumerate() {
+                let mut violation = |message: String| {
+                    report.violations.push(Violation {
+                        row: Some(row),
+                        column: col_name.to_string(),
+                        value: value.to_string(),
+                        message,
+                    })
+                };
+                if value.is_null() {
+                    if not_null {
+                        violation(messages::text(Message::NullNotAllowed, &[]));
+                    }
+                    continue;
+                }
+                match (date_format, column_type) {
+                    (Some(format), _) => {
+                        if dates::parse_date(value, format).is_none() {
+                            violation(messages::text(Message::ValueNotDateInFormat, &[&format]));
+                        }
+                    }
+                    (None, Some(column_type)) => {
+                        if !column_type.accepts(value) {
+                            violation(messages::text(
+                                Message::ValueNotOfType,
+                                &[&column_type.name()],
+                            ));
+                        }
+                    }
+                    (None, None) => {}
+                }
+                for (_, pattern, regex) in col_patterns.iter() {
+                    if !regex.is_match(value) {
+                        violation(messages::text(Message::ValueNotMatching, &[&pattern]));
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn columns(&self) -> &[(String, ColumnType)] {
+        &self.columns
+    }
+
+    pub fn column_type(&self, col_name: &str) -> Option<ColumnType> {
+        self.columns
+            .iter()
+            .find(|(name, _)| name == col_name)
+            .map(|(_, column_type)| *column_type)
+    }
+}
+
+pub fn coerce(value: &str, column_type: ColumnType) -> Res<String> {
+    let invalid = || messages::text(Message::NotOfType, &[&value, &column_type.name()]);
+    if value.is_empty() {
+        return Ok(String::new());
+    }
+    match column_type {
+        ColumnType::Int64 => value
+            .parse::<i64>()
+            .map(|n| n.to_string())
+            .map_err(|_| invalid()),
+        ColumnType::Float64 => value
+            .parse::<f64>()
+            .map(|n| n.to_string())
+            .map_err(|_| invalid()),
+        ColumnType::Bool => types::parse_bool(value)
+            .map(|b| b.to_string())
+            .ok_or_else(invalid),
+        ColumnType::Date | ColumnType::Str => {
+            if column_type.accepts(value) {
+                Ok(value.to_string())
+            } else {
+                Err(invalid())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoadOptions;
+
+    fn places(report: &ValidationReport) -> Vec<(Option<usize>, &str, &str)> {
+        report
+            .violations
+            .iter()
+            .map(|v| (v.row, v.column.as_str(), v.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn validation_lists_every_violation() {
+        let table = Table::parse_tsv(
+            "id\tcode\tday\n1\tAB-12\t01/02/2023\nx\tab-12\t2023-02-01\n\t\t31/02/2023\n",
+            0,
+        )
+        .unwrap();
+        let schema = Schema::new()
+            .column("id", ColumnType::Int64)
+            .not_null("id")
+            .pattern("code", "[A-Z]+-[0-9]+")
+            .date_format("day", "%d/%m/%Y")
+            .not_null("missing");
+        let report = table.validate(&schema).unwrap();
+        assert_eq!(
+            places(&report),
+            vec![
+                (Some(1), "id", "x"),
+                (Some(2), "id", ""),
+                (None, "missing", ""),
+                (Some(1), "code", "ab-12"),
+                (Some(1), "day", "2023-02-01"),
+                (Some(2), "day", "31/02/2023"),
+            ]
+        );
+        assert_eq!(
+            report.violations[0].message,
+            "E054: value is not of type int64"
+        );
+        assert!(report.into_result().is_err());
+
+        let schema = Schema::new().pattern("code", "[A-Z");
+        assert!(table.validate(&schema).is_err());
+        let schema = Schema::new().column("id", ColumnType::Str);
+        assert!(table.validate(&schema).unwrap().is_valid());
+    }
+
+    #[test]
+    fn coerce_gives_the_canonical_text() {
+        assert_eq!(coerce("007", ColumnType::Int64).unwrap(), "7");
+        assert_eq!(coerce("1.50", ColumnType::Float64).unwrap(), "1.5");
+        assert_eq!(coerce("TRUE", ColumnType::Bool).unwrap(), "true");
+        assert_eq!(coerce("", ColumnType::Date).unwrap(), "");
+        assert!(coerce("2023-02-30", ColumnType::Date).is_err());
+    }
+
+    #[test]
+    fn declared_schemas_coerce_cells_while_parsing() {
+        let schema = Schema::new()
+            .column("n", ColumnType::Int64)
+            .column("ok", ColumnType::Bool);
+        let options = LoadOptions::new().schema(schema);
+        let text = "n\tok\tname\n007\tTRUE\tx\n\tFalse\ty\n-3\tfalse\t007\n";
+        let table = Table::parse_tsv_with(text, &options).unwrap();
+        let cells = |col_name: &str| -> Vec<String> {
+            let column = table.column(col_name).unwrap();
+            column.iter().map(|c| c.to_string()).collect()
+        };
+        assert_eq!(cells("n"), ["7", "", "-3"]);
+        assert_eq!(cells("ok"), ["true", "false", "false"]);
+        assert_eq!(cells("name"), ["x", "y", "007"]);
+        let n = table.column("n").unwrap();
+        assert_eq!(n.as_i64().unwrap(), [Some(7), None, Some(-3)]);
+
+        let path = std::env::temp_dir().join(format!("sql-schema-{}.tsv", std::process::id()));
+        std::fs::write(&path, "n\tok\tname\n1\ttrue\tx\n2\tmaybe\ty\n").unwrap();
+        let err = Table::load_tsv_with(path.to_str().unwrap(), &options)
+            .err()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            err.ends_with(": E113: line 3, column 'ok': E026: value 'maybe' is not of type bool"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use super::messages::{self, Message};
+use super::table::{Res, Table};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::SystemTime;
+
+#[derive(Clone, Copy, Default)]
+pub struct ReadStats {
+    pub reads: u64,
+    pub refreshes: u64,
+    pub last_refresh: Option<SystemTime>,
+}
+
+struct Entry {
+    table: Arc<Table>,
+    stats: ReadStats,
+}
+
+// snapshots are shared, a refresh swaps the table without touching snapshots already handed out.
+// The service can be shared between threads: the lock is only held to swap or clone an Arc.
+#[derive(Default)]
+pub struct TableService {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl TableService {
+    pub fn new() -> TableService {
+        TableService::default()
+    }
+
+    pub fn register(&self, name: &str, table: Table) {
+        let entry = Entry {
+            table: Arc::new(table),
+            stats: ReadStats {
+                last_refresh: Some(SystemTime::now()),
+                ..ReadStats::default()
+            },
+        };
+        self.entries().insert(name.to_string(), entry);
+    }
+
+    pub fn snapshot(&self, name: &str) -> Res<Arc<Table>> {
+        let mut entries = self.entries();
+        let entry = entries.get_mut(name).ok_or_else(|| not_registered(name))?;
+        entry.stats.reads += 1;
+        Ok(entry.table.clone())
+    }
+
+    pub fn query<T>(&self, name: &str, query: impl FnOnce(&Table) -> Res<T>) -> Res<T> {
+        let snapshot = self.snapshot(name)?;
+        query(&snapshot)
+    }
+
+    pub fn refresh(&self, name: &str, table: Table) -> Res<()> {
+        let mut entries = self.entries();
+        let entry = entries.get_mut(name).ok_or_else(|| not_registered(name))?;
+        entry.table = Arc::new(table);
+        entry.stats.refreshes += 1;
+        entry.stats.last_refresh = Some(SystemTime::now());
+        Ok(())
+    }
+
+    pub fn refresh_with(&self, name: &str, build: impl FnOnce(&Table) -> Res<Table>) -> Res<()> {
+        // built outside the lock so the builder may read other tables of the service
+        let current = self
+            .entries()
+            .get(name)
+            .map(|entry| entry.table.clone())
+            .ok_or_else(|| not_registered(name))?;
+        self.refresh(name, build(&current)?)
+    }
+
+    pub fn stats(&self, name: &str) -> Res<ReadStats> {
+        self.entries()
+            .get(name)
+            .map(|entry| entry.stats)
+            .ok_or_else(|| not_registered(name))
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.entries().keys().cloned().collect();
+        names.sort_unstable();
+        names
+    }
+
+    // a panic while the lock was held cannot leave an entry half written
+    fn entries(&self) -> MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn not_registered(name: &str) -> String {
+    messages::text(Message::TableNotRegistered, &[&name])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(rows: &str) -> Table {
+        Table::parse_tsv(&format!("n\n{}", rows), 0).unwrap()
+    }
+
+    #[test]
+    fn snapshots_survive_refresh() {
+        let service = TableService::new();
+        service.register("t", table("1\n"));
+        let before = service.snapshot("t").unwrap();
+        service.refresh("t", table("1\n2\n")).unwrap();
+        assert_eq!(before.rows_count(), 1);
+        assert_eq!(service.snapshot("t").unwrap().rows_count(), 2);
+        let stats = service.stats("t").unwrap();
+        assert_eq!((stats.reads, stats.refreshes), (2, 1));
+        assert!(service.snapshot("missing").is_err());
+    }
+
+    #[test]
+    fn readers_and_refresh_run_on_other_threads() {
+        let service = Arc::new(TableService::new());
+        service.register("t", table("1\n"));
+        let snapshot = service.snapshot("t").unwrap();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let service = &service;
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        let rows = service.query("t", |t| Ok(t.rows_count())).unwrap();
+                        assert!(rows == 1 || rows == 2);
+                    }
+                });
+            }
+            scope.spawn(|| service.refresh("t", table("1\n2\n")).unwrap());
+        });
+        assert_eq!(snapshot.rows_count(), 1);
+        assert_eq!(service.stats("t").unwrap().refreshes, 1);
+    }
+
+    #[test]
+    fn refresh_with_can_read_other_tables() {
+        let service = TableService::new();
+        service.register("a", table("1\n"));
+        service.register("b", table("2\n"));
+        service
+            .refresh_with("a", |a| a.concatenate(&*service.snapshot("b")?))
+            .unwrap();
+        assert_eq!(service.snapshot("a").unwrap().rows_count(), 2);
+        assert_eq!(service.names(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn failed_refreshes_keep_the_current_table() {
+        let service = TableService::new();
+        service.register("t", table("1\n2\n"));
+        let rows = service.query("t", |t| Ok(t.rows_count())).unwrap();
+        assert_eq!(rows, 2);
+        let err = service.refresh_with("t", |t| t.filter_expr("missing = 1"));
+        assert_eq!(err.err().unwrap(), "E001: column 'missing' does not exist");
+        assert_eq!(service.snapshot("t").unwrap().rows_count(), 2);
+        let stats = service.stats("t").unwrap();
+        assert_eq!((stats.reads, stats.refreshes), (2, 0));
+        assert!(stats.last_refresh.is_some());
+
+        let err = service.refresh("missing", table("1\n")).err().unwrap();
+        assert_eq!(err, "E058: table 'missing' is not registered");
+        service.register("t", table("3\n"));
+        assert_eq!(service.stats("t").unwrap().reads, 0);
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Levenshtein,
+    Trigram,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FuzzyMatch {
+    MaxEditDistance(usize),
+    MinScore(Metric, f64),
+}
+
+impl Metric {
+    pub fn score(self, a: &str, b: &str) -> f64 {
+        match self {
+            Metric::Levenshtein => {
+                let longest = a.chars().count().max(b.chars().count());
+                if longest == 0 {
+                    1.0
+                } else {
+                    1.0 - levenshtein(a, b) as f64 / longest as f64
+                }
+            }
+            Metric::Trigram => {
+                let trigrams_a = trigrams(a);
+                let trigrams_b = trigrams(b);
+                let shared = trigrams_a.intersection(&trigrams_b).count();
+                trigram_score(trigrams_a.len(), trigrams_b.len(), shared)
+            }
+        }
+    }
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+    for (i, char_a) in a.chars().enumerate() {
+        let mut diagonal = distances[0];
+        distances[0] = i + 1;
+        for (j, &char_b) in b.iter().enumerate() {
+            let substitution = diagonal + if char_a == char_b { 0 } else { 1 };
+            diagonal = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(diagonal + 1);
+        }
+    }
+    distances[b.len()]
+}
+
+pub fn levenshtein_within(a: &str, b: &str, max_distance: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let length_difference = if a.len() > b.len() {
+        a.len() - b.len()
+    } else {
+        b.len() - a.len()
+    };
+    if length_difference > max_distance {
+        return None;
+    }
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+    for (i, &char_a) in a.iter().enumerate() {
+        let mut diagonal = distances[0];
+        distances[0] = i + 1;
+        let mut row_min = distances[0];
+        for (j, &char_b) in b.iter().enumerate() {
+            let substitution = diagonal + if char_a == char_b { 0 } else { 1 };
+            diagonal = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(diagonal + 1);
+            row_min = row_min.min(distances[j + 1]);
+        }
+        if row_min > max_distance {
+            return None;
+        }
+    }
+    Some(distances[b.len()]).filter(|&d| d <= max_distance)
+}
+
+pub fn trigram_score(trigrams_a: usize, trigrams_b: usize, shared: usize) -> f64 {
+    let union = trigrams_a + trigrams_b - shared;
+    if union == 0 {
+        1.0
+    } else {
+        shared as f64 / union as f64
+    }
+}
+
+pub fn trigrams(string: &str) -> HashSet<[char; 3]> {
+    // padded so that short strings still produce trigrams
+    let chars: Vec<char> = "  "
+        .chars()
+        .chain(string.chars().flat_map(char::to_lowercase))
+        .chain(" ".chars())
+        .collect();
+    chars.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+// lowercase words, punctuation and repeated spaces don't count
+pub fn normalize(string: &str) -> String {
+    string
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// cluster id per string, strings at least min_score similar end up in the same cluster
+pub fn trigram_clusters(strings: &[String], min_score: f64) -> Vec<usize> {
+    // equal strings are one cluster, only the distinct ones are compared
+    let mut distinct: HashMap<&str, usize> = HashMap::new();
+    let of_position: Vec<usize> = strings
+        .iter()
+        .map(|string| {
+            let next = distinct.len();
+            *distinct.entry(string.as_str()).or_insert(next)
+        })
+        .collect();
+    let mut distinct_strings = vec![""; distinct.len()];
+    for (&string, &i) in distinct.iter() {
+        distinct_strings[i] = string;
+    }
+    let sets = ranked_trigrams(&distinct_strings);
+    // prefix filtering: with trigrams ordered from the rarest, two strings at least min_score
+    // similar share one of the first len - ceil(min_score * len) + 1 trigrams of each. Only
+    // those are indexed, so trigrams common to most strings rarely make candidates
+    let mut parents: Vec<usize> = (0..sets.len()).collect();
+    let mut trigram_index: HashMap<u32, Vec<usize>> = HashMap::new();
+    let mut candidates: HashSet<usize> = HashSet::new();
+    for (position, trigrams) in sets.iter().enumerate() {
+        let needed = (min_score * trigrams.len() as f64 - 1e-9).ceil().max(0.0) as usize;
+        let prefix = (trigrams.len() + 1)
+            .saturating_sub(needed)
+            .clamp(1, trigrams.len());
+        for trigram in trigrams[..prefix].iter() {
+            let earlier = trigram_index.entry(*trigram).or_default();
+            candidates.extend(earlier.iter().copied());
+            earlier.push(position);
+        }
+        for other in candidates.drain() {
+            let shared = shared_count(trigrams, &sets[other]);
+            let score = trigram_score(trigrams.len(), sets[other].len(), shared);
+            if score >= min_score {
+                let (a, b) = (root(&mut parents, position), root(&mut parents, other));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+    // numbered in order of the first string of each cluster
+    let mut ids: HashMap<usize, usize> = HashMap::new();
+    of_position
+        .into_iter()
+        .map(|i| {
+            let next = ids.len();
+            *ids.entry(root(&mut parents, i)).or_insert(next)
+        })
+        .collect()
+}
+
+// the trigrams of each string as ranks, sorted from the trigram found in fewest strings
+fn ranked_trigrams(strings: &[&str]) -> Vec<Vec<u32>> {
+    let all_trigrams: Vec<HashSet<[char; 3]>> = strings.iter().map(|s| trigrams(s)).collect();
+    let mut frequencies: HashMap<[char; 3], usize> = HashMap::new();
+    for trigram in all_trigrams.iter().flatten() {
+        *frequencies.entry(*trigram).or_insert(0) += 1;
+    }
+    let mut by_frequency: Vec<([char; 3], usize)> = frequencies.into_iter().collect();
+    by_frequency.sort_unstable_by_key(|&(trigram, frequency)| (frequency, trigram));
+    let ranks: HashMap<[char; 3], u32> = by_frequency
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (trigram, _))| (trigram, rank as u32))
+        .collect();
+    all_trigrams
+        .iter()
+        .map(|trigrams| {
+            let mut ranked: Vec<u32> = trigrams.iter().map(|t| ranks[t]).collect();
+            ranked.sort_unstable();
+            ranked
+        })
+        .collect()
+}
+
+// both sorted
+fn shared_count(a: &[u32], b: &[u32]) -> usize {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                shared += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    shared
+}
+
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Table;
+
+    // every pair compared, the clustering the index must reproduce
+    fn clusters_of_all_pairs(strings: &[String], min_score: f64) -> Vec<usize> {
+        let mut parents: Vec<usize> = (0..strings.len()).collect();
+        for a in 0..strings.len() {
+            for b in 0..a {
+                if Metric::Trigram.score(&strings[a], &strings[b]) >= min_score {
+                    let (ra, rb) = (root(&mut parents, a), root(&mut parents, b));
+                    parents[ra.max(rb)] = ra.min(rb);
+                }
+            }
+        }
+        let mut ids: HashMap<usize, usize> = HashMap::new();
+        (0..strings.len())
+            .map(|position| {
+                let next = ids.len();
+                *ids.entry(root(&mut parents, position)).or_insert(next)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prefix_filtering_finds_every_similar_pair() {
+        let mut seed = 7u32;
+        let strings: Vec<String> = (0..120)
+            .map(|_| {
+                (0..6)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                        (b'a' + (seed >> 16) as u8 % 4) as char
+                    })
+                    .collect()
+            })
+            .collect();
+        for min_score in [0.0, 0.3, 0.5, 0.7, 1.0] {
+            assert_eq!(
+                trigram_clusters(&strings, min_score),
+                clusters_of_all_pairs(&strings, min_score),
+                "{}",
+                min_score
+            );
+        }
+    }
+
+    #[test]
+    fn equal_strings_share_a_cluster() {
+        let strings: Vec<String> = ["acme", "beta", "acme", "ACME inc", "beta"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(trigram_clusters(&strings, 0.6), [0, 1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn metrics_score_between_zero_and_one() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(Metric::Levenshtein.score("", ""), 1.0);
+        assert_eq!(Metric::Levenshtein.score("abcd", "abce"), 0.75);
+        assert_eq!(Metric::Trigram.score("Night", "night"), 1.0);
+        assert_eq!(Metric::Trigram.score("abc", "xyz"), 0.0);
+        for (a, b) in [
+            ("kitten", "sitting"),
+            ("flaw", "lawn"),
+            ("", "ab"),
+            ("same", "same"),
+        ] {
+            for max in 0..4 {
+                let distance = Some(levenshtein(a, b)).filter(|&d| d <= max);
+                assert_eq!(
+                    levenshtein_within(a, b, max),
+                    distance,
+                    "{} {} {}",
+                    a,
+                    b,
+                    max
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn similarity_column_scores_each_row() {
+        let table = Table::parse_tsv("a\tb\nabcd\tabce\nx\tx\n", 0).unwrap();
+        let scored = table
+            .similarity_column("a", "b", Metric::Levenshtein, "score")
+            .unwrap();
+        let scores: Vec<String> = scored
+            .column("score")
+            .unwrap()
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(scores, ["0.7500", "1.0000"]);
+    }
+}
@@ -0,0 +1,371 @@
+use super::aggregate::NullPolicy;
+use super::column::Column;
+use super::column_map::ColumnMap;
+use super::format::TsvEscaping;
+use super::load::{self, EncodingPolicy, LoadOptions, LoadReport};
+use super::messages::{self, Message};
+use super::table::{Op, Res, Table};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+// reads a file a few rows at a time, every chunk is parsed like a whole file with the same header.
+// Types inferred from the first chunk are kept for the later ones.
+pub struct TsvChunks {
+    path: String,
+    reader: BufReader<File>,
+    options: LoadOptions,
+    header: String,
+    chunk_rows: usize,
+    // lines of the file read so far
+    line: usize,
+    report: LoadReport,
+    started: bool,
+    done: bool,
+}
+
+impl TsvChunks {
+    pub(crate) fn open(path: &str, chunk_rows: usize, options: &LoadOptions) -> Res<TsvChunks> {
+        let file = File::open(path)
+            .map_err(|err| messages::text(Message::ReadTsv, &[&path, &err.to_string()]))?;
+        let mut chunks = TsvChunks {
+            path: path.to_string(),
+            reader: BufReader::new(file),
+            options: options.clone().skip_lines(0),
+            header: String::new(),
+            chunk_rows: chunk_rows.max(1),
+            line: 0,
+            report: LoadReport::default(),
+            started: false,
+            done: false,
+        };
+        let mut line = Vec::new();
+        for _ in 0..options.skip_lines {
+            chunks.read_line(&mut line)?;
+        }
+        loop {
+            if !chunks.read_line(&mut line)? {
+                return Err(messages::text(Message::HeaderMissing, &[]));
+            }
+            if !is_blank(&line) {
+                break;
+            }
+        }
+        // whatever the policy, a header that is not text cannot name the columns
+        let header = load::decode_line(
+            &line,
+            EncodingPolicy::Error,
+            chunks.line,
+            &mut chunks.report,
+        )
+        .map_err(|err| messages::text(Message::ReadTsv, &[&path, &err]))?;
+        chunks.header = header.trim_end_matches(['\n', '\r']).to_string();
+        Ok(chunks)
+    }
+
+    // lines of the file skipped because they were not valid UTF-8
+    pub fn report(&self) -> &LoadReport {
+        &self.report
+    }
+
+    fn read_line(&mut self, line: &mut Vec<u8>) -> Res<bool> {
+        line.clear();
+        let read = self
+            .reader
+            .read_until(b'\n', line)
+            .map_err(|err| messages::text(Message::ReadTsv, &[&self.path, &err.to_string()]))?;
+        if read > 0 {
+            self.line += 1;
+        }
+        Ok(read > 0)
+    }
+
+    fn next_chunk(&mut self) -> Res<Option<Table>> {
+        // blank and skipped lines are kept empty, so lines of the chunk map to lines of the file
+        let first_line = self.line + 1;
+        let mut text = self.header.clone();
+        text.push('\n');
+        let mut rows = 0;
+        let mut line = Vec::new();
+        while rows < self.chunk_rows && self.read_line(&mut line)? {
+            let decoded =
+                load::decode_line(&line, self.options.encoding, self.line, &mut self.report)
+                    .map_err(|err| messages::text(Message::ReadTsv, &[&self.path, &err]))?;
+            if !is_blank(decoded.as_bytes()) {
+                rows += 1;
+            }
+            text.push_str(&decoded);
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+        }
+        // the first chunk is returned even when empty, so there is always a table with the columns
+        if rows == 0 && self.started {
+            return Ok(None);
+        }
+        self.started = true;
+        // the header is line 1 of the chunk, its line 2 is first_line of the file
+        self.options.line_offset = first_line - 2;
+        let table = Table::parse_tsv_with(&text, &self.options)
+            .map_err(|err| messages::text(Message::ParseTsv, &[&self.path, &err]))?;
+        if self.options.infer_types && self.options.pinned_types.is_none() {
+            self.options.pinned_types = Some(table.schema());
+        }
+        Ok(Some(table))
+    }
+}
+
+impl Iterator for TsvChunks {
+    type Item = Res<Table>;
+
+    fn next(&mut self) -> Option<Res<Table>> {
+        if self.done {
+            return None;
+        }
+        let chunk = self.next_chunk().transpose();
+        // stop after the end or the first error
+        self.done = !matches!(chunk, Some(Ok(_)));
+        chunk
+    }
+}
+
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(|&b| b == b'\n' || b == b'\r')
+}
+
+// operations on a sequence of chunks, only one chunk at a time is kept in memory
+pub trait TableStream: Iterator<Item = Res<Table>> + Sized {
+    fn filter_column(
+        self,
+        col_name: &str,
+        filter: impl Fn(&str) -> bool,
+    ) -> impl Iterator<Item = Res<Table>> {
+        let col_name = col_name.to_string();
+        self.map(move |chunk| chunk?.filter_column(&col_name, &filter))
+    }
+
+    fn map_column(
+        self,
+        col_name: &str,
+        map: impl Fn(&str) -> String,
+    ) -> impl Iterator<Item = Res<Table>> {
+        let col_name = col_name.to_string();
+        self.map(move |chunk| chunk?.map_column(&col_name, &map))
+    }
+
+    // groups of every chunk are folded into the groups seen so far
+    fn group_by_columns(self, col_names: &[&str], column_operations: &[Op]) -> Res<Table> {
+        group_chunks(self, col_names, column_operations, None)
+    }
+
+    fn group_by_columns_with(
+        self,
+        col_names: &[&str],
+        column_operations: &[Op],
+        nulls: NullPolicy,
+    ) -> Res<Table> {
+        group_chunks(self, col_names, column_operations, Some(nulls))
+    }
+
+    // same bytes as write_tsv_file on all the chunks concatenated, returns the rows written
+    fn write_tsv_file(self, path: &str, header: Vec<String>) -> Res<usize> {
+        self.write_tsv_file_with(path, header, TsvEscaping::default())
+    }
+
+    fn write_tsv_file_with(
+        self,
+        path: &str,
+        header: Vec<String>,
+        escaping: TsvEscaping,
+    ) -> Res<usize> {
+        let write_error = |err: std::io::Error| messages::text(Message::WriteTsv, &[&path, &err]);
+        let file = File::create(path).map_err(write_error)?;
+        let mut out = BufWriter::new(file);
+        let header_line = header
+            .iter()
+            .map(|col_name| escaping.escape(col_name, col_name))
+            .collect::<Res<Vec<_>>>()?
+            .join("\t");
+        out.write_all(header_line.as_bytes()).map_err(write_error)?;
+        let mut rows = 0;
+        for chunk in self {
+            let chunk = chunk?;
+            let mut columns = Vec::with_capacity(header.len());
+            for col_name in header.iter() {
+                columns.push(chunk.column(col_name)?);
+            }
+            let mut cells: Vec<_> = columns.iter().map(Column::iter).collect();
+            for _ in 0..chunk.rows_count() {
+                for (i, (cells, col_name)) in cells.iter_mut().zip(&header).enumerate() {
+                    out.write_all(if i == 0 { b"\n" } else { b"\t" })
+                        .map_err(write_error)?;
+                    let cell = escaping.escape(cells.next().unwrap(), col_name)?;
+                    out.write_all(cell.as_bytes()).map_err(write_error)?;
+                }
+            }
+            rows += chunk.rows_count();
+        }
+        out.flush().map_err(write_error)?;
+        Ok(rows)
+    }
+}
+
+impl<I: Iterator<Item = Res<Table>>> TableStream for I {}
+
+fn group_chunks(
+    chunks: impl Iterator<Item = Res<Table>>,
+    col_names: &[&str],
+    column_operations: &[Op],
+    nulls: Option<NullPolicy>,
+) -> Res<Table> {
+    let merge_operations = column_operations
+        .iter()
+        .map(Op::merge_op)
+        .collect::<Res<Vec<Op>>>()?;
+    let mut groups: Option<Table> = None;
+    for chunk in chunks {
+        let partial = chunk?.group_by_columns_nulls(col_names, column_operations, nulls)?;
+        groups = Some(match groups {
+            None => partial,
+            Some(groups) => groups.concatenate(&partial)?.group_by_columns_nulls(
+                col_names,
+                &merge_operations,
+                nulls,
+            )?,
+        });
+    }
+    Ok(groups.unwrap_or_else(|| Table::from_columns(ColumnMap::default())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Aggregate, ColumnType, Schema};
+
+    fn write_temp(name: &str, bytes: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("sql-stream-{}-{}", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn cells(table: &Table, col_name: &str) -> Vec<String> {
+        let column = table.column(col_name).unwrap();
+        column.iter().map(|c| c.to_string()).collect()
+    }
+
+    fn collect(chunks: TsvChunks) -> Res<Table> {
+        let mut all: Option<Table> = None;
+        for chunk in chunks {
+            let chunk = chunk?;
+            all = Some(match all {
+                None => chunk,
+                Some(all) => all.concatenate(&chunk)?,
+            });
+        }
+        Ok(all.unwrap())
+    }
+
+    #[test]
+    fn chunks_keep_the_types_of_the_first_chunk() {
+        let path = write_temp("zips.tsv", b"zip\n02134\n00501\n00210\n");
+        let options = LoadOptions::new().infer_types();
+        let streamed = collect(Table::stream_tsv_with(&path, 2, &options).unwrap()).unwrap();
+        let loaded = Table::load_tsv_with(&path, &options).unwrap();
+        assert_eq!(cells(&streamed, "zip"), cells(&loaded, "zip"));
+        assert_eq!(
+            streamed.schema().column_type("zip"),
+            Some(ColumnType::Int64)
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn later_chunks_are_checked_against_the_first_types_with_file_lines() {
+        let path = write_temp("mixed.tsv", b"zip\n02134\n\n00501\nA1\n");
+        let options = LoadOptions::new().infer_types();
+        let err = collect(Table::stream_tsv_with(&path, 2, &options).unwrap())
+            .err()
+            .unwrap();
+        assert!(err.contains(" 5, "), "{}", err);
+        let schema = Schema::new().column("zip", ColumnType::Int64);
+        let options = LoadOptions::new().schema(schema);
+        let err = collect(Table::stream_tsv_with(&path, 2, &options).unwrap())
+            .err()
+            .unwrap();
+        assert!(err.contains(" 5, "), "{}", err);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn skipped_lines_are_reported_with_file_lines() {
+        let path = write_temp("utf8.tsv", b"a\n1\n2\n\xff\n3\n");
+        let options = LoadOptions::new().on_invalid_utf8(EncodingPolicy::SkipRow);
+        let mut chunks = Table::stream_tsv_with(&path, 2, &options).unwrap();
+        let rows: usize = chunks
+            .by_ref()
+            .map(|chunk| chunk.unwrap().rows_count())
+            .sum();
+        assert_eq!(rows, 3);
+        assert_eq!(chunks.report().skipped_lines, vec![4]);
+        let err = collect(Table::stream_tsv(&path, 2).unwrap()).err().unwrap();
+        assert!(err.contains('4'), "{}", err);
+        std::fs::write(&path, b"\xff\n1\n").unwrap();
+        assert!(Table::stream_tsv_with(&path, 2, &options).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn stream_group_and_write_match_the_whole_table() {
+        let text = "k\tv\nb\t1\na\t2\nb\t3\nc\t4\na\t5\n";
+        let path = write_temp("group.tsv", text.as_bytes());
+        let ops = [Op::aggregate("v", Aggregate::Sum)];
+        let streamed = Table::stream_tsv(&path, 2)
+            .unwrap()
+            .group_by_columns(&["k"], &ops)
+            .unwrap();
+        let whole = Table::parse_tsv(text, 0)
+            .unwrap()
+            .group_by_columns(&["k"], &ops);
+        assert_eq!(streamed.to_tsv_all(), whole.unwrap().to_tsv_all());
+        let mean = [Op::aggregate("v", Aggregate::Mean)];
+        assert!(Table::stream_tsv(&path, 2)
+            .unwrap()
+            .group_by_columns(&["k"], &mean)
+            .is_err());
+
+        let out = write_temp("group-out.tsv", b"");
+        let rows = Table::stream_tsv(&path, 2)
+            .unwrap()
+            .filter_column("k", |k| k != "c")
+            .write_tsv_file(&out, vec!["k".into(), "v".into()])
+            .unwrap();
+        assert_eq!(rows, 4);
+        let expected = Table::parse_tsv(text, 0)
+            .unwrap()
+            .filter_column("k", |k| k != "c");
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            expected.unwrap().to_tsv_all().unwrap()
+        );
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn stream_write_escapes_on_request() {
+        let mut table = Table::parse_tsv("a\nx\n", 0).unwrap();
+        table.push_row(vec!["tab\there".into()]).unwrap();
+        let out = write_temp("escape-out.tsv", b"");
+        let header = vec!["a".to_string()];
+        let rejected = vec![Ok(table.clone())]
+            .into_iter()
+            .write_tsv_file(&out, header.clone());
+        assert!(rejected.is_err());
+        vec![Ok(table.clone())]
+            .into_iter()
+            .write_tsv_file_with(&out, header, TsvEscaping::Backslash)
+            .unwrap();
+        let back = Table::load_tsv_with(&out, &LoadOptions::new().unescape()).unwrap();
+        assert_eq!(cells(&back, "a"), cells(&table, "a"));
+        std::fs::remove_file(out).unwrap();
+    }
+}
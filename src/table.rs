@@ -1,20 +1,128 @@
+use super::aggregate::{Aggregate, CumOp, NullPolicy, RollingStart};
+#[cfg(feature = "arrow")]
+use super::arrow;
+use super::checksum::Checksum;
 use super::column::Column;
+use super::column_map::ColumnMap;
+use super::composite::{self, CompositeIndex};
+#[cfg(feature = "encryption")]
+use super::crypto::Cipher;
+use super::dates::{self, DateTime, TimeUnit};
+use super::diff;
+use super::expr::Expr;
+use super::format::{self, Format, PartitionLayout, TsvEscaping};
+#[cfg(feature = "json")]
+use super::json;
+use super::lazy::LazyTable;
+use super::lineage::{self, Lineage, Source};
+use super::load::{self, LoadOptions, LoadReport, LoadState, RejectedRow, RowFilter};
+use super::mapping::{FromRow, ToRow};
+use super::messages::{self, Message};
+use super::order;
+use super::outlier::Method;
+use super::parallel;
+use super::parts::Part;
+use super::profile;
+use super::random::SplitMix64;
+use super::regex::Regex;
+use super::repr::{self, ReprOptions};
+use super::row::Row;
+use super::rules::Rules;
+use super::schema::{self, missing_column, Schema, ValidationReport};
+use super::similarity::{self, FuzzyMatch, Metric};
+use super::stream::TsvChunks;
+use super::template;
+use super::tuner::{self, FilterStrategy, GroupStrategy, JoinStrategy};
+use super::types::{self, ColumnType};
 use super::value::Value;
+use super::view::TableView;
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::sync::Arc;
+
+// other rows named in a duplicate violation of assert_unique, the rest are only counted
+const LISTED_DUPLICATES: usize = 10;
 
 pub struct Op {
     column_name: String,
-    operation: Function,
+    operation: Operation,
+    nulls: Option<NullPolicy>,
 }
 
 type Function = Box<dyn Fn(&[&str]) -> String>;
+// a function MiOp::new_par may run on several threads
+type SharedFunction = Box<dyn Fn(&[&str]) -> String + Send + Sync>;
+
+enum Operation {
+    Custom(Function),
+    Builtin(Aggregate),
+}
 
 impl Op {
     pub fn new(column_name: &str, operation: Function) -> Op {
         Op {
             column_name: column_name.into(),
-            operation,
+            operation: Operation::Custom(operation),
+            nulls: None,
+        }
+    }
+
+    pub fn aggregate(column_name: &str, aggregate: Aggregate) -> Op {
+        Op {
+            column_name: column_name.into(),
+            operation: Operation::Builtin(aggregate),
+            nulls: None,
+        }
+    }
+
+    pub fn nulls(mut self, nulls: NullPolicy) -> Op {
+        self.nulls = Some(nulls);
+        self
+    }
+
+    // the operation combining results of this one computed on separate chunks
+    pub(crate) fn merge_op(&self) -> Res<Op> {
+        let aggregate = match &self.operation {
+            Operation::Builtin(Aggregate::Count) => Aggregate::Sum,
+            Operation::Builtin(Aggregate::Mean) | Operation::Custom(_) => {
+                return Err(messages::text(Message::NotMergeable, &[&self.column_name]))
+            }
+            Operation::Builtin(aggregate) => aggregate.clone(),
+        };
+        Ok(Op {
+            column_name: self.column_name.clone(),
+            operation: Operation::Builtin(aggregate),
+            nulls: self.nulls,
+        })
+    }
+
+    fn apply(&self, items: &[&str], default_nulls: Option<NullPolicy>) -> Res<String> {
+        match self.nulls.or(default_nulls) {
+            Some(nulls) => {
+                let counts_rows = matches!(self.operation, Operation::Builtin(Aggregate::Count));
+                nulls.apply(
+                    items,
+                    counts_rows,
+                    |items| self.apply_all(items),
+                    || Ok(String::new()),
+                )
+            }
+            None => self.apply_all(items),
+        }
+    }
+
+    fn apply_all(&self, items: &[&str]) -> Res<String> {
+        match &self.operation {
+            Operation::Custom(function) => Ok(function(items)),
+            Operation::Builtin(aggregate) => aggregate.apply(items).map_err(|err| {
+                messages::text(Message::AggregationOnColumn, &[&self.column_name, &err])
+            }),
         }
     }
 }
@@ -22,11 +130,25 @@ impl Op {
 pub struct MiOp {
     out_column: String,
     in_columns: Vec<String>,
-    operation: Function,
+    operation: RowFunction,
+}
+
+enum RowFunction {
+    Local(Function),
+    Shared(SharedFunction),
 }
 
 impl MiOp {
     pub fn new(input: &[&str], output: &str, operation: Function) -> MiOp {
+        MiOp::with_function(input, output, RowFunction::Local(operation))
+    }
+
+    // with the parallel feature the rows are split across threads
+    pub fn new_par(input: &[&str], output: &str, operation: SharedFunction) -> MiOp {
+        MiOp::with_function(input, output, RowFunction::Shared(operation))
+    }
+
+    fn with_function(input: &[&str], output: &str, operation: RowFunction) -> MiOp {
         MiOp {
             in_columns: input.iter().map(|v| String::from(*v)).collect(),
             out_column: String::from(output),
@@ -35,463 +157,3630 @@ impl MiOp {
     }
 }
 
+#[derive(Default)]
+pub struct Derivations {
+    steps: Vec<MiOp>,
+}
+
+impl Derivations {
+    pub fn new() -> Derivations {
+        Derivations::default()
+    }
+
+    pub fn derive(mut self, expr: MiOp) -> Derivations {
+        self.steps.push(expr);
+        self
+    }
+
+    pub fn affected_by(&self, changed: &[&str]) -> Vec<String> {
+        let mut dirty: HashSet<&str> = changed.iter().copied().collect();
+        let mut affected = Vec::new();
+        for step in &self.steps {
+            if step.in_columns.iter().any(|c| dirty.contains(c.as_str())) {
+                dirty.insert(&step.out_column);
+                affected.push(step.out_column.clone());
+            }
+        }
+        affected
+    }
+}
+
 #[derive(Clone)]
+pub enum JoinCollision {
+    Error,
+    Suffix(String, String),
+    KeepLeft,
+    KeepRight,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Rank {
+    RowNumber,
+    Rank,
+    DenseRank,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MaskOp {
+    And,
+    Or,
+    Xor,
+}
+
+pub struct Sniff {
+    pub delimiter: char,
+    pub columns: Vec<(String, ColumnType)>,
+    pub sampled_rows: usize,
+}
+
 pub struct Table {
-    columns: HashMap<Value, Column>,
+    columns: ColumnMap,
+    primary_key: Vec<Value>,
+    name: Option<Value>,
+    lineage: Lineage,
+    composite_indexes: Vec<Arc<CompositeIndex>>,
+}
+
+// a stale composite index would keep the old columns alive in every clone
+impl Clone for Table {
+    fn clone(&self) -> Table {
+        Table {
+            columns: self.columns.clone(),
+            primary_key: self.primary_key.clone(),
+            name: self.name.clone(),
+            lineage: self.lineage.clone(),
+            composite_indexes: self
+                .composite_indexes
+                .iter()
+                .filter(|index| index.is_valid(&self.columns))
+                .cloned()
+                .collect(),
+        }
+    }
 }
 
 pub type Res<T> = Result<T, String>;
 
 impl Table {
     pub fn load_tsv(path: &str, skip_lines: usize) -> Res<Table> {
-        let input = std::fs::read_to_string(path)
-            .map_err(|err| format!("Errore leggendo tsv file {}: {}", path, err))?;
-        Table::parse_tsv(input.as_ref(), skip_lines)
-            .map_err(|err| format!("Errore creando la table dal tsv file {}: {}", path, err))
+        Table::load_tsv_with(path, &LoadOptions::new().skip_lines(skip_lines))
     }
 
-    pub fn parse_tsv(input: &str, skip_lines: usize) -> Res<Table> {
-        let mut lines = input.lines().skip(skip_lines).skip_while(|&l| l.is_empty());
-        if let Some(header) = lines.next() {
-            let mut builder = TableBuilder::new(
-                header
-                    .split('\t')
-                    .map(|col_name| col_name.trim().into())
-                    .collect(),
-            );
+    pub fn load_tsv_with(path: &str, options: &LoadOptions) -> Res<Table> {
+        Table::load_tsv_reporting(path, options).map(|(table, _)| table)
+    }
 
-            for line in lines {
-                if !line.is_empty() {
-                    builder =
-                        builder.add_row(line.split('\t').map(|s| s.trim().into()).collect())?;
-                }
-            }
+    pub fn load_tsv_reporting(path: &str, options: &LoadOptions) -> Res<(Table, LoadReport)> {
+        let mut report = LoadReport::default();
+        let input = Table::read_tsv(path, options, &mut report)?;
+        let table = Table::parse_tsv_with(input.as_ref(), options)
+            .map_err(|err| messages::text(Message::ParseTsv, &[&path, &err]))?;
+        Ok((table, report))
+    }
 
-            Ok(builder.build())
-        } else {
-            Err(String::from("mancano i nomi di colonna"))
-        }
+    fn read_tsv(path: &str, options: &LoadOptions, report: &mut LoadReport) -> Res<String> {
+        std::fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| {
+                load::decode(bytes, options.encoding, Some(options.skip_lines), report)
+            })
+            .map_err(|err| messages::text(Message::ReadTsv, &[&path, &err]))
     }
 
-    pub fn columns_count(&self) -> usize {
-        self.columns.len()
+    // filters and the first selection of the plan are applied while the file is parsed
+    pub fn scan_tsv(path: &str) -> LazyTable {
+        LazyTable::scan_tsv(path, &LoadOptions::new())
     }
 
-    pub fn rows_count(&self) -> usize {
-        self.columns.values().next().map(|c| c.len()).unwrap_or(0)
+    pub fn scan_tsv_with(path: &str, options: &LoadOptions) -> LazyTable {
+        LazyTable::scan_tsv(path, options)
     }
 
-    pub fn column(&self, col_name: &str) -> Res<Column> {
-        if let Some(column) = self.columns.get(col_name) {
-            Ok(column.clone())
-        } else {
-            Err(format!("colonna '{}' non esiste", col_name))
-        }
+    // chunks of at most chunk_rows rows, read only when the iterator gets to them
+    pub fn stream_tsv(path: &str, chunk_rows: usize) -> Res<TsvChunks> {
+        TsvChunks::open(path, chunk_rows, &LoadOptions::new())
     }
 
-    pub fn select_columns(&self, col_names: &[&str]) -> Res<Table> {
-        let mut columns: HashMap<Value, _> = HashMap::with_capacity(col_names.len());
-        for &col_name in col_names {
-            columns.insert(Value::from(col_name), self.column(col_name)?);
-        }
-        Ok(Table { columns })
+    pub fn stream_tsv_with(path: &str, chunk_rows: usize, options: &LoadOptions) -> Res<TsvChunks> {
+        TsvChunks::open(path, chunk_rows, options)
     }
 
-    pub fn deselect_column(&self, col_name: &str) -> Res<Table> {
-        let mut columns: HashMap<Value, _> = HashMap::with_capacity(self.columns.len());
-        for (colmun_name, column) in self.columns.iter() {
-            if col_name != colmun_name.as_str() {
-                columns.insert(colmun_name.clone(), column.clone());
+    pub fn load_tsv_validated(path: &str, rules: &Rules) -> Res<(Table, Table)> {
+        Table::load_tsv_validated_with(path, &LoadOptions::new(), rules)
+    }
+
+    // rows breaking a rule go to the quarantine table, with their row number and the reasons.
+    // So do rows that cannot be loaded at all, with the wrong number of fields or a cell the
+    // schema rejects; their fields are kept as they are, missing ones empty.
+    pub fn load_tsv_validated_with(
+        path: &str,
+        options: &LoadOptions,
+        rules: &Rules,
+    ) -> Res<(Table, Table)> {
+        let input = Table::read_tsv(path, options, &mut LoadReport::default())?;
+        let (table, unloaded) = Table::parse_tsv_rows(&input, options, true)
+            .map_err(|err| messages::text(Message::ParseTsv, &[&path, &err]))?;
+        for col_name in ["row", "reasons"].iter() {
+            if table.columns.contains_key(col_name) {
+                return Err(messages::text(Message::ColumnExists, &[col_name]));
             }
         }
-        if columns.len() != self.columns.len() - 1 {
-            Err(format!("colonna '{}' non esiste", col_name))
-        } else {
-            Ok(Table { columns })
+        let mut reasons: Vec<Vec<String>> = vec![Vec::new(); table.rows_count()];
+        for violation in rules.violations(&table)? {
+            reasons[violation.row].push(format!("{}: {}", violation.rule, violation.message));
         }
-    }
+        let (rejected, clean): (Vec<usize>, Vec<usize>) =
+            (0..table.rows_count()).partition(|&position| !reasons[position].is_empty());
 
-    pub fn rename_column(&self, old_col_name: &str, new_col_name: &str) -> Res<Table> {
-        let mut columns: HashMap<Value, _> = HashMap::with_capacity(self.columns.len());
-        let mut not_found = true;
-        for (colmun_name, column) in self.columns.iter() {
-            let name = if old_col_name == colmun_name.as_str() {
-                not_found = false;
-                Value::from(new_col_name)
-            } else {
-                colmun_name.clone()
-            };
-            columns.insert(name, column.clone());
+        // both kinds of rejected rows, in file order
+        let mut quarantine: Vec<(usize, Vec<Value>, String)> =
+            Vec::with_capacity(rejected.len() + unloaded.len());
+        let mut unloaded = unloaded.into_iter().peekable();
+        let mut skipped = 0;
+        let columns: Vec<&Column> = table.columns.values().collect();
+        for position in rejected {
+            // rows that did not load come before this one in the file
+            while let Some(row) = unloaded.next_if(|row| row.row <= position + skipped) {
+                quarantine.push(unloaded_row(row));
+                skipped += 1;
+            }
+            let cells = columns
+                .iter()
+                .map(|col| col.get(position).clone())
+                .collect();
+            quarantine.push((position + skipped, cells, reasons[position].join("; ")));
         }
-        if not_found {
-            Err(format!("colonna '{}' non esiste", old_col_name))
-        } else {
-            Ok(Table { columns })
+        quarantine.extend(unloaded.map(unloaded_row));
+
+        let mut cells: Vec<Vec<Value>> = vec![Vec::with_capacity(quarantine.len()); columns.len()];
+        let mut rows = Vec::with_capacity(quarantine.len());
+        let mut all_reasons = Vec::with_capacity(quarantine.len());
+        for (row, row_cells, reasons) in quarantine {
+            for (col_cells, cell) in cells.iter_mut().zip(row_cells) {
+                col_cells.push(cell);
+            }
+            rows.push(Value::new(row.to_string()));
+            all_reasons.push(Value::new(reasons));
+        }
+        let mut quarantine_columns = ColumnMap::with_capacity(columns.len() + 2);
+        for (col_name, col_cells) in table.columns.keys().zip(cells) {
+            quarantine_columns.insert(col_name.clone(), Column::new(col_cells));
         }
+        quarantine_columns.insert(Value::from("row"), Column::new(rows));
+        quarantine_columns.insert(Value::from("reasons"), Column::new(all_reasons));
+        Ok((table.remap(&clean), table.derived(quarantine_columns)))
     }
 
-    fn remap(&self, positions: &[usize]) -> Table {
-        let mut columns = HashMap::with_capacity(self.columns.len());
-        for (col_name, col) in self.columns.iter() {
-            columns.insert(col_name.clone(), col.remap(positions));
+    pub fn load_tsv_tracked(path: &str, options: &LoadOptions) -> Res<(Table, LoadState)> {
+        let read_error = |err: String| messages::text(Message::ReadTsv, &[&path, &err]);
+        let mut bytes = std::fs::read(path).map_err(|err| read_error(err.to_string()))?;
+        // a line without its newline may still be being written, leave it for the next reload
+        let offset = load::complete_lines_len(&bytes);
+        bytes.truncate(offset);
+        let tail = bytes[offset.saturating_sub(load::TAIL_LEN)..].to_vec();
+        let input = load::decode(
+            bytes,
+            options.encoding,
+            Some(options.skip_lines),
+            &mut LoadReport::default(),
+        )
+        .map_err(read_error)?;
+        let header = input
+            .lines()
+            .skip(options.skip_lines)
+            .find(|line| !line.is_empty())
+            .unwrap_or_default()
+            .to_string();
+        let table = Table::parse_tsv_with(input.as_ref(), options)
+            .map_err(|err| messages::text(Message::ParseTsv, &[&path, &err]))?;
+        let state = LoadState {
+            options: options.clone(),
+            header,
+            offset: offset as u64,
+            tail,
+        };
+        Ok((table, state))
+    }
+
+    pub fn reload_appended(&self, path: &str, state: &LoadState) -> Res<(Table, LoadState)> {
+        let read_error = |err: String| messages::text(Message::ReadTsv, &[&path, &err]);
+        let io_error = |err: std::io::Error| read_error(err.to_string());
+        let mut file = File::open(path).map_err(io_error)?;
+        let size = file.metadata().map_err(io_error)?.len();
+        let tail_start = state.offset - state.tail.len() as u64;
+        let mut bytes = Vec::new();
+        if size >= state.offset {
+            file.seek(SeekFrom::Start(tail_start)).map_err(io_error)?;
+            file.read_to_end(&mut bytes).map_err(io_error)?;
         }
-        Table { columns }
+        if state.header.is_empty() || !bytes.starts_with(&state.tail) {
+            // truncated or rewritten, the checkpoint is useless
+            return Table::load_tsv_tracked(path, &state.options);
+        }
+        bytes.drain(..state.tail.len());
+        let appended = load::complete_lines_len(&bytes);
+        if appended == 0 {
+            return Ok((self.clone(), state.clone()));
+        }
+        bytes.truncate(appended);
+        let tail = if appended >= load::TAIL_LEN {
+            bytes[appended - load::TAIL_LEN..].to_vec()
+        } else {
+            let dropped = (state.tail.len() + appended).saturating_sub(load::TAIL_LEN);
+            let mut tail = state.tail[dropped..].to_vec();
+            tail.extend_from_slice(&bytes);
+            tail
+        };
+        let text = load::decode(
+            bytes,
+            state.options.encoding,
+            None,
+            &mut LoadReport::default(),
+        )
+        .map_err(read_error)?;
+        let mut input = String::with_capacity(state.header.len() + 1 + text.len());
+        input += &state.header;
+        input.push('\n');
+        input += &text;
+        let options = state.options.clone().skip_lines(0);
+        let new_rows = Table::parse_tsv_with(input.as_ref(), &options)
+            .map_err(|err| messages::text(Message::ParseTsv, &[&path, &err]))?;
+        let state = LoadState {
+            offset: state.offset + appended as u64,
+            tail,
+            ..state.clone()
+        };
+        Ok((self.concatenate(&new_rows)?, state))
     }
 
-    pub fn filter_column(&self, col_name: &str, filter: impl Fn(&str) -> bool) -> Res<Table> {
-        let column = self.column(col_name)?;
-        let retained_positions: Vec<usize> = column
-            .cells()
-            .iter()
-            .enumerate()
-            .filter_map(|(i, v)| if filter(v) { Some(i) } else { None })
-            .collect();
+    pub fn parse_tsv(input: &str, skip_lines: usize) -> Res<Table> {
+        Table::parse_tsv_with(input, &LoadOptions::new().skip_lines(skip_lines))
+    }
 
-        Ok(if retained_positions.len() == self.rows_count() {
-            self.clone()
-        } else {
-            self.remap(&retained_positions)
-        })
+    pub fn parse_tsv_with(input: &str, options: &LoadOptions) -> Res<Table> {
+        Table::parse_tsv_rows(input, options, false).map(|(table, _)| table)
     }
 
-    pub fn diff_on_columns(
-        &self,
-        col_name_self: &str,
-        other: &Table,
-        col_name_other: &str,
-    ) -> Res<Table> {
-        let column_self = self.column(col_name_self)?;
-        let column_other = other.column(col_name_other)?;
-        let other_index = column_other.get_index();
-        let retained_positions: Vec<usize> = column_self
-            .cells()
-            .iter()
+    // with reject, a row that cannot be loaded is returned aside instead of failing the parse
+    fn parse_tsv_rows(
+        input: &str,
+        options: &LoadOptions,
+        reject: bool,
+    ) -> Res<(Table, Vec<RejectedRow>)> {
+        let mut lines = input
+            .lines()
             .enumerate()
-            .filter_map(|(position, value)| {
-                if !other_index.contains_key(value) {
-                    Some(position)
-                } else {
-                    None
+            .skip(options.skip_lines)
+            .skip_while(|(_, l)| l.is_empty());
+        let header_fields: Vec<Cow<str>> = match lines.next() {
+            Some((_, header)) => header.split('\t').map(|h| options.read_header(h)).collect(),
+            None => return Err(messages::text(Message::HeaderMissing, &[])),
+        };
+        let header: Vec<&str> = header_fields.iter().map(|h| h.as_ref()).collect();
+
+        // for every field of a line, the output column it goes to if it is loaded
+        let mut targets: Vec<Option<usize>> = vec![None; header.len()];
+        let col_names: Vec<&str> = match &options.columns {
+            Some(wanted) => {
+                for (target, col_name) in wanted.iter().enumerate() {
+                    let field = header
+                        .iter()
+                        .position(|h| h == col_name)
+                        .ok_or_else(|| messages::text(Message::ColumnMissing, &[&col_name]))?;
+                    targets[field] = Some(target);
                 }
-            })
-            .collect();
-        Ok(self.remap(&retained_positions))
-    }
+                wanted.iter().map(String::as_str).collect()
+            }
+            None => {
+                for (field, target) in targets.iter_mut().enumerate() {
+                    *target = Some(field);
+                }
+                header.clone()
+            }
+        };
 
-    pub fn map_column(&self, col_name: &str, map: impl Fn(&str) -> String) -> Res<Table> {
-        let col = self.column(col_name)?;
-        let column_cells = col.cells();
-        let mut columns: HashMap<Value, _> = HashMap::with_capacity(self.columns_count());
-        for (cn, column) in self.columns.iter() {
-            let new_column = if col_name == cn.as_str() {
-                let mapped_cells = column_cells.iter().map(|v| Value::new(map(v))).collect();
-                Column::new(mapped_cells)
-            } else {
-                column.clone()
-            };
-            columns.insert(cn.clone(), new_column);
+        let mut filters = Vec::with_capacity(options.filters.len());
+        for (col_name, filter) in options.filters.iter() {
+            let field = header
+                .iter()
+                .position(|h| h == col_name)
+                .ok_or_else(|| messages::text(Message::ColumnMissing, &[&col_name]))?;
+            filters.push((field, filter));
         }
-        Ok(Table { columns })
-    }
 
-    pub fn dinstinct_column(&self, col_name: &str) -> Res<Table> {
-        let col = self.column(col_name)?;
-        let mut found: HashSet<&str> = HashSet::with_capacity(self.rows_count());
-        let positions: Vec<usize> = col
-            .cells()
-            .iter()
-            .enumerate()
-            .filter_map(|(position, value)| {
-                if found.contains(value.as_str()) {
-                    None
+        let mut pinned: Vec<Option<ColumnType>> = vec![None; header.len()];
+        if options.infer_types {
+            for (field, col_name) in header.iter().enumerate() {
+                pinned[field] = options.pinned_type(col_name);
+            }
+        }
+        let mut types: Vec<Option<ColumnType>> = vec![None; header.len()];
+        if let Some(schema) = &options.schema {
+            for (col_name, column_type) in schema.columns.iter() {
+                let field = header
+                    .iter()
+                    .position(|h| h == col_name)
+                    .ok_or_else(|| messages::text(Message::ColumnMissing, &[&col_name]))?;
+                types[field] = Some(*column_type);
+            }
+        }
+
+        // the cells of each column, the rejected rows and how many rows were seen
+        type Parsed = (Vec<Vec<Value>>, Vec<RejectedRow>, usize);
+        let reader = options.reader();
+        let line_offset = options.line_offset;
+        let parse_lines = |lines: &mut dyn Iterator<Item = (usize, &str)>,
+                           filters: &[(usize, &RowFilter)]|
+         -> Res<Parsed> {
+            let mut cells: Vec<Vec<Value>> = vec![Vec::new(); col_names.len()];
+            let mut rejected = Vec::new();
+            let mut rows = 0;
+            let mut fields: Vec<Cow<str>> = Vec::with_capacity(header.len());
+            let mut values: Vec<Value> = Vec::with_capacity(col_names.len());
+            for (line_number, line) in lines {
+                if line.is_empty() {
+                    continue;
+                }
+                fields.clear();
+                fields.extend(line.split('\t').map(|field| reader.read_field(field)));
+                let line = line_offset + line_number + 1;
+                let loaded = if fields.len() != header.len() {
+                    Err(messages::text(
+                        Message::RowLength,
+                        &[&fields.len(), &header.len()],
+                    ))
+                } else if !filters
+                    .iter()
+                    .all(|(field, filter)| filter(&fields[*field]))
+                {
+                    continue;
                 } else {
-                    found.insert(value.as_str());
-                    Some(position)
+                    values.clear();
+                    fields
+                        .iter()
+                        .enumerate()
+                        .filter(|(field, _)| targets[*field].is_some())
+                        .try_for_each(|(field, cell)| {
+                            values.push(match (types[field], pinned[field]) {
+                                (Some(column_type), _) => {
+                                    load::coerce_cell(cell, column_type, line, header[field])?
+                                }
+                                (None, Some(column_type)) => {
+                                    load::check_cell(cell, column_type, line, header[field])?;
+                                    Value::from(cell.as_ref())
+                                }
+                                (None, None) => Value::from(cell.as_ref()),
+                            });
+                            Ok(())
+                        })
+                };
+                match loaded {
+                    Ok(()) => {
+                        let targets = targets.iter().filter_map(|target| *target);
+                        for (target, value) in targets.zip(values.drain(..)) {
+                            cells[target].push(value);
+                        }
+                    }
+                    Err(reason) if reject => {
+                        let mut row_cells = vec![String::new(); col_names.len()];
+                        for (field, cell) in fields.iter().enumerate() {
+                            if let Some(Some(target)) = targets.get(field) {
+                                row_cells[*target] = cell.to_string();
+                            }
+                        }
+                        rejected.push(RejectedRow {
+                            row: rows,
+                            cells: row_cells,
+                            reason,
+                        });
+                    }
+                    Err(err) => return Err(err),
                 }
+                rows += 1;
+            }
+            Ok((cells, rejected, rows))
+        };
+        // lines are parsed in chunks, each chunk fills its own columns. Only the parallel
+        // feature needs the lines gathered up front, the filters run while gathering them as
+        // they may not be shared between threads.
+        #[cfg(feature = "parallel")]
+        let (cells, rejected) = {
+            let lines: Vec<(usize, &str)> = lines
+                .filter(|(_, line)| {
+                    if filters.is_empty() || line.is_empty() {
+                        return true;
+                    }
+                    let fields: Vec<Cow<str>> = line
+                        .split('\t')
+                        .map(|field| reader.read_field(field))
+                        .collect();
+                    // a line of the wrong length is kept for the parse to report
+                    fields.len() != header.len()
+                        || filters
+                            .iter()
+                            .all(|(field, filter)| filter(&fields[*field]))
+                })
+                .collect();
+            let mut chunks = parallel::chunks(lines.len(), |rows| {
+                parse_lines(&mut lines[rows].iter().copied(), &[])
             })
-            .collect();
-        Ok(self.remap(&positions))
+            .into_iter();
+            // there is always at least one chunk, even for no lines
+            let (mut cells, mut rejected, mut rows) = chunks.next().unwrap()?;
+            for chunk in chunks {
+                let (chunk_cells, chunk_rejected, chunk_rows) = chunk?;
+                for (col_cells, chunk_cells) in cells.iter_mut().zip(chunk_cells) {
+                    col_cells.extend(chunk_cells);
+                }
+                rejected.extend(chunk_rejected.into_iter().map(|mut rejected| {
+                    rejected.row += rows;
+                    rejected
+                }));
+                rows += chunk_rows;
+            }
+            (cells, rejected)
+        };
+        #[cfg(not(feature = "parallel"))]
+        let (cells, rejected) = {
+            let (cells, rejected, _) = parse_lines(&mut lines, &filters)?;
+            (cells, rejected)
+        };
+
+        let mut columns = ColumnMap::with_capacity(col_names.len());
+        for (col_name, col_cells) in col_names.into_iter().zip(cells) {
+            columns.insert(Value::from(col_name), options.column(col_name, col_cells)?);
+        }
+        Ok((Table::from_columns(columns), rejected))
     }
 
-    pub fn sort_column(&self, col_name: &str) -> Res<Table> {
-        let col = self.column(col_name)?;
-        let mut values_with_pos = col.cells().iter().enumerate().collect::<Vec<_>>();
-        values_with_pos.sort_by_key(|(_, value)| *value);
-        let new_order: Vec<usize> = values_with_pos.into_iter().map(|(pos, _)| pos).collect();
-        Ok(self.remap(&new_order))
+    pub fn read_header(path: &str, skip_lines: usize) -> Res<Vec<String>> {
+        let mut header = None;
+        Table::scan_tsv_lines(path, skip_lines, |line| {
+            header = Some(
+                String::from_utf8_lossy(line)
+                    .split('\t')
+                    .map(|col_name| col_name.trim().to_string())
+                    .collect(),
+            );
+            false
+        })?;
+        header.ok_or_else(|| messages::text(Message::HeaderMissingInFile, &[&path]))
     }
 
-    pub fn sort_column_by(
-        &self,
-        col_name: &str,
-        order: impl Fn(&str, &str) -> std::cmp::Ordering,
-    ) -> Res<Table> {
-        let col = self.column(col_name)?;
-        let mut values_with_pos = col.cells().iter().enumerate().collect::<Vec<_>>();
-        values_with_pos.sort_by(|(_, v1), (_, v2)| order(*v1, *v2));
-        let new_order: Vec<usize> = values_with_pos.into_iter().map(|(pos, _)| pos).collect();
-        Ok(self.remap(&new_order))
+    pub fn count_rows(path: &str, skip_lines: usize) -> Res<usize> {
+        let mut lines = 0;
+        Table::scan_tsv_lines(path, skip_lines, |_| {
+            lines += 1;
+            true
+        })?;
+        Ok(lines.max(1) - 1)
     }
 
-    pub fn concatenate(&self, other: &Table) -> Res<Table> {
-        let mut columns: HashMap<Value, _> = HashMap::with_capacity(self.columns_count());
-        let new_rows_count = self.rows_count() + other.rows_count();
-        for (col_name, col) in self.columns.iter() {
-            let mut cells: Vec<Value> = Vec::with_capacity(new_rows_count);
-            for cell in col.cells() {
-                cells.push(cell.clone());
-            }
-            let other_col = other.column(col_name).map_err(|_| {
-                format!(
-                    "la seconda table in concatenazione non ha la colonna '{}'",
-                    col_name.as_str()
-                )
-            })?;
-            for cell in other_col.cells() {
-                cells.push(cell.clone());
+    fn scan_tsv_lines(
+        path: &str,
+        skip_lines: usize,
+        mut on_line: impl FnMut(&[u8]) -> bool,
+    ) -> Res<()> {
+        let read_error = |err: std::io::Error| messages::text(Message::ReadFile, &[&path, &err]);
+        let mut reader = BufReader::new(File::open(path).map_err(read_error)?);
+        let mut buffer = Vec::new();
+        let mut line_number = 0;
+        loop {
+            buffer.clear();
+            if reader.read_until(b'\n', &mut buffer).map_err(read_error)? == 0 {
+                return Ok(());
+            }
+            line_number += 1;
+            let line = buffer
+                .strip_suffix(b"\n")
+                .map(|l| l.strip_suffix(b"\r").unwrap_or(l))
+                .unwrap_or(&buffer);
+            if line_number > skip_lines && !line.is_empty() && !on_line(line) {
+                return Ok(());
             }
-            columns.insert(col_name.clone(), Column::new(cells));
         }
-        Ok(Table { columns })
     }
 
-    pub fn create_fixed_column(&self, col_name: &str, fixed_value: &str) -> Table {
-        let value = Value::new(fixed_value.to_string());
-        let cells: Vec<Value> = (0..self.rows_count()).map(|_| value.clone()).collect();
+    pub fn sniff(path: &str, sample_rows: usize) -> Res<Sniff> {
+        let read_error = |err: std::io::Error| messages::text(Message::ReadFile, &[&path, &err]);
+        let file = File::open(path).map_err(read_error)?;
+        let size = file.metadata().map_err(read_error)?.len();
+        let mut reader = BufReader::new(file);
+        let mut buffer = Vec::new();
+        let mut read_line = |reader: &mut BufReader<File>| -> Res<Option<String>> {
+            buffer.clear();
+            if reader.read_until(b'\n', &mut buffer).map_err(read_error)? == 0 {
+                return Ok(None);
+            }
+            let line = String::from_utf8_lossy(&buffer);
+            Ok(Some(line.trim_end_matches(&['\r', '\n'][..]).to_string()))
+        };
 
-        let mut clone = self.clone();
-        clone
-            .columns
-            .insert(Value::new(col_name.to_string()), Column::new(cells));
-        clone
-    }
+        // header and first rows
+        let mut lines: Vec<String> = Vec::with_capacity(sample_rows * 2 + 1);
+        while lines.len() <= sample_rows {
+            match read_line(&mut reader)? {
+                Some(line) if line.is_empty() => continue,
+                Some(line) => lines.push(line),
+                None => break,
+            }
+        }
+        if lines.is_empty() {
+            return Err(messages::text(Message::HeaderMissing, &[]));
+        }
 
-    pub fn create_column(&self, expr: MiOp) -> Res<Table> {
-        let mut inputs_cols = Vec::with_capacity(expr.out_column.len());
-        for cname in expr.in_columns.into_iter() {
-            let col = self.column(cname.as_ref())?;
-            inputs_cols.push(col);
+        // a few blocks spread over the rest of the file
+        const BLOCKS: u64 = 4;
+        let block_rows = (sample_rows as u64 / BLOCKS).max(1);
+        let head_end = reader.stream_position().map_err(read_error)?;
+        for block in 1..=BLOCKS {
+            let offset = size * block / (BLOCKS + 1);
+            if offset <= head_end {
+                continue;
+            }
+            reader.seek(SeekFrom::Start(offset)).map_err(read_error)?;
+            // the first line is likely cut in half
+            read_line(&mut reader)?;
+            for _ in 0..block_rows {
+                match read_line(&mut reader)? {
+                    Some(line) if !line.is_empty() => lines.push(line),
+                    Some(_) => {}
+                    None => break,
+                }
+            }
         }
-        let function = expr.operation;
-        let col_rows = (0..self.rows_count())
-            .map(|position| {
-                let args: Vec<&str> = inputs_cols
+
+        let delimiter = ['\t', ',', ';', '|']
+            .iter()
+            .copied()
+            .filter_map(|candidate| {
+                let header_count = lines[0].matches(candidate).count();
+                let consistent = lines
                     .iter()
-                    .map(|col| col.cells()[position].as_str())
-                    .collect();
-                let value = (function)(args.as_slice());
-                Value::new(value)
+                    .all(|line| line.matches(candidate).count() == header_count);
+                if header_count > 0 && consistent {
+                    Some((header_count, candidate))
+                } else {
+                    None
+                }
             })
+            .max_by_key(|&(count, _)| count)
+            .map_or('\t', |(_, candidate)| candidate);
+
+        let rows: Vec<Vec<&str>> = lines[1..]
+            .iter()
+            .map(|line| line.split(delimiter).map(str::trim).collect())
+            .collect();
+        let columns = lines[0]
+            .split(delimiter)
+            .enumerate()
+            .map(|(i, name)| {
+                let values = rows.iter().filter_map(|row| row.get(i).copied());
+                (name.trim().to_string(), ColumnType::infer(values))
+            })
+            .collect();
+        Ok(Sniff {
+            delimiter,
+            columns,
+            sampled_rows: rows.len(),
+        })
+    }
+
+    pub fn date_range(col_name: &str, start: &str, end: &str, step_days: usize) -> Res<Table> {
+        let parse = |date: &str| {
+            dates::parse_iso_date(date)
+                .ok_or_else(|| messages::text(Message::InvalidIsoDate, &[&date]))
+        };
+        let start_day = parse(start)?;
+        let end_day = parse(end)?;
+        if step_days == 0 {
+            return Err(messages::text(Message::DateStepZero, &[]));
+        }
+        let cells = if start_day <= end_day {
+            (start_day..=end_day)
+                .step_by(step_days)
+                .map(|day| Value::new(dates::format_iso_date(day)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut columns = ColumnMap::with_capacity(1);
+        columns.insert(Value::from(col_name), Column::new(cells));
+        Ok(Table::from_columns(columns))
+    }
+
+    pub(crate) fn from_columns(columns: ColumnMap) -> Table {
+        Table {
+            columns,
+            primary_key: Vec::new(),
+            name: None,
+            lineage: Lineage::new(),
+            composite_indexes: Vec::new(),
+        }
+    }
+
+    // columns taken from self keep its name and lineage
+    fn derived(&self, columns: ColumnMap) -> Table {
+        let mut table = Table::from_columns(columns);
+        table.name = self.name.clone();
+        table.lineage = self
+            .lineage
+            .iter()
+            .filter(|(col_name, _)| table.columns.contains_key(col_name))
+            .map(|(col_name, sources)| (col_name.clone(), sources.clone()))
+            .collect();
+        table.composite_indexes = self
+            .composite_indexes
+            .iter()
+            .filter(|index| index.is_valid(&table.columns))
+            .cloned()
             .collect();
+        table
+    }
+
+    pub fn with_name(&self, name: &str) -> Table {
         let mut clone = self.clone();
-        let out_col_name = expr.out_column;
+        clone.name = Some(Value::from(name));
         clone
-            .columns
-            .insert(Value::new(out_col_name), Column::new(col_rows));
-        Ok(clone)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn column_lineage(&self, col_name: &str) -> Res<Vec<Source>> {
+        self.column_ref(col_name)?;
+        Ok(self.sources(col_name))
+    }
+
+    fn sources(&self, col_name: &str) -> Vec<Source> {
+        match self.lineage.get(col_name) {
+            Some(sources) => sources.clone(),
+            None => vec![Source::new(self.name.clone(), Value::from(col_name))],
+        }
+    }
+
+    // every column with explicit sources, for outputs that are a new table
+    fn frozen_lineage(&self) -> Lineage {
+        self.columns
+            .keys()
+            .map(|col_name| (col_name.clone(), self.sources(col_name)))
+            .collect()
+    }
+
+    fn record_lineage(&mut self, expr: &MiOp) {
+        let sources = lineage::union(expr.in_columns.iter().flat_map(|c| self.sources(c)));
+        self.lineage
+            .insert(Value::new(expr.out_column.clone()), sources);
+    }
+
+    pub fn columns_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn rows_count(&self) -> usize {
+        self.columns.values().next().map(|c| c.len()).unwrap_or(0)
+    }
+
+    pub fn distinct_count(&self, col_name: &str) -> Res<usize> {
+        Ok(self.column(col_name)?.distinct_count())
+    }
+
+    // the index is shared by the clones of the table and follows the rows through remaps
+    pub fn create_index(&self, col_name: &str) -> Res<()> {
+        self.column_ref(col_name)?.get_index();
+        Ok(())
+    }
+
+    pub fn drop_index(&mut self, col_name: &str) -> Res<()> {
+        self.columns
+            .get_mut(col_name)
+            .ok_or_else(|| messages::text(Message::ColumnMissing, &[&col_name]))?
+            .drop_index();
+        Ok(())
+    }
+
+    pub fn has_index(&self, col_name: &str) -> Res<bool> {
+        Ok(self.column_ref(col_name)?.has_index())
+    }
+
+    // an index on the combination of the columns, in this order
+    pub fn create_index_multi(&mut self, col_names: &[&str]) -> Res<()> {
+        if self.composite_index(col_names).is_some() {
+            return Ok(());
+        }
+        let mut columns = Vec::with_capacity(col_names.len());
+        for &col_name in col_names {
+            columns.push((Value::from(col_name), self.column(col_name)?));
+        }
+        self.forget_stale_indexes();
+        self.composite_indexes
+            .push(Arc::new(CompositeIndex::build(columns)));
+        Ok(())
+    }
+
+    pub fn drop_index_multi(&mut self, col_names: &[&str]) {
+        self.composite_indexes
+            .retain(|index| !index.is_on(col_names));
+    }
+
+    pub fn has_index_multi(&self, col_names: &[&str]) -> bool {
+        self.composite_index(col_names).is_some()
+    }
+
+    fn composite_index(&self, col_names: &[&str]) -> Option<&CompositeIndex> {
+        self.composite_indexes
+            .iter()
+            .find(|index| index.is_on(col_names) && index.is_valid(&self.columns))
+            .map(|index| index.as_ref())
+    }
+
+    // composite indexes hold their columns, so they are dropped once the table changes them
+    fn forget_stale_indexes(&mut self) {
+        let columns = &self.columns;
+        self.composite_indexes
+            .retain(|index| index.is_valid(columns));
+    }
+
+    pub fn column(&self, col_name: &str) -> Res<Column> {
+        if let Some(column) = self.columns.get(col_name) {
+            Ok(column.clone())
+        } else {
+            Err(messages::text(Message::ColumnMissing, &[&col_name]))
+        }
+    }
+
+    pub fn schema(&self) -> Schema {
+        self.columns
+            .iter()
+            .fold(Schema::new(), |schema, (col_name, column)| {
+                schema.column(col_name, column.column_type())
+            })
+    }
+
+    pub fn validate(&self, schema: &Schema) -> Res<ValidationReport> {
+        schema.validate(self)
+    }
+
+    pub fn check_rules(&self, rules: &Rules) -> Res<Table> {
+        rules.evaluate(self)
+    }
+
+    // nulls are not compared, as with join keys
+    pub fn assert_unique(&self, col_name: &str) -> Result<(), ValidationReport> {
+        let column = self
+            .column(col_name)
+            .map_err(|_| missing_column(col_name))?;
+        let index = column.get_index();
+        let mut rows: Vec<usize> = index
+            .iter()
+            .filter(|(value, rows)| !value.is_null() && rows.len() > 1)
+            .flat_map(|(_, rows)| rows.iter().copied())
+            .collect();
+        rows.sort_unstable();
+        let violations = rows
+            .into_iter()
+            .map(|row| {
+                let value = column.get(row);
+                // a value repeated n times would list n - 1 rows in each of its n violations
+                let count = index[value].len() - 1;
+                let mut others: Vec<String> = index[value]
+                    .iter()
+                    .filter(|&&other| other != row)
+                    .take(LISTED_DUPLICATES)
+                    .map(|other| other.to_string())
+                    .collect();
+                if count > LISTED_DUPLICATES {
+                    others.push("…".to_string());
+                }
+                schema::Violation {
+                    row: Some(row),
+                    column: col_name.to_string(),
+                    value: value.to_string(),
+                    message: if count == 1 {
+                        messages::text(Message::DuplicateAtRow, &[&others[0]])
+                    } else {
+                        messages::text(Message::DuplicateAtRows, &[&count, &others.join(", ")])
+                    },
+                }
+            })
+            .collect();
+        ValidationReport { violations }.into_result()
+    }
+
+    pub fn assert_non_empty(&self, col_name: &str) -> Result<(), ValidationReport> {
+        let column = self
+            .column(col_name)
+            .map_err(|_| missing_column(col_name))?;
+        let violations = column
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| value.is_null())
+            .map(|(row, _)| schema::Violation {
+                row: Some(row),
+                column: col_name.to_string(),
+                value: String::new(),
+                message: messages::text(Message::EmptyValue, &[]),
+            })
+            .collect();
+        ValidationReport { violations }.into_result()
+    }
+
+    pub fn with_primary_key(&self, col_names: &[&str]) -> Res<Table> {
+        if col_names.is_empty() {
+            return Err(messages::text(Message::PrimaryKeyEmpty, &[]));
+        }
+        let mut clone = self.clone();
+        clone.primary_key = col_names.iter().map(|&c| Value::from(c)).collect();
+        clone.check_primary_key()?;
+        Ok(clone)
+    }
+
+    pub fn without_primary_key(&self) -> Table {
+        let mut clone = self.clone();
+        clone.primary_key.clear();
+        clone
+    }
+
+    pub fn primary_key(&self) -> Vec<&str> {
+        self.primary_key.iter().map(Value::as_str).collect()
+    }
+
+    fn in_primary_key(&self, col_name: &str) -> bool {
+        self.primary_key.iter().any(|k| k.as_str() == col_name)
+    }
+
+    // key cells can't be null and every combination appears once
+    fn check_primary_key(&self) -> Res<()> {
+        if self.primary_key.is_empty() {
+            return Ok(());
+        }
+        let mut key_columns = Vec::with_capacity(self.primary_key.len());
+        for col_name in self.primary_key.iter() {
+            key_columns.push(self.column_ref(col_name)?);
+        }
+        let mut seen: HashMap<Vec<&str>, usize> = HashMap::with_capacity(self.rows_count());
+        for position in 0..self.rows_count() {
+            let mut key = Vec::with_capacity(key_columns.len());
+            for (col_name, column) in self.primary_key.iter().zip(key_columns.iter()) {
+                let value = column.get(position);
+                if value.is_null() {
+                    return Err(messages::text(
+                        Message::PrimaryKeyNull,
+                        &[&position, &col_name.as_str()],
+                    ));
+                }
+                key.push(value.as_str());
+            }
+            if let Some(first) = seen.insert(key, position) {
+                return Err(messages::text(
+                    Message::PrimaryKeyDuplicate,
+                    &[&first, &position],
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn cast_column(&self, col_name: &str, column_type: ColumnType) -> Res<Table> {
+        let column = self
+            .column(col_name)?
+            .cast(column_type)
+            .map_err(|err| messages::text(Message::InColumn, &[&col_name, &err]))?;
+        let mut clone = self.clone();
+        // casting can normalize values, "01" and "1" both become 1, put_column checks the key
+        clone.put_column(col_name, column)?;
+        Ok(clone)
+    }
+
+    pub fn cell(&self, row: usize, col_name: &str) -> Res<&str> {
+        let column = self.column_ref(col_name)?;
+        (row < column.len())
+            .then(|| column.get(row).as_str())
+            .ok_or_else(|| messages::text(Message::RowMissingInColumn, &[&row, &col_name]))
+    }
+
+    pub fn render_template(&self, template: &str) -> Res<String> {
+        template::render(template, self)
+    }
+
+    // cells in column order, the columns are copied first if shared with other tables
+    pub fn push_row(&mut self, cells: Vec<String>) -> Res<()> {
+        if cells.len() != self.columns_count() {
+            return Err(messages::text(
+                Message::RowLength,
+                &[&cells.len(), &self.columns_count()],
+            ));
+        }
+        self.check_new_key(&cells)?;
+        for (column, cell) in self.columns.values_mut().zip(cells) {
+            column.push(Value::new(cell));
+        }
+        self.forget_stale_indexes();
+        Ok(())
+    }
+
+    // stops at the first invalid row, the rows before it stay appended
+    pub fn extend_rows(&mut self, rows: impl IntoIterator<Item = Vec<String>>) -> Res<()> {
+        for row in rows {
+            self.push_row(row)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_cell(&mut self, col_name: &str, row: usize, value: &str) -> Res<()> {
+        let previous = Value::from(self.cell(row, col_name)?);
+        let column = self.columns.get_mut(col_name).unwrap();
+        column.set(row, Value::from(value));
+        if self.in_primary_key(col_name) {
+            if let Err(err) = self.check_primary_key() {
+                self.columns.get_mut(col_name).unwrap().set(row, previous);
+                return Err(err);
+            }
+        }
+        self.forget_stale_indexes();
+        Ok(())
+    }
+
+    // the new column has no lineage of its own, it is attributed to this table
+    pub fn replace_column(&mut self, col_name: &str, column: Column) -> Res<()> {
+        self.column_ref(col_name)?;
+        if column.len() != self.rows_count() {
+            return Err(messages::text(
+                Message::ColumnLength,
+                &[&column.len(), &self.rows_count()],
+            ));
+        }
+        self.put_column(col_name, column)?;
+        self.lineage.remove(col_name);
+        Ok(())
+    }
+
+    // filters in place, columns shared with other tables are copied first
+    pub fn retain_rows(&mut self, col_names: &[&str], filter: impl Fn(&[&str]) -> bool) -> Res<()> {
+        let mut columns = Vec::with_capacity(col_names.len());
+        for &col_name in col_names {
+            columns.push(self.column_ref(col_name)?);
+        }
+        let mut cells: Vec<&str> = Vec::with_capacity(columns.len());
+        let keep: Vec<bool> = (0..self.rows_count())
+            .map(|position| {
+                cells.clear();
+                cells.extend(columns.iter().map(|col| col.get(position).as_str()));
+                filter(&cells)
+            })
+            .collect();
+        if keep.iter().all(|&k| k) {
+            return Ok(());
+        }
+        for column in self.columns.values_mut() {
+            column.retain(&keep);
+        }
+        self.forget_stale_indexes();
+        Ok(())
+    }
+
+    // the key columns keep their index in sync on push, so this does not scan the rows: only
+    // the rows holding the rarest of the key cells are compared with the other key cells
+    fn check_new_key(&self, cells: &[String]) -> Res<()> {
+        let position = self.rows_count();
+        let mut key = Vec::with_capacity(self.primary_key.len());
+        for col_name in self.primary_key.iter() {
+            let i = self.columns.position(col_name).unwrap();
+            if cells[i].is_empty() {
+                return Err(messages::text(
+                    Message::PrimaryKeyNull,
+                    &[&position, &col_name.as_str()],
+                ));
+            }
+            key.push((&self.columns[col_name], cells[i].as_str()));
+        }
+        let candidates = key
+            .iter()
+            .map(|(column, cell)| column.get_index().get(*cell).map_or(&[][..], Vec::as_slice))
+            .min_by_key(|rows| rows.len())
+            .unwrap_or_default();
+        let duplicate = candidates.iter().copied().find(|&row| {
+            key.iter()
+                .all(|(column, cell)| column.get(row).as_str() == *cell)
+        });
+        match duplicate {
+            Some(first) => Err(messages::text(
+                Message::PrimaryKeyDuplicate,
+                &[&first, &position],
+            )),
+            None => Ok(()),
+        }
+    }
+
+    pub fn rows_as<T: FromRow>(&self) -> Res<Vec<T>> {
+        self.rows().map(|row| T::from_row(&row)).collect()
+    }
+
+    pub fn from_rows(
+        col_names: &[&str],
+        rows: impl IntoIterator<Item = Vec<String>>,
+    ) -> Res<Table> {
+        let col_names = col_names.iter().map(|c| c.to_string()).collect();
+        let mut builder = TableBuilder::new(col_names);
+        for row in rows {
+            builder = builder.add_row(row)?;
+        }
+        Ok(builder.build())
+    }
+
+    pub fn from_structs<T: ToRow>(items: &[T]) -> Res<Table> {
+        Table::from_rows(&T::column_names(), items.iter().map(T::to_row))
+    }
+
+    // fails when to_row and column_names disagree on the number of columns
+    pub fn from_records<T: ToRow>(items: impl IntoIterator<Item = T>) -> Res<Table> {
+        Table::from_rows(
+            &T::column_names(),
+            items.into_iter().map(|item| item.to_row()),
+        )
+    }
+
+    pub fn row(&self, row: usize) -> Res<Row<'_>> {
+        if row >= self.rows_count() {
+            return Err(messages::text(Message::RowMissing, &[&row]));
+        }
+        Ok(Row::new(self, row))
+    }
+
+    pub fn is_null(&self, col_name: &str, row: usize) -> Res<bool> {
+        let column = self.column_ref(col_name)?;
+        (row < column.len())
+            .then(|| column.get(row).is_null())
+            .ok_or_else(|| messages::text(Message::RowMissingInColumn, &[&row, &col_name]))
+    }
+
+    pub fn drop_nulls(&self, col_name: &str) -> Res<Table> {
+        self.filter_column(col_name, |value| !value.is_empty())
+    }
+
+    pub fn select_columns(&self, col_names: &[&str]) -> Res<Table> {
+        let mut columns = ColumnMap::with_capacity(col_names.len());
+        for &col_name in col_names {
+            columns.insert(Value::from(col_name), self.column(col_name)?);
+        }
+        let mut table = self.derived(columns);
+        // the rows stay unique only when the whole key is kept
+        if self
+            .primary_key
+            .iter()
+            .all(|k| col_names.contains(&k.as_str()))
+        {
+            table.primary_key = self.primary_key.clone();
+        }
+        Ok(table)
+    }
+
+    pub fn deselect_column(&self, col_name: &str) -> Res<Table> {
+        if self.in_primary_key(col_name) {
+            return Err(messages::text(Message::ColumnInPrimaryKey, &[&col_name]));
+        }
+        let mut columns = ColumnMap::with_capacity(self.columns.len());
+        for (colmun_name, column) in self.columns.iter() {
+            if col_name != colmun_name.as_str() {
+                columns.insert(colmun_name.clone(), column.clone());
+            }
+        }
+        if columns.len() != self.columns.len() - 1 {
+            Err(messages::text(Message::ColumnMissing, &[&col_name]))
+        } else {
+            let mut table = self.derived(columns);
+            table.primary_key = self.primary_key.clone();
+            Ok(table)
+        }
+    }
+
+    pub fn rename_column(&self, old_col_name: &str, new_col_name: &str) -> Res<Table> {
+        let mut columns = ColumnMap::with_capacity(self.columns.len());
+        let mut not_found = true;
+        for (colmun_name, column) in self.columns.iter() {
+            let name = if old_col_name == colmun_name.as_str() {
+                not_found = false;
+                Value::from(new_col_name)
+            } else {
+                colmun_name.clone()
+            };
+            columns.insert(name, column.clone());
+        }
+        if not_found {
+            Err(messages::text(Message::ColumnMissing, &[&old_col_name]))
+        } else {
+            let mut table = self.derived(columns);
+            table.lineage.remove(old_col_name);
+            table
+                .lineage
+                .insert(Value::from(new_col_name), self.sources(old_col_name));
+            table.primary_key = self
+                .primary_key
+                .iter()
+                .map(|k| {
+                    if k.as_str() == old_col_name {
+                        Value::from(new_col_name)
+                    } else {
+                        k.clone()
+                    }
+                })
+                .collect();
+            Ok(table)
+        }
+    }
+
+    pub(crate) fn remap(&self, positions: &[usize]) -> Table {
+        let mut columns = ColumnMap::with_capacity(self.columns.len());
+        for (col_name, col) in self.columns.iter() {
+            columns.insert(col_name.clone(), col.remap(positions));
+        }
+        let composite_indexes = self
+            .composite_indexes
+            .iter()
+            .filter(|index| index.is_valid(&self.columns))
+            .filter_map(|index| index.remap(&columns, positions).map(Arc::new))
+            .collect();
+        Table {
+            columns,
+            primary_key: self.primary_key.clone(),
+            name: self.name.clone(),
+            lineage: self.lineage.clone(),
+            composite_indexes,
+        }
+    }
+
+    pub fn head(&self, n: usize) -> Table {
+        self.slice(0, n)
+    }
+
+    pub fn tail(&self, n: usize) -> Table {
+        let rows = self.rows_count();
+        self.slice(rows.saturating_sub(n), n)
+    }
+
+    pub fn slice(&self, offset: usize, len: usize) -> Table {
+        let rows = self.rows_count();
+        let start = offset.min(rows);
+        let end = offset.saturating_add(len).min(rows);
+        if start == 0 && end == rows {
+            return self.clone();
+        }
+        let positions: Vec<usize> = (start..end).collect();
+        self.remap(&positions)
+    }
+
+    pub fn sample(&self, n: usize, seed: u64) -> Table {
+        let rows = self.rows_count();
+        if n >= rows {
+            return self.clone();
+        }
+        // partial Fisher-Yates, then back to the original row order
+        let mut rng = SplitMix64::new(seed);
+        let mut positions: Vec<usize> = (0..rows).collect();
+        for i in 0..n {
+            let j = i + rng.below(rows - i);
+            positions.swap(i, j);
+        }
+        positions.truncate(n);
+        positions.sort_unstable();
+        self.remap(&positions)
+    }
+
+    pub fn sample_fraction(&self, fraction: f64, seed: u64) -> Res<Table> {
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(messages::text(Message::FractionOutOfRange, &[&fraction]));
+        }
+        let mut rng = SplitMix64::new(seed);
+        let positions: Vec<usize> = (0..self.rows_count())
+            .filter(|_| rng.next_f64() < fraction)
+            .collect();
+        Ok(self.remap(&positions))
+    }
+
+    pub fn filter_column(&self, col_name: &str, filter: impl Fn(&str) -> bool) -> Res<Table> {
+        self.filter_by_column(col_name, &filter, |column| {
+            (0..column.len())
+                .filter(|&i| filter(column.get(i)))
+                .collect()
+        })
+    }
+
+    // same as filter_column, with the parallel feature the rows are split across threads
+    pub fn filter_column_par(
+        &self,
+        col_name: &str,
+        filter: impl Fn(&str) -> bool + Send + Sync,
+    ) -> Res<Table> {
+        self.filter_by_column(col_name, &filter, |column| {
+            parallel::chunks(column.len(), |rows| {
+                rows.filter(|&i| filter(column.get(i)))
+                    .collect::<Vec<usize>>()
+            })
+            .concat()
+        })
+    }
+
+    // scan gives the retained positions when the filter is not run on the index
+    fn filter_by_column(
+        &self,
+        col_name: &str,
+        filter: &dyn Fn(&str) -> bool,
+        scan: impl FnOnce(&Column) -> Vec<usize>,
+    ) -> Res<Table> {
+        let column = self.column(col_name)?;
+        let retained_positions: Vec<usize> = match tuner::choose_filter(&column) {
+            // the filter runs once per distinct value
+            FilterStrategy::Index => {
+                let index = column.get_index();
+                let mut positions: Vec<usize> = index
+                    .iter()
+                    .filter(|(value, _)| filter(value))
+                    .flat_map(|(_, positions)| positions.iter().copied())
+                    .collect();
+                positions.sort_unstable();
+                positions
+            }
+            _ => scan(&column),
+        };
+
+        Ok(if retained_positions.len() == self.rows_count() {
+            self.clone()
+        } else {
+            self.remap(&retained_positions)
+        })
+    }
+
+    pub fn filter_column_regex(&self, col_name: &str, pattern: &str) -> Res<Table> {
+        let regex = Regex::new(pattern)?;
+        self.filter_column(col_name, |value| regex.is_match(value))
+    }
+
+    pub fn mask_column(
+        &self,
+        col_name: &str,
+        new_col: &str,
+        predicate: impl Fn(&str) -> bool,
+    ) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let mask: Vec<bool> = col.iter().map(|value| predicate(value)).collect();
+        self.with_mask(new_col, mask)
+    }
+
+    pub fn combine_masks(
+        &self,
+        col_name_a: &str,
+        op: MaskOp,
+        col_name_b: &str,
+        new_col: &str,
+    ) -> Res<Table> {
+        let a = self.mask(col_name_a)?;
+        let b = self.mask(col_name_b)?;
+        let mask = a
+            .into_iter()
+            .zip(b)
+            .map(|(a, b)| match op {
+                MaskOp::And => a && b,
+                MaskOp::Or => a || b,
+                MaskOp::Xor => a != b,
+            })
+            .collect();
+        self.with_mask(new_col, mask)
+    }
+
+    pub fn invert_mask(&self, col_name: &str, new_col: &str) -> Res<Table> {
+        let mask = self.mask(col_name)?.into_iter().map(|m| !m).collect();
+        self.with_mask(new_col, mask)
+    }
+
+    pub fn filter_mask(&self, col_name: &str) -> Res<Table> {
+        let retained_positions: Vec<usize> = self
+            .mask(col_name)?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(position, keep)| if keep { Some(position) } else { None })
+            .collect();
+
+        Ok(if retained_positions.len() == self.rows_count() {
+            self.clone()
+        } else {
+            self.remap(&retained_positions)
+        })
+    }
+
+    // null cells count as false
+    fn mask(&self, col_name: &str) -> Res<Vec<bool>> {
+        let col = self.column(col_name)?;
+        if let Some(values) = col.as_bool() {
+            return Ok(values.iter().map(|v| v.unwrap_or(false)).collect());
+        }
+        col.iter()
+            .enumerate()
+            .map(|(position, value)| {
+                if value.is_null() {
+                    return Ok(false);
+                }
+                types::parse_bool(value).ok_or_else(|| {
+                    messages::text(
+                        Message::NotBooleanAt,
+                        &[&value.as_str(), &position, &col_name],
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn with_mask(&self, new_col: &str, mask: Vec<bool>) -> Res<Table> {
+        let (cells, values) = mask
+            .into_iter()
+            .map(|m| (Value::from(if m { "true" } else { "false" }), Some(m)))
+            .unzip();
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::bools(cells, values))?;
+        Ok(clone)
+    }
+
+    pub fn filter_rows(&self, col_names: &[&str], filter: impl Fn(&[&str]) -> bool) -> Res<Table> {
+        let mut columns = Vec::with_capacity(col_names.len());
+        for &col_name in col_names {
+            columns.push(self.column(col_name)?);
+        }
+        let mut cells: Vec<&str> = Vec::with_capacity(columns.len());
+        let retained_positions: Vec<usize> = (0..self.rows_count())
+            .filter(|&position| {
+                cells.clear();
+                cells.extend(columns.iter().map(|col| col.get(position).as_str()));
+                filter(&cells)
+            })
+            .collect();
+
+        Ok(if retained_positions.len() == self.rows_count() {
+            self.clone()
+        } else {
+            self.remap(&retained_positions)
+        })
+    }
+
+    pub fn diff_on_columns(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        col_name_other: &str,
+    ) -> Res<Table> {
+        let column_self = self.column(col_name_self)?;
+        let column_other = other.column(col_name_other)?;
+        let other_index = column_other.get_index();
+        let retained_positions: Vec<usize> = column_self
+            .iter()
+            .enumerate()
+            .filter_map(|(position, value)| {
+                if !other_index.contains_key(value) {
+                    Some(position)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        Ok(self.remap(&retained_positions))
+    }
+
+    // self is the old version, other the new one
+    pub fn render_diff(&self, other: &Table, key_cols: &[&str]) -> Res<String> {
+        diff::render(self, other, key_cols)
+    }
+
+    pub fn filter_expr(&self, expr: &str) -> Res<Table> {
+        let mut expr = Expr::parse(expr)?;
+        let mut columns: Vec<(String, Column)> = Vec::new();
+        expr.bind(&mut |col_name| {
+            if let Some(slot) = columns.iter().position(|(name, _)| name == col_name) {
+                return Ok(slot);
+            }
+            columns.push((col_name.to_string(), self.column(col_name)?));
+            Ok(columns.len() - 1)
+        })?;
+        let mut cells: Vec<&str> = Vec::with_capacity(columns.len());
+        let retained_positions: Vec<usize> = (0..self.rows_count())
+            .filter(|&position| {
+                cells.clear();
+                cells.extend(columns.iter().map(|(_, col)| col.get(position).as_str()));
+                expr.eval(&cells)
+            })
+            .collect();
+        Ok(self.remap(&retained_positions))
+    }
+
+    pub fn intersect_on_columns(
+        &self,
+        col_names_self: &[&str],
+        other: &Table,
+        col_names_other: &[&str],
+    ) -> Res<Table> {
+        self.rows_matching(col_names_self, other, col_names_other, true)
+    }
+
+    pub fn intersect(&self, other: &Table) -> Res<Table> {
+        let col_names = self.shared_column_names(other)?;
+        self.rows_matching(&col_names, other, &col_names, true)
+    }
+
+    pub fn except(&self, other: &Table) -> Res<Table> {
+        let col_names = self.shared_column_names(other)?;
+        self.rows_matching(&col_names, other, &col_names, false)
+    }
+
+    fn shared_column_names(&self, other: &Table) -> Res<Vec<&str>> {
+        let col_names: Vec<&str> = self
+            .columns
+            .keys()
+            .filter(|col_name| other.columns.contains_key(col_name))
+            .map(Value::as_str)
+            .collect();
+        if col_names.is_empty() {
+            return Err(messages::text(Message::NoSharedColumns, &[]));
+        }
+        Ok(col_names)
+    }
+
+    fn rows_matching(
+        &self,
+        col_names_self: &[&str],
+        other: &Table,
+        col_names_other: &[&str],
+        present: bool,
+    ) -> Res<Table> {
+        if col_names_self.len() != col_names_other.len() {
+            return Err(messages::text(
+                Message::ColumnCountMismatch,
+                &[&col_names_self.len(), &col_names_other.len()],
+            ));
+        }
+        let mut columns_self = Vec::with_capacity(col_names_self.len());
+        for &col_name in col_names_self {
+            columns_self.push(self.column(col_name)?);
+        }
+        let mut columns_other = Vec::with_capacity(col_names_other.len());
+        for &col_name in col_names_other {
+            columns_other.push(other.column(col_name)?);
+        }
+        let row_key = |columns: &[Column], position: usize| -> Vec<Value> {
+            columns
+                .iter()
+                .map(|col| col.get(position).clone())
+                .collect()
+        };
+        let other_keys: HashSet<Vec<Value>> = (0..other.rows_count())
+            .map(|position| row_key(&columns_other, position))
+            .collect();
+        let retained_positions: Vec<usize> = (0..self.rows_count())
+            .filter(|&position| other_keys.contains(&row_key(&columns_self, position)) == present)
+            .collect();
+        Ok(self.remap(&retained_positions))
+    }
+
+    pub fn map_column(&self, col_name: &str, map: impl Fn(&str) -> String) -> Res<Table> {
+        let mut table = self.clone();
+        table.map_column_in_place(col_name, map)?;
+        Ok(table)
+    }
+
+    pub fn map_column_in_place(&mut self, col_name: &str, map: impl Fn(&str) -> String) -> Res<()> {
+        let column = self.column_ref(col_name)?;
+        let mapped_cells: Vec<Value> = column.iter().map(|value| Value::new(map(value))).collect();
+        self.put_column(col_name, Column::new(mapped_cells))
+    }
+
+    // same as map_column, with the parallel feature the rows are split across threads
+    pub fn map_column_par(
+        &self,
+        col_name: &str,
+        map: impl Fn(&str) -> String + Send + Sync,
+    ) -> Res<Table> {
+        let mut table = self.clone();
+        table.map_column_in_place_par(col_name, map)?;
+        Ok(table)
+    }
+
+    pub fn map_column_in_place_par(
+        &mut self,
+        col_name: &str,
+        map: impl Fn(&str) -> String + Send + Sync,
+    ) -> Res<()> {
+        let column = self.column_ref(col_name)?;
+        let mapped_cells = parallel::chunks(column.len(), |rows| {
+            rows.map(|row| Value::new(map(column.get(row))))
+                .collect::<Vec<Value>>()
+        })
+        .concat();
+        self.put_column(col_name, Column::new(mapped_cells))
+    }
+
+    // adds or replaces a column, a replaced key column comes back if the key breaks
+    fn put_column(&mut self, col_name: &str, column: Column) -> Res<()> {
+        let previous = self.columns.insert(Value::from(col_name), column);
+        if self.in_primary_key(col_name) {
+            if let Err(err) = self.check_primary_key() {
+                self.columns
+                    .insert(Value::from(col_name), previous.unwrap());
+                return Err(err);
+            }
+        }
+        self.forget_stale_indexes();
+        Ok(())
+    }
+
+    pub fn dinstinct_column(&self, col_name: &str) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let mut found: HashSet<&str> = HashSet::with_capacity(self.rows_count());
+        let positions: Vec<usize> = col
+            .iter()
+            .enumerate()
+            .filter_map(|(position, value)| {
+                if found.contains(value.as_str()) {
+                    None
+                } else {
+                    found.insert(value.as_str());
+                    Some(position)
+                }
+            })
+            .collect();
+        Ok(self.remap(&positions))
+    }
+
+    pub fn sort_column(&self, col_name: &str) -> Res<Table> {
+        let col = self.column(col_name)?;
+        // the sort is stable, an already sorted column gives back the same rows
+        if col.is_sorted() {
+            return Ok(self.clone());
+        }
+        let new_order = match (col.sorted_positions(), col.typed()) {
+            (Some(positions), _) => positions,
+            (None, Some(typed)) => {
+                let mut new_order: Vec<usize> = (0..col.len()).collect();
+                new_order.sort_by(|&a, &b| typed.compare(a, b));
+                new_order
+            }
+            (None, None) => {
+                let mut values_with_pos = col.iter().enumerate().collect::<Vec<_>>();
+                values_with_pos.sort_by_key(|(_, value)| *value);
+                values_with_pos.into_iter().map(|(pos, _)| pos).collect()
+            }
+        };
+        let table = self.remap(&new_order);
+        table.column_ref(col_name)?.mark_sorted();
+        Ok(table)
+    }
+
+    // sort_column order, numeric for typed columns, bounds included
+    pub fn filter_between(&self, col_name: &str, low: &str, high: &str) -> Res<Table> {
+        let mut positions = self.lookup_range(col_name, low, high)?;
+        if positions.len() == self.rows_count() {
+            return Ok(self.clone());
+        }
+        positions.sort_unstable();
+        Ok(self.remap(&positions))
+    }
+
+    // rows in sort_column order, the sorted index is built on the first call
+    pub fn lookup_range(&self, col_name: &str, low: &str, high: &str) -> Res<Vec<usize>> {
+        self.column_ref(col_name)?.range(low, high)
+    }
+
+    pub fn create_sorted_index(&self, col_name: &str) -> Res<()> {
+        self.column_ref(col_name)?.create_sorted_index();
+        Ok(())
+    }
+
+    pub fn sort_column_desc(&self, col_name: &str) -> Res<Table> {
+        let col = self.column(col_name)?;
+        if let Some(typed) = col.typed() {
+            let mut new_order: Vec<usize> = (0..col.len()).collect();
+            new_order.sort_by(|&a, &b| typed.compare(b, a));
+            return Ok(self.remap(&new_order));
+        }
+        let mut values_with_pos = col.iter().enumerate().collect::<Vec<_>>();
+        values_with_pos.sort_by(|(_, v1), (_, v2)| v2.cmp(v1));
+        let new_order: Vec<usize> = values_with_pos.into_iter().map(|(pos, _)| pos).collect();
+        Ok(self.remap(&new_order))
+    }
+
+    // nulls sort first, as they do in typed columns
+    pub fn sort_column_numeric(&self, col_name: &str) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let mut values_with_pos = Vec::with_capacity(col.len());
+        for (position, value) in col.iter().enumerate() {
+            if value.is_empty() {
+                values_with_pos.push((position, None));
+                continue;
+            }
+            let number = value.parse::<f64>().map_err(|_| {
+                messages::text(
+                    Message::NotNumericAt,
+                    &[&value.as_str(), &position, &col_name],
+                )
+            })?;
+            values_with_pos.push((position, Some(number)));
+        }
+        values_with_pos.sort_by(|(_, n1), (_, n2)| match (n1, n2) {
+            (Some(n1), Some(n2)) => n1.total_cmp(n2),
+            (n1, n2) => n1.is_some().cmp(&n2.is_some()),
+        });
+        let new_order: Vec<usize> = values_with_pos.into_iter().map(|(pos, _)| pos).collect();
+        Ok(self.remap(&new_order))
+    }
+
+    pub fn sort_column_date(&self, col_name: &str, format: &str) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let mut values_with_pos = Vec::with_capacity(col.len());
+        for (position, value) in col.iter().enumerate() {
+            let days = dates::parse_date(value, format).ok_or_else(|| {
+                messages::text(
+                    Message::NotDateInFormatAt,
+                    &[&value.as_str(), &position, &col_name, &format],
+                )
+            })?;
+            values_with_pos.push((position, days));
+        }
+        values_with_pos.sort_by_key(|&(_, days)| days);
+        let new_order: Vec<usize> = values_with_pos.into_iter().map(|(pos, _)| pos).collect();
+        Ok(self.remap(&new_order))
+    }
+
+    pub fn sort_column_natural(&self, col_name: &str) -> Res<Table> {
+        self.sort_column_by(col_name, order::natural)
+    }
+
+    pub fn reverse_rows(&self) -> Table {
+        let new_order: Vec<usize> = (0..self.rows_count()).rev().collect();
+        self.remap(&new_order)
+    }
+
+    pub fn sort_column_by(
+        &self,
+        col_name: &str,
+        order: impl Fn(&str, &str) -> std::cmp::Ordering,
+    ) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let mut values_with_pos = col.iter().enumerate().collect::<Vec<_>>();
+        values_with_pos.sort_by(|(_, v1), (_, v2)| order(v1, v2));
+        let new_order: Vec<usize> = values_with_pos.into_iter().map(|(pos, _)| pos).collect();
+        Ok(self.remap(&new_order))
+    }
+
+    pub fn concatenate(&self, other: &Table) -> Res<Table> {
+        let mut columns = ColumnMap::with_capacity(self.columns_count());
+        for (col_name, col) in self.columns.iter() {
+            let other_col = other
+                .column(col_name)
+                .map_err(|_| messages::text(Message::ConcatColumnMissing, &[&col_name.as_str()]))?;
+            columns.insert(col_name.clone(), col.concat(&other_col));
+        }
+        let mut table = self.derived(columns);
+        table.primary_key = self.primary_key.clone();
+        table.check_primary_key()?;
+        Ok(table)
+    }
+
+    // rows of other replace the rows of self with the same primary key, new keys are appended
+    pub fn upsert(&self, other: &Table) -> Res<Table> {
+        if self.primary_key.is_empty() {
+            return Err(messages::text(Message::NoPrimaryKey, &[]));
+        }
+        let mut key_columns_self = Vec::with_capacity(self.primary_key.len());
+        let mut key_columns_other = Vec::with_capacity(self.primary_key.len());
+        for col_name in self.primary_key.iter() {
+            key_columns_self.push(self.column_ref(col_name)?);
+            key_columns_other.push(other.column_ref(col_name)?);
+        }
+        let rows_self = self.rows_count();
+        let positions_self: HashMap<Vec<&str>, usize> = (0..rows_self)
+            .map(|position| (row_key(&key_columns_self, position), position))
+            .collect();
+        // positions in self followed by other, a later row of other wins over an earlier one
+        let mut positions: Vec<usize> = (0..rows_self).collect();
+        for position in 0..other.rows_count() {
+            match positions_self.get(&row_key(&key_columns_other, position)) {
+                Some(&replaced) => positions[replaced] = rows_self + position,
+                None => positions.push(rows_self + position),
+            }
+        }
+        let mut combined = self.without_primary_key().concatenate(other)?;
+        combined.primary_key = self.primary_key.clone();
+        let table = combined.remap(&positions);
+        table.check_primary_key()?;
+        Ok(table)
+    }
+
+    pub fn create_fixed_column(&self, col_name: &str, fixed_value: &str) -> Res<Table> {
+        let value = Value::new(fixed_value.to_string());
+        let cells: Vec<Value> = (0..self.rows_count()).map(|_| value.clone()).collect();
+
+        let mut clone = self.clone();
+        clone.put_column(col_name, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn create_fixed_column_in_place(&mut self, col_name: &str, fixed_value: &str) -> Res<()> {
+        let value = Value::new(fixed_value.to_string());
+        let cells: Vec<Value> = (0..self.rows_count()).map(|_| value.clone()).collect();
+        self.put_column(col_name, Column::new(cells))
+    }
+
+    pub fn create_column(&self, expr: MiOp) -> Res<Table> {
+        let mut clone = self.clone();
+        clone.create_column_in_place(expr)?;
+        Ok(clone)
+    }
+
+    pub fn create_column_in_place(&mut self, expr: MiOp) -> Res<()> {
+        let column = self.compute_column(&expr)?;
+        self.put_column(&expr.out_column, column)?;
+        self.record_lineage(&expr);
+        Ok(())
+    }
+
+    pub fn apply_derivations(&self, derivations: &Derivations) -> Res<Table> {
+        let mut table = self.clone();
+        for step in &derivations.steps {
+            let column = table.compute_column(step)?;
+            table.record_lineage(step);
+            table.put_column(&step.out_column, column)?;
+        }
+        Ok(table)
+    }
+
+    pub fn refresh_derivations(&self, previous: &Table, derivations: &Derivations) -> Res<Table> {
+        let outputs: HashSet<&str> = derivations
+            .steps
+            .iter()
+            .map(|step| step.out_column.as_str())
+            .collect();
+        let same_rows = previous.rows_count() == self.rows_count();
+        let mut dirty = HashSet::new();
+        let mut table = self.clone();
+        for step in &derivations.steps {
+            let mut stale = !same_rows;
+            for cname in &step.in_columns {
+                let unchanged = if outputs.contains(cname.as_str()) {
+                    !dirty.contains(cname.as_str())
+                } else {
+                    let current = self.column(cname)?;
+                    previous
+                        .columns
+                        .get(cname.as_str())
+                        .is_some_and(|old| old.same_cells(&current))
+                };
+                stale |= !unchanged;
+            }
+            let kept = previous.columns.get(step.out_column.as_str());
+            let column = match kept {
+                Some(column) if !stale => column.clone(),
+                _ => {
+                    dirty.insert(step.out_column.as_str());
+                    table.compute_column(step)?
+                }
+            };
+            table.record_lineage(step);
+            table.put_column(&step.out_column, column)?;
+        }
+        Ok(table)
+    }
+
+    fn compute_column(&self, expr: &MiOp) -> Res<Column> {
+        let mut inputs_cols = Vec::with_capacity(expr.in_columns.len());
+        for cname in expr.in_columns.iter() {
+            let col = self.column(cname.as_ref())?;
+            inputs_cols.push(col);
+        }
+        let cell = |function: &dyn Fn(&[&str]) -> String, position: usize| {
+            let args: Vec<&str> = inputs_cols
+                .iter()
+                .map(|col| col.get(position).as_str())
+                .collect();
+            Value::new(function(args.as_slice()))
+        };
+        let col_rows: Vec<Value> = match &expr.operation {
+            RowFunction::Local(function) => (0..self.rows_count())
+                .map(|position| cell(function, position))
+                .collect(),
+            RowFunction::Shared(function) => parallel::chunks(self.rows_count(), |rows| {
+                rows.map(|position| cell(function, position))
+                    .collect::<Vec<Value>>()
+            })
+            .concat(),
+        };
+        Ok(Column::new(col_rows))
+    }
+
+    pub fn parse_date_column(&self, col_name: &str, format: &str) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let mut cells = Vec::with_capacity(col.len());
+        for (position, value) in col.iter().enumerate() {
+            if value.is_null() {
+                cells.push(value.clone());
+                continue;
+            }
+            let days = dates::parse_date(value, format).ok_or_else(|| {
+                messages::text(
+                    Message::NotDateInFormatAt,
+                    &[&value.as_str(), &position, &col_name, &format],
+                )
+            })?;
+            cells.push(Value::new(dates::format_iso_date(days)));
+        }
+        let column = Column::new(cells).cast(ColumnType::Date)?;
+        let mut clone = self.clone();
+        clone.put_column(col_name, column)?;
+        Ok(clone)
+    }
+
+    pub fn format_date_column(&self, col_name: &str, format: &str) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let mut cells = Vec::with_capacity(col.len());
+        for (position, value) in col.iter().enumerate() {
+            if value.is_null() {
+                cells.push(value.clone());
+                continue;
+            }
+            let days = dates::parse_iso_date(value).ok_or_else(|| {
+                messages::text(Message::NotDateAt, &[&value.as_str(), &position, &col_name])
+            })?;
+            cells.push(Value::new(dates::format_date(days, format)));
+        }
+        let mut clone = self.clone();
+        clone.put_column(col_name, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn convert_timezone(&self, col_name: &str, offset: &str) -> Res<Table> {
+        let offset_seconds = dates::parse_offset(offset)
+            .ok_or_else(|| messages::text(Message::InvalidTimezone, &[&offset]))?;
+        self.map_datetimes(col_name, col_name, |datetime| {
+            datetime.with_offset(offset_seconds)
+        })
+    }
+
+    pub fn truncate_datetime(&self, col_name: &str, unit: TimeUnit, new_col: &str) -> Res<Table> {
+        self.map_datetimes(col_name, new_col, |datetime| datetime.truncate(unit))
+    }
+
+    fn map_datetimes(
+        &self,
+        col_name: &str,
+        new_col: &str,
+        map: impl Fn(DateTime) -> DateTime,
+    ) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let mut cells = Vec::with_capacity(col.len());
+        for (position, value) in col.iter().enumerate() {
+            if value.is_null() {
+                cells.push(value.clone());
+                continue;
+            }
+            let datetime = DateTime::parse(value).ok_or_else(|| {
+                messages::text(
+                    Message::NotDateTimeAt,
+                    &[&value.as_str(), &position, &col_name],
+                )
+            })?;
+            cells.push(Value::new(map(datetime).format()));
+        }
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn extract_part(&self, col_name: &str, part: Part, new_col: &str) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let cells: Vec<Value> = col.iter().map(|v| Value::new(part.extract(v))).collect();
+
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    #[cfg(feature = "json")]
+    pub fn extract_json(&self, col_name: &str, path: &str, new_col: &str) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let steps = json::parse_path(path)?;
+        let mut cells: Vec<Value> = Vec::with_capacity(col.len());
+        for (position, cell) in col.iter().enumerate() {
+            let extracted = if cell.is_empty() {
+                String::new()
+            } else {
+                let document = json::parse(cell).map_err(|err| {
+                    messages::text(Message::AtRowOfColumn, &[&position, &col_name, &err])
+                })?;
+                json::get(&document, &steps).map_or(String::new(), json::to_cell)
+            };
+            cells.push(Value::new(extracted));
+        }
+
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn extract_regex(&self, col_name: &str, pattern: &str, out_cols: &[&str]) -> Res<Table> {
+        let regex = Regex::new(pattern)?;
+        if regex.groups() != out_cols.len() {
+            return Err(messages::text(
+                Message::RegexGroupsMismatch,
+                &[&regex.groups(), &out_cols.len()],
+            ));
+        }
+        let column = self.column(col_name)?;
+        let mut cells: Vec<Vec<Value>> =
+            vec![Vec::with_capacity(self.rows_count()); out_cols.len()];
+        for value in column.iter() {
+            let captures = regex.captures(value);
+            for (group, group_cells) in cells.iter_mut().enumerate() {
+                let captured = captures
+                    .as_ref()
+                    .and_then(|captures| captures[group + 1])
+                    .unwrap_or_default();
+                group_cells.push(Value::from(captured));
+            }
+        }
+
+        let mut clone = self.clone();
+        for (out_col, out_cells) in out_cols.iter().zip(cells) {
+            clone.put_column(out_col, Column::new(out_cells))?;
+        }
+        Ok(clone)
+    }
+
+    pub fn parse_kv(&self, col_name: &str, pair_separator: char, kv_separator: char) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let rows = col.len();
+        let mut new_columns: HashMap<&str, Vec<Value>> = HashMap::new();
+        // new columns follow the order in which their keys first appear
+        let mut keys: Vec<&str> = Vec::new();
+        let empty = Value::from("");
+        for (position, cell) in col.iter().enumerate() {
+            for pair in cell.split(pair_separator) {
+                if pair.trim().is_empty() {
+                    continue;
+                }
+                let mut kv = pair.splitn(2, kv_separator);
+                let key = kv.next().unwrap_or("").trim();
+                let value = kv.next().unwrap_or("").trim();
+                let cells = new_columns.entry(key).or_insert_with(|| {
+                    keys.push(key);
+                    vec![empty.clone(); rows]
+                });
+                cells[position] = Value::from(value);
+            }
+        }
+
+        let mut clone = self.clone();
+        for key in keys {
+            let cells = new_columns.remove(key).unwrap();
+            if clone.columns.contains_key(key) {
+                return Err(messages::text(Message::ColumnExists, &[&key]));
+            }
+            clone.put_column(key, Column::new(cells))?;
+        }
+        Ok(clone)
+    }
+
+    // null cells stay null, every other cell gets its own random nonce
+    #[cfg(feature = "encryption")]
+    pub fn encrypt_column(&self, col_name: &str, key: &[u8]) -> Res<Table> {
+        let cipher = Cipher::new(key)?;
+        self.map_column(col_name, |cell| {
+            if cell.is_empty() {
+                String::new()
+            } else {
+                cipher.encrypt(cell)
+            }
+        })
+    }
+
+    #[cfg(feature = "encryption")]
+    pub fn decrypt_column(&self, col_name: &str, key: &[u8]) -> Res<Table> {
+        let cipher = Cipher::new(key)?;
+        let col = self.column(col_name)?;
+        let mut cells: Vec<Value> = Vec::with_capacity(col.len());
+        for (position, cell) in col.iter().enumerate() {
+            let decrypted = if cell.is_empty() {
+                String::new()
+            } else {
+                cipher.decrypt(cell).map_err(|err| {
+                    messages::text(Message::AtRowOfColumn, &[&position, &col_name, &err])
+                })?
+            };
+            cells.push(Value::new(decrypted));
+        }
+        let mut clone = self.clone();
+        clone.put_column(col_name, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn add_checksum_column(
+        &self,
+        col_names: &[&str],
+        new_col: &str,
+        checksum: Checksum,
+    ) -> Res<Table> {
+        let mut cols = Vec::with_capacity(col_names.len());
+        for &col_name in col_names {
+            cols.push(self.column(col_name)?);
+        }
+        let mut buffer: Vec<u8> = Vec::new();
+        let cells: Vec<Value> = (0..self.rows_count())
+            .map(|position| {
+                // length prefixes keep ("ab", "c") and ("a", "bc") distinct
+                buffer.clear();
+                for col in cols.iter() {
+                    let cell = col.get(position).as_bytes();
+                    buffer.extend_from_slice(&(cell.len() as u64).to_le_bytes());
+                    buffer.extend_from_slice(cell);
+                }
+                Value::new(checksum.hex_digest(&buffer))
+            })
+            .collect();
+
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn similarity_column(
+        &self,
+        col_name_a: &str,
+        col_name_b: &str,
+        metric: Metric,
+        new_col: &str,
+    ) -> Res<Table> {
+        let col_a = self.column(col_name_a)?;
+        let col_b = self.column(col_name_b)?;
+        let cells: Vec<Value> = col_a
+            .iter()
+            .zip(col_b.iter())
+            .map(|(a, b)| Value::new(format!("{:.4}", metric.score(a, b))))
+            .collect();
+
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    // rows are compared on the normalized cells of col_names, joined together
+    pub fn near_duplicates(&self, col_names: &[&str], threshold: f64, new_col: &str) -> Res<Table> {
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(messages::text(Message::ThresholdOutOfRange, &[&threshold]));
+        }
+        let mut columns = Vec::with_capacity(col_names.len());
+        for &col_name in col_names {
+            columns.push(self.column_ref(col_name)?);
+        }
+        let rows: Vec<String> = (0..self.rows_count())
+            .map(|position| {
+                let cells: Vec<String> = columns
+                    .iter()
+                    .map(|col| similarity::normalize(col.get(position)))
+                    .collect();
+                cells.join(" ")
+            })
+            .collect();
+        let cells: Vec<Value> = similarity::trigram_clusters(&rows, threshold)
+            .into_iter()
+            .map(|id| Value::new(id.to_string()))
+            .collect();
+
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::new(cells))?;
+        Ok(clone)
     }
 
     pub fn concatenate_columns(
         &self,
-        col_1: &str,
-        separator: char,
-        col_2: &str,
-        new_col: &str,
+        col_1: &str,
+        separator: char,
+        col_2: &str,
+        new_col: &str,
+    ) -> Res<Table> {
+        let col_1 = self.column(col_1)?;
+        let col_2 = self.column(col_2)?;
+        let cells: Vec<Value> = col_1
+            .iter()
+            .zip(col_2.iter())
+            .map(|(a, b)| {
+                let mut result = String::with_capacity(a.len() + 1 + b.len());
+                result += a;
+                result.push(separator);
+                result += b;
+                Value::new(result)
+            })
+            .collect();
+
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn join_on_columns(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        col_name_other: &str,
+    ) -> Res<Table> {
+        // same-named columns are merged as they always were, the column of other wins
+        self.join_on_columns_with(
+            col_name_self,
+            other,
+            col_name_other,
+            JoinCollision::KeepRight,
+        )
+    }
+
+    pub fn join_on_columns_with(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        col_name_other: &str,
+        on_collision: JoinCollision,
+    ) -> Res<Table> {
+        let (positions_self, positions_other) =
+            self.join_positions(col_name_self, other, col_name_other)?;
+        self.remap(&positions_self).merge_join_columns(
+            other,
+            &positions_other,
+            shared_key(col_name_self, col_name_other).as_slice(),
+            &on_collision,
+        )
+    }
+
+    pub fn join_on_primary_key(&self, other: &Table) -> Res<Table> {
+        self.join_on_primary_key_with(other, JoinCollision::Error)
+    }
+
+    // joins on the key columns of self, which other has to contain with the same names
+    pub fn join_on_primary_key_with(
+        &self,
+        other: &Table,
+        on_collision: JoinCollision,
+    ) -> Res<Table> {
+        if self.primary_key.is_empty() {
+            return Err(messages::text(Message::NoPrimaryKey, &[]));
+        }
+        let key_names = self.primary_key();
+        self.join_on_columns_multi_with(&key_names, other, &key_names, on_collision)
+    }
+
+    pub fn join_on_columns_multi(
+        &self,
+        col_names_self: &[&str],
+        other: &Table,
+        col_names_other: &[&str],
+    ) -> Res<Table> {
+        self.join_on_columns_multi_with(
+            col_names_self,
+            other,
+            col_names_other,
+            JoinCollision::Error,
+        )
+    }
+
+    // rows match when all key columns are equal, keys with a null never match
+    pub fn join_on_columns_multi_with(
+        &self,
+        col_names_self: &[&str],
+        other: &Table,
+        col_names_other: &[&str],
+        on_collision: JoinCollision,
+    ) -> Res<Table> {
+        if col_names_self.len() != col_names_other.len() {
+            return Err(messages::text(
+                Message::KeyColumnsCount,
+                &[&col_names_self.len(), &col_names_other.len()],
+            ));
+        }
+        let key_columns = |table: &Table, col_names: &[&str]| -> Res<Vec<(Value, Column)>> {
+            col_names
+                .iter()
+                .map(|&col_name| Ok((Value::from(col_name), table.column(col_name)?)))
+                .collect()
+        };
+        let key_columns_self = key_columns(self, col_names_self)?;
+        let key_columns_other = key_columns(other, col_names_other)?;
+        // an existing composite index is probed, otherwise one is built on other for this join
+        let (positions_self, positions_other) = match (
+            self.composite_index(col_names_self),
+            other.composite_index(col_names_other),
+        ) {
+            (Some(index_self), None) => {
+                // probing with other finds the pairs in other order, they are put back in self
+                // order so the result does not depend on which side has an index
+                let (positions_self, positions_other) =
+                    probe_composite(index_self, &key_columns_other);
+                let mut pairs: Vec<(usize, usize)> =
+                    positions_self.into_iter().zip(positions_other).collect();
+                pairs.sort_unstable();
+                pairs.into_iter().unzip()
+            }
+            (_, Some(index_other)) => {
+                let (positions_other, positions_self) =
+                    probe_composite(index_other, &key_columns_self);
+                (positions_self, positions_other)
+            }
+            (None, None) => {
+                let index_other = CompositeIndex::build(key_columns_other);
+                let (positions_other, positions_self) =
+                    probe_composite(&index_other, &key_columns_self);
+                (positions_self, positions_other)
+            }
+        };
+        let shared_keys: Vec<&str> = col_names_self
+            .iter()
+            .zip(col_names_other)
+            .filter(|(a, b)| a == b)
+            .map(|(a, _)| *a)
+            .collect();
+        self.remap(&positions_self).merge_join_columns(
+            other,
+            &positions_other,
+            &shared_keys,
+            &on_collision,
+        )
+    }
+
+    pub fn join_broadcast(
+        &self,
+        col_name_self: &str,
+        small: &Table,
+        col_name_small: &str,
+        on_collision: JoinCollision,
+    ) -> Res<Table> {
+        let column_self = self.column(col_name_self)?;
+        let column_small = small.column(col_name_small)?;
+        // the index is cached on the small column and reused by later joins
+        let (positions_small, positions_self) = probe_index(&column_small, &column_self);
+        self.remap(&positions_self).merge_join_columns(
+            small,
+            &positions_small,
+            shared_key(col_name_self, col_name_small).as_slice(),
+            &on_collision,
+        )
+    }
+
+    pub fn join_sorted_on_columns(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        col_name_other: &str,
+    ) -> Res<Table> {
+        self.join_sorted_on_columns_with(col_name_self, other, col_name_other, JoinCollision::Error)
+    }
+
+    pub fn join_sorted_on_columns_with(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        col_name_other: &str,
+        on_collision: JoinCollision,
+    ) -> Res<Table> {
+        let column_self = self.column(col_name_self)?;
+        let column_other = other.column(col_name_other)?;
+        for (col_name, column) in [
+            (col_name_self, &column_self),
+            (col_name_other, &column_other),
+        ] {
+            if column.iter().zip(column.iter().skip(1)).any(|(a, b)| a > b) {
+                return Err(messages::text(Message::ColumnNotSorted, &[&col_name]));
+            }
+        }
+        // the end of the run of cells equal to the one at start
+        let run_end = |column: &Column, start: usize| {
+            (start..column.len())
+                .find(|&position| column.get(position) != column.get(start))
+                .unwrap_or(column.len())
+        };
+
+        // merge both sides in order, matching runs of equal keys
+        let mut positions_self = Vec::new();
+        let mut positions_other = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < column_self.len() && j < column_other.len() {
+            match column_self.get(i).cmp(column_other.get(j)) {
+                Ordering::Less => i += 1,
+                Ordering::Greater => j += 1,
+                Ordering::Equal => {
+                    let run_end_self = run_end(&column_self, i);
+                    let run_end_other = run_end(&column_other, j);
+                    // null keys never match, not even each other
+                    if !column_self.get(i).is_null() {
+                        for position_self in i..run_end_self {
+                            for position_other in j..run_end_other {
+                                positions_self.push(position_self);
+                                positions_other.push(position_other);
+                            }
+                        }
+                    }
+                    i = run_end_self;
+                    j = run_end_other;
+                }
+            }
+        }
+        self.remap(&positions_self).merge_join_columns(
+            other,
+            &positions_other,
+            shared_key(col_name_self, col_name_other).as_slice(),
+            &on_collision,
+        )
+    }
+
+    pub fn asof_join_on_columns(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        col_name_other: &str,
+        on_collision: JoinCollision,
+    ) -> Res<Table> {
+        self.asof_join_on_columns_by(
+            col_name_self,
+            other,
+            col_name_other,
+            on_collision,
+            |a, b| a.cmp(b),
+        )
+    }
+
+    pub fn asof_join_on_columns_by(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        col_name_other: &str,
+        on_collision: JoinCollision,
+        order: impl Fn(&str, &str) -> Ordering,
+    ) -> Res<Table> {
+        let column_self = self.column(col_name_self)?;
+        let column_other = other.column(col_name_other)?;
+        // null keys never match, rows of self with one are dropped as in the other joins
+        let mut order_self: Vec<usize> = (0..column_self.len())
+            .filter(|&position| !column_self.get(position).is_null())
+            .collect();
+        order_self.sort_by(|&a, &b| order(column_self.get(a), column_self.get(b)));
+        let mut order_other: Vec<usize> = (0..column_other.len())
+            .filter(|&position| !column_other.get(position).is_null())
+            .collect();
+        order_other.sort_by(|&a, &b| order(column_other.get(a), column_other.get(b)));
+
+        // for each key of self, the last other row whose key is not greater
+        let mut matches: Vec<Option<usize>> = vec![None; column_self.len()];
+        let mut next_other = 0;
+        let mut last_match = None;
+        for position_self in order_self {
+            while next_other < order_other.len()
+                && order(
+                    column_other.get(order_other[next_other]),
+                    column_self.get(position_self),
+                ) != Ordering::Greater
+            {
+                last_match = Some(order_other[next_other]);
+                next_other += 1;
+            }
+            matches[position_self] = last_match;
+        }
+
+        let (positions_self, positions_other): (Vec<usize>, Vec<usize>) = matches
+            .into_iter()
+            .enumerate()
+            .filter_map(|(position_self, m)| {
+                m.map(|position_other| (position_self, position_other))
+            })
+            .unzip();
+        self.remap(&positions_self)
+            .merge_join_columns(other, &positions_other, &[], &on_collision)
+    }
+
+    pub fn interval_join_on_columns(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        start_col_name: &str,
+        end_col_name: &str,
+        on_collision: JoinCollision,
+    ) -> Res<Table> {
+        self.interval_join_on_columns_by(
+            col_name_self,
+            other,
+            start_col_name,
+            end_col_name,
+            on_collision,
+            |a, b| a.cmp(b),
+        )
+    }
+
+    pub fn interval_join_on_columns_by(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        start_col_name: &str,
+        end_col_name: &str,
+        on_collision: JoinCollision,
+        order: impl Fn(&str, &str) -> Ordering,
+    ) -> Res<Table> {
+        let column_self = self.column(col_name_self)?;
+        let start_column = other.column(start_col_name)?;
+        let end_column = other.column(end_col_name)?;
+
+        // intervals sorted by start, with the running maximum of the ends; an interval with a
+        // null bound and a null key never match
+        let mut by_start: Vec<usize> = (0..start_column.len())
+            .filter(|&position| {
+                !start_column.get(position).is_null() && !end_column.get(position).is_null()
+            })
+            .collect();
+        by_start.sort_by(|&a, &b| order(start_column.get(a), start_column.get(b)));
+        let mut max_ends: Vec<&str> = Vec::with_capacity(by_start.len());
+        for &position in by_start.iter() {
+            let end = end_column.get(position).as_str();
+            let max_end = match max_ends.last() {
+                Some(&max_end) if order(max_end, end) == Ordering::Greater => max_end,
+                _ => end,
+            };
+            max_ends.push(max_end);
+        }
+
+        let mut positions_self = Vec::new();
+        let mut positions_other = Vec::new();
+        let mut matched = Vec::new();
+        for (position_self, key) in column_self.iter().enumerate() {
+            if key.is_null() {
+                continue;
+            }
+            let started =
+                by_start.partition_point(|&p| order(start_column.get(p), key) != Ordering::Greater);
+            for i in (0..started).rev() {
+                if order(max_ends[i], key) == Ordering::Less {
+                    break;
+                }
+                if order(end_column.get(by_start[i]), key) != Ordering::Less {
+                    matched.push(by_start[i]);
+                }
+            }
+            for &position_other in matched.iter().rev() {
+                positions_self.push(position_self);
+                positions_other.push(position_other);
+            }
+            matched.clear();
+        }
+        self.remap(&positions_self)
+            .merge_join_columns(other, &positions_other, &[], &on_collision)
+    }
+
+    pub fn fuzzy_join_on_columns(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        col_name_other: &str,
+        matching: FuzzyMatch,
+        score_col: &str,
+        on_collision: JoinCollision,
+    ) -> Res<Table> {
+        let column_self = self.column(col_name_self)?;
+        let column_other = other.column(col_name_other)?;
+
+        let mut positions_self = Vec::new();
+        let mut positions_other = Vec::new();
+        let mut scores = Vec::new();
+        match matching {
+            FuzzyMatch::MinScore(Metric::Trigram, min_score) => {
+                // inverted index from trigram to the other rows containing it
+                let mut other_trigrams: Vec<usize> = Vec::with_capacity(column_other.len());
+                let mut trigram_index: HashMap<[char; 3], Vec<usize>> = HashMap::new();
+                for (position, value) in column_other.iter().enumerate() {
+                    let trigrams = similarity::trigrams(value);
+                    other_trigrams.push(trigrams.len());
+                    for trigram in trigrams {
+                        trigram_index.entry(trigram).or_default().push(position);
+                    }
+                }
+                let mut shared: HashMap<usize, usize> = HashMap::new();
+                for (position_self, value) in column_self.iter().enumerate() {
+                    let trigrams = similarity::trigrams(value);
+                    for trigram in trigrams.iter() {
+                        for &position_other in trigram_index.get(trigram).into_iter().flatten() {
+                            *shared.entry(position_other).or_insert(0) += 1;
+                        }
+                    }
+                    let mut candidates: Vec<(usize, usize)> = shared.drain().collect();
+                    candidates.sort_unstable();
+                    for (position_other, count) in candidates {
+                        let score = similarity::trigram_score(
+                            trigrams.len(),
+                            other_trigrams[position_other],
+                            count,
+                        );
+                        if score >= min_score {
+                            positions_self.push(position_self);
+                            positions_other.push(position_other);
+                            scores.push(score);
+                        }
+                    }
+                }
+            }
+            FuzzyMatch::MinScore(Metric::Levenshtein, min_score) => {
+                for (position_self, a) in column_self.iter().enumerate() {
+                    for (position_other, b) in column_other.iter().enumerate() {
+                        let score = Metric::Levenshtein.score(a, b);
+                        if score >= min_score {
+                            positions_self.push(position_self);
+                            positions_other.push(position_other);
+                            scores.push(score);
+                        }
+                    }
+                }
+            }
+            FuzzyMatch::MaxEditDistance(max_distance) => {
+                for (position_self, a) in column_self.iter().enumerate() {
+                    for (position_other, b) in column_other.iter().enumerate() {
+                        if similarity::levenshtein_within(a, b, max_distance).is_some() {
+                            positions_self.push(position_self);
+                            positions_other.push(position_other);
+                            scores.push(Metric::Levenshtein.score(a, b));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut joined = self.remap(&positions_self).merge_join_columns(
+            other,
+            &positions_other,
+            &[],
+            &on_collision,
+        )?;
+        if joined.columns.contains_key(score_col) {
+            return Err(messages::text(Message::ColumnExistsInJoin, &[&score_col]));
+        }
+        let score_cells = scores
+            .into_iter()
+            .map(|score| Value::new(format!("{:.4}", score)))
+            .collect();
+        joined.put_column(score_col, Column::new(score_cells))?;
+        Ok(joined)
+    }
+
+    pub fn estimate_join_rows(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        col_name_other: &str,
+    ) -> Res<usize> {
+        let column_self = self.column(col_name_self)?;
+        let column_other = other.column(col_name_other)?;
+        let index_self = column_self.get_index();
+        let index_other = column_other.get_index();
+        let (smaller, larger) = if index_self.len() <= index_other.len() {
+            (index_self, index_other)
+        } else {
+            (index_other, index_self)
+        };
+        Ok(smaller.iter().fold(0usize, |rows, (key, positions)| {
+            if key.is_null() {
+                return rows;
+            }
+            let matches = larger.get(key).map_or(0, Vec::len);
+            rows.saturating_add(positions.len().saturating_mul(matches))
+        }))
+    }
+
+    pub fn join_key_stats(
+        &self,
+        col_name_self: &str,
+        other: &Table,
+        col_name_other: &str,
+        top: usize,
     ) -> Res<Table> {
-        let col_1 = self.column(col_1)?;
-        let col_2 = self.column(col_2)?;
-        let cells: Vec<Value> = col_1
-            .cells()
+        let column_self = self.column(col_name_self)?;
+        let column_other = other.column(col_name_other)?;
+        let index_self = column_self.get_index();
+        let index_other = column_other.get_index();
+        let mut keys: Vec<(&Value, usize, usize)> = index_self
             .iter()
-            .zip(col_2.cells().iter())
-            .map(|(a, b)| {
-                let mut result = String::with_capacity(a.len() + 1 + b.len());
-                result += a;
-                result.push(separator);
-                result += b;
-                Value::new(result)
+            .filter(|(key, _)| !key.is_null())
+            .filter_map(|(key, positions_self)| {
+                index_other
+                    .get(key)
+                    .map(|positions_other| (key, positions_self.len(), positions_other.len()))
             })
             .collect();
+        keys.sort_unstable_by(|(k1, s1, o1), (k2, s2, o2)| {
+            s2.saturating_mul(*o2)
+                .cmp(&s1.saturating_mul(*o1))
+                .then_with(|| k1.cmp(k2))
+        });
+        keys.truncate(top);
 
-        let mut clone = self.clone();
-        clone
-            .columns
-            .insert(Value::new(new_col.to_string()), Column::new(cells));
-        Ok(clone)
+        let mut builder = TableBuilder::new(vec![
+            String::from("key"),
+            String::from("rows_self"),
+            String::from("rows_other"),
+            String::from("output_rows"),
+        ]);
+        for (key, rows_self, rows_other) in keys {
+            builder = builder.add_row(vec![
+                key.to_string(),
+                rows_self.to_string(),
+                rows_other.to_string(),
+                rows_self.saturating_mul(rows_other).to_string(),
+            ])?;
+        }
+        Ok(builder.build())
     }
 
-    pub fn join_on_columns(
+    // one row per measure: cell lengths, characters by class and the most frequent shapes
+    pub fn profile_column(&self, col_name: &str) -> Res<Table> {
+        let column = self.column(col_name)?;
+        let mut nulls = 0;
+        let mut lengths: HashMap<usize, usize> = HashMap::new();
+        let mut classes = [0; profile::CHAR_CLASSES.len()];
+        let mut shapes: HashMap<String, usize> = HashMap::new();
+        for value in column.iter() {
+            if value.is_null() {
+                nulls += 1;
+                continue;
+            }
+            *lengths.entry(value.chars().count()).or_insert(0) += 1;
+            for c in value.chars() {
+                classes[profile::char_class(c)] += 1;
+            }
+            *shapes.entry(profile::shape(value)).or_insert(0) += 1;
+        }
+        let mut lengths: Vec<(usize, usize)> = lengths.into_iter().collect();
+        lengths.sort_unstable();
+        let mut shapes: Vec<(String, usize)> = shapes.into_iter().collect();
+        shapes.sort_unstable_by(|(s1, c1), (s2, c2)| c2.cmp(c1).then_with(|| s1.cmp(s2)));
+        shapes.truncate(profile::TOP_SHAPES);
+
+        let mut builder = TableBuilder::new(vec![
+            String::from("measure"),
+            String::from("value"),
+            String::from("count"),
+        ]);
+        let add = |builder: TableBuilder, measure: &str, value: String, count: usize| {
+            builder.add_row(vec![measure.to_string(), value, count.to_string()])
+        };
+        builder = add(builder, "nulls", String::new(), nulls)?;
+        for (length, count) in lengths {
+            builder = add(builder, "length", length.to_string(), count)?;
+        }
+        for (class, count) in profile::CHAR_CLASSES.iter().zip(classes.iter()) {
+            builder = add(builder, "chars", class.to_string(), *count)?;
+        }
+        for (shape, count) in shapes {
+            builder = add(builder, "shape", shape, count)?;
+        }
+        Ok(builder.build())
+    }
+
+    fn join_positions(
         &self,
         col_name_self: &str,
         other: &Table,
         col_name_other: &str,
-    ) -> Res<Table> {
+    ) -> Res<(Vec<usize>, Vec<usize>)> {
         let column_self = self.column(col_name_self)?;
         let column_other = other.column(col_name_other)?;
 
-        if column_self.has_index()
-            || (!column_other.has_index() && column_self.len() <= column_other.len())
-        {
+        if tuner::choose_join(&column_self, &column_other) == JoinStrategy::IndexLeft {
             // join using/building index on self
-            let mut remapped_positions_self: Vec<usize> = Vec::with_capacity(column_self.len());
-            let mut remapped_positions_other: Vec<usize> = Vec::with_capacity(column_self.len());
-            let self_index = column_self.get_index();
-            for (position, other_value) in column_other.cells().iter().enumerate() {
-                if let Some(self_positions_with_other_value) = self_index.get(other_value) {
-                    remapped_positions_self.extend(self_positions_with_other_value);
-                    let additions = self_positions_with_other_value.len();
-                    remapped_positions_other.reserve(additions);
-                    for _ in 0..additions {
-                        remapped_positions_other.push(position);
+            Ok(probe_index(&column_self, &column_other))
+        } else {
+            // join building index on other
+            let (positions_other, positions_self) = probe_index(&column_other, &column_self);
+            Ok((positions_self, positions_other))
+        }
+    }
+
+    fn merge_join_columns(
+        mut self,
+        other: &Table,
+        positions_other: &[usize],
+        shared_keys: &[&str],
+        on_collision: &JoinCollision,
+    ) -> Res<Table> {
+        // a row of self can appear more than once in the join
+        self.primary_key.clear();
+        self.lineage = self.frozen_lineage();
+        self.name = None;
+        // columns of other are remapped only when they end up in the result
+        for (col_name, column) in other.columns.iter() {
+            let col_name = col_name.clone();
+            if !self.columns.contains_key(&col_name) {
+                self.lineage
+                    .insert(col_name.clone(), other.sources(&col_name));
+                self.columns.insert(col_name, column.remap(positions_other));
+                continue;
+            }
+            if shared_keys.contains(&col_name.as_str()) {
+                // same key column on both sides, the values are equal row by row
+                let sources = self.sources(&col_name).into_iter();
+                let sources = lineage::union(sources.chain(other.sources(&col_name)));
+                self.lineage.insert(col_name, sources);
+                continue;
+            }
+            match on_collision {
+                JoinCollision::Error => {
+                    return Err(messages::text(
+                        Message::ColumnInBothTables,
+                        &[&col_name.as_str()],
+                    ))
+                }
+                JoinCollision::KeepLeft => {}
+                JoinCollision::KeepRight => {
+                    self.lineage
+                        .insert(col_name.clone(), other.sources(&col_name));
+                    self.columns.insert(col_name, column.remap(positions_other));
+                }
+                JoinCollision::Suffix(suffix_self, suffix_other) => {
+                    let name_self = Value::new(format!("{}{}", col_name.as_str(), suffix_self));
+                    let name_other = Value::new(format!("{}{}", col_name.as_str(), suffix_other));
+                    for name in [&name_self, &name_other].iter() {
+                        let taken = self.columns.contains_key(name) && **name != col_name;
+                        if taken || name_self == name_other {
+                            return Err(messages::text(
+                                Message::ColumnExistsInJoin,
+                                &[&name.as_str()],
+                            ));
+                        }
                     }
+                    self.columns.rename(&col_name, name_self.clone());
+                    let sources_self = self.lineage.remove(&col_name).unwrap_or_default();
+                    self.lineage.insert(name_self, sources_self);
+                    self.lineage
+                        .insert(name_other.clone(), other.sources(&col_name));
+                    self.columns
+                        .insert(name_other, column.remap(positions_other));
                 }
             }
-            let mut table1 = self.remap(&remapped_positions_self);
-            let table2 = other.remap(&remapped_positions_other);
-            table1.columns.extend(table2.columns);
-            Ok(table1)
-        } else {
-            // join building index on other
-            other.join_on_columns(col_name_other, self, col_name_self)
         }
+        Ok(self)
     }
 
     pub fn group_by_column(&self, col_name: &str, column_operations: &[Op]) -> Res<Table> {
         let group_column = self.column(col_name)?;
-        let mut columns: HashMap<Value, _> = HashMap::with_capacity(self.columns.len());
+        if tuner::choose_group(std::slice::from_ref(&group_column)) == GroupStrategy::Sort {
+            return self.group_by_columns_nulls(&[col_name], column_operations, None);
+        }
         let groups_index = group_column.get_index();
+        let groups: Vec<&[usize]> = groups_index.values().map(|p| p.as_slice()).collect();
+        self.aggregate_groups(groups, column_operations, None)
+    }
+
+    pub fn group_by_columns(&self, col_names: &[&str], column_operations: &[Op]) -> Res<Table> {
+        self.group_by_columns_nulls(col_names, column_operations, None)
+    }
+
+    pub fn group_by_columns_with(
+        &self,
+        col_names: &[&str],
+        column_operations: &[Op],
+        nulls: NullPolicy,
+    ) -> Res<Table> {
+        self.group_by_columns_nulls(col_names, column_operations, Some(nulls))
+    }
+
+    pub(crate) fn group_by_columns_nulls(
+        &self,
+        col_names: &[&str],
+        column_operations: &[Op],
+        nulls: Option<NullPolicy>,
+    ) -> Res<Table> {
+        let mut group_columns = Vec::with_capacity(col_names.len());
+        for &col_name in col_names {
+            group_columns.push(self.column(col_name)?);
+        }
+        let key = |position: usize| -> Vec<&str> {
+            group_columns
+                .iter()
+                .map(|col| col.get(position).as_str())
+                .collect()
+        };
+        if let Some(index) = self.composite_index(col_names) {
+            let groups: Vec<&[usize]> = index.groups().collect();
+            return self.aggregate_groups(groups, column_operations, nulls);
+        }
+        let dictionaries: Option<Vec<(&[u32], &[Value])>> =
+            group_columns.iter().map(Column::dictionary).collect();
+        if let Some(dictionaries) = dictionaries.filter(|d| !d.is_empty()) {
+            let groups = dictionary_groups(&dictionaries);
+            let groups: Vec<&[usize]> = groups.iter().map(|p| p.as_slice()).collect();
+            return self.aggregate_groups(groups, column_operations, nulls);
+        }
+        let groups: Vec<Vec<usize>> = match tuner::choose_group(&group_columns) {
+            // equal keys end up next to each other
+            GroupStrategy::Sort => {
+                let mut keyed: Vec<(Vec<&str>, usize)> = (0..self.rows_count())
+                    .map(|position| (key(position), position))
+                    .collect();
+                keyed.sort_unstable();
+                keyed
+                    .chunk_by(|(a, _), (b, _)| a == b)
+                    .map(|group| group.iter().map(|(_, position)| *position).collect())
+                    .collect()
+            }
+            _ => {
+                let mut groups_index: HashMap<Vec<&str>, Vec<usize>> = HashMap::new();
+                for position in 0..self.rows_count() {
+                    groups_index
+                        .entry(key(position))
+                        .or_default()
+                        .push(position);
+                }
+                groups_index.into_values().collect()
+            }
+        };
+        let groups: Vec<&[usize]> = groups.iter().map(|p| p.as_slice()).collect();
+        self.aggregate_groups(groups, column_operations, nulls)
+    }
+
+    fn aggregate_groups(
+        &self,
+        mut groups: Vec<&[usize]>,
+        column_operations: &[Op],
+        default_nulls: Option<NullPolicy>,
+    ) -> Res<Table> {
+        // groups in order of first occurrence, whatever hashing or sorting found them
+        groups.sort_unstable_by_key(|positions| positions[0]);
+        let mut aggregated = ColumnMap::with_capacity(column_operations.len());
         for op in column_operations {
             let column_operation: &str = op.column_name.as_ref();
             let col = self.column(column_operation)?;
-            let column_cells = col.cells();
-            let new_column_cells = groups_index
-                .values()
+            let new_column_cells = groups
+                .iter()
                 .map(|positions| {
-                    let items: Vec<&str> = positions
+                    // the native path skips nulls
+                    let skips_nulls = op.nulls.or(default_nulls) != Some(NullPolicy::Propagate);
+                    let native = match (&op.operation, col.typed()) {
+                        (Operation::Builtin(aggregate), Some(typed)) if skips_nulls => {
+                            typed.aggregate(aggregate, positions)
+                        }
+                        _ => None,
+                    };
+                    if let Some(result) = native {
+                        return result.map(Value::new).map_err(|err| {
+                            messages::text(Message::AggregationOnColumn, &[&column_operation, &err])
+                        });
+                    }
+                    let items: Vec<&str> = positions.iter().map(|&p| col.get(p).as_str()).collect();
+                    op.apply(items.as_slice(), default_nulls).map(Value::new)
+                })
+                .collect::<Res<Vec<Value>>>()?;
+            aggregated.insert(column_operation.into(), Column::new(new_column_cells));
+        }
+        // same column order as self
+        let mut columns = ColumnMap::with_capacity(self.columns.len());
+        for (col_name, col) in self.columns.iter() {
+            let column = match aggregated.remove(col_name) {
+                Some(column) => column,
+                None => {
+                    let new_column_cells: Vec<Value> = groups
                         .iter()
-                        .map(|&p| column_cells[p].as_str())
+                        .map(|positions| col.get(positions[0]).clone())
                         .collect();
-                    Value::new((op.operation)(items.as_slice()))
+                    Column::new(new_column_cells)
+                }
+            };
+            columns.insert(col_name.clone(), column);
+        }
+        let mut table = Table::from_columns(columns);
+        table.lineage = self.frozen_lineage();
+        Ok(table)
+    }
+
+    pub fn pivot(
+        &self,
+        index_col_name: &str,
+        key_col_name: &str,
+        value_col_name: &str,
+        aggregate: Aggregate,
+    ) -> Res<Table> {
+        let index_col = self.column(index_col_name)?;
+        let key_col = self.column(key_col_name)?;
+        let value_col = self.column(value_col_name)?;
+
+        let mut index_rows: HashMap<&str, usize> = HashMap::new();
+        let mut index_values: Vec<&Value> = Vec::new();
+        let mut groups: HashMap<&str, HashMap<usize, Vec<&str>>> = HashMap::new();
+        let mut keys: Vec<&str> = Vec::new();
+        for position in 0..self.rows_count() {
+            let row = *index_rows
+                .entry(index_col.get(position).as_str())
+                .or_insert_with(|| {
+                    index_values.push(index_col.get(position));
+                    index_values.len() - 1
+                });
+            groups
+                .entry(key_col.get(position).as_str())
+                .or_insert_with(|| {
+                    keys.push(key_col.get(position).as_str());
+                    HashMap::new()
                 })
-                .collect();
-            columns.insert(column_operation.into(), Column::new(new_column_cells));
-        }
-        for col_name in self.columns.keys() {
-            if !columns.contains_key(col_name) {
-                let col = self.column(col_name.as_ref())?;
-                let column_cells = col.cells();
-                let new_column_cells: Vec<Value> = groups_index
-                    .values()
-                    .map(|positions| column_cells[positions[0]].clone())
-                    .collect();
-                columns.insert(col_name.clone(), Column::new(new_column_cells));
+                .entry(row)
+                .or_default()
+                .push(value_col.get(position).as_str());
+        }
+
+        let mut columns = ColumnMap::with_capacity(groups.len() + 1);
+        columns.insert(
+            Value::from(index_col_name),
+            Column::new(index_values.into_iter().cloned().collect()),
+        );
+        for key in keys {
+            let rows = groups.remove(key).unwrap();
+            if key == index_col_name {
+                return Err(messages::text(
+                    Message::PivotValueIsIndex,
+                    &[&key, &key_col_name],
+                ));
+            }
+            let mut cells = vec![Value::from(""); index_rows.len()];
+            for (row, values) in rows {
+                cells[row] = Value::new(aggregate.apply(&values).map_err(|err| {
+                    messages::text(Message::AggregationOnColumn, &[&value_col_name, &err])
+                })?);
+            }
+            columns.insert(Value::from(key), Column::new(cells));
+        }
+        Ok(Table::from_columns(columns))
+    }
+
+    pub fn first_per_group(&self, group_col_name: &str, order_col_name: &str) -> Res<Table> {
+        let order_column = self.column(order_col_name)?;
+        self.pick_per_group(group_col_name, |positions| {
+            positions
+                .iter()
+                .copied()
+                .min_by_key(|&p| order_column.get(p))
+        })
+    }
+
+    pub fn last_per_group(&self, group_col_name: &str, order_col_name: &str) -> Res<Table> {
+        let order_column = self.column(order_col_name)?;
+        self.pick_per_group(group_col_name, |positions| {
+            positions
+                .iter()
+                .copied()
+                .max_by_key(|&p| order_column.get(p))
+        })
+    }
+
+    fn pick_per_group(
+        &self,
+        group_col_name: &str,
+        pick: impl Fn(&[usize]) -> Option<usize>,
+    ) -> Res<Table> {
+        let group_column = self.column(group_col_name)?;
+        let mut positions: Vec<usize> = group_column
+            .get_index()
+            .values()
+            .filter_map(|positions| pick(positions))
+            .collect();
+        positions.sort_unstable();
+        Ok(self.remap(&positions))
+    }
+
+    // the first rows of every group, groups in value order
+    pub fn preview_groups(&self, group_col_name: &str, rows_per_group: usize) -> Res<Table> {
+        let group_column = self.column(group_col_name)?;
+        let index = group_column.get_index();
+        let mut groups: Vec<(&Value, &Vec<usize>)> = index.iter().collect();
+        groups.sort_unstable_by_key(|(value, _)| *value);
+        let positions: Vec<usize> = groups
+            .into_iter()
+            .flat_map(|(_, positions)| positions.iter().copied().take(rows_per_group))
+            .collect();
+        Ok(self.remap(&positions))
+    }
+
+    pub fn with_row_number(&self, col_name: &str) -> Res<Table> {
+        let cells: Vec<Value> = (1..=self.rows_count())
+            .map(|n| Value::new(n.to_string()))
+            .collect();
+
+        let mut clone = self.clone();
+        clone.put_column(col_name, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn with_rank_over(
+        &self,
+        partition_col_name: &str,
+        order_col_name: &str,
+        new_col: &str,
+        rank: Rank,
+    ) -> Res<Table> {
+        self.with_rank_over_by(partition_col_name, order_col_name, new_col, rank, |a, b| {
+            a.cmp(b)
+        })
+    }
+
+    pub fn with_rank_over_by(
+        &self,
+        partition_col_name: &str,
+        order_col_name: &str,
+        new_col: &str,
+        rank: Rank,
+        order: impl Fn(&str, &str) -> Ordering,
+    ) -> Res<Table> {
+        let partition_col = self.column(partition_col_name)?;
+        let order_col = self.column(order_col_name)?;
+        let mut ranks = vec![0; self.rows_count()];
+        for positions in partition_col.get_index().values() {
+            let mut ordered = positions.clone();
+            ordered.sort_by(|&a, &b| order(order_col.get(a), order_col.get(b)));
+            let mut current = 0;
+            for (i, &position) in ordered.iter().enumerate() {
+                let tied = i > 0
+                    && order(order_col.get(ordered[i - 1]), order_col.get(position))
+                        == Ordering::Equal;
+                current = match rank {
+                    Rank::RowNumber => i + 1,
+                    Rank::Rank if tied => current,
+                    Rank::Rank => i + 1,
+                    Rank::DenseRank if tied => current,
+                    Rank::DenseRank => current + 1,
+                };
+                ranks[position] = current;
+            }
+        }
+        let cells: Vec<Value> = ranks
+            .into_iter()
+            .map(|n| Value::new(n.to_string()))
+            .collect();
+
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn with_cumulative(
+        &self,
+        col_name: &str,
+        new_col: &str,
+        cum_op: CumOp,
+        partition_col_name: Option<&str>,
+    ) -> Res<Table> {
+        let col = self.column(col_name)?;
+        let partitions: Vec<Vec<usize>> = match partition_col_name {
+            Some(partition_col_name) => self
+                .column(partition_col_name)?
+                .get_index()
+                .values()
+                .cloned()
+                .collect(),
+            None => vec![(0..self.rows_count()).collect()],
+        };
+        let mut cells = vec![Value::from(""); self.rows_count()];
+        for positions in partitions {
+            let items: Vec<&str> = positions.iter().map(|&p| col.get(p).as_str()).collect();
+            let running = cum_op
+                .running(&items)
+                .map_err(|err| messages::text(Message::InColumn, &[&col_name, &err]))?;
+            for (position, value) in positions.into_iter().zip(running) {
+                cells[position] = Value::new(value);
+            }
+        }
+
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn with_rolling(
+        &self,
+        col_name: &str,
+        window_size: usize,
+        new_col: &str,
+        aggregate: Aggregate,
+        start: RollingStart,
+    ) -> Res<Table> {
+        if window_size == 0 {
+            return Err(messages::text(Message::EmptyWindow, &[]));
+        }
+        let col = self.column(col_name)?;
+        let items: Vec<&str> = col.iter().map(|v| v.as_str()).collect();
+        let mut cells: Vec<Value> = Vec::with_capacity(items.len());
+        for end in 1..=items.len() {
+            let value = if end < window_size && start == RollingStart::Empty {
+                String::new()
+            } else {
+                let window = &items[end.saturating_sub(window_size)..end];
+                aggregate.apply(window).map_err(|err| {
+                    messages::text(Message::AtRowOfColumn, &[&(end - 1), &col_name, &err])
+                })?
+            };
+            cells.push(Value::new(value));
+        }
+
+        let mut clone = self.clone();
+        clone.put_column(new_col, Column::new(cells))?;
+        Ok(clone)
+    }
+
+    pub fn flag_outliers(&self, col_name: &str, method: Method, new_col: &str) -> Res<Table> {
+        let values = self
+            .column(col_name)?
+            .numbers_by_row()
+            .map_err(|err| messages::text(Message::InColumn, &[&col_name, &err]))?;
+        self.with_mask(new_col, method.flag(&values))
+    }
+
+    // bounds are computed separately within every partition
+    pub fn flag_outliers_over(
+        &self,
+        partition_col_name: &str,
+        col_name: &str,
+        method: Method,
+        new_col: &str,
+    ) -> Res<Table> {
+        let partition_col = self.column(partition_col_name)?;
+        let values = self
+            .column(col_name)?
+            .numbers_by_row()
+            .map_err(|err| messages::text(Message::InColumn, &[&col_name, &err]))?;
+        let mut mask = vec![false; self.rows_count()];
+        for positions in partition_col.get_index().values() {
+            let group: Vec<Option<f64>> = positions.iter().map(|&p| values[p]).collect();
+            for (&position, flagged) in positions.iter().zip(method.flag(&group)) {
+                mask[position] = flagged;
             }
         }
-        Ok(Table { columns })
+        self.with_mask(new_col, mask)
+    }
+
+    // a row per run of missing numbers, from and to included, and per duplicated number
+    pub fn find_gaps(&self, key_col_name: &str, seq_col_name: &str) -> Res<Table> {
+        let key_column = self.column(key_col_name)?;
+        let seq_column = self.column(seq_col_name)?;
+        let keys_index = key_column.get_index();
+        let mut keys: Vec<&Value> = keys_index.keys().collect();
+        keys.sort_unstable();
+
+        let mut builder = TableBuilder::new(vec![
+            key_col_name.to_string(),
+            String::from("from"),
+            String::from("to"),
+            String::from("issue"),
+        ]);
+        for key in keys {
+            let mut sequence = Vec::with_capacity(keys_index[key].len());
+            for &position in keys_index[key].iter() {
+                let seq = seq_column.get(position).parse::<i64>().map_err(|_| {
+                    messages::text(
+                        Message::NotIntegerAt,
+                        &[&seq_column.get(position).as_str(), &position, &seq_col_name],
+                    )
+                })?;
+                sequence.push(seq);
+            }
+            sequence.sort_unstable();
+            for (i, pair) in sequence.windows(2).enumerate() {
+                let (previous, seq) = (pair[0], pair[1]);
+                if seq == previous {
+                    if i == 0 || sequence[i - 1] != seq {
+                        builder = builder.add_row(vec![
+                            key.to_string(),
+                            seq.to_string(),
+                            seq.to_string(),
+                            String::from("duplicated"),
+                        ])?;
+                    }
+                } else if previous + 1 < seq {
+                    builder = builder.add_row(vec![
+                        key.to_string(),
+                        (previous + 1).to_string(),
+                        (seq - 1).to_string(),
+                        String::from("missing"),
+                    ])?;
+                }
+            }
+        }
+        Ok(builder.build())
     }
 
     pub fn to_tsv(&self, header: Vec<String>) -> Res<String> {
-        let mut cols = Vec::with_capacity(self.columns_count());
+        let header: Vec<&str> = header.iter().map(String::as_str).collect();
+        self.to_tsv_columns(&header)
+    }
+
+    // every column, in table order
+    pub fn to_tsv_all(&self) -> Res<String> {
+        self.to_tsv_columns(&self.column_names())
+    }
+
+    pub fn to_tsv_columns(&self, col_names: &[&str]) -> Res<String> {
+        self.to_tsv_with(col_names, TsvEscaping::default())
+    }
+
+    pub fn to_tsv_with(&self, col_names: &[&str], escaping: TsvEscaping) -> Res<String> {
+        let mut cols = Vec::with_capacity(col_names.len());
+        for col_name in col_names {
+            cols.push(self.column(col_name)?);
+        }
+        let escape_row = |cells: &mut dyn Iterator<Item = (&str, &str)>| -> Res<String> {
+            let cells = cells
+                .map(|(cell, col_name)| escaping.escape(cell, col_name))
+                .collect::<Res<Vec<_>>>()?;
+            Ok(cells.join("\t"))
+        };
+        let mut lines = Vec::with_capacity(self.rows_count() + 1);
+        lines.push(escape_row(&mut col_names.iter().map(|&c| (c, c)))?);
+        // one iterator per column, sparse and dictionary cells are never expanded
+        let mut cells: Vec<_> = cols.iter().map(Column::iter).collect();
+        for _ in 0..self.rows_count() {
+            lines.push(escape_row(&mut cells.iter_mut().zip(col_names).map(
+                |(cells, &col_name)| (cells.next().unwrap().as_str(), col_name),
+            ))?);
+        }
+        Ok(lines.join("\n"))
+    }
+
+    pub fn write_tsv_file(&self, path: &str, header: Vec<String>) -> Res<()> {
+        write_file(path, self.to_tsv(header)?)
+    }
+
+    pub fn write_tsv_file_all(&self, path: &str) -> Res<()> {
+        write_file(path, self.to_tsv_all()?)
+    }
+
+    pub fn write_tsv_file_columns(&self, path: &str, col_names: &[&str]) -> Res<()> {
+        write_file(path, self.to_tsv_columns(col_names)?)
+    }
+
+    pub fn write_tsv_file_with(
+        &self,
+        path: &str,
+        col_names: &[&str],
+        escaping: TsvEscaping,
+    ) -> Res<()> {
+        write_file(path, self.to_tsv_with(col_names, escaping)?)
+    }
+
+    pub fn verify_roundtrip(&self, format: Format) -> Res<Table> {
+        let header: Vec<String> = self.columns.keys().map(|c| c.to_string()).collect();
+        let cols: Vec<Column> = header
+            .iter()
+            .map(|c| self.columns[c.as_str()].clone())
+            .collect();
+        let all_rows: Vec<usize> = (0..self.rows_count()).collect();
+
+        let mut builder = TableBuilder::new(vec![
+            String::from("row"),
+            String::from("column"),
+            String::from("written"),
+            String::from("read"),
+            String::from("issue"),
+        ]);
+        let read_back = |positions: &[usize]| -> Option<Table> {
+            // written raw, the point is to find the cells that do not survive
+            let mut written = Vec::new();
+            format
+                .write_rows(
+                    &mut written,
+                    &header,
+                    &cols,
+                    positions,
+                    TsvEscaping::Raw,
+                    |err| err.to_string(),
+                )
+                .ok()?;
+            let table = format.parse(&String::from_utf8(written).ok()?).ok()?;
+            Some(table).filter(|t| t.rows_count() == positions.len())
+        };
+        let whole = read_back(&all_rows);
+        for position in 0..self.rows_count() {
+            let single;
+            let row = match &whole {
+                Some(table) => Some((table, position)),
+                None => {
+                    single = read_back(&[position]);
+                    single.as_ref().map(|table| (table, 0))
+                }
+            };
+            // when a row does not come back, blame the cells that break it if there are any
+            let any_breaking = cols.iter().any(|col| format.breaks_row(col.get(position)));
+            for (col_name, col) in header.iter().zip(cols.iter()) {
+                let written = col.get(position).as_str();
+                let (read, issue) = match row {
+                    Some((table, read_position)) => match table.columns.get(col_name.as_str()) {
+                        Some(read_col) => {
+                            let read = read_col.get(read_position);
+                            if read.as_str() == written {
+                                continue;
+                            }
+                            (read.to_string(), "changed")
+                        }
+                        None => (String::new(), "column lost"),
+                    },
+                    None if any_breaking && !format.breaks_row(written) => continue,
+                    None => (String::new(), "row lost"),
+                };
+                builder = builder.add_row(vec![
+                    position.to_string(),
+                    col_name.clone(),
+                    written.to_string(),
+                    read,
+                    String::from(issue),
+                ])?;
+            }
+        }
+        Ok(builder.build())
+    }
+
+    pub fn write_partitioned(
+        &self,
+        dir: &str,
+        partition_col: &str,
+        header: Vec<String>,
+        format: Format,
+        layout: PartitionLayout,
+    ) -> Res<()> {
+        self.write_partitioned_with(
+            dir,
+            partition_col,
+            header,
+            format,
+            layout,
+            TsvEscaping::default(),
+        )
+    }
+
+    pub fn write_partitioned_with(
+        &self,
+        dir: &str,
+        partition_col: &str,
+        header: Vec<String>,
+        format: Format,
+        layout: PartitionLayout,
+        escaping: TsvEscaping,
+    ) -> Res<()> {
+        let partition_column = self.column(partition_col)?;
+        let mut cols = Vec::with_capacity(header.len());
         for col_name in header.iter() {
             cols.push(self.column(col_name)?);
         }
+        let write_error = |path: &std::path::Path, err: std::io::Error| {
+            messages::text(Message::WritePartition, &[&path.display(), &err])
+        };
+        for (value, positions) in partition_column.get_index().iter() {
+            let component = format::path_component(value);
+            let (partition_dir, file_name) = match layout {
+                PartitionLayout::Flat => (
+                    std::path::PathBuf::from(dir),
+                    format!("{}.{}", component, format.extension()),
+                ),
+                PartitionLayout::Hive => (
+                    std::path::Path::new(dir).join(format!(
+                        "{}={}",
+                        format::path_component(partition_col),
+                        component
+                    )),
+                    format!("part.{}", format.extension()),
+                ),
+            };
+            std::fs::create_dir_all(&partition_dir)
+                .map_err(|err| write_error(&partition_dir, err))?;
+            let path = partition_dir.join(file_name);
+            let file = File::create(&path).map_err(|err| write_error(&path, err))?;
+            let mut out = BufWriter::new(file);
+            format.write_rows(&mut out, &header, &cols, positions, escaping, |err| {
+                write_error(&path, err)
+            })?;
+            out.flush().map_err(|err| write_error(&path, err))?;
+        }
+        Ok(())
+    }
+
+    // typed columns keep their type, the others are utf8 and empty cells are nulls
+    #[cfg(feature = "arrow")]
+    pub fn to_arrow(&self) -> Res<arrow_array::RecordBatch> {
+        let schema = std::sync::Arc::new(arrow::schema(self));
+        arrow::batch(self, &schema, 0..self.rows_count())
+    }
+
+    // writes the table as arrow IPC stream messages of at most rows_per_batch rows each,
+    // flushing after every batch; returns the number of batches written
+    #[cfg(feature = "arrow")]
+    pub fn write_arrow_stream(&self, out: impl Write, rows_per_batch: usize) -> Res<usize> {
+        arrow::write_stream(self, out, rows_per_batch)
+    }
+
+    pub fn to_repr(&self) -> String {
+        self.to_repr_with(&ReprOptions::default())
+    }
+
+    pub fn to_repr_truncated(&self, max_rows: usize, max_col_width: usize) -> String {
+        self.to_repr_with(
+            &ReprOptions::new()
+                .max_rows(max_rows)
+                .max_col_width(max_col_width),
+        )
+    }
+
+    pub fn to_repr_with(&self, options: &ReprOptions) -> String {
+        repr::repr_columns(
+            self.columns
+                .iter()
+                .map(|(col_name, col)| (col_name.as_str(), col))
+                .collect(),
+            0..self.rows_count(),
+            options,
+        )
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = Row<'_>> {
+        (0..self.rows_count()).map(move |position| Row::new(self, position))
+    }
+
+    // steps recorded on the returned plan run on collect
+    pub fn lazy(&self) -> LazyTable {
+        LazyTable::new(self.clone())
+    }
 
-        let tsv = std::iter::once(header.join("\t"))
-            .chain((0..self.rows_count()).map(|row| {
-                cols.iter()
-                    .map(|col| col.cells()[row].as_str())
-                    .collect::<Vec<&str>>()
-                    .join("\t")
-            }))
-            .collect::<Vec<String>>()
-            .join("\n");
+    pub fn view_rows(&self, rows: Range<usize>) -> TableView<'_> {
+        let end = rows.end.min(self.rows_count());
+        TableView::new(self, rows.start.min(end)..end)
+    }
 
-        Ok(tsv)
+    pub(crate) fn column_ref(&self, col_name: &str) -> Res<&Column> {
+        self.columns
+            .get(col_name)
+            .ok_or_else(|| messages::text(Message::ColumnMissing, &[&col_name]))
     }
 
-    pub fn write_tsv_file(&self, path: &str, header: Vec<String>) -> Res<()> {
-        std::fs::write(path, self.to_tsv(header)?).map_err(|e| {
-            format!(
-                "Impossibile scrivere su file tsv '{}' la table: {}",
-                path,
-                e.to_string()
-            )
-        })?;
-        Ok(())
+    pub(crate) fn column_names(&self) -> Vec<&str> {
+        self.columns.keys().map(Value::as_str).collect()
     }
+}
 
-    pub fn to_repr(&self) -> String {
-        let mut witdh_sums = 0;
-        let mut col_widths: Vec<(&str, isize)> = self
-            .columns
-            .iter()
-            .map(|(col_name, col)| {
-                let max_item_len = col
-                    .cells()
-                    .iter()
-                    .map(|cell| cell.chars().count())
-                    .max()
-                    .unwrap_or(0);
-                let col_width = col_name.chars().count().max(max_item_len) as isize;
-                witdh_sums += col_width;
-                (col_name.as_ref(), col_width)
-            })
-            .collect();
-        col_widths.sort_unstable();
-        let rows = self.columns.iter().next().map_or(0, |v| v.1.len());
-        let width = witdh_sums + 3 * (self.columns.len() as isize) + 1;
-        let buffer_size = (rows + 3) * (width + (self.columns.len() * 2) as isize) as usize;
+fn unloaded_row(row: RejectedRow) -> (usize, Vec<Value>, String) {
+    let cells = row.cells.into_iter().map(Value::new).collect();
+    (row.row, cells, row.reason)
+}
+
+fn write_file(path: &str, text: String) -> Res<()> {
+    std::fs::write(path, text).map_err(|e| messages::text(Message::WriteTsv, &[&path, &e]))
+}
 
-        let mut result = String::with_capacity(buffer_size);
-        for _ in 0..width {
-            result.push('_');
+// rows grouped on the codes of dictionary columns, no cell is hashed
+fn dictionary_groups(dictionaries: &[(&[u32], &[Value])]) -> Vec<Vec<usize>> {
+    let (first_codes, first_values) = dictionaries[0];
+    let mut keys: Vec<usize> = first_codes.iter().map(|&code| code as usize).collect();
+    let mut distinct = first_values.len();
+    for (codes, _) in &dictionaries[1..] {
+        // the key so far and the code of the next column get a new dense key
+        let mut renumbered: HashMap<(usize, u32), usize> = HashMap::with_capacity(distinct);
+        for (key, &code) in keys.iter_mut().zip(codes.iter()) {
+            let next = renumbered.len();
+            *key = *renumbered.entry((*key, code)).or_insert(next);
         }
-        result += "\n| ";
-        for (value, w) in col_widths.iter() {
-            result += value;
-            let padding = w - (value.chars().count() as isize);
-            for _ in 0..padding {
-                result.push(' ');
+        distinct = renumbered.len();
+    }
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); distinct];
+    for (position, key) in keys.into_iter().enumerate() {
+        groups[key].push(position);
+    }
+    // values no row uses anymore stay in the dictionary
+    groups.retain(|positions| !positions.is_empty());
+    groups
+}
+
+fn probe_index(indexed: &Column, probe: &Column) -> (Vec<usize>, Vec<usize>) {
+    // two dictionary columns are joined on their codes: the rows of the indexed side are
+    // bucketed by code and no index of values is built
+    let buckets: Option<Vec<Vec<usize>>> = match (indexed.dictionary(), probe.dictionary()) {
+        (Some((codes, values)), Some(_)) if !indexed.has_index() => {
+            let mut buckets = vec![Vec::new(); values.len()];
+            for (position, &code) in codes.iter().enumerate() {
+                buckets[code as usize].push(position);
             }
-            result += " | ";
+            Some(buckets)
         }
-        result += "\n|";
-        for (i, (_, w)) in col_widths.iter().enumerate() {
-            for _ in 0..(*w + 2) {
-                result.push('-');
-            }
-            if i < self.columns_count() - 1 {
-                result.push('+');
-            }
+        _ => None,
+    };
+    let index = match buckets {
+        Some(_) => None,
+        None => Some(indexed.get_index()),
+    };
+    let find = |value: &Value| -> Option<&[usize]> {
+        if value.is_null() {
+            return None;
         }
-        result.push('|');
-        if rows > 0 {
-            result += "\n| ";
+        match &buckets {
+            Some(buckets) => indexed
+                .dictionary_code(value)
+                .map(|code| buckets[code as usize].as_slice())
+                .filter(|positions| !positions.is_empty()),
+            None => index?.get(value).map(Vec::as_slice),
         }
-        let col_widths: Vec<(&[Value], isize)> = col_widths
-            .into_iter()
-            .map(|(c, w)| (self.columns.get(c).unwrap().cells(), w))
-            .collect();
-        for row in 0..rows {
-            for (values, w) in col_widths.iter() {
-                let value = values[row].as_ref();
-                result += value;
-                let padding = w - (value.chars().count() as isize);
-                for _ in 0..padding {
-                    result.push(' ');
+    };
+    // a dictionary probe side is looked up once per distinct value
+    let dictionary_matches = probe.dictionary().map(|(codes, values)| {
+        let matches: Vec<Option<&[usize]>> = values.iter().map(find).collect();
+        (codes, matches)
+    });
+    let matches = |position: usize| match &dictionary_matches {
+        Some((codes, matches)) => matches[codes[position] as usize],
+        None => find(probe.get(position)),
+    };
+    // the index is built once, the probe side is split across threads
+    let chunks = parallel::chunks(probe.len(), |rows| {
+        let mut positions_indexed: Vec<usize> = Vec::with_capacity(rows.len());
+        let mut positions_probe: Vec<usize> = Vec::with_capacity(rows.len());
+        for position in rows {
+            if let Some(indexed_positions) = matches(position) {
+                positions_indexed.extend(indexed_positions);
+                let additions = indexed_positions.len();
+                positions_probe.reserve(additions);
+                for _ in 0..additions {
+                    positions_probe.push(position);
                 }
-                result += " | ";
-            }
-            if row < rows - 1 {
-                result += "\n| ";
             }
         }
-        result += "\n|";
-        for _ in 0..width - 2 {
-            result.push('_');
+        (positions_indexed, positions_probe)
+    });
+    if chunks.len() == 1 {
+        return chunks.into_iter().next().unwrap();
+    }
+    chunks.into_iter().fold(
+        (Vec::new(), Vec::new()),
+        |(mut positions_indexed, mut positions_probe), (indexed_chunk, probe_chunk)| {
+            positions_indexed.extend(indexed_chunk);
+            positions_probe.extend(probe_chunk);
+            (positions_indexed, positions_probe)
+        },
+    )
+}
+
+// positions in the index and in probe of every matching pair, in probe order
+fn probe_composite(
+    indexed: &CompositeIndex,
+    probe: &[(Value, Column)],
+) -> (Vec<usize>, Vec<usize>) {
+    let mut positions_indexed = Vec::new();
+    let mut positions_probe = Vec::new();
+    let rows = probe.first().map_or(0, |(_, column)| column.len());
+    for position in 0..rows {
+        let key = composite::key(probe, position);
+        if key.iter().any(Value::is_null) {
+            continue;
+        }
+        if let Some(matches) = indexed.get(&key) {
+            positions_indexed.extend(matches);
+            positions_probe.extend(std::iter::repeat_n(position, matches.len()));
         }
-        result += "|\n";
-        result
+    }
+    (positions_indexed, positions_probe)
+}
+
+pub(crate) fn row_key<'a>(columns: &[&'a Column], position: usize) -> Vec<&'a str> {
+    columns
+        .iter()
+        .map(|col| col.get(position).as_str())
+        .collect()
+}
+
+fn shared_key<'a>(col_name_self: &'a str, col_name_other: &str) -> Option<&'a str> {
+    if col_name_self == col_name_other {
+        Some(col_name_self)
+    } else {
+        None
+    }
+}
+
+// the precision limits the rows, "{:.20}" shows the first and last 10
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let options = match f.precision() {
+            Some(max_rows) => ReprOptions::new().max_rows(max_rows).row_count(),
+            None => ReprOptions::new(),
+        };
+        f.write_str(&self.to_repr_with(&options))
     }
 }
 
@@ -510,10 +3799,9 @@ impl TableBuilder {
 
     pub fn add_row(mut self, cells: Vec<String>) -> Result<TableBuilder, String> {
         if cells.len() != self.columns.len() {
-            Err(format!(
-                "fornita riga di lunghezza {} ma dovrebbe essere {}",
-                cells.len(),
-                self.columns.len()
+            Err(messages::text(
+                Message::RowLength,
+                &[&cells.len(), &self.columns.len()],
             ))
         } else {
             for (col, cell) in self.columns.iter_mut().zip(cells) {
@@ -524,12 +3812,1667 @@ impl TableBuilder {
     }
 
     pub fn build(self) -> Table {
-        let mut new_columns = HashMap::with_capacity(self.columns.len());
+        let mut new_columns = ColumnMap::with_capacity(self.columns.len());
         for (col_name, cells) in self.columns {
             new_columns.insert(col_name, Column::new(cells));
         }
-        Table {
-            columns: new_columns,
+        Table::from_columns(new_columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::similarity::{FuzzyMatch, Metric};
+
+    fn cells(table: &Table, col_name: &str) -> Vec<String> {
+        let column = table.column(col_name).unwrap();
+        column.iter().map(|c| c.to_string()).collect()
+    }
+
+    fn orders() -> Table {
+        Table::parse_tsv("a\tb\torder\n1\tx\to1\n2\ty\to2\n1\tx\to3\n3\tz\to4\n", 0).unwrap()
+    }
+
+    fn customers() -> Table {
+        Table::parse_tsv("a\tb\tname\n3\tz\tc3\n1\tx\tc1\n1\tx\tc1bis\n2\ty\tc2\n", 0).unwrap()
+    }
+
+    #[test]
+    fn multi_column_joins_keep_self_order_whichever_side_is_indexed() {
+        let keys = ["a", "b"];
+        let plain = orders()
+            .join_on_columns_multi(&keys, &customers(), &keys)
+            .unwrap();
+        let mut indexed_self = orders();
+        indexed_self.create_index_multi(&keys).unwrap();
+        let with_self_index = indexed_self
+            .join_on_columns_multi(&keys, &customers(), &keys)
+            .unwrap();
+        let mut indexed_other = customers();
+        indexed_other.create_index_multi(&keys).unwrap();
+        let with_other_index = orders()
+            .join_on_columns_multi(&keys, &indexed_other, &keys)
+            .unwrap();
+        assert_eq!(cells(&plain, "order"), ["o1", "o1", "o2", "o3", "o3", "o4"]);
+        assert_eq!(
+            cells(&plain, "name"),
+            ["c1", "c1bis", "c2", "c1", "c1bis", "c3"]
+        );
+        for joined in [&with_self_index, &with_other_index] {
+            assert_eq!(cells(joined, "order"), cells(&plain, "order"));
+            assert_eq!(cells(joined, "name"), cells(&plain, "name"));
+        }
+    }
+
+    #[test]
+    fn composite_indexes_group_like_plain_keys_and_drop_by_name() {
+        let ops = [Op::aggregate("order", Aggregate::Count)];
+        let plain = orders().group_by_columns(&["a", "b"], &ops).unwrap();
+        let mut table = orders();
+        table.create_index_multi(&["a", "b"]).unwrap();
+        assert!(table.has_index_multi(&["a", "b"]));
+        // the index is on the columns in this order
+        assert!(!table.has_index_multi(&["b", "a"]));
+        let grouped = table.group_by_columns(&["a", "b"], &ops).unwrap();
+        assert_eq!(cells(&grouped, "a"), ["1", "2", "3"]);
+        assert_eq!(cells(&grouped, "order"), ["2", "1", "1"]);
+        for col_name in ["a", "b", "order"] {
+            assert_eq!(cells(&grouped, col_name), cells(&plain, col_name));
+        }
+        table.drop_index_multi(&["a", "b"]);
+        assert!(!table.has_index_multi(&["a", "b"]));
+        assert!(table.create_index_multi(&["a", "missing"]).is_err());
+    }
+
+    #[test]
+    fn stale_composite_indexes_are_not_used_or_cloned() {
+        let mut table = orders();
+        table.create_index_multi(&["a", "b"]).unwrap();
+        assert_eq!(table.clone().composite_indexes.len(), 1);
+        table.set_cell("b", 1, "x").unwrap();
+        assert!(!table.has_index_multi(&["a", "b"]));
+        assert!(table.clone().composite_indexes.is_empty());
+        let joined = table
+            .join_on_columns_multi(&["a", "b"], &customers(), &["a", "b"])
+            .unwrap();
+        assert_eq!(cells(&joined, "order"), ["o1", "o1", "o3", "o3", "o4"]);
+    }
+
+    #[test]
+    fn remapped_composite_indexes_find_the_new_positions() {
+        let mut table = orders();
+        table.create_index_multi(&["a", "b"]).unwrap();
+        let tail = table.slice(1, 3);
+        assert!(tail.has_index_multi(&["a", "b"]));
+        let joined = customers()
+            .join_on_columns_multi(&["a", "b"], &tail, &["a", "b"])
+            .unwrap();
+        assert_eq!(cells(&joined, "order"), ["o4", "o3", "o3", "o2"]);
+    }
+
+    #[test]
+    fn join_key_stats_rank_keys_by_output_rows() {
+        let left = Table::parse_tsv("k\na\na\nb\nc\n\n", 0).unwrap();
+        let right = Table::parse_tsv("k\na\nb\nb\nb\n\n", 0).unwrap();
+        assert_eq!(left.estimate_join_rows("k", &right, "k").unwrap(), 5);
+        let stats = left.join_key_stats("k", &right, "k", 10).unwrap();
+        assert_eq!(cells(&stats, "key"), ["b", "a"]);
+        assert_eq!(cells(&stats, "output_rows"), ["3", "2"]);
+        let top = left.join_key_stats("k", &right, "k", 1).unwrap();
+        assert_eq!(cells(&top, "key"), ["b"]);
+    }
+
+    fn write_temp(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("sql-table-{}-{}", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn validated_loads_quarantine_rows_that_do_not_load() {
+        let path = write_temp(
+            "validated",
+            "id\tqty\tcode\n1\t5\tA\n2\t7\n3\tx\tB\n4\t9\tZ\n5\t1\tA\textra\n6\t2\tB\n",
+        );
+        let options = LoadOptions::new().schema(Schema::new().column("qty", ColumnType::Int64));
+        let rules = Rules::new().in_set("code", "code", &["A", "B"]);
+        let (clean, quarantine) = Table::load_tsv_validated_with(&path, &options, &rules).unwrap();
+        assert_eq!(cells(&clean, "id"), ["1", "6"]);
+        assert_eq!(cells(&quarantine, "id"), ["2", "3", "4", "5"]);
+        assert_eq!(cells(&quarantine, "row"), ["1", "2", "3", "4"]);
+        assert_eq!(cells(&quarantine, "code"), ["", "B", "Z", "A"]);
+        let reasons = cells(&quarantine, "reasons");
+        assert!(reasons[0].contains('2') && reasons[0].contains('3'));
+        assert!(reasons[1].contains("qty"));
+        assert!(reasons[2].starts_with("code"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn plain_loads_still_fail_on_rows_that_do_not_load() {
+        let path = write_temp("plain", "id\tqty\n1\t5\n2\n");
+        assert!(Table::load_tsv(&path, 0).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn pushed_rows_keep_a_composite_primary_key_unique() {
+        let mut table = orders().with_primary_key(&["a", "order"]).unwrap();
+        table
+            .push_row(vec!["1".into(), "w".into(), "o5".into()])
+            .unwrap();
+        table
+            .push_row(vec!["2".into(), "w".into(), "o1".into()])
+            .unwrap();
+        let err = table.push_row(vec!["1".into(), "w".into(), "o3".into()]);
+        assert_eq!(
+            err.err().unwrap(),
+            "E046: duplicate primary key at rows 2 and 6"
+        );
+        let err = table.push_row(vec!["".into(), "w".into(), "o9".into()]);
+        assert!(err.err().unwrap().starts_with("E045: "));
+        assert_eq!(cells(&table, "order"), ["o1", "o2", "o3", "o4", "o5", "o1"]);
+    }
+
+    #[test]
+    fn assert_unique_lists_a_few_duplicates_and_counts_the_rest() {
+        let text = format!("id\n{}7\n", "1\n".repeat(12));
+        let table = Table::parse_tsv(&text, 0).unwrap();
+        let report = table.assert_unique("id").err().unwrap();
+        assert_eq!(report.violations.len(), 12);
+        assert_eq!(
+            report.violations[0].message,
+            "E049: duplicate value at 11 other rows: 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, …"
+        );
+        let table = Table::parse_tsv("id\n1\n2\n1\n", 0).unwrap();
+        let report = table.assert_unique("id").err().unwrap();
+        assert!(report.violations[1].message.ends_with(" 0"));
+    }
+
+    #[test]
+    fn find_gaps_reports_runs_of_missing_numbers() {
+        let text = "k\tn\na\t1\na\t1000000\na\t3\nb\t5\nb\t5\nb\t6\na\t4\n";
+        let gaps = Table::parse_tsv(text, 0)
+            .unwrap()
+            .find_gaps("k", "n")
+            .unwrap();
+        assert_eq!(cells(&gaps, "k"), ["a", "a", "b"]);
+        assert_eq!(cells(&gaps, "from"), ["2", "5", "5"]);
+        assert_eq!(cells(&gaps, "to"), ["2", "999999", "5"]);
+        assert_eq!(cells(&gaps, "issue"), ["missing", "missing", "duplicated"]);
+    }
+
+    #[test]
+    fn filter_between_rejects_bounds_out_of_column_order() {
+        let text = "n\n5\n9\n10\n12\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        let err = table.filter_between("n", "9", "10").err().unwrap();
+        assert!(err.starts_with("E115: "), "{}", err);
+        let within = table.filter_between("n", "10", "9").unwrap();
+        assert_eq!(cells(&within, "n"), ["5", "9", "10", "12"]);
+        let options = LoadOptions::new().schema(Schema::new().column("n", ColumnType::Int64));
+        let typed = Table::parse_tsv_with(text, &options).unwrap();
+        let within = typed.filter_between("n", "9", "10").unwrap();
+        assert_eq!(cells(&within, "n"), ["9", "10"]);
+        assert!(typed.filter_between("n", "10", "9").is_err());
+    }
+
+    #[test]
+    fn indexes_are_created_kept_through_filters_and_dropped() {
+        let mut text = String::from("k\tv\n");
+        for row in 0..100 {
+            text += &format!("{}\t{}\n", row % 10, row);
+        }
+        let mut table = Table::parse_tsv_with(&text, &LoadOptions::new().plain_cells()).unwrap();
+        let lookup = Table::parse_tsv("k\tname\n3\tthree\n", 0).unwrap();
+        assert!(!table.has_index("k").unwrap());
+        table.create_index("k").unwrap();
+        assert!(table.has_index("k").unwrap());
+        let shared = table.clone();
+
+        // most rows stay, so the index follows them to their new positions
+        let filtered = table.filter_column("v", |v| v != "3").unwrap();
+        assert!(filtered.has_index("k").unwrap());
+        let joined = lookup.join_on_columns("k", &filtered, "k").unwrap();
+        assert_eq!(cells(&joined, "v")[..3], ["13", "23", "33"]);
+        assert_eq!(joined.rows_count(), 9);
+
+        table.drop_index("k").unwrap();
+        assert!(!table.has_index("k").unwrap());
+        assert!(shared.has_index("k").unwrap());
+        assert!(table.create_index("missing").is_err());
+        assert!(table.drop_index("missing").is_err());
+    }
+
+    #[test]
+    fn range_lookups_use_the_sort_order_and_sorted_columns_stay_sorted() {
+        let table = Table::parse_tsv("n\tid\nb\t0\nd\t1\na\t2\nc\t3\nb\t4\n", 0).unwrap();
+        assert!(!table.column("n").unwrap().has_sorted_index());
+        assert_eq!(table.lookup_range("n", "b", "c").unwrap(), [0, 4, 3]);
+        assert!(table.column("n").unwrap().has_sorted_index());
+        let within = table.filter_between("n", "b", "c").unwrap();
+        assert_eq!(cells(&within, "id"), ["0", "3", "4"]);
+        assert!(table.lookup_range("n", "x", "z").unwrap().is_empty());
+
+        let sorted = table.sort_column("n").unwrap();
+        assert_eq!(cells(&sorted, "id"), ["2", "0", "4", "3", "1"]);
+        assert!(sorted.column("n").unwrap().is_sorted());
+        // rows kept in order stay sorted, a reordering does not
+        let kept = sorted.filter_column("id", |id| id != "4").unwrap();
+        assert!(kept.column("n").unwrap().is_sorted());
+        assert_eq!(kept.lookup_range("n", "b", "d").unwrap(), [1, 2, 3]);
+        let reordered = sorted.sort_column("id").unwrap();
+        assert!(!reordered.column("n").unwrap().is_sorted());
+        assert_eq!(
+            cells(&sorted.sort_column("n").unwrap(), "id"),
+            cells(&sorted, "id")
+        );
+    }
+
+    #[test]
+    fn join_collisions_follow_the_policy() {
+        let left = Table::parse_tsv("id\tname\n1\tl1\n2\tl2\n", 0).unwrap();
+        let right = Table::parse_tsv("id\tname\n2\tr2\n1\tr1\n", 0).unwrap();
+        // the order of a single column join depends on the side that is indexed
+        let join = |on_collision| {
+            left.join_on_columns_with("id", &right, "id", on_collision)
+                .and_then(|joined| joined.sort_column("id"))
+        };
+        let err = join(JoinCollision::Error).err().unwrap();
+        assert_eq!(err, "E039: column 'name' is in both tables of the join");
+        let merged = left
+            .join_on_columns("id", &right, "id")
+            .and_then(|joined| joined.sort_column("id"))
+            .unwrap();
+        assert_eq!(merged.column_names(), ["id", "name"]);
+        assert_eq!(cells(&merged, "name"), ["r1", "r2"]);
+        assert_eq!(
+            cells(&join(JoinCollision::KeepRight).unwrap(), "name"),
+            ["r1", "r2"]
+        );
+        let kept = join(JoinCollision::KeepLeft).unwrap();
+        assert_eq!(kept.column_names(), ["id", "name"]);
+        assert_eq!(cells(&kept, "name"), ["l1", "l2"]);
+        let suffixed = join(JoinCollision::Suffix("_l".into(), "_r".into())).unwrap();
+        assert_eq!(suffixed.column_names(), ["id", "name_l", "name_r"]);
+        assert_eq!(cells(&suffixed, "name_l"), ["l1", "l2"]);
+        assert_eq!(cells(&suffixed, "name_r"), ["r1", "r2"]);
+        assert!(join(JoinCollision::Suffix("".into(), "".into())).is_err());
+    }
+
+    #[test]
+    fn date_range_steps_through_month_and_year_ends() {
+        let days = Table::date_range("day", "2023-12-30", "2024-01-02", 1).unwrap();
+        let expected = ["2023-12-30", "2023-12-31", "2024-01-01", "2024-01-02"];
+        assert_eq!(cells(&days, "day"), expected);
+        let weeks = Table::date_range("day", "2024-02-20", "2024-03-10", 7).unwrap();
+        assert_eq!(
+            cells(&weeks, "day"),
+            ["2024-02-20", "2024-02-27", "2024-03-05"]
+        );
+        let empty = Table::date_range("day", "2024-01-02", "2024-01-01", 1).unwrap();
+        assert_eq!(empty.rows_count(), 0);
+        assert!(Table::date_range("day", "2024-01-01", "2024-01-02", 0).is_err());
+        assert!(Table::date_range("day", "2024-02-30", "2024-03-01", 1).is_err());
+    }
+
+    #[test]
+    fn sorted_joins_match_runs_of_equal_keys() {
+        let left = Table::parse_tsv("k\tl\n\tl0\na\tl1\nb\tl2\nb\tl3\nd\tl4\n", 0).unwrap();
+        let right = Table::parse_tsv("k\tr\n\tr0\nb\tr1\nb\tr2\nc\tr3\nd\tr4\n", 0).unwrap();
+        let joined = left.join_sorted_on_columns("k", &right, "k").unwrap();
+        assert_eq!(cells(&joined, "k"), ["b", "b", "b", "b", "d"]);
+        assert_eq!(cells(&joined, "l"), ["l2", "l2", "l3", "l3", "l4"]);
+        assert_eq!(cells(&joined, "r"), ["r1", "r2", "r1", "r2", "r4"]);
+        let unsorted = Table::parse_tsv("k\tr\nb\tr1\na\tr2\n", 0).unwrap();
+        let err = left
+            .join_sorted_on_columns("k", &unsorted, "k")
+            .err()
+            .unwrap();
+        assert_eq!(err, "E038: column 'k' is not sorted");
+    }
+
+    #[test]
+    fn asof_joins_match_the_nearest_preceding_key() {
+        let trades = Table::parse_tsv("t\tqty\n05\ta\n12\tb\n01\tc\n10\td\n", 0).unwrap();
+        let quotes = Table::parse_tsv("qt\tprice\n10\tp10\n02\tp02\n08\tp08\n", 0).unwrap();
+        let joined = trades
+            .asof_join_on_columns("t", &quotes, "qt", JoinCollision::Error)
+            .unwrap();
+        // 01 has no quote at or before it, the other rows keep their order
+        assert_eq!(cells(&joined, "qty"), ["a", "b", "d"]);
+        assert_eq!(cells(&joined, "price"), ["p02", "p10", "p10"]);
+        let numeric = |a: &str, b: &str| a.parse::<i64>().unwrap().cmp(&b.parse().unwrap());
+        let trades = Table::parse_tsv("t\n9\n10\n", 0).unwrap();
+        let quotes = Table::parse_tsv("qt\tprice\n8\tp8\n10\tp10\n", 0).unwrap();
+        let joined = trades
+            .asof_join_on_columns_by("t", &quotes, "qt", JoinCollision::Error, numeric)
+            .unwrap();
+        assert_eq!(cells(&joined, "price"), ["p8", "p10"]);
+    }
+
+    #[test]
+    fn first_and_last_per_group_keep_table_order() {
+        let text = "g\tt\tv\nb\t2\tb2\na\t3\ta3\nb\t1\tb1\na\t5\ta5\nc\t4\tc4\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        let first = table.first_per_group("g", "t").unwrap();
+        assert_eq!(cells(&first, "v"), ["a3", "b1", "c4"]);
+        let last = table.last_per_group("g", "t").unwrap();
+        assert_eq!(cells(&last, "v"), ["b2", "a5", "c4"]);
+        assert!(table.first_per_group("g", "missing").is_err());
+    }
+
+    #[test]
+    fn interval_joins_match_every_covering_range() {
+        let events = Table::parse_tsv("at\tname\n3\te3\n7\te7\n9\te9\n", 0).unwrap();
+        let text = "from\tto\tperiod\n1\t4\tp1\n2\t8\tp2\n5\t6\tp3\n7\t7\tp4\n";
+        let periods = Table::parse_tsv(text, 0).unwrap();
+        let joined = events
+            .interval_join_on_columns("at", &periods, "from", "to", JoinCollision::Error)
+            .unwrap();
+        // bounds are included, 9 is in no range
+        assert_eq!(cells(&joined, "name"), ["e3", "e3", "e7", "e7"]);
+        assert_eq!(cells(&joined, "period"), ["p1", "p2", "p2", "p4"]);
+    }
+
+    #[test]
+    fn fuzzy_joins_pair_close_strings_with_their_score() {
+        let left = Table::parse_tsv("name\nJonathan\nMaria\n", 0).unwrap();
+        let right = Table::parse_tsv("alias\nJonathon\nMario\nZed\n", 0).unwrap();
+        let join = |matching| {
+            left.fuzzy_join_on_columns(
+                "name",
+                &right,
+                "alias",
+                matching,
+                "score",
+                JoinCollision::Error,
+            )
+            .unwrap()
+        };
+        let by_distance = join(FuzzyMatch::MaxEditDistance(1));
+        assert_eq!(cells(&by_distance, "alias"), ["Jonathon", "Mario"]);
+        assert_eq!(cells(&by_distance, "score"), ["0.8750", "0.8000"]);
+        let by_levenshtein = join(FuzzyMatch::MinScore(Metric::Levenshtein, 0.85));
+        assert_eq!(cells(&by_levenshtein, "alias"), ["Jonathon"]);
+        let by_trigram = join(FuzzyMatch::MinScore(Metric::Trigram, 0.5));
+        assert_eq!(cells(&by_trigram, "name"), ["Jonathan", "Maria"]);
+        assert_eq!(cells(&by_trigram, "alias"), ["Jonathon", "Mario"]);
+        let clash = left.fuzzy_join_on_columns(
+            "name",
+            &right,
+            "alias",
+            FuzzyMatch::MaxEditDistance(1),
+            "name",
+            JoinCollision::Error,
+        );
+        assert!(clash.is_err());
+    }
+
+    #[test]
+    fn group_by_columns_groups_on_the_combined_key() {
+        let text = "a\tb\tv\nx\t1\t1\ny\t1\t2\nx\t2\t3\nx\t1\t4\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        let ops = [Op::aggregate("v", Aggregate::Sum)];
+        let grouped = table.group_by_columns(&["a", "b"], &ops).unwrap();
+        // groups in order of first occurrence
+        assert_eq!(cells(&grouped, "a"), ["x", "y", "x"]);
+        assert_eq!(cells(&grouped, "b"), ["1", "1", "2"]);
+        assert_eq!(cells(&grouped, "v"), ["5", "2", "3"]);
+        // "x\t1" and "x1" style keys do not run into each other
+        let table = Table::parse_tsv("a\tb\tv\nx1\t\t1\nx\t1\t2\n", 0).unwrap();
+        let grouped = table.group_by_columns(&["a", "b"], &ops).unwrap();
+        assert_eq!(grouped.rows_count(), 2);
+    }
+
+    #[test]
+    fn parse_kv_spreads_pairs_into_columns() {
+        let text = "id\tattrs\n1\tcolor=red; size = L\n2\t\n3\tsize=M;flag;note=a=b\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        let parsed = table.parse_kv("attrs", ';', '=').unwrap();
+        assert_eq!(
+            parsed.column_names(),
+            ["id", "attrs", "color", "size", "flag", "note"]
+        );
+        assert_eq!(cells(&parsed, "color"), ["red", "", ""]);
+        assert_eq!(cells(&parsed, "size"), ["L", "", "M"]);
+        assert_eq!(cells(&parsed, "flag"), ["", "", ""]);
+        assert_eq!(cells(&parsed, "note"), ["", "", "a=b"]);
+        let clash = Table::parse_tsv("id\tattrs\n1\tid=2\n", 0).unwrap();
+        let err = clash.parse_kv("attrs", ';', '=').err().unwrap();
+        assert_eq!(err, "E002: column 'id' already exists");
+    }
+
+    #[test]
+    fn checksum_columns_tell_shifted_cells_apart() {
+        let table = Table::parse_tsv("a\tb\nab\tc\na\tbc\nab\tc\n", 0).unwrap();
+        let hashed = table
+            .add_checksum_column(&["a", "b"], "hash", Checksum::Fnv1a64)
+            .unwrap();
+        let hashes = cells(&hashed, "hash");
+        assert_ne!(hashes[0], hashes[1]);
+        assert_eq!(hashes[0], hashes[2]);
+        assert_eq!(hashes[0].len(), 16);
+    }
+
+    #[test]
+    fn pivot_spreads_keys_into_aggregated_columns() {
+        let text = "day\tcity\ttemp\nmon\trome\t20\nmon\toslo\t5\ntue\trome\t22\nmon\trome\t24\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        let pivoted = table.pivot("day", "city", "temp", Aggregate::Max).unwrap();
+        assert_eq!(pivoted.column_names(), ["day", "rome", "oslo"]);
+        assert_eq!(cells(&pivoted, "day"), ["mon", "tue"]);
+        assert_eq!(cells(&pivoted, "rome"), ["24", "22"]);
+        assert_eq!(cells(&pivoted, "oslo"), ["5", ""]);
+        let counted = table
+            .pivot("day", "city", "temp", Aggregate::Count)
+            .unwrap();
+        assert_eq!(cells(&counted, "rome"), ["2", "1"]);
+        let clash = Table::parse_tsv("day\tk\tv\nmon\tday\t1\n", 0).unwrap();
+        assert!(clash.pivot("day", "k", "v", Aggregate::Sum).is_err());
+    }
+
+    #[test]
+    fn sniff_picks_the_consistent_delimiter_and_infers_types() {
+        let path = write_temp("sniff.csv", "id,price,name\n1,2.5,a;b\n2,3,c\n");
+        let sniff = Table::sniff(&path, 10).unwrap();
+        assert_eq!(sniff.delimiter, ',');
+        assert_eq!(
+            sniff.columns,
+            vec![
+                ("id".to_string(), ColumnType::Int64),
+                ("price".to_string(), ColumnType::Float64),
+                ("name".to_string(), ColumnType::Str),
+            ]
+        );
+        assert_eq!(sniff.sampled_rows, 2);
+
+        let path = write_temp("sniff-empty.csv", "\n\n");
+        assert!(Table::sniff(&path, 10).is_err());
+    }
+
+    #[test]
+    fn header_and_row_count_skip_leading_and_blank_lines() {
+        let path = write_temp("header.tsv", "# export\n id \tname\n\n1\ta\n2\tb\n\n3\tc\n");
+        assert_eq!(Table::read_header(&path, 1).unwrap(), vec!["id", "name"]);
+        assert_eq!(Table::count_rows(&path, 1).unwrap(), 3);
+
+        let path = write_temp("header-empty.tsv", "\n");
+        assert!(Table::read_header(&path, 0).is_err());
+        assert_eq!(Table::count_rows(&path, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn ranks_restart_in_each_partition() {
+        let table = Table::parse_tsv("team\tscore\na\t5\nb\t7\na\t3\na\t5\nb\t2\n", 0).unwrap();
+        assert_eq!(
+            cells(&table.with_row_number("n").unwrap(), "n"),
+            vec!["1", "2", "3", "4", "5"]
+        );
+        let rank = |rank| {
+            let ranked = table.with_rank_over("team", "score", "r", rank).unwrap();
+            cells(&ranked, "r")
+        };
+        assert_eq!(rank(Rank::RowNumber), vec!["2", "2", "1", "3", "1"]);
+        assert_eq!(rank(Rank::Rank), vec!["2", "2", "1", "2", "1"]);
+        assert_eq!(rank(Rank::DenseRank), vec!["2", "2", "1", "2", "1"]);
+
+        let table = Table::parse_tsv("team\tscore\na\t1\na\t1\na\t2\n", 0).unwrap();
+        let rank = |rank| {
+            let ranked = table.with_rank_over("team", "score", "r", rank).unwrap();
+            cells(&ranked, "r")
+        };
+        assert_eq!(rank(Rank::Rank), vec!["1", "1", "3"]);
+        assert_eq!(rank(Rank::DenseRank), vec!["1", "1", "2"]);
+    }
+
+    #[test]
+    fn projected_loads_keep_only_the_named_columns() {
+        let input = "id\tname\tnote\n1\ta\tx\n2\tb\ty\n";
+        let options = LoadOptions::new().columns(&["note", "id"]);
+        let table = Table::parse_tsv_with(input, &options).unwrap();
+        assert_eq!(table.columns_count(), 2);
+        assert_eq!(cells(&table, "id"), vec!["1", "2"]);
+        assert_eq!(cells(&table, "note"), vec!["x", "y"]);
+        assert!(table.column("name").is_err());
+
+        let options = LoadOptions::new().columns(&["missing"]);
+        let err = Table::parse_tsv_with(input, &options).err().unwrap();
+        assert_eq!(err, "E001: column 'missing' does not exist");
+        let short = "id\tname\tnote\n1\ta\n";
+        let options = LoadOptions::new().columns(&["id"]);
+        assert!(Table::parse_tsv_with(short, &options).is_err());
+    }
+
+    #[test]
+    fn cumulative_columns_run_in_row_order_per_partition() {
+        let table = Table::parse_tsv("acct\tamount\na\t5\nb\t7\na\t-2\nb\t1\na\t4\n", 0).unwrap();
+        let running = |op, partition| {
+            let table = table
+                .with_cumulative("amount", "run", op, partition)
+                .unwrap();
+            cells(&table, "run")
+        };
+        assert_eq!(running(CumOp::Sum, None), vec!["5", "12", "10", "11", "15"]);
+        assert_eq!(
+            running(CumOp::Sum, Some("acct")),
+            vec!["5", "7", "3", "8", "7"]
+        );
+        assert_eq!(
+            running(CumOp::Count, Some("acct")),
+            vec!["1", "1", "2", "2", "3"]
+        );
+        assert_eq!(
+            running(CumOp::Max, Some("acct")),
+            vec!["5", "7", "5", "7", "5"]
+        );
+        assert_eq!(running(CumOp::Min, None), vec!["5", "5", "-2", "-2", "-2"]);
+        assert!(table
+            .with_cumulative("amount", "run", CumOp::Sum, Some("nope"))
+            .is_err());
+    }
+
+    #[test]
+    fn rolling_windows_fill_or_blank_the_first_rows() {
+        let table = Table::parse_tsv("n\n1\n2\n3\n6\n", 0).unwrap();
+        let rolling = |aggregate, start| {
+            let table = table.with_rolling("n", 2, "r", aggregate, start).unwrap();
+            cells(&table, "r")
+        };
+        assert_eq!(
+            rolling(Aggregate::Sum, RollingStart::Partial),
+            vec!["1", "3", "5", "9"]
+        );
+        assert_eq!(
+            rolling(Aggregate::Sum, RollingStart::Empty),
+            vec!["", "3", "5", "9"]
+        );
+        assert_eq!(
+            rolling(Aggregate::Mean, RollingStart::Empty),
+            vec!["", "1.5", "2.5", "4.5"]
+        );
+        assert!(table
+            .with_rolling("n", 0, "r", Aggregate::Sum, RollingStart::Partial)
+            .is_err());
+
+        let table = Table::parse_tsv("n\n1\nx\n", 0).unwrap();
+        assert!(table
+            .with_rolling("n", 2, "r", Aggregate::Sum, RollingStart::Partial)
+            .is_err());
+    }
+
+    #[test]
+    fn load_filters_drop_rows_on_columns_not_loaded() {
+        let input = "id\tstatus\tamount\n1\tok\t5\n2\tvoid\t7\n3\tok\t12\n";
+        let options = LoadOptions::new()
+            .columns(&["id"])
+            .filter("status", |status| status == "ok")
+            .filter("amount", |amount| amount.len() > 1);
+        let table = Table::parse_tsv_with(input, &options).unwrap();
+        assert_eq!(cells(&table, "id"), vec!["3"]);
+
+        let options = LoadOptions::new().filter("missing", |_| true);
+        let err = Table::parse_tsv_with(input, &options).err().unwrap();
+        assert_eq!(err, "E001: column 'missing' does not exist");
+    }
+
+    #[test]
+    fn descending_sorts_keep_ties_in_row_order() {
+        let table = Table::parse_tsv("k\tn\nb\t1\nc\t2\na\t3\nc\t4\n", 0).unwrap();
+        let sorted = table.sort_column_desc("k").unwrap();
+        assert_eq!(cells(&sorted, "n"), vec!["2", "4", "1", "3"]);
+        let reversed = table.reverse_rows();
+        assert_eq!(cells(&reversed, "n"), vec!["4", "3", "2", "1"]);
+        assert_eq!(cells(&reversed, "k"), vec!["c", "a", "c", "b"]);
+        assert!(table.sort_column_desc("missing").is_err());
+    }
+
+    #[test]
+    fn numeric_sorts_reject_text_and_natural_sorts_order_digit_runs() {
+        let table = Table::parse_tsv("id\n10\n9\n-2\n", 0).unwrap();
+        let sorted = table.sort_column_numeric("id").unwrap();
+        assert_eq!(cells(&sorted, "id"), vec!["-2", "9", "10"]);
+        let table = Table::parse_tsv("id\n10\nx\n", 0).unwrap();
+        let err = table.sort_column_numeric("id").err().unwrap();
+        assert_eq!(
+            err,
+            "E020: value 'x' at row 1 of column 'id' is not numeric"
+        );
+        // blank lines are skipped, so nulls need a second column
+        let table = Table::parse_tsv("id\tk\n10\ta\n\tb\n-2\tc\n\td\n", 0).unwrap();
+        let sorted = table.sort_column_numeric("id").unwrap();
+        assert_eq!(cells(&sorted, "k"), vec!["b", "d", "c", "a"]);
+
+        let table = Table::parse_tsv("f\nfile10\nfile9\nfile1\n", 0).unwrap();
+        let sorted = table.sort_column_natural("f").unwrap();
+        assert_eq!(cells(&sorted, "f"), vec!["file1", "file9", "file10"]);
+    }
+
+    #[test]
+    fn broadcast_joins_probe_in_row_order_and_keep_the_small_index() {
+        let small = customers();
+        let joined = orders()
+            .join_broadcast("a", &small, "a", JoinCollision::KeepLeft)
+            .unwrap();
+        assert_eq!(
+            cells(&joined, "order"),
+            vec!["o1", "o1", "o2", "o3", "o3", "o4"]
+        );
+        assert_eq!(
+            cells(&joined, "name"),
+            vec!["c1", "c1bis", "c2", "c1", "c1bis", "c3"]
+        );
+        assert!(small.column("a").unwrap().has_index());
+        assert!(orders()
+            .join_broadcast("a", &small, "a", JoinCollision::Error)
+            .is_err());
+    }
+
+    #[test]
+    fn head_tail_and_slice_clamp_to_the_table() {
+        let table = orders();
+        assert_eq!(cells(&table.head(2), "order"), vec!["o1", "o2"]);
+        assert_eq!(cells(&table.tail(3), "order"), vec!["o2", "o3", "o4"]);
+        assert_eq!(
+            cells(&table.tail(10), "order"),
+            vec!["o1", "o2", "o3", "o4"]
+        );
+        assert_eq!(cells(&table.slice(3, 5), "order"), vec!["o4"]);
+        assert_eq!(table.slice(9, 2).rows_count(), 0);
+        assert_eq!(table.slice(1, usize::MAX).rows_count(), 3);
+    }
+
+    #[test]
+    fn samples_are_reproducible_and_keep_row_order() {
+        let table = Table::parse_tsv(
+            &format!(
+                "n\n{}\n",
+                (0..100)
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            0,
+        )
+        .unwrap();
+        let sample = cells(&table.sample(10, 42), "n");
+        assert_eq!(sample.len(), 10);
+        assert_eq!(sample, cells(&table.sample(10, 42), "n"));
+        assert_ne!(sample, cells(&table.sample(10, 43), "n"));
+        let numbers: Vec<usize> = sample.iter().map(|n| n.parse().unwrap()).collect();
+        assert!(numbers.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(table.sample(500, 1).rows_count(), 100);
+
+        let fraction = table.sample_fraction(0.3, 42).unwrap();
+        assert_eq!(
+            cells(&fraction, "n"),
+            cells(&table.sample_fraction(0.3, 42).unwrap(), "n")
+        );
+        assert_eq!(table.sample_fraction(0.0, 42).unwrap().rows_count(), 0);
+        assert_eq!(table.sample_fraction(1.0, 42).unwrap().rows_count(), 100);
+        assert!(table.sample_fraction(1.5, 42).is_err());
+    }
+
+    #[test]
+    fn join_estimates_match_the_join_and_skip_null_keys() {
+        let left = Table::parse_tsv("k\tl\na\t1\n\t2\nb\t3\na\t4\n\t5\n", 0).unwrap();
+        let right = Table::parse_tsv("k\tr\na\t1\n\t2\na\t3\nc\t4\n", 0).unwrap();
+        assert_eq!(left.distinct_count("k").unwrap(), 3);
+        assert_eq!(right.distinct_count("k").unwrap(), 3);
+        let estimate = left.estimate_join_rows("k", &right, "k").unwrap();
+        assert_eq!(estimate, 4);
+        let joined = left.join_on_columns("k", &right, "k").unwrap();
+        assert_eq!(joined.rows_count(), estimate);
+        assert!(left.distinct_count("missing").is_err());
+    }
+
+    #[test]
+    fn refreshed_derivations_recompute_only_what_a_change_reaches() {
+        let derivations = Derivations::new()
+            .derive(MiOp::new(
+                &["a", "b"],
+                "total",
+                Box::new(|args| {
+                    (args[0].parse::<i64>().unwrap() + args[1].parse::<i64>().unwrap()).to_string()
+                }),
+            ))
+            .derive(MiOp::new(
+                &["total"],
+                "doubled",
+                Box::new(|args| (args[0].parse::<i64>().unwrap() * 2).to_string()),
+            ))
+            .derive(MiOp::new(
+                &["c"],
+                "label",
+                Box::new(|args| args[0].to_uppercase()),
+            ));
+        assert_eq!(derivations.affected_by(&["a"]), vec!["total", "doubled"]);
+        assert_eq!(derivations.affected_by(&["c"]), vec!["label"]);
+        assert!(derivations.affected_by(&["doubled"]).is_empty());
+
+        let table = Table::parse_tsv("a\tb\tc\n1\t2\tx\n3\t4\ty\n", 0).unwrap();
+        let previous = table.apply_derivations(&derivations).unwrap();
+        assert_eq!(cells(&previous, "doubled"), vec!["6", "14"]);
+        let mut changed = table.clone();
+        changed.set_cell("a", 1, "10").unwrap();
+        let refreshed = changed
+            .refresh_derivations(&previous, &derivations)
+            .unwrap();
+        assert_eq!(cells(&refreshed, "total"), vec!["3", "14"]);
+        assert_eq!(cells(&refreshed, "doubled"), vec!["6", "28"]);
+        let label = |table: &Table| table.column("label").unwrap();
+        assert!(label(&refreshed).same_data(&label(&previous)));
+    }
+
+    #[test]
+    fn appended_reloads_parse_only_complete_new_lines() {
+        let path = write_temp("growing.tsv", "n\tv\n1\ta\n2\tb");
+        let options = LoadOptions::new();
+        let (table, state) = Table::load_tsv_tracked(&path, &options).unwrap();
+        assert_eq!(cells(&table, "n"), vec!["1"]);
+        assert_eq!(state.offset(), 8);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"\n3\tc\n").unwrap();
+        let (table, state) = table.reload_appended(&path, &state).unwrap();
+        assert_eq!(cells(&table, "n"), vec!["1", "2", "3"]);
+        assert_eq!(cells(&table, "v"), vec!["a", "b", "c"]);
+        let (same, _) = table.reload_appended(&path, &state).unwrap();
+        assert_eq!(same.rows_count(), 3);
+
+        // a rewritten file is loaded again from the start
+        std::fs::write(&path, "n\tv\n9\tz\n3\tc\n4\td\n").unwrap();
+        let (table, _) = table.reload_appended(&path, &state).unwrap();
+        assert_eq!(cells(&table, "n"), vec!["9", "3", "4"]);
+    }
+
+    #[test]
+    fn intersections_keep_self_rows_found_in_the_other_table() {
+        let joined = orders()
+            .intersect_on_columns(&["a", "b"], &customers(), &["a", "b"])
+            .unwrap();
+        assert_eq!(cells(&joined, "order"), vec!["o1", "o2", "o3", "o4"]);
+        let other = Table::parse_tsv("x\ty\n1\tx\n3\ty\n", 0).unwrap();
+        let joined = orders()
+            .intersect_on_columns(&["a", "b"], &other, &["x", "y"])
+            .unwrap();
+        assert_eq!(cells(&joined, "order"), vec!["o1", "o3"]);
+        let err = orders()
+            .intersect_on_columns(&["a", "b"], &other, &["x"])
+            .err()
+            .unwrap();
+        assert_eq!(err, "E036: comparing 2 columns with 1 columns");
+
+        let other = Table::parse_tsv("b\ta\tz\nx\t1\t0\nz\t1\t0\n", 0).unwrap();
+        assert_eq!(
+            cells(&orders().intersect(&other).unwrap(), "order"),
+            vec!["o1", "o3"]
+        );
+        let unrelated = Table::parse_tsv("z\n1\n", 0).unwrap();
+        assert_eq!(
+            orders().intersect(&unrelated).err().unwrap(),
+            "E035: the two tables have no columns in common"
+        );
+    }
+
+    #[test]
+    fn except_drops_rows_whose_shared_columns_all_match() {
+        let other =
+            Table::parse_tsv("b\ta\tnote\nx\t1\tseen\nz\t1\tseen\ny\t2\tseen\n", 0).unwrap();
+        let remaining = orders().except(&other).unwrap();
+        assert_eq!(cells(&remaining, "order"), vec!["o4"]);
+        assert_eq!(cells(&remaining, "a"), vec!["3"]);
+        let empty = Table::parse_tsv("a\tb\n", 0).unwrap();
+        assert_eq!(orders().except(&empty).unwrap().rows_count(), 4);
+    }
+
+    #[test]
+    fn row_filters_see_the_named_cells_in_order() {
+        let table = Table::parse_tsv("start\tend\tid\n1\t5\ta\n7\t3\tb\n2\t2\tc\n", 0).unwrap();
+        let kept = table
+            .filter_rows(&["end", "start"], |cells| {
+                cells[1].parse::<i64>().unwrap() < cells[0].parse::<i64>().unwrap()
+            })
+            .unwrap();
+        assert_eq!(cells(&kept, "id"), vec!["a"]);
+        assert_eq!(
+            table.filter_rows(&["id"], |_| true).unwrap().rows_count(),
+            3
+        );
+        assert!(table.filter_rows(&["missing"], |_| true).is_err());
+    }
+
+    #[test]
+    fn cast_columns_expose_typed_values_and_sort_by_them() {
+        let table = Table::parse_tsv("n\tflag\n10\ttrue\n9\tFALSE\n\t\n", 0).unwrap();
+        let table = table
+            .cast_column("n", ColumnType::Int64)
+            .unwrap()
+            .cast_column("flag", ColumnType::Bool)
+            .unwrap();
+        let n = table.column("n").unwrap();
+        assert_eq!(n.column_type(), ColumnType::Int64);
+        assert_eq!(n.as_i64().unwrap(), &[Some(10), Some(9), None]);
+        assert!(n.as_f64().is_none());
+        let flag = table.column("flag").unwrap();
+        assert_eq!(flag.as_bool().unwrap(), &[Some(true), Some(false), None]);
+        assert_eq!(
+            cells(&table.sort_column("n").unwrap(), "n"),
+            vec!["", "9", "10"]
+        );
+        assert!(table.cast_column("flag", ColumnType::Date).is_err());
+    }
+
+    #[test]
+    fn configured_null_sentinels_load_as_nulls() {
+        let input = "k\tn\na\tNA\nb\t4\nNA\tnull\nc\tNaN\n";
+        let options = LoadOptions::new().null_values(&["NA", "null"]);
+        let table = Table::parse_tsv_with(input, &options).unwrap();
+        assert_eq!(cells(&table, "n"), vec!["", "4", "", "NaN"]);
+        assert!(table.is_null("n", 0).unwrap());
+        assert!(!table.is_null("n", 3).unwrap());
+        assert!(table.is_null("n", 9).is_err());
+        assert_eq!(cells(&table.drop_nulls("n").unwrap(), "k"), vec!["b", "c"]);
+        assert_eq!(
+            cells(&table.drop_nulls("k").unwrap(), "k"),
+            vec!["a", "b", "c"]
+        );
+        let n = cells(&table, "n");
+        let items: Vec<&str> = n.iter().map(String::as_str).collect();
+        assert_eq!(Aggregate::Mean.apply(&items[..3]).unwrap(), "4");
+    }
+
+    #[test]
+    fn date_columns_parse_to_iso_and_format_back() {
+        let table = Table::parse_tsv("d\tn\n02/01/2023\t1\n\t2\n31/12/2022\t3\n", 0).unwrap();
+        let sorted = table.head(1).concatenate(&table.tail(1)).unwrap();
+        let sorted = sorted.sort_column_date("d", "%d/%m/%Y").unwrap();
+        assert_eq!(cells(&sorted, "d"), vec!["31/12/2022", "02/01/2023"]);
+        assert!(table.sort_column_date("d", "%d/%m/%Y").is_err());
+        let parsed = table.parse_date_column("d", "%d/%m/%Y").unwrap();
+        assert_eq!(cells(&parsed, "d"), vec!["2023-01-02", "", "2022-12-31"]);
+        assert_eq!(parsed.column("d").unwrap().column_type(), ColumnType::Date);
+        let formatted = parsed.format_date_column("d", "%b %d").unwrap();
+        assert_eq!(cells(&formatted, "d"), vec!["Jan 02", "", "Dec 31"]);
+        assert!(table.parse_date_column("d", "%Y-%m-%d").is_err());
+    }
+
+    #[test]
+    fn masks_combine_and_filter_with_nulls_as_false() {
+        let table = Table::parse_tsv("n\tpaid\n1\ttrue\n5\tFALSE\n8\t\n12\ttrue\n", 0).unwrap();
+        let table = table
+            .mask_column("n", "big", |n| n.parse::<i64>().unwrap() > 4)
+            .unwrap();
+        assert_eq!(cells(&table, "big"), vec!["false", "true", "true", "true"]);
+        assert_eq!(table.column("big").unwrap().column_type(), ColumnType::Bool);
+        let combined = |op| {
+            let table = table.combine_masks("big", op, "paid", "m").unwrap();
+            cells(&table, "m")
+        };
+        assert_eq!(
+            combined(MaskOp::And),
+            vec!["false", "false", "false", "true"]
+        );
+        assert_eq!(combined(MaskOp::Or), vec!["true", "true", "true", "true"]);
+        assert_eq!(combined(MaskOp::Xor), vec!["true", "true", "true", "false"]);
+        let unpaid = table.invert_mask("paid", "unpaid").unwrap();
+        assert_eq!(
+            cells(&unpaid.filter_mask("unpaid").unwrap(), "n"),
+            vec!["5", "8"]
+        );
+        assert_eq!(table.filter_mask("paid").unwrap().rows_count(), 2);
+        let err = table.filter_mask("n").err().unwrap();
+        assert_eq!(err, "E022: value '1' at row 0 of column 'n' is not boolean");
+    }
+
+    #[test]
+    fn utc_logs_group_by_local_business_day() {
+        let table = Table::parse_tsv(
+            "at\tn\n2024-03-10T22:30:00Z\t1\n\t2\n2024-03-10T21:59:00Z\t3\n",
+            0,
+        )
+        .unwrap();
+        let local = table.convert_timezone("at", "+02:00").unwrap();
+        assert_eq!(
+            cells(&local, "at"),
+            vec!["2024-03-11T00:30:00+02:00", "", "2024-03-10T23:59:00+02:00"]
+        );
+        let days = local.truncate_datetime("at", TimeUnit::Day, "day").unwrap();
+        assert_eq!(
+            cells(&days, "day"),
+            vec!["2024-03-11T00:00:00+02:00", "", "2024-03-10T00:00:00+02:00"]
+        );
+        assert!(table.convert_timezone("at", "Europe/Rome").is_err());
+        let bad = Table::parse_tsv("at\tn\nyesterday\t1\n", 0).unwrap();
+        assert!(bad.truncate_datetime("at", TimeUnit::Hour, "h").is_err());
+    }
+
+    #[test]
+    fn null_policies_follow_sql_or_propagate_per_op() {
+        let table = Table::parse_tsv("k\tn\tm\na\t1\t1\na\t\t\na\t4\t4\n", 0).unwrap();
+        let grouped = |ops: &[Op], nulls| {
+            let table = table.group_by_columns_with(&["k"], ops, nulls).unwrap();
+            let row = |col: &str| table.column(col).unwrap().get(0).to_string();
+            (row("n"), row("m"))
+        };
+        let ops = [
+            Op::aggregate("n", Aggregate::Sum),
+            Op::aggregate("m", Aggregate::Sum).nulls(NullPolicy::Skip),
+        ];
+        assert_eq!(
+            grouped(&ops, NullPolicy::Skip),
+            ("5".to_string(), "5".to_string())
+        );
+        assert_eq!(
+            grouped(&ops, NullPolicy::Propagate),
+            ("".to_string(), "5".to_string())
+        );
+        let ops = [
+            Op::aggregate("n", Aggregate::Count),
+            Op::aggregate("m", Aggregate::Count).nulls(NullPolicy::Skip),
+        ];
+        assert_eq!(
+            grouped(&ops, NullPolicy::Propagate),
+            ("3".to_string(), "2".to_string())
+        );
+    }
+
+    #[test]
+    fn group_previews_take_the_first_rows_of_each_group() {
+        let table = Table::parse_tsv("c\tn\nb\t1\na\t2\nb\t3\nb\t4\nc\t5\na\t6\n", 0).unwrap();
+        let preview = table.preview_groups("c", 2).unwrap();
+        assert_eq!(cells(&preview, "c"), vec!["a", "a", "b", "b", "c"]);
+        assert_eq!(cells(&preview, "n"), vec!["2", "6", "1", "3", "5"]);
+        assert_eq!(table.preview_groups("c", 0).unwrap().rows_count(), 0);
+    }
+
+    #[test]
+    fn outliers_are_flagged_against_their_own_partition() {
+        let table = Table::parse_tsv(
+            "g\tn\na\t1\na\t2\na\t3\na\t50\nb\t50\nb\t51\nb\t52\nb\t49\n",
+            0,
+        )
+        .unwrap();
+        let flagged = table
+            .flag_outliers("n", Method::IqrFactor(1.5), "out")
+            .unwrap();
+        assert_eq!(cells(&flagged, "out"), vec!["false"; 8]);
+        let flagged = table
+            .flag_outliers_over("g", "n", Method::IqrFactor(1.5), "out")
+            .unwrap();
+        let expected = [
+            "false", "false", "false", "true", "false", "false", "false", "false",
+        ];
+        assert_eq!(cells(&flagged, "out"), expected);
+        let text = Table::parse_tsv("n\n1\nx\n", 0).unwrap();
+        assert!(text.flag_outliers("n", Method::ZScore(3.0), "out").is_err());
+    }
+
+    #[test]
+    fn column_profiles_count_lengths_classes_and_shapes() {
+        let table = Table::parse_tsv("code\tn\nAB-12\t1\nCD-34\t2\n\t3\nx1\t4\n", 0).unwrap();
+        let profile = table.profile_column("code").unwrap();
+        let rows: Vec<String> = (0..profile.rows_count())
+            .map(|row| {
+                ["measure", "value", "count"]
+                    .iter()
+                    .map(|col| profile.column(col).unwrap().get(row).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                "nulls  1",
+                "length 2 1",
+                "length 5 2",
+                "chars upper 4",
+                "chars lower 1",
+                "chars digit 5",
+                "chars space 0",
+                "chars punct 2",
+                "chars other 0",
+                "shape AA-99 2",
+                "shape a9 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn columns_come_out_in_header_order() {
+        let table = Table::parse_tsv("z\ta\tm\n1\t2\t3\n", 0).unwrap();
+        assert_eq!(table.column_names(), vec!["z", "a", "m"]);
+        assert_eq!(table.to_tsv_all().unwrap(), "z\ta\tm\n1\t2\t3");
+        let renamed = table.rename_column("a", "b").unwrap();
+        assert_eq!(renamed.column_names(), vec!["z", "b", "m"]);
+        let notes = Table::parse_tsv("note\tid\nlate\to2\n", 0).unwrap();
+        let joined = orders().join_on_columns("order", &notes, "id").unwrap();
+        assert_eq!(joined.column_names(), vec!["a", "b", "order", "note", "id"]);
+    }
+
+    #[test]
+    fn lineage_traces_output_columns_to_named_sources() {
+        let lineage = |table: &Table, col_name: &str| -> Vec<String> {
+            let sources = table.column_lineage(col_name).unwrap();
+            sources
+                .iter()
+                .map(|s| format!("{}.{}", s.table().unwrap_or("?"), s.column()))
+                .collect()
+        };
+        let orders = orders().with_name("orders");
+        let customers = customers().with_name("customers");
+        assert_eq!(lineage(&orders, "order"), vec!["orders.order"]);
+        assert_eq!(
+            lineage(&orders.with_name("renamed"), "order"),
+            vec!["renamed.order"]
+        );
+
+        let joined = orders
+            .join_on_columns_with("a", &customers, "a", JoinCollision::KeepLeft)
+            .unwrap()
+            .with_name("report");
+        assert_eq!(lineage(&joined, "name"), vec!["customers.name"]);
+        assert_eq!(lineage(&joined, "a"), vec!["customers.a", "orders.a"]);
+        assert_eq!(lineage(&joined, "b"), vec!["orders.b"]);
+
+        let derived = joined
+            .create_column(MiOp::new(
+                &["order", "name"],
+                "label",
+                Box::new(|args| args.join("-")),
+            ))
+            .unwrap();
+        assert_eq!(
+            lineage(&derived, "label"),
+            vec!["customers.name", "orders.order"]
+        );
+        let grouped = derived
+            .group_by_column("b", &[Op::aggregate("label", Aggregate::Count)])
+            .unwrap();
+        assert_eq!(
+            lineage(&grouped, "label"),
+            vec!["customers.name", "orders.order"]
+        );
+        assert_eq!(lineage(&grouped, "b"), vec!["orders.b"]);
+        assert!(grouped.column_lineage("missing").is_err());
+        assert_eq!(
+            lineage(&Table::parse_tsv("x\n1\n", 0).unwrap(), "x"),
+            vec!["?.x"]
+        );
+    }
+
+    #[test]
+    fn point_lookups_read_one_cell_or_row() {
+        let table = orders();
+        assert_eq!(table.cell(2, "order").unwrap(), "o3");
+        assert_eq!(
+            table.cell(4, "order").err().unwrap(),
+            "E006: row 4 does not exist in column 'order'"
+        );
+        assert!(table.cell(0, "missing").is_err());
+        assert_eq!(table.row(3).unwrap().values(), vec!["3", "z", "o4"]);
+        assert_eq!(
+            table.row(4).err().unwrap(),
+            "E096: row 4 does not exist in the table"
+        );
+    }
+
+    struct Item(&'static str, u32);
+
+    impl ToRow for Item {
+        fn column_names() -> Vec<&'static str> {
+            vec!["name", "qty"]
+        }
+
+        fn to_row(&self) -> Vec<String> {
+            vec![self.0.to_string(), self.1.to_string()]
+        }
+    }
+
+    #[test]
+    fn tables_build_from_rows_and_records() {
+        let rows = vec![
+            vec!["a".to_string(), "1".to_string()],
+            vec!["b".to_string(), "2".to_string()],
+        ];
+        let table = Table::from_rows(&["name", "qty"], rows).unwrap();
+        assert_eq!(cells(&table, "qty"), vec!["1", "2"]);
+        assert!(Table::from_rows(&["name", "qty"], vec![vec!["a".to_string()]]).is_err());
+
+        let items = [Item("a", 1), Item("b", 2)];
+        let collected =
+            Table::from_records(items.iter().map(|item| Item(item.0, item.1 * 10))).unwrap();
+        assert_eq!(collected.column_names(), vec!["name", "qty"]);
+        assert_eq!(cells(&collected, "qty"), vec!["10", "20"]);
+        assert_eq!(
+            cells(&Table::from_structs(&items).unwrap(), "name"),
+            vec!["a", "b"]
+        );
+        let empty = Table::from_records(std::iter::empty::<Item>()).unwrap();
+        assert_eq!((empty.rows_count(), empty.columns_count()), (0, 2));
+    }
+
+    #[test]
+    fn pushed_rows_append_in_place_without_touching_clones() {
+        let mut table = orders();
+        let before = table.clone();
+        table
+            .push_row(vec!["4".into(), "w".into(), "o5".into()])
+            .unwrap();
+        let err = table.push_row(vec!["5".into(), "v".into()]);
+        assert_eq!(
+            err.err().unwrap(),
+            "E008: row of length 2 given but it should be 3"
+        );
+        let err = table.extend_rows(vec![
+            vec!["5".into(), "v".into(), "o6".into()],
+            vec!["6".into()],
+            vec!["7".into(), "u".into(), "o8".into()],
+        ]);
+        assert!(err.err().unwrap().starts_with("E008: "));
+        assert_eq!(cells(&table, "order"), ["o1", "o2", "o3", "o4", "o5", "o6"]);
+        assert_eq!(cells(&table, "a"), ["1", "2", "1", "3", "4", "5"]);
+        assert_eq!(cells(&before, "order"), ["o1", "o2", "o3", "o4"]);
+    }
+
+    #[test]
+    fn retained_rows_filter_in_place_and_drop_stale_indexes() {
+        let mut table = orders();
+        let before = table.clone();
+        let names = Table::parse_tsv("a\tname\n1\tone\n2\ttwo\n3\tthree\n", 0).unwrap();
+        table.join_on_columns("a", &names, "a").unwrap();
+        names.join_on_columns("a", &table, "a").unwrap();
+        table
+            .retain_rows(&["b", "order"], |cells| cells[0] != "x" || cells[1] == "o3")
+            .unwrap();
+        assert_eq!(cells(&table, "order"), ["o2", "o3", "o4"]);
+        // the row order of a join depends on the indexed side, compare the pairs
+        let pairs = |joined: Table| {
+            let mut pairs: Vec<_> = cells(&joined, "order")
+                .into_iter()
+                .zip(cells(&joined, "name"))
+                .collect();
+            pairs.sort();
+            pairs
+        };
+        let expected = [("o2", "two"), ("o3", "one"), ("o4", "three")]
+            .iter()
+            .map(|&(o, n)| (o.to_string(), n.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pairs(names.join_on_columns("a", &table, "a").unwrap()),
+            expected
+        );
+        assert_eq!(
+            pairs(table.join_on_columns("a", &names, "a").unwrap()),
+            expected
+        );
+        let err = table.retain_rows(&["nope"], |_| true);
+        assert_eq!(err.err().unwrap(), "E001: column 'nope' does not exist");
+        assert_eq!(cells(&before, "order"), ["o1", "o2", "o3", "o4"]);
+    }
+
+    #[test]
+    fn cells_and_columns_update_in_place() {
+        let mut table = orders()
+            .with_primary_key(&["order"])
+            .unwrap()
+            .with_name("orders");
+        let before = table.clone();
+        table.set_cell("b", 1, "w").unwrap();
+        let err = table.set_cell("order", 0, "o2");
+        assert_eq!(
+            err.err().unwrap(),
+            "E046: duplicate primary key at rows 0 and 1"
+        );
+        let err = table.set_cell("b", 4, "w");
+        assert_eq!(
+            err.err().unwrap(),
+            "E006: row 4 does not exist in column 'b'"
+        );
+        assert_eq!(cells(&table, "b"), ["x", "w", "x", "z"]);
+        assert_eq!(cells(&table, "order"), ["o1", "o2", "o3", "o4"]);
+
+        let a = Column::new(
+            ["9", "8", "7", "6"]
+                .iter()
+                .map(|&c| Value::from(c))
+                .collect(),
+        );
+        table.replace_column("a", a).unwrap();
+        assert_eq!(cells(&table, "a"), ["9", "8", "7", "6"]);
+        let sources = table.column_lineage("a").unwrap();
+        assert_eq!(sources[0].table(), Some("orders"));
+        let short = Column::new(vec![Value::from("1")]);
+        let err = table.replace_column("a", short);
+        assert_eq!(
+            err.err().unwrap(),
+            "E106: column of length 1 given but the table has 4 rows"
+        );
+        let err = table.replace_column("nope", Column::new(vec![]));
+        assert_eq!(err.err().unwrap(), "E001: column 'nope' does not exist");
+        assert_eq!(cells(&before, "a"), ["1", "2", "1", "3"]);
+        assert_eq!(cells(&before, "b"), ["x", "y", "x", "z"]);
+    }
+
+    #[test]
+    fn in_place_derivations_add_or_replace_one_column() {
+        let mut table = orders()
+            .with_primary_key(&["order"])
+            .unwrap()
+            .with_name("orders");
+        let before = table.clone();
+        table
+            .map_column_in_place("b", |b| b.to_uppercase())
+            .unwrap();
+        table.create_fixed_column_in_place("source", "web").unwrap();
+        table
+            .create_column_in_place(MiOp::new(
+                &["b", "order"],
+                "label",
+                Box::new(|args| args.join("-")),
+            ))
+            .unwrap();
+        assert_eq!(cells(&table, "b"), ["X", "Y", "X", "Z"]);
+        assert_eq!(cells(&table, "source"), ["web"; 4]);
+        assert_eq!(cells(&table, "label"), ["X-o1", "Y-o2", "X-o3", "Z-o4"]);
+        let sources = table.column_lineage("label").unwrap();
+        let sources: Vec<&str> = sources.iter().map(|s| s.column()).collect();
+        assert_eq!(sources, ["b", "order"]);
+
+        let err = table.map_column_in_place("order", |_| "o".to_string());
+        assert!(err.err().unwrap().starts_with("E046: "));
+        let err = table.create_fixed_column_in_place("order", "o");
+        assert!(err.err().unwrap().starts_with("E046: "));
+        assert_eq!(cells(&table, "order"), ["o1", "o2", "o3", "o4"]);
+        assert_eq!(cells(&before, "b"), ["x", "y", "x", "z"]);
+        assert!(before.column("label").is_err());
+    }
+
+    #[test]
+    fn tables_cross_threads_and_share_one_index() {
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<Table>();
+        send_sync::<Column>();
+        send_sync::<Value>();
+
+        static CACHED: std::sync::OnceLock<Table> = std::sync::OnceLock::new();
+        let table = CACHED.get_or_init(orders);
+        let names = Table::parse_tsv("a\tname\n1\tone\n2\ttwo\n3\tthree\n", 0).unwrap();
+        let counts: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let column = table.column("a").unwrap();
+                        column.get_index();
+                        table
+                            .join_on_columns("a", &names, "a")
+                            .unwrap()
+                            .rows_count()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(counts, [4; 4]);
+        assert!(table.column("a").unwrap().has_index());
+    }
+
+    #[test]
+    fn groups_come_out_in_order_of_first_occurrence() {
+        let keys = ["k7", "k2", "k9", "k2", "k0", "k7", "k5", "k1", "k9", "k3"];
+        let mut text = String::from("k\tn\tv\n");
+        for (i, k) in keys.iter().enumerate() {
+            text.push_str(&format!("{}\t{}\t{}\n", k, i % 2, i));
+        }
+        let mut table = Table::parse_tsv(&text, 0).unwrap();
+        let ops = [Op::aggregate("v", Aggregate::Count)];
+        let first_seen = ["k7", "k2", "k9", "k0", "k5", "k1", "k3"];
+        let grouped = table.group_by_column("k", &ops).unwrap();
+        assert_eq!(cells(&grouped, "k"), first_seen);
+        assert_eq!(cells(&grouped, "v"), ["2", "2", "2", "1", "1", "1", "1"]);
+
+        let expected = cells(&table.group_by_columns(&["k", "n"], &ops).unwrap(), "k");
+        assert_eq!(expected, ["k7", "k2", "k9", "k0", "k7", "k5", "k1", "k3"]);
+        table.create_index_multi(&["k", "n"]).unwrap();
+        let indexed = table.group_by_columns(&["k", "n"], &ops).unwrap();
+        assert_eq!(cells(&indexed, "k"), expected);
+    }
+
+    #[test]
+    fn tsv_output_defaults_to_every_column_in_table_order() {
+        let table = orders().create_fixed_column("source", "web").unwrap();
+        assert_eq!(
+            table.to_tsv_all().unwrap(),
+            "a\tb\torder\tsource\n1\tx\to1\tweb\n2\ty\to2\tweb\n1\tx\to3\tweb\n3\tz\to4\tweb"
+        );
+        assert_eq!(
+            table.to_tsv_columns(&["order", "a"]).unwrap(),
+            "order\ta\no1\t1\no2\t2\no3\t1\no4\t3"
+        );
+        let err = table.to_tsv_columns(&["nope"]);
+        assert_eq!(err.err().unwrap(), "E001: column 'nope' does not exist");
+
+        let path = write_temp("tsv-all.tsv", "");
+        table.write_tsv_file_all(&path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            table.to_tsv_all().unwrap()
+        );
+        table.write_tsv_file_columns(&path, &["b"]).unwrap();
+        let reloaded = Table::load_tsv(&path, 0).unwrap();
+        assert_eq!(reloaded.column_names(), ["b"]);
+        assert_eq!(cells(&reloaded, "b"), ["x", "y", "x", "z"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn extract_json_pulls_fields_and_reports_bad_cells() {
+        let text = "id\tdoc\n1\t{\"user\":{\"id\":7,\"tags\":[\"a\"]}}\n2\t\n3\t{\"user\":null}\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        let extracted = table.extract_json("doc", "$.user.id", "user_id").unwrap();
+        assert_eq!(cells(&extracted, "user_id"), ["7", "", ""]);
+        let tags = table.extract_json("doc", "$.user.tags", "tags").unwrap();
+        assert_eq!(cells(&tags, "tags"), ["[\"a\"]", "", ""]);
+        let broken = Table::parse_tsv("id\tdoc\n1\t{\"a\":\n", 0).unwrap();
+        let err = broken.extract_json("doc", "$.a", "a").err().unwrap();
+        assert_eq!(
+            err,
+            "E004: row 0 of column 'doc': E067: invalid JSON at line 1, column 5"
+        );
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypted_columns_decrypt_back_and_keep_nulls() {
+        let table = Table::parse_tsv("id\tssn\n1\t123-45\n2\t\n3\t987-65\n", 0).unwrap();
+        let key = [42u8; 16];
+        let sealed = table.encrypt_column("ssn", &key).unwrap();
+        let ssn = cells(&sealed, "ssn");
+        assert!(ssn[0] != "123-45" && ssn[1].is_empty() && ssn[2] != ssn[0]);
+        assert_eq!(cells(&sealed, "id"), ["1", "2", "3"]);
+        let opened = sealed.decrypt_column("ssn", &key).unwrap();
+        assert_eq!(cells(&opened, "ssn"), ["123-45", "", "987-65"]);
+        let err = sealed.decrypt_column("ssn", &[1; 16]).err().unwrap();
+        assert_eq!(
+            err,
+            "E004: row 0 of column 'ssn': E060: invalid encrypted data or wrong key"
+        );
+        assert!(table.encrypt_column("ssn", b"short").is_err());
+    }
+
+    #[test]
+    fn join_key_stats_count_both_sides_and_leave_the_indexes_built() {
+        let left = Table::parse_tsv("k\tv\nx\t1\ny\t2\nx\t3\nz\t4\n", 0).unwrap();
+        let right = Table::parse_tsv("id\nz\ny\nx\nq\n", 0).unwrap();
+        let stats = left.join_key_stats("k", &right, "id", 5).unwrap();
+        assert_eq!(
+            stats.column_names(),
+            ["key", "rows_self", "rows_other", "output_rows"]
+        );
+        // y and z tie on output rows and come in key order
+        assert_eq!(cells(&stats, "key"), ["x", "y", "z"]);
+        assert_eq!(cells(&stats, "rows_self"), ["2", "1", "1"]);
+        assert_eq!(cells(&stats, "rows_other"), ["1", "1", "1"]);
+        assert!(left.column("k").unwrap().has_index());
+        assert!(right.column("id").unwrap().has_index());
+        let err = left.join_key_stats("k", &right, "nope", 5).err().unwrap();
+        assert_eq!(err, "E001: column 'nope' does not exist");
+    }
+
+    #[test]
+    fn filter_expressions_compare_numbers_text_and_columns() {
+        let text =
+            "amount\tregion\tlimit\n150\tUS\t200\n90\tUS\t50\n1000\tEU\t10\n101\tAPAC\t100\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        let kept = |expr: &str| cells(&table.filter_expr(expr).unwrap(), "amount");
+        assert_eq!(kept("amount > 100 AND region != 'EU'"), ["150", "101"]);
+        assert_eq!(
+            kept("(region = 'EU' OR amount < 100) AND NOT amount = 90"),
+            ["1000"]
+        );
+        // numbers compare as numbers, columns can be compared with each other
+        assert_eq!(kept("amount > limit"), ["90", "1000", "101"]);
+        assert_eq!(kept("region >= 'EU'"), ["150", "90", "1000"]);
+        let err = table.filter_expr("amount > missing").err().unwrap();
+        assert_eq!(err, "E001: column 'missing' does not exist");
+        let err = table.filter_expr("amount > 100 region").err().unwrap();
+        assert!(
+            err.starts_with("E066: invalid expression at position "),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn regexes_filter_rows_and_capture_groups_into_columns() {
+        let text = "line\n2024-01-05 ERROR disk full\n2024-01-05 INFO started\nnoise\n2024-02-11 ERROR timeout\n";
+        let logs = Table::parse_tsv(text, 0).unwrap();
+        let errors = logs
+            .filter_column_regex("line", r"^\d{4}-\d\d-\d\d ERROR ")
+            .unwrap();
+        assert_eq!(errors.rows_count(), 2);
+        let parsed = logs
+            .extract_regex(
+                "line",
+                r"^(\d{4})-(\d\d)-\d\d (\w+) (.*)$",
+                &["year", "month", "level", "msg"],
+            )
+            .unwrap();
+        assert_eq!(cells(&parsed, "year"), ["2024", "2024", "", "2024"]);
+        assert_eq!(cells(&parsed, "level"), ["ERROR", "INFO", "", "ERROR"]);
+        assert_eq!(
+            cells(&parsed, "msg"),
+            ["disk full", "started", "", "timeout"]
+        );
+        let err = logs
+            .extract_regex("line", r"(\d+)-(\d+)", &["a"])
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            "E042: the regular expression has 2 groups but 1 columns were given"
+        );
+        assert!(logs.filter_column_regex("line", "(").is_err());
+    }
+
+    #[test]
+    fn asof_and_interval_joins_skip_null_keys_on_both_sides() {
+        // the order would panic on an empty cell, so nulls must not reach it
+        let numeric = |a: &str, b: &str| a.parse::<i64>().unwrap().cmp(&b.parse().unwrap());
+        let trades = Table::parse_tsv("t\tqty\n9\ta\n\tb\n10\tc\n", 0).unwrap();
+        let quotes = Table::parse_tsv("qt\tprice\n8\tp8\n\tnull\n10\tp10\n", 0).unwrap();
+        let joined = trades
+            .asof_join_on_columns_by("t", &quotes, "qt", JoinCollision::Error, numeric)
+            .unwrap();
+        assert_eq!(cells(&joined, "qty"), ["a", "c"]);
+        assert_eq!(cells(&joined, "price"), ["p8", "p10"]);
+
+        let events = Table::parse_tsv("at\tname\n3\te3\n\tnull\n7\te7\n", 0).unwrap();
+        let text = "from\tto\tperiod\n1\t\topen\n\t9\tunknown\n2\t8\tp2\n";
+        let periods = Table::parse_tsv(text, 0).unwrap();
+        let joined = events
+            .interval_join_on_columns_by(
+                "at",
+                &periods,
+                "from",
+                "to",
+                JoinCollision::Error,
+                numeric,
+            )
+            .unwrap();
+        assert_eq!(cells(&joined, "name"), ["e3", "e7"]);
+        assert_eq!(cells(&joined, "period"), ["p2", "p2"]);
+    }
+
+    #[test]
+    fn column_checks_report_duplicate_and_empty_positions() {
+        let table = Table::parse_tsv("id\tname\na\tx\n\t\nb\ty\na\t\n\tz\n", 0).unwrap();
+        // repeated nulls are not duplicates
+        let report = table.assert_unique("id").err().unwrap();
+        let found: Vec<(Option<usize>, &str, &str)> = report
+            .violations
+            .iter()
+            .map(|v| (v.row, v.value.as_str(), v.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Some(0), "a", "E048: duplicate value at row 3"),
+                (Some(3), "a", "E048: duplicate value at row 0"),
+            ]
+        );
+        assert!(table.assert_unique("name").is_ok());
+
+        let report = table.assert_non_empty("name").err().unwrap();
+        let rows: Vec<Option<usize>> = report.violations.iter().map(|v| v.row).collect();
+        assert_eq!(rows, [Some(1), Some(3)]);
+        assert_eq!(report.violations[0].message, "E050: empty value");
+        let filled = table.filter_expr("name != ''").unwrap();
+        assert!(filled.assert_non_empty("name").is_ok());
+
+        let report = table.assert_non_empty("missing").err().unwrap();
+        assert_eq!(report.violations[0].row, None);
+        assert_eq!(report.violations[0].message, "E051: missing column");
+    }
+
+    #[test]
+    fn derived_columns_cannot_break_the_primary_key() {
+        let table = Table::parse_tsv("id\tname\n1\tAnn\n2\tann\n", 0)
+            .unwrap()
+            .with_primary_key(&["id"])
+            .unwrap();
+        let err = table.create_fixed_column("id", "x").err().unwrap();
+        assert_eq!(err, "E046: duplicate primary key at rows 0 and 1");
+        let lower = table
+            .map_column("name", |cell| cell.to_lowercase())
+            .unwrap();
+        let err = lower.concatenate_columns("name", ' ', "name", "id");
+        assert!(err.err().unwrap().starts_with("E046: "));
+        // replacing a key column with unique values, or writing any other column, is fine
+        let numbered = table.with_row_number("id").unwrap();
+        assert_eq!(cells(&numbered, "id"), ["1", "2"]);
+        let fixed = table.create_fixed_column("source", "web").unwrap();
+        assert_eq!(cells(&fixed, "source"), ["web", "web"]);
+    }
+
+    #[test]
+    fn near_duplicates_cluster_rows_entered_twice_with_a_typo() {
+        let text = "name\tcity\nJohn Smith\tRome\nMaria Rossi\tMilan\njohn  smith.\tRome\nJon Smith\tRome\nJohn Smith\tTurin\n";
+        let table = Table::parse_tsv(text, 0).unwrap();
+        let clustered = table
+            .near_duplicates(&["name", "city"], 0.6, "cluster")
+            .unwrap();
+        let ids = cells(&clustered, "cluster");
+        // case, punctuation and spacing are normalized away, a typo keeps the row close
+        assert_eq!(ids[0], ids[2]);
+        assert_eq!(ids[0], ids[3]);
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[0], ids[4]);
+        assert_ne!(ids[1], ids[4]);
+        let exact = table
+            .near_duplicates(&["name", "city"], 1.0, "cluster")
+            .unwrap();
+        let ids = cells(&exact, "cluster");
+        assert_eq!(ids[0], ids[2]);
+        assert_ne!(ids[0], ids[3]);
+        let err = table
+            .near_duplicates(&["name"], 1.5, "cluster")
+            .err()
+            .unwrap();
+        assert_eq!(err, "E032: threshold 1.5 is not between 0 and 1");
+    }
+
+    #[test]
+    fn validated_loads_split_clean_rows_from_quarantined_ones() {
+        let path = write_temp(
+            "quarantine",
+            "id\tqty\tcode\n1\t5\tA\n2\t50\tB\n3\t-1\tQ\n4\t9\tB\n",
+        );
+        let rules = Rules::new().between("qty_range", "qty", 0.0, 10.0).in_set(
+            "known_code",
+            "code",
+            &["A", "B"],
+        );
+        let (clean, quarantine) = Table::load_tsv_validated(&path, &rules).unwrap();
+        assert_eq!(cells(&clean, "id"), ["1", "4"]);
+        assert_eq!(cells(&quarantine, "id"), ["2", "3"]);
+        assert_eq!(cells(&quarantine, "row"), ["1", "2"]);
+        // every broken rule of a row is listed
+        assert_eq!(
+            cells(&quarantine, "reasons"),
+            [
+                "qty_range: E093: value is not between 0 and 10",
+                "qty_range: E093: value is not between 0 and 10; \
+                 known_code: E092: value is not one of the allowed values",
+            ]
+        );
+
+        let all_clean = Rules::new().in_set("known_code", "code", &["A", "B", "Q"]);
+        let (clean, quarantine) = Table::load_tsv_validated(&path, &all_clean).unwrap();
+        assert_eq!(clean.rows_count(), 4);
+        assert_eq!(quarantine.rows_count(), 0);
+        std::fs::remove_file(path).unwrap();
+
+        let path = write_temp("quarantine_row", "id\trow\n1\t2\n");
+        let err = Table::load_tsv_validated(&path, &rules).err().unwrap();
+        assert_eq!(err, "E002: column 'row' already exists");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn dictionary_columns_group_and_join_like_plain_ones() {
+        let countries = ["it", "fr", "de", ""];
+        let mut sales = String::from("country\tcity\tamount\n");
+        for row in 0..200 {
+            let city = if row % 3 == 0 { "a" } else { "b" };
+            sales += &format!("{}\t{}\t{}\n", countries[row % 4], city, row);
+        }
+        let mut rates = String::from("code\trate\n");
+        for row in 0..80 {
+            rates += &format!("{}\t{}\n", ["fr", "es", "", "it"][row % 4], row % 5);
+        }
+        let encoded = Table::parse_tsv(&sales, 0).unwrap();
+        let plain = Table::parse_tsv_with(&sales, &LoadOptions::new().plain_cells()).unwrap();
+        let encoded_rates = Table::parse_tsv(&rates, 0).unwrap();
+        let plain_rates = Table::parse_tsv_with(&rates, &LoadOptions::new().plain_cells()).unwrap();
+        assert!(encoded.column("country").unwrap().is_dictionary());
+        assert!(encoded_rates.column("code").unwrap().is_dictionary());
+        assert!(!plain.column("country").unwrap().is_dictionary());
+
+        // a filtered copy keeps "de" in its dictionary with no row using it
+        let encoded = encoded.filter_column("country", |c| c != "de").unwrap();
+        let plain = plain.filter_column("country", |c| c != "de").unwrap();
+        let ops = [Op::aggregate("amount", Aggregate::Sum)];
+        for keys in [&["country"][..], &["country", "city"], &["city", "country"]] {
+            let grouped = encoded.group_by_columns(keys, &ops).unwrap();
+            let expected = plain.group_by_columns(keys, &ops).unwrap();
+            for col_name in ["country", "city", "amount"] {
+                assert_eq!(cells(&grouped, col_name), cells(&expected, col_name));
+            }
+        }
+        let grouped = encoded.group_by_columns(&["country"], &ops).unwrap();
+        assert_eq!(cells(&grouped, "country"), ["it", "fr", ""]);
+
+        let joined = encoded
+            .join_on_columns("country", &encoded_rates, "code")
+            .unwrap();
+        let expected = plain
+            .join_on_columns("country", &plain_rates, "code")
+            .unwrap();
+        assert_eq!(joined.rows_count(), 100 * 20);
+        for col_name in ["country", "amount", "rate"] {
+            assert_eq!(cells(&joined, col_name), cells(&expected, col_name));
         }
+        assert!(!encoded_rates.has_index("code").unwrap());
+        assert!(cells(&joined, "country")
+            .iter()
+            .all(|c| c == "it" || c == "fr"));
     }
 }
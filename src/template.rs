@@ -0,0 +1,171 @@
+use super::messages::{self, Message};
+use super::table::{Res, Table};
+
+enum Node {
+    Text(String),
+    Cell(String),
+    // the body once per row, or once when the table has no rows if inverted
+    Rows(Vec<Node>, bool),
+}
+
+//   Invoices:
+//   {{#rows}}
+//   - {{id}}: {{amount}}
+//   {{/rows}}
+//   {{^rows}}
+//   no invoices
+//   {{/rows}}
+// {{! comments }} are dropped, lines holding only a section or comment tag are removed
+pub(crate) fn render(template: &str, table: &Table) -> Res<String> {
+    let nodes = parse(template)?;
+    let mut output = String::with_capacity(template.len());
+    render_nodes(&nodes, table, None, &mut output)?;
+    Ok(output)
+}
+
+fn render_nodes(nodes: &[Node], table: &Table, row: Option<usize>, output: &mut String) -> Res<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Cell(col_name) => match row {
+                Some(row) => output.push_str(table.cell(row, col_name)?),
+                None => return Err(messages::text(Message::CellOutsideRows, &[col_name])),
+            },
+            Node::Rows(body, false) => {
+                for row in 0..table.rows_count() {
+                    render_nodes(body, table, Some(row), output)?;
+                }
+            }
+            Node::Rows(body, true) => {
+                if table.rows_count() == 0 {
+                    render_nodes(body, table, row, output)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse(template: &str) -> Res<Vec<Node>> {
+    // open sections with the nodes collected before them
+    let mut stack: Vec<(Vec<Node>, bool)> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let offset = template.len() - rest.len() + start;
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| messages::text(Message::UnclosedTag, &[&offset]))?;
+        let tag = rest[start + 2..start + end].trim();
+        let mut text = &rest[..start];
+        let mut after = &rest[start + end + 2..];
+        let kind = tag.chars().next().filter(|c| "#^/!".contains(*c));
+        if kind.is_some() {
+            if let Some((line_start, line_end)) = standalone(text, after) {
+                text = line_start;
+                after = line_end;
+            }
+        }
+        if !text.is_empty() {
+            nodes.push(Node::Text(text.to_string()));
+        }
+        let name = if kind.is_some() { tag[1..].trim() } else { tag };
+        match kind {
+            Some('!') => {}
+            Some('#') | Some('^') => {
+                section_name(name, offset)?;
+                stack.push((std::mem::take(&mut nodes), kind == Some('^')));
+            }
+            Some(_) => {
+                section_name(name, offset)?;
+                let (outer, inverted) = stack
+                    .pop()
+                    .ok_or_else(|| messages::text(Message::UnopenedSection, &[&offset]))?;
+                let body = std::mem::replace(&mut nodes, outer);
+                nodes.push(Node::Rows(body, inverted));
+            }
+            None => nodes.push(Node::Cell(tag.to_string())),
+        }
+        rest = after;
+    }
+    if !stack.is_empty() {
+        return Err(messages::text(Message::UnclosedSection, &[]));
+    }
+    if !rest.is_empty() {
+        nodes.push(Node::Text(rest.to_string()));
+    }
+    Ok(nodes)
+}
+
+fn section_name(name: &str, offset: usize) -> Res<()> {
+    if name == "rows" {
+        Ok(())
+    } else {
+        Err(messages::text(Message::UnknownSection, &[&name, &offset]))
+    }
+}
+
+// the text around a tag without the tag's line, when nothing else is on it
+fn standalone<'t>(before: &'t str, after: &'t str) -> Option<(&'t str, &'t str)> {
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    if !before[line_start..].trim().is_empty() {
+        return None;
+    }
+    let line_end = match after.find('\n') {
+        Some(i) => i + 1,
+        None => after.len(),
+    };
+    if !after[..line_end].trim().is_empty() {
+        return None;
+    }
+    Some((&before[..line_start], &after[line_end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVOICES: &str = "Invoices:\n{{#rows}}\n- {{id}}: {{ amount }}\n{{/rows}}\n{{^rows}}\nno invoices\n{{/rows}}\n{{! end }}\nbye";
+
+    #[test]
+    fn sections_repeat_per_row_and_their_lines_disappear() {
+        let table = Table::parse_tsv("id\tamount\nA1\t10\nB2\t7\n", 0).unwrap();
+        assert_eq!(
+            render(INVOICES, &table).unwrap(),
+            "Invoices:\n- A1: 10\n- B2: 7\nbye"
+        );
+        let empty = Table::parse_tsv("id\tamount\n", 0).unwrap();
+        assert_eq!(
+            render(INVOICES, &empty).unwrap(),
+            "Invoices:\nno invoices\nbye"
+        );
+        assert_eq!(
+            render("{{#rows}}{{id}},{{/rows}}", &table).unwrap(),
+            "A1,B2,"
+        );
+    }
+
+    #[test]
+    fn malformed_templates_and_unknown_columns_are_errors() {
+        let table = Table::parse_tsv("id\nA1\n", 0).unwrap();
+        let err = |template| render(template, &table).err().unwrap();
+        assert_eq!(
+            err("{{id}}"),
+            "E102: column 'id' can only be used inside a rows section"
+        );
+        assert_eq!(err("ab {{id"), "E098: tag opened at offset 3 is not closed");
+        assert_eq!(
+            err("{{/rows}}"),
+            "E100: section closed at offset 0 was never opened"
+        );
+        assert_eq!(err("{{#rows}}"), "E101: rows section is not closed");
+        assert_eq!(
+            err("{{#cols}}{{/cols}}"),
+            "E099: unknown section 'cols' at offset 0, only 'rows' is supported"
+        );
+        assert_eq!(
+            err("{{#rows}}{{nope}}{{/rows}}"),
+            "E001: column 'nope' does not exist"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use super::column::Column;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// Auto picks from the data, the other variants force a choice process wide
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GroupStrategy {
+    #[default]
+    Auto,
+    Hash,
+    Sort,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FilterStrategy {
+    #[default]
+    Auto,
+    Index,
+    Scan,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum JoinStrategy {
+    #[default]
+    Auto,
+    IndexLeft,
+    IndexRight,
+}
+
+static GROUP: AtomicU8 = AtomicU8::new(0);
+static FILTER: AtomicU8 = AtomicU8::new(0);
+static JOIN: AtomicU8 = AtomicU8::new(0);
+
+// rows looked at to estimate how many distinct values a column has
+const SAMPLE_ROWS: usize = 1024;
+// sorting beats hashing when most keys are unique
+const SORT_DISTINCT_SHARE: f64 = 0.5;
+// testing each distinct value once beats a scan on long repetitive columns
+const INDEX_MIN_ROWS: usize = 10_000;
+const INDEX_DISTINCT_SHARE: f64 = 0.05;
+
+pub fn set_group_strategy(strategy: GroupStrategy) {
+    GROUP.store(strategy as u8, Ordering::Relaxed);
+}
+
+pub fn set_filter_strategy(strategy: FilterStrategy) {
+    FILTER.store(strategy as u8, Ordering::Relaxed);
+}
+
+pub fn set_join_strategy(strategy: JoinStrategy) {
+    JOIN.store(strategy as u8, Ordering::Relaxed);
+}
+
+pub fn group_strategy() -> GroupStrategy {
+    match GROUP.load(Ordering::Relaxed) {
+        1 => GroupStrategy::Hash,
+        2 => GroupStrategy::Sort,
+        _ => GroupStrategy::Auto,
+    }
+}
+
+pub fn filter_strategy() -> FilterStrategy {
+    match FILTER.load(Ordering::Relaxed) {
+        1 => FilterStrategy::Index,
+        2 => FilterStrategy::Scan,
+        _ => FilterStrategy::Auto,
+    }
+}
+
+pub fn join_strategy() -> JoinStrategy {
+    match JOIN.load(Ordering::Relaxed) {
+        1 => JoinStrategy::IndexLeft,
+        2 => JoinStrategy::IndexRight,
+        _ => JoinStrategy::Auto,
+    }
+}
+
+// the strategies below never return Auto
+
+pub(crate) fn choose_group(key_columns: &[Column]) -> GroupStrategy {
+    match group_strategy() {
+        GroupStrategy::Auto => {
+            if distinct_share(key_columns) > SORT_DISTINCT_SHARE {
+                GroupStrategy::Sort
+            } else {
+                GroupStrategy::Hash
+            }
+        }
+        strategy => strategy,
+    }
+}
+
+pub(crate) fn choose_filter(column: &Column) -> FilterStrategy {
+    match filter_strategy() {
+        FilterStrategy::Auto => {
+            let repetitive = column.len() >= INDEX_MIN_ROWS
+                && distinct_share(std::slice::from_ref(column)) < INDEX_DISTINCT_SHARE;
+            if column.has_index() || repetitive {
+                FilterStrategy::Index
+            } else {
+                FilterStrategy::Scan
+            }
+        }
+        strategy => strategy,
+    }
+}
+
+pub(crate) fn choose_join(left: &Column, right: &Column) -> JoinStrategy {
+    match join_strategy() {
+        // reuse an index if there is one, otherwise index the shorter side
+        JoinStrategy::Auto => {
+            if left.has_index() || (!right.has_index() && left.len() <= right.len()) {
+                JoinStrategy::IndexLeft
+            } else {
+                JoinStrategy::IndexRight
+            }
+        }
+        strategy => strategy,
+    }
+}
+
+// share of distinct keys in the first rows, exact for a single indexed column
+fn distinct_share(columns: &[Column]) -> f64 {
+    let rows = columns.first().map_or(0, Column::len);
+    if rows == 0 {
+        return 0.0;
+    }
+    if let [column] = columns {
+        if column.has_index() {
+            return column.distinct_count() as f64 / rows as f64;
+        }
+    }
+    let sampled = rows.min(SAMPLE_ROWS);
+    let mut keys: Vec<Vec<&str>> = (0..sampled)
+        .map(|position| {
+            columns
+                .iter()
+                .map(|column| column.get(position).as_str())
+                .collect()
+        })
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys.len() as f64 / sampled as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    // the strategies stay on Auto, forcing one would leak into tests running alongside
+    fn column(len: usize, distinct: usize) -> Column {
+        Column::new(
+            (0..len)
+                .map(|i| Value::new((i % distinct).to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn mostly_unique_keys_group_by_sorting() {
+        assert_eq!(choose_group(&[column(100, 100)]), GroupStrategy::Sort);
+        assert_eq!(choose_group(&[column(100, 10)]), GroupStrategy::Hash);
+        // the pair is unique even though each column repeats
+        assert_eq!(
+            choose_group(&[column(100, 10), column(100, 11)]),
+            GroupStrategy::Sort
+        );
+        assert_eq!(choose_group(&[column(0, 1)]), GroupStrategy::Hash);
+    }
+
+    #[test]
+    fn long_repetitive_or_indexed_columns_filter_through_the_index() {
+        assert_eq!(choose_filter(&column(20_000, 3)), FilterStrategy::Index);
+        assert_eq!(choose_filter(&column(20_000, 20_000)), FilterStrategy::Scan);
+        let short = column(100, 3);
+        assert_eq!(choose_filter(&short), FilterStrategy::Scan);
+        short.get_index();
+        assert_eq!(choose_filter(&short), FilterStrategy::Index);
+    }
+
+    #[test]
+    fn joins_reuse_an_index_or_index_the_shorter_side() {
+        let (short, long) = (column(10, 10), column(50, 50));
+        assert_eq!(choose_join(&short, &long), JoinStrategy::IndexLeft);
+        assert_eq!(choose_join(&long, &short), JoinStrategy::IndexRight);
+        long.get_index();
+        assert_eq!(choose_join(&long, &short), JoinStrategy::IndexLeft);
+        assert_eq!(choose_join(&short, &long), JoinStrategy::IndexRight);
+    }
+}
@@ -0,0 +1,304 @@
+use super::aggregate::{checked_sum, Aggregate};
+use super::dates;
+use super::messages::{self, Message};
+use super::schema;
+use super::table::Res;
+use super::value::Value;
+use std::cmp::Ordering;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColumnType {
+    Int64,
+    Float64,
+    Bool,
+    Date,
+    Str,
+}
+
+impl ColumnType {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColumnType::Int64 => "int64",
+            ColumnType::Float64 => "float64",
+            ColumnType::Bool => "bool",
+            ColumnType::Date => "date",
+            ColumnType::Str => "str",
+        }
+    }
+
+    pub fn accepts(self, value: &str) -> bool {
+        match self {
+            ColumnType::Int64 => value.parse::<i64>().is_ok(),
+            ColumnType::Float64 => value.parse::<f64>().is_ok(),
+            ColumnType::Bool => parse_bool(value).is_some(),
+            ColumnType::Date => dates::parse_iso_date(value).is_some(),
+            ColumnType::Str => true,
+        }
+    }
+
+    pub fn infer<'a>(values: impl IntoIterator<Item = &'a str>) -> ColumnType {
+        let mut candidates = vec![
+            ColumnType::Int64,
+            ColumnType::Float64,
+            ColumnType::Bool,
+            ColumnType::Date,
+        ];
+        let mut seen = false;
+        for value in values {
+            if value.is_empty() {
+                continue;
+            }
+            seen = true;
+            candidates.retain(|candidate| candidate.accepts(value));
+            if candidates.is_empty() {
+                break;
+            }
+        }
+        if seen {
+            candidates.first().copied().unwrap_or(ColumnType::Str)
+        } else {
+            ColumnType::Str
+        }
+    }
+}
+
+pub fn parse_bool(value: &str) -> Option<bool> {
+    if value.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if value.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[derive(Clone)]
+pub(crate) enum TypedCells {
+    Int64(Vec<Option<i64>>),
+    Float64(Vec<Option<f64>>),
+    Bool(Vec<Option<bool>>),
+    Date(Vec<Option<i64>>),
+}
+
+impl TypedCells {
+    // coerces every cell to the canonical text of its typed value
+    pub(crate) fn cast<'a>(
+        cells: impl ExactSizeIterator<Item = &'a Value>,
+        column_type: ColumnType,
+    ) -> Res<(Vec<Value>, Option<TypedCells>)> {
+        let mut texts = Vec::with_capacity(cells.len());
+        for (row, cell) in cells.enumerate() {
+            let text = schema::coerce(cell, column_type)
+                .map_err(|err| messages::text(Message::AtRow, &[&row, &err]))?;
+            texts.push(if text == cell.as_str() {
+                cell.clone()
+            } else {
+                Value::new(text)
+            });
+        }
+        let typed = TypedCells::parse(&texts, column_type);
+        Ok((texts, typed))
+    }
+
+    pub(crate) fn parse(cells: &[Value], column_type: ColumnType) -> Option<TypedCells> {
+        fn each<T>(cells: &[Value], parse: impl Fn(&str) -> Option<T>) -> Vec<Option<T>> {
+            cells.iter().map(|cell| parse(cell)).collect()
+        }
+        Some(match column_type {
+            ColumnType::Int64 => TypedCells::Int64(each(cells, |c| c.parse().ok())),
+            ColumnType::Float64 => TypedCells::Float64(each(cells, |c| c.parse().ok())),
+            ColumnType::Bool => TypedCells::Bool(each(cells, parse_bool)),
+            ColumnType::Date => TypedCells::Date(each(cells, dates::parse_iso_date)),
+            ColumnType::Str => return None,
+        })
+    }
+
+    pub(crate) fn column_type(&self) -> ColumnType {
+        match self {
+            TypedCells::Int64(_) => ColumnType::Int64,
+            TypedCells::Float64(_) => ColumnType::Float64,
+            TypedCells::Bool(_) => ColumnType::Bool,
+            TypedCells::Date(_) => ColumnType::Date,
+        }
+    }
+
+    // nulls first, like the empty string in text order
+    pub(crate) fn compare(&self, a: usize, b: usize) -> Ordering {
+        self.compare_with(a, self, b)
+    }
+
+    // other must hold the same type
+    pub(crate) fn compare_with(&self, a: usize, other: &TypedCells, b: usize) -> Ordering {
+        match (self, other) {
+            (TypedCells::Int64(x), TypedCells::Int64(y))
+            | (TypedCells::Date(x), TypedCells::Date(y)) => x[a].cmp(&y[b]),
+            (TypedCells::Bool(x), TypedCells::Bool(y)) => x[a].cmp(&y[b]),
+            (TypedCells::Float64(x), TypedCells::Float64(y)) => match (x[a], y[b]) {
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                (x, y) => x.is_some().cmp(&y.is_some()),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    // None when the aggregate has no native form for this type
+    pub(crate) fn aggregate(
+        &self,
+        aggregate: &Aggregate,
+        positions: &[usize],
+    ) -> Option<Res<String>> {
+        match self {
+            TypedCells::Int64(values) => {
+                let mut numbers = positions.iter().filter_map(|&p| values[p]).peekable();
+                let result = match aggregate {
+                    Aggregate::Sum if numbers.peek().is_none() => None,
+                    Aggregate::Sum => match checked_sum(numbers) {
+                        Ok(sum) => Some(sum),
+                        Err(err) => return Some(Err(err)),
+                    },
+                    Aggregate::Min => numbers.min(),
+                    Aggregate::Max => numbers.max(),
+                    Aggregate::Mean => {
+                        let (sum, count) =
+                            numbers.fold((0.0, 0), |(s, c), n| (s + n as f64, c + 1));
+                        return Some(Ok(mean_text(sum, count)));
+                    }
+                    _ => return None,
+                };
+                Some(Ok(result.map_or(String::new(), |n| n.to_string())))
+            }
+            TypedCells::Float64(values) => {
+                let numbers = positions.iter().filter_map(|&p| values[p]);
+                let result = match aggregate {
+                    Aggregate::Sum => numbers.reduce(|a, b| a + b),
+                    Aggregate::Min => numbers.reduce(f64::min),
+                    Aggregate::Max => numbers.reduce(f64::max),
+                    Aggregate::Mean => {
+                        let (sum, count) = numbers.fold((0.0, 0), |(s, c), n| (s + n, c + 1));
+                        return Some(Ok(mean_text(sum, count)));
+                    }
+                    _ => return None,
+                };
+                Some(Ok(result.map_or(String::new(), |n| n.to_string())))
+            }
+            TypedCells::Bool(_) | TypedCells::Date(_) => None,
+        }
+    }
+
+    pub(crate) fn remap(&self, indices: &[usize]) -> TypedCells {
+        fn pick<T: Copy>(values: &[Option<T>], indices: &[usize]) -> Vec<Option<T>> {
+            indices.iter().map(|&i| values[i]).collect()
+        }
+        match self {
+            TypedCells::Int64(values) => TypedCells::Int64(pick(values, indices)),
+            TypedCells::Float64(values) => TypedCells::Float64(pick(values, indices)),
+            TypedCells::Bool(values) => TypedCells::Bool(pick(values, indices)),
+            TypedCells::Date(values) => TypedCells::Date(pick(values, indices)),
+        }
+    }
+
+    pub(crate) fn retain(&mut self, keep: &[bool]) {
+        fn keep_marked<T>(values: &mut Vec<Option<T>>, keep: &[bool]) {
+            let mut keep = keep.iter();
+            values.retain(|_| *keep.next().unwrap());
+        }
+        match self {
+            TypedCells::Int64(values) => keep_marked(values, keep),
+            TypedCells::Float64(values) => keep_marked(values, keep),
+            TypedCells::Bool(values) => keep_marked(values, keep),
+            TypedCells::Date(values) => keep_marked(values, keep),
+        }
+    }
+
+    pub(crate) fn concat(&self, other: &TypedCells) -> Option<TypedCells> {
+        fn join<T: Copy>(a: &[Option<T>], b: &[Option<T>]) -> Vec<Option<T>> {
+            a.iter().chain(b.iter()).copied().collect()
+        }
+        Some(match (self, other) {
+            (TypedCells::Int64(a), TypedCells::Int64(b)) => TypedCells::Int64(join(a, b)),
+            (TypedCells::Float64(a), TypedCells::Float64(b)) => TypedCells::Float64(join(a, b)),
+            (TypedCells::Bool(a), TypedCells::Bool(b)) => TypedCells::Bool(join(a, b)),
+            (TypedCells::Date(a), TypedCells::Date(b)) => TypedCells::Date(join(a, b)),
+            _ => return None,
+        })
+    }
+
+    // false when the value does not fit the type and the typed cells must be dropped
+    pub(crate) fn set(&mut self, position: usize, value: &str) -> bool {
+        fn put<T>(
+            values: &mut [Option<T>],
+            position: usize,
+            value: &str,
+            parsed: Option<T>,
+        ) -> bool {
+            if parsed.is_none() && !value.is_empty() {
+                return false;
+            }
+            values[position] = parsed;
+            true
+        }
+        match self {
+            TypedCells::Int64(values) => put(values, position, value, value.parse().ok()),
+            TypedCells::Float64(values) => put(values, position, value, value.parse().ok()),
+            TypedCells::Bool(values) => put(values, position, value, parse_bool(value)),
+            TypedCells::Date(values) => put(values, position, value, dates::parse_iso_date(value)),
+        }
+    }
+
+    // false when the value does not fit the type and the typed cells must be dropped
+    pub(crate) fn push(&mut self, value: &str) -> bool {
+        fn add<T>(values: &mut Vec<Option<T>>, value: &str, parsed: Option<T>) -> bool {
+            if parsed.is_none() && !value.is_empty() {
+                return false;
+            }
+            values.push(parsed);
+            true
+        }
+        match self {
+            TypedCells::Int64(values) => add(values, value, value.parse().ok()),
+            TypedCells::Float64(values) => add(values, value, value.parse().ok()),
+            TypedCells::Bool(values) => add(values, value, parse_bool(value)),
+            TypedCells::Date(values) => add(values, value, dates::parse_iso_date(value)),
+        }
+    }
+}
+
+fn mean_text(sum: f64, count: usize) -> String {
+    if count == 0 {
+        String::new()
+    } else {
+        (sum / count as f64).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(cells: &[&str]) -> Vec<Value> {
+        cells.iter().map(|&cell| Value::from(cell)).collect()
+    }
+
+    #[test]
+    fn infer_picks_the_narrowest_type_every_value_fits() {
+        assert_eq!(ColumnType::infer(vec!["1", "", "-3"]), ColumnType::Int64);
+        assert_eq!(ColumnType::infer(vec!["1", "2.5"]), ColumnType::Float64);
+        assert_eq!(ColumnType::infer(vec!["TRUE", "false"]), ColumnType::Bool);
+        assert_eq!(ColumnType::infer(vec!["2024-02-29"]), ColumnType::Date);
+        assert_eq!(ColumnType::infer(vec!["1", "x"]), ColumnType::Str);
+        assert_eq!(ColumnType::infer(vec!["", ""]), ColumnType::Str);
+    }
+
+    #[test]
+    fn cast_rewrites_cells_to_their_canonical_text() {
+        let (cells, typed) =
+            TypedCells::cast(values(&["01", "", "-7"]).iter(), ColumnType::Int64).unwrap();
+        assert_eq!(cells, values(&["1", "", "-7"]));
+        let typed = typed.unwrap();
+        assert_eq!(typed.column_type(), ColumnType::Int64);
+        assert_eq!(typed.compare(1, 2), Ordering::Less);
+        assert_eq!(typed.compare(0, 2), Ordering::Greater);
+        assert!(TypedCells::cast(values(&["1", "x"]).iter(), ColumnType::Int64).is_err());
+        assert!(TypedCells::parse(&values(&["a"]), ColumnType::Str).is_none());
+    }
+}
@@ -1,9 +1,10 @@
 pub use std::borrow::Borrow;
+use std::fmt;
 use std::ops::Deref;
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Value(Rc<str>);
+pub struct Value(Arc<str>);
 
 impl Value {
     pub fn new(string: String) -> Value {
@@ -13,6 +14,14 @@ impl Value {
     pub fn as_str(&self) -> &str {
         self.borrow()
     }
+
+    pub fn null() -> Value {
+        Value::from("")
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl Deref for Value {
@@ -34,3 +43,9 @@ impl Borrow<str> for Value {
         self.0.borrow()
     }
 }
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
@@ -0,0 +1,91 @@
+use super::messages::{self, Message};
+use super::repr::{self, ReprOptions};
+use super::table::{Res, Table};
+use super::value::Value;
+use std::borrow::Cow;
+use std::ops::Range;
+
+// borrows the table, cells are only cloned by to_table
+pub struct TableView<'a> {
+    table: &'a Table,
+    rows: Range<usize>,
+}
+
+impl<'a> TableView<'a> {
+    pub(crate) fn new(table: &'a Table, rows: Range<usize>) -> TableView<'a> {
+        TableView { table, rows }
+    }
+
+    pub fn rows_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn columns_count(&self) -> usize {
+        self.table.columns_count()
+    }
+
+    pub fn column_names(&self) -> Vec<&'a str> {
+        self.table.column_names()
+    }
+
+    pub fn cells(&self, col_name: &str) -> Res<Cow<'a, [Value]>> {
+        let column = self.table.column_ref(col_name)?;
+        Ok(column.cells_range(self.rows.clone()))
+    }
+
+    pub fn cell(&self, row: usize, col_name: &str) -> Res<&'a str> {
+        let column = self.table.column_ref(col_name)?;
+        if row >= self.rows.len() {
+            return Err(messages::text(Message::RowMissingInView, &[&row]));
+        }
+        Ok(column.get(self.rows.start + row).as_str())
+    }
+
+    pub fn to_repr(&self) -> String {
+        let mut columns = Vec::with_capacity(self.columns_count());
+        for col_name in self.column_names() {
+            if let Ok(column) = self.table.column_ref(col_name) {
+                columns.push((col_name, column));
+            }
+        }
+        repr::repr_columns(columns, self.rows.clone(), &ReprOptions::default())
+    }
+
+    pub fn to_table(&self) -> Table {
+        self.table.slice(self.rows.start, self.rows.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        Table::parse_tsv("n\tl\n0\ta\n1\tb\n2\tc\n3\td\n", 0).unwrap()
+    }
+
+    #[test]
+    fn views_borrow_the_rows_of_their_range() {
+        let table = table();
+        let view = table.view_rows(1..3);
+        assert_eq!(view.rows_count(), 2);
+        assert_eq!(view.cell(0, "l").unwrap(), "b");
+        let cells = view.cells("n").unwrap();
+        assert!(matches!(cells, Cow::Borrowed(_)));
+        assert_eq!(cells.as_ref(), &[Value::from("1"), Value::from("2")]);
+        assert_eq!(
+            view.cell(2, "l").err().unwrap(),
+            "E007: row 2 does not exist in the view"
+        );
+        assert!(view.cell(0, "missing").is_err());
+        assert_eq!(view.to_repr(), table.slice(1, 2).to_repr());
+        assert_eq!(view.to_table().column("l").unwrap().get(1).as_str(), "c");
+    }
+
+    #[test]
+    fn view_ranges_are_clamped_to_the_table() {
+        let table = table();
+        assert_eq!(table.view_rows(2..10).rows_count(), 2);
+        assert_eq!(table.view_rows(7..9).rows_count(), 0);
+    }
+}